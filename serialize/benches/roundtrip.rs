@@ -0,0 +1,172 @@
+//! Times `to_bytes`, `from_bytes` and `serialized_size` on values shaped like the rows,
+//! tables and storage layer v0 writes out, and a full flush of a 100k row table.
+//!
+//! Run with `cargo bench -p serialize --bench roundtrip`.
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+use serialize::{from_bytes, serialized_size, to_bytes, to_writer};
+
+// v0 isn't part of the workspace, so its types can't be imported here. These copies mirror
+// them and have to be kept in sync by hand: a change to how v0 lays out rows should be made
+// here too, or the numbers stop describing what v0 actually writes.
+
+/// `DbValue` in v0/lib.rs, variant for variant. v0's `Float` wraps the f64 in `DbFloat`.
+#[derive(Serialize, Deserialize)]
+enum DbValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    UnsignedInt(u64),
+    SmallInt(i32),
+    Char(String),
+}
+
+/// `Row` in v0/storage/mod.rs
+#[derive(Serialize, Deserialize)]
+struct Row {
+    data: Vec<DbValue>,
+}
+
+/// `StorageRow` in v0/storage/mod.rs
+#[derive(Serialize, Deserialize)]
+struct StorageRow {
+    row: Row,
+    id: usize,
+}
+
+/// A cut down `Table` from v0/storage/mod.rs. The rows are the same, but the header is just
+/// its name and column names, and there's no primary key set.
+#[derive(Serialize, Deserialize)]
+struct Table {
+    name: String,
+    columns: Vec<String>,
+    rows: Vec<StorageRow>,
+    next_id: usize,
+}
+
+/// A cut down `StorageLayer` from v0/storage/mod.rs, with a version number in place of
+/// its header
+#[derive(Serialize, Deserialize)]
+struct StorageLayer {
+    version: u16,
+    tables: Vec<Table>,
+}
+
+const TABLE_SIZES: [usize; 3] = [100, 1_000, 10_000];
+const FLUSH_ROWS: usize = 100_000;
+const FLUSH_FILENAME: &str = "serialize_flush_bench.test";
+
+fn row(id: usize) -> Row {
+    Row {
+        data: vec![
+            DbValue::Integer(id as i64),
+            DbValue::String(format!("user number {id}")),
+            DbValue::Float(id as f64 / 3.0),
+            DbValue::UnsignedInt(id as u64 * 7),
+            DbValue::SmallInt(id as i32 % 100),
+            DbValue::Char(format!("{:<8}", id % 1000)),
+        ],
+    }
+}
+
+fn table(name: &str, rows: usize) -> Table {
+    Table {
+        name: name.to_string(),
+        columns: ["id", "name", "score", "visits", "age", "code"]
+            .map(String::from)
+            .to_vec(),
+        rows: (0..rows)
+            .map(|id| StorageRow { row: row(id), id })
+            .collect(),
+        next_id: rows,
+    }
+}
+
+fn storage_layer(rows_per_table: usize) -> StorageLayer {
+    StorageLayer {
+        version: 1,
+        tables: ["users", "orders", "items"]
+            .iter()
+            .map(|name| table(name, rows_per_table))
+            .collect(),
+    }
+}
+
+fn rows(c: &mut Criterion) {
+    let row = row(12345);
+    let bytes = to_bytes(&row).unwrap();
+    let mut group = c.benchmark_group("row");
+    group.bench_function("to_bytes", |b| b.iter(|| to_bytes(&row).unwrap()));
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| from_bytes::<Row>(&bytes).unwrap())
+    });
+    group.bench_function("serialized_size", |b| b.iter(|| serialized_size(&row)));
+    group.finish();
+}
+
+fn tables(c: &mut Criterion) {
+    let mut group = c.benchmark_group("table");
+    for size in TABLE_SIZES {
+        let table = table("users", size);
+        let bytes = to_bytes(&table).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("to_bytes", size), &table, |b, t| {
+            b.iter(|| to_bytes(t).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("from_bytes", size), &bytes, |b, bytes| {
+            b.iter(|| from_bytes::<Table>(bytes).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("serialized_size", size), &table, |b, t| {
+            b.iter(|| serialized_size(t))
+        });
+    }
+    group.finish();
+}
+
+fn storage_layers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage_layer");
+    for size in TABLE_SIZES {
+        let layer = storage_layer(size);
+        let bytes = to_bytes(&layer).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("to_bytes", size), &layer, |b, l| {
+            b.iter(|| to_bytes(l).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("from_bytes", size), &bytes, |b, bytes| {
+            b.iter(|| from_bytes::<StorageLayer>(bytes).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("serialized_size", size), &layer, |b, l| {
+            b.iter(|| serialized_size(l))
+        });
+    }
+    group.finish();
+}
+
+/// A flush rewrites every table in full, so this is what a single changed row costs today
+fn flush(c: &mut Criterion) {
+    let layer = StorageLayer {
+        version: 1,
+        tables: vec![table("users", FLUSH_ROWS)],
+    };
+    let mut group = c.benchmark_group("flush");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(serialized_size(&layer) as u64));
+    group.bench_function(BenchmarkId::new("table", FLUSH_ROWS), |b| {
+        b.iter(|| {
+            let mut writer = BufWriter::new(File::create(FLUSH_FILENAME).unwrap());
+            to_writer(&mut writer, &layer).unwrap();
+            writer.into_inner().unwrap().sync_all().unwrap();
+        })
+    });
+    group.finish();
+    fs::remove_file(FLUSH_FILENAME).unwrap();
+}
+
+criterion_group!(benches, rows, tables, storage_layers, flush);
+criterion_main!(benches);
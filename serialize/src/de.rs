@@ -23,6 +23,17 @@ where
     T::deserialize(&mut deserializer)
 }
 
+/// Deserializes a value from the start of `bytes`, leaving anything after it unread.
+/// Returns the value along with how many bytes it took up.
+pub fn from_bytes_prefix<'de, T>(bytes: &'de [u8]) -> Result<(T, usize)>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_bytes(bytes);
+    let value = T::deserialize(&mut deserializer)?;
+    Ok((value, deserializer.offset))
+}
+
 impl<'de> Deserializer<'de> {
     fn parse_bool(&mut self) -> Result<bool> {
         let mut buf = [0; 1];
@@ -122,10 +133,25 @@ impl<'de> Deserializer<'de> {
         Ok(f64::from_be_bytes(buf))
     }
 
+    /// Reads a length prefix. Every byte, and every element that isn't zero-sized, takes
+    /// up at least one byte, so a length longer than the rest of the input can only come
+    /// from corrupt input. It's rejected before anything tries to read that many elements.
+    /// Sequences of zero-sized values, like `Vec<()>`, aren't supported.
+    fn parse_len(&mut self) -> Result<usize> {
+        let len = self.parse_u64()?;
+        let remaining = self.bytes.len() - self.offset;
+        if len > remaining as u64 {
+            return Err(Error::Eof);
+        }
+        Ok(len as usize)
+    }
+
     fn parse_byte_slice(&mut self) -> Result<&'de [u8]> {
-        let len: usize = self.parse_u64()? as usize;
+        let len = self.parse_len()?;
         let slice_end = self.offset + len;
-        Ok(&self.bytes[self.offset..slice_end])
+        let slice = &self.bytes[self.offset..slice_end];
+        self.offset = slice_end;
+        Ok(slice)
     }
 
     fn parse_str(&mut self) -> Result<&'de str> {
@@ -148,9 +174,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!(
-            "This format is not self describing, therefore deserialize_any is not supported"
-        );
+        Err(Error::NotSelfDescribing)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -326,8 +350,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.parse_u64()?;
-        visitor.visit_seq(SequenceWithLength::new(self, len))
+        let len = self.parse_len()?;
+        visitor.visit_seq(SequenceWithLength::new(self, len as u64))
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
@@ -353,8 +377,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.parse_u64()?;
-        visitor.visit_map(SequenceWithLength::new(self, len))
+        let len = self.parse_len()?;
+        visitor.visit_map(SequenceWithLength::new(self, len as u64))
     }
 
     fn deserialize_struct<V>(
@@ -392,9 +416,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!(
-            "This format is not self describing, therefore deserialize_ignored_any is not supported"
-        );
+        Err(Error::NotSelfDescribing)
     }
 }
 
@@ -11,6 +11,15 @@ pub enum Error {
     ExpectedUtf8String(Utf8Error),
     ExpectedChar,
     ExpectedOption,
+    /// The input ended before the value did, or a length prefix was longer than the rest
+    /// of the input
+    Eof,
+    /// Sequences and maps are length-prefixed, so their length has to be known up front.
+    /// Flattened structs are serialized as maps of unknown length.
+    UnknownLength,
+    /// Values are written without describing their types, so anything that needs to
+    /// discover the type while reading, like untagged enums and flattened structs, can't be read
+    NotSelfDescribing,
 }
 impl ser::Error for Error {
     fn custom<T>(msg: T) -> Self
@@ -40,6 +49,11 @@ impl Display for Error {
             )),
             Self::ExpectedChar => f.write_str("Expected a char"),
             Self::ExpectedOption => f.write_str("Expected an Option"),
+            Self::Eof => f.write_str("Unexpected end of input"),
+            Self::UnknownLength => f.write_str("Sequences and maps must have a known length"),
+            Self::NotSelfDescribing => f.write_str(
+                "This format is not self describing, so the type being read must be known",
+            ),
         }
     }
 }
@@ -48,6 +62,9 @@ impl std::error::Error for Error {}
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self::Message(value.to_string())
+        match value.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::Eof,
+            _ => Self::Message(value.to_string()),
+        }
     }
 }
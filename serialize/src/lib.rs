@@ -3,10 +3,10 @@ pub mod error;
 pub mod ser;
 pub mod serialized_size;
 
-pub use de::{from_bytes, Deserializer};
+pub use de::{from_bytes, from_bytes_prefix, Deserializer};
 pub use error::{Error, Result};
 pub use ser::{to_bytes, to_writer, Serializer};
-pub use serialized_size::serialized_size;
+pub use serialized_size::{serialized_size, FixedSize};
 
 #[cfg(not(target_pointer_width = "64"))]
 compile_error!("This serialization format is only supported on 64-bit systems");
@@ -22,11 +22,11 @@ struct FooBar {
 
 #[cfg(test)]
 mod tests {
-    use std::fmt::Debug;
+    use std::{collections::BTreeSet, fmt::Debug};
 
     use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-    use crate::{from_bytes, serialized_size, to_bytes};
+    use crate::{from_bytes, from_bytes_prefix, serialized_size, to_bytes, Error, FixedSize};
 
     fn assert_value_serdes_correctly<T>(input: T)
     where
@@ -114,6 +114,37 @@ mod tests {
         assert_value_serialized_size_is_correct(&byte_slice);
     }
 
+    fn assert_fixed_size_is_correct<T>(input: &T)
+    where
+        T: Serialize + FixedSize,
+    {
+        assert_eq!(T::SERIALIZED_SIZE, serialized_size(input));
+    }
+
+    #[test]
+    fn fixed_sizes() {
+        assert_fixed_size_is_correct(&true);
+        assert_fixed_size_is_correct(&42u8);
+        assert_fixed_size_is_correct(&42u16);
+        assert_fixed_size_is_correct(&42u32);
+        assert_fixed_size_is_correct(&42u64);
+        assert_fixed_size_is_correct(&42u128);
+        assert_fixed_size_is_correct(&42usize);
+        assert_fixed_size_is_correct(&42i8);
+        assert_fixed_size_is_correct(&42i16);
+        assert_fixed_size_is_correct(&42i32);
+        assert_fixed_size_is_correct(&42i64);
+        assert_fixed_size_is_correct(&42i128);
+        assert_fixed_size_is_correct(&42isize);
+        assert_fixed_size_is_correct(&42.42f32);
+        assert_fixed_size_is_correct(&42.42f64);
+        assert_fixed_size_is_correct(&'f');
+        assert_fixed_size_is_correct(&());
+        assert_fixed_size_is_correct(&(42u32, 52u64));
+        assert_fixed_size_is_correct(&(&42u32, 'f', 1u8, -1i16));
+        assert_fixed_size_is_correct(&[31u16, 32u16, 33u16]);
+    }
+
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
     struct BasicStruct {
         a: usize,
@@ -151,6 +182,135 @@ mod tests {
         assert_value_serialized_size_is_correct(&UnitEnum::Baz);
     }
 
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct TupleStruct(u8, String);
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum KeySet {
+        Strings(BTreeSet<String>),
+        Integers(BTreeSet<i64>),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Column {
+        name: String,
+        kind: UnitEnum,
+    }
+
+    // shaped like the primary keys the database stores, with an enum in a struct in an enum
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum PrimaryKey {
+        Rowid,
+        Column { col: Column, keyset: KeySet },
+        Pair(u16, KeySet),
+    }
+
+    fn data_enum_values() -> Vec<PrimaryKey> {
+        vec![
+            PrimaryKey::Rowid,
+            PrimaryKey::Column {
+                col: Column {
+                    name: String::from("id"),
+                    kind: UnitEnum::Baz,
+                },
+                keyset: KeySet::Integers(BTreeSet::from([-3, 7, 42])),
+            },
+            PrimaryKey::Pair(
+                9,
+                KeySet::Strings(BTreeSet::from([String::from("a"), String::from("bc")])),
+            ),
+        ]
+    }
+
+    #[test]
+    fn data_enums() {
+        assert_value_serdes_correctly(TupleStruct(4, String::from("four")));
+        for value in data_enum_values() {
+            assert_value_serdes_correctly(value);
+        }
+        // values following an enum are still read from the right place
+        assert_value_serdes_correctly((data_enum_values(), 42u32));
+    }
+
+    #[test]
+    fn data_enums_sized() {
+        assert_value_serialized_size_is_correct(&TupleStruct(4, String::from("four")));
+        for value in data_enum_values() {
+            assert_value_serialized_size_is_correct(&value);
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Flattened {
+        a: u32,
+        #[serde(flatten)]
+        inner: BasicStruct,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Number(u32),
+        Text(String),
+    }
+
+    #[test]
+    fn types_needing_a_self_describing_format_are_errors() {
+        let flattened = Flattened {
+            a: 1,
+            inner: BasicStruct { a: 2, b: 3 },
+        };
+        assert!(matches!(to_bytes(&flattened), Err(Error::UnknownLength)));
+
+        // untagged enums are written as their contents, but can't be read back
+        let bytes = to_bytes(&Untagged::Number(5)).unwrap();
+        assert!(matches!(
+            from_bytes::<Untagged>(&bytes),
+            Err(Error::NotSelfDescribing)
+        ));
+    }
+
+    #[test]
+    fn huge_length_prefixes_are_errors() {
+        let mut bytes = to_bytes(&(1u64 << 40)).unwrap();
+        bytes.extend_from_slice(b"abc");
+        assert!(matches!(from_bytes::<String>(&bytes), Err(Error::Eof)));
+        assert!(matches!(from_bytes::<Vec<u8>>(&bytes), Err(Error::Eof)));
+        assert!(matches!(from_bytes::<&[u8]>(&bytes), Err(Error::Eof)));
+        assert!(matches!(
+            from_bytes::<std::collections::HashMap<u8, u8>>(&bytes),
+            Err(Error::Eof)
+        ));
+        let bytes = to_bytes(&u64::MAX).unwrap();
+        assert!(matches!(from_bytes::<String>(&bytes), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn corrupt_input_is_an_error_not_a_panic() {
+        let value = (data_enum_values(), String::from("tail"), vec![1u32, 2, 3]);
+        let bytes = to_bytes(&value).unwrap();
+        for len in 0..bytes.len() {
+            assert!(from_bytes::<(Vec<PrimaryKey>, String, Vec<u32>)>(&bytes[..len]).is_err());
+        }
+
+        // flipping bytes can produce a valid value, but reading must never panic
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        for _ in 0..2000 {
+            let mut corrupt = bytes.clone();
+            for _ in 0..=(next() % 4) {
+                let index = next() as usize % corrupt.len();
+                corrupt[index] = next() as u8;
+            }
+            _ = from_bytes::<(Vec<PrimaryKey>, String, Vec<u32>)>(&corrupt);
+        }
+    }
+
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
     struct NestedTypes {
         a: u16,
@@ -187,6 +347,30 @@ mod tests {
         assert_value_serialized_size_is_correct(&input);
     }
 
+    #[test]
+    fn strings_followed_by_other_values() {
+        assert_value_serdes_correctly((String::from("foo"), String::from("barbaz"), 42u32));
+        assert_value_serdes_correctly(vec![String::from("foo"), String::from("bar")]);
+    }
+
+    #[test]
+    fn borrowed_strings() {
+        let bytes = to_bytes(&(String::from("foo"), 42u32)).unwrap();
+        let (s, n): (&str, u32) = from_bytes(&bytes).unwrap();
+        assert_eq!((s, n), ("foo", 42));
+        assert!(bytes.as_ptr_range().contains(&s.as_ptr()));
+    }
+
+    #[test]
+    fn prefixes_leave_the_rest_unread() {
+        let bytes = to_bytes(&(String::from("foo"), 42u32)).unwrap();
+        let (s, used): (&str, usize) = from_bytes_prefix(&bytes).unwrap();
+        assert_eq!(s, "foo");
+        assert_eq!(used, serialized_size(&"foo"));
+        let n: u32 = from_bytes(&bytes[used..]).unwrap();
+        assert_eq!(n, 42);
+    }
+
     #[test]
     fn option() {
         assert_value_serdes_correctly::<Option<u32>>(None);
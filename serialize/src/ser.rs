@@ -173,7 +173,7 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        let len = len.expect("Only sequences with known lengths are supported");
+        let len = len.ok_or(Error::UnknownLength)?;
         self.serialize_u64(len as u64)?;
         Ok(self)
     }
@@ -185,9 +185,10 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        self.serialize_seq(Some(len))
+        // the length is known from the type, like a tuple's
+        Ok(self)
     }
 
     fn serialize_tuple_variant(
@@ -195,14 +196,14 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         _name: &'static str,
         variant_index: u32,
         _variant: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.serialize_u32(variant_index)?;
-        self.serialize_seq(Some(len))
+        Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        let len = len.expect("Only maps with known lengths are supported");
+        let len = len.ok_or(Error::UnknownLength)?;
         self.serialize_u64(len as u64)?;
         Ok(self)
     }
@@ -19,6 +19,53 @@ pub fn serialized_size<T: Serialize>(value: &T) -> usize {
     ser.size
 }
 
+/// A type that always serializes to the same number of bytes, so its size is known
+/// without walking a value
+pub trait FixedSize {
+    const SERIALIZED_SIZE: usize;
+}
+
+macro_rules! impl_fixed_size {
+    ($($t:ty => $size:expr),* $(,)?) => {
+        $(
+            impl FixedSize for $t {
+                const SERIALIZED_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+impl_fixed_size! {
+    bool => 1,
+    u8 => 1, u16 => 2, u32 => 4, u64 => 8, u128 => 16, usize => 8,
+    i8 => 1, i16 => 2, i32 => 4, i64 => 8, i128 => 16, isize => 8,
+    f32 => 4, f64 => 8,
+    char => 4,
+    () => 0,
+}
+
+macro_rules! impl_fixed_size_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: FixedSize),+> FixedSize for ($($t,)+) {
+            const SERIALIZED_SIZE: usize = 0 $(+ $t::SERIALIZED_SIZE)+;
+        }
+    };
+}
+
+impl_fixed_size_tuple!(A);
+impl_fixed_size_tuple!(A, B);
+impl_fixed_size_tuple!(A, B, C);
+impl_fixed_size_tuple!(A, B, C, D);
+
+// arrays serialize as tuples, so there's no length prefix
+impl<T: FixedSize, const N: usize> FixedSize for [T; N] {
+    const SERIALIZED_SIZE: usize = T::SERIALIZED_SIZE * N;
+}
+
+impl<T: FixedSize + ?Sized> FixedSize for &T {
+    const SERIALIZED_SIZE: usize = T::SERIALIZED_SIZE;
+}
+
 impl<'a> ser::Serializer for &'a mut SerializedSize {
     type Ok = ();
     type Error = Error;
@@ -175,9 +222,10 @@ impl<'a> ser::Serializer for &'a mut SerializedSize {
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        self.serialize_seq(Some(len))
+        // the length is known from the type, like a tuple's
+        Ok(self)
     }
 
     fn serialize_tuple_variant(
@@ -185,10 +233,10 @@ impl<'a> ser::Serializer for &'a mut SerializedSize {
         _name: &'static str,
         variant_index: u32,
         _variant: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.serialize_u32(variant_index)?;
-        self.serialize_seq(Some(len))
+        Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
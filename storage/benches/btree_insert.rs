@@ -0,0 +1,43 @@
+//! Times inserting random keys into a file-backed `BTree`.
+//!
+//! Run with `cargo bench -p rjsdb_storage --bench btree_insert`.
+
+use std::{
+    cell::RefCell,
+    fs::{self, OpenOptions},
+    os::fd::AsRawFd,
+    rc::Rc,
+    time::Instant,
+};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rjsdb_storage::{BTree, PageBuffer16K, Pager};
+
+const KEY_COUNT: usize = 200_000;
+const FILENAME: &str = "btree_insert_bench.test";
+
+fn main() {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(FILENAME)
+        .unwrap();
+    let backing_fd = file.as_raw_fd();
+    let pager_ref = Rc::new(RefCell::new(Pager::<PageBuffer16K>::new(vec![file]).unwrap()));
+    let mut tree = BTree::<_, _, u64, u64>::init(pager_ref, backing_fd).unwrap();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let keys: Vec<u64> = (0..KEY_COUNT).map(|_| rng.gen()).collect();
+
+    let start = Instant::now();
+    for key in keys {
+        tree.insert(key, key).unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    eprintln!("inserted {KEY_COUNT} random u64 keys in {elapsed:?}");
+    fs::remove_file(FILENAME).unwrap();
+}
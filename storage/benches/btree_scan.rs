@@ -0,0 +1,72 @@
+//! Times scanning a file-backed `BTree` with wide values, reading whole entries with `iter`
+//! against reading only the keys with `keys_in_range`.
+//!
+//! Run with `cargo bench -p rjsdb_storage --bench btree_scan`.
+
+use std::{
+    cell::RefCell,
+    fs::{self, OpenOptions},
+    hint::black_box,
+    os::fd::AsRawFd,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use rjsdb_storage::{BTree, KeyLimit, PageBuffer4K, Pager};
+
+// small enough for the whole tree to stay in the page cache
+const KEY_COUNT: u64 = 20_000;
+const VALUE_LEN: usize = 200;
+const PASSES: u32 = 20;
+const FILENAME: &str = "btree_scan_bench.test";
+
+fn time_passes(mut pass: impl FnMut() -> usize) -> Duration {
+    // a first untimed pass, so every timed one reads from the cache
+    assert_eq!(pass(), KEY_COUNT as usize);
+    let start = Instant::now();
+    for _ in 0..PASSES {
+        black_box(pass());
+    }
+    start.elapsed() / PASSES
+}
+
+fn main() {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(FILENAME)
+        .unwrap();
+    let backing_fd = file.as_raw_fd();
+    let pager_ref = Rc::new(RefCell::new(
+        Pager::<PageBuffer4K>::new(vec![file]).unwrap(),
+    ));
+    let mut tree = BTree::<_, _, u64, String>::init(pager_ref.clone(), backing_fd).unwrap();
+    for key in 0..KEY_COUNT {
+        tree.insert(key, format!("{key:0>VALUE_LEN$}")).unwrap();
+    }
+
+    let full = time_passes(|| {
+        tree.iter(KeyLimit::None, KeyLimit::None)
+            .unwrap()
+            .inspect(|entry| {
+                black_box(entry.as_ref().unwrap());
+            })
+            .count()
+    });
+    let keys = time_passes(|| {
+        tree.keys_in_range(KeyLimit::None, KeyLimit::None)
+            .unwrap()
+            .inspect(|key| {
+                black_box(key.as_ref().unwrap());
+            })
+            .count()
+    });
+    eprintln!("scanned {KEY_COUNT} entries with {VALUE_LEN} byte values in {full:?}");
+    eprintln!("scanned {KEY_COUNT} keys only in {keys:?}");
+
+    drop(tree);
+    drop(pager_ref);
+    fs::remove_file(FILENAME).unwrap();
+}
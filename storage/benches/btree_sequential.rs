@@ -0,0 +1,51 @@
+//! Times inserting ascending and descending keys into a file-backed `BTree`, and counts the
+//! pages each tree ends up using.
+//!
+//! Run with `cargo bench -p rjsdb_storage --bench btree_sequential`.
+
+use std::{
+    cell::RefCell,
+    fs::{self, OpenOptions},
+    os::fd::AsRawFd,
+    rc::Rc,
+    time::Instant,
+};
+
+use rjsdb_storage::{BTree, PageBuffer, PageBuffer4K, Pager};
+
+const KEY_COUNT: u64 = 200_000;
+const FILENAME: &str = "btree_sequential_bench.test";
+
+fn insert_all(name: &str, keys: impl Iterator<Item = u64>) {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(FILENAME)
+        .unwrap();
+    let backing_fd = file.as_raw_fd();
+    let pager_ref = Rc::new(RefCell::new(
+        Pager::<PageBuffer4K>::new(vec![file]).unwrap(),
+    ));
+    let mut tree = BTree::<_, _, u64, u64>::init(pager_ref.clone(), backing_fd).unwrap();
+
+    let start = Instant::now();
+    for key in keys {
+        tree.insert(key, key).unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    drop(tree);
+    pager_ref.borrow_mut().flush_all().unwrap();
+    drop(pager_ref);
+    // less the header page at the start of the file
+    let pages = fs::metadata(FILENAME).unwrap().len() / PageBuffer4K::page_size() as u64 - 1;
+    eprintln!("inserted {KEY_COUNT} {name} u64 keys in {elapsed:?}, using {pages} pages");
+    fs::remove_file(FILENAME).unwrap();
+}
+
+fn main() {
+    insert_all("ascending", 0..KEY_COUNT);
+    insert_all("descending", (0..KEY_COUNT).rev());
+}
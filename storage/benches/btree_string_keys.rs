@@ -0,0 +1,91 @@
+//! Times inserting and looking up `String` keys in a file-backed `BTree`, where searching a
+//! page compares keys borrowed from it rather than deserialized copies.
+//!
+//! Run with `cargo bench -p rjsdb_storage --bench btree_string_keys`.
+
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    hint::black_box,
+    os::fd::AsRawFd,
+    rc::Rc,
+};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rjsdb_storage::{BTree, PageBuffer16K, Pager};
+
+const KEY_COUNT: usize = 100_000;
+const INSERT_FILENAME: &str = "btree_string_keys_insert_bench.test";
+const LOOKUP_FILENAME: &str = "btree_string_keys_lookup_bench.test";
+
+type StringTree = BTree<i32, PageBuffer16K, String, u64>;
+
+fn open_file(filename: &str) -> File {
+    OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(filename)
+        .unwrap()
+}
+
+fn empty_tree(filename: &str) -> StringTree {
+    let file = open_file(filename);
+    let backing_fd = file.as_raw_fd();
+    let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file]).unwrap()));
+    BTree::init(pager_ref, backing_fd).unwrap()
+}
+
+// shuffled, so inserts land all over the tree rather than always at its end
+fn keys() -> Vec<String> {
+    let mut keys: Vec<String> = (0..KEY_COUNT).map(|i| format!("user{i:08}")).collect();
+    keys.shuffle(&mut ChaCha8Rng::seed_from_u64(42));
+    keys
+}
+
+fn inserts(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("string_keys");
+    group.sample_size(10);
+    group.bench_function("insert", |b| {
+        b.iter_batched(
+            || empty_tree(INSERT_FILENAME),
+            |mut tree| {
+                for (i, key) in keys.iter().enumerate() {
+                    tree.insert(key.clone(), i as u64).unwrap();
+                }
+                tree
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+    fs::remove_file(INSERT_FILENAME).unwrap();
+}
+
+fn lookups(c: &mut Criterion) {
+    let keys = keys();
+    let mut tree = empty_tree(LOOKUP_FILENAME);
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(key.clone(), i as u64).unwrap();
+    }
+
+    let mut group = c.benchmark_group("string_keys");
+    group.sample_size(10);
+    group.bench_function("lookup", |b| {
+        b.iter(|| {
+            for key in keys.iter() {
+                black_box(tree.get(key).unwrap());
+            }
+        })
+    });
+    group.finish();
+    drop(tree);
+    fs::remove_file(LOOKUP_FILENAME).unwrap();
+}
+
+criterion_group!(benches, inserts, lookups);
+criterion_main!(benches);
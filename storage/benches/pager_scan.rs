@@ -0,0 +1,74 @@
+//! Times reading every page of a file in order through a freshly opened `Pager`, with and
+//! without prefetching runs of pages ahead of the reads.
+//!
+//! Run with `cargo bench -p rjsdb_storage --bench pager_scan`.
+
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    hint::black_box,
+    os::fd::AsRawFd,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use rjsdb_storage::{BTree, PageBuffer, PageBuffer4K, Pager};
+
+const KEY_COUNT: u64 = 400_000;
+const PREFETCH_RUN: usize = 64;
+const FILENAME: &str = "pager_scan_bench.test";
+
+fn open() -> File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(FILENAME)
+        .unwrap()
+}
+
+fn scan(prefetch: bool) -> Duration {
+    let file = open();
+    let fd = file.as_raw_fd();
+    let mut pager = Pager::<PageBuffer4K>::new(vec![file]).unwrap();
+    let start = Instant::now();
+    let mut page_id = 0;
+    while pager.file_has_page(&fd, page_id) {
+        if prefetch && page_id % PREFETCH_RUN as u64 == 0 {
+            pager.prefetch(fd, page_id, PREFETCH_RUN).unwrap();
+        }
+        black_box(pager.get_page(fd, page_id).unwrap());
+        page_id += 1;
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(FILENAME)
+        .unwrap();
+    let backing_fd = file.as_raw_fd();
+    let pager_ref = Rc::new(RefCell::new(
+        Pager::<PageBuffer4K>::new(vec![file]).unwrap(),
+    ));
+    let mut tree = BTree::<_, _, u64, u64>::init(pager_ref.clone(), backing_fd).unwrap();
+    for key in 0..KEY_COUNT {
+        tree.insert(key, key).unwrap();
+    }
+    drop(tree);
+    pager_ref.borrow_mut().flush_all().unwrap();
+    drop(pager_ref);
+
+    // less the header page at the start of the file
+    let pages = fs::metadata(FILENAME).unwrap().len() / PageBuffer4K::page_size() as u64 - 1;
+    // the first pass reads the file into the OS's cache, so both timed passes start warm
+    scan(false);
+    let without = scan(false);
+    let with = scan(true);
+    eprintln!("scanned {pages} pages without prefetch in {without:?}");
+    eprintln!("scanned {pages} pages with prefetch in {with:?}");
+    fs::remove_file(FILENAME).unwrap();
+}
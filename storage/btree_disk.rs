@@ -1,7 +1,6 @@
 #![allow(dead_code)]
 
 use std::{
-    borrow::Cow,
     cell::RefCell,
     cmp::Ordering,
     fmt::{Debug, Display},
@@ -25,7 +24,9 @@ use crate::pager::{
 use itertools::Itertools;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serialize::{from_bytes, serialized_size, to_bytes, Error as SerdeError};
+use serialize::{
+    from_bytes, from_bytes_prefix, serialized_size, to_bytes, Error as SerdeError, FixedSize,
+};
 
 /// # Notes on Page Structure
 /// - Leaf node cells are (K, V). Cells at index 0 and 1 are left and right page ids to siblings.
@@ -69,11 +70,65 @@ impl std::error::Error for Error {}
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// A type that can be used as a `BTree` key. Searching compares the wanted key
+/// against the keys stored in a page, so those are read in their `Borrowed` form,
+/// which can point into the page instead of being copied out of it.
+pub trait Key: Ord + Serialize + DeserializeOwned + Debug + Clone {
+    /// Must serialize to the same bytes as the owned key it's read from
+    type Borrowed<'a>: Deserialize<'a> + Serialize + Debug;
+
+    /// The serialized size of every key of this type, if it is fixed. Sizing a key
+    /// uses this instead of walking the value when it's known.
+    const SIZE: Option<usize> = None;
+
+    fn cmp_borrowed(borrowed: &Self::Borrowed<'_>, other: &Self) -> Ordering;
+
+    fn from_borrowed(borrowed: &Self::Borrowed<'_>) -> Self;
+}
+
+macro_rules! impl_copy_key {
+    ($($t:ty),*) => {
+        $(
+            impl Key for $t {
+                type Borrowed<'a> = $t;
+
+                const SIZE: Option<usize> = Some(<$t as FixedSize>::SERIALIZED_SIZE);
+
+                fn cmp_borrowed(borrowed: &$t, other: &$t) -> Ordering {
+                    borrowed.cmp(other)
+                }
+
+                fn from_borrowed(borrowed: &$t) -> $t {
+                    *borrowed
+                }
+            }
+        )*
+    };
+}
+impl_copy_key!(bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Key for String {
+    type Borrowed<'a> = &'a str;
+
+    fn cmp_borrowed(borrowed: &&str, other: &String) -> Ordering {
+        (*borrowed).cmp(other.as_str())
+    }
+
+    fn from_borrowed(borrowed: &&str) -> String {
+        borrowed.to_string()
+    }
+}
+
+/// The serialized size of a key of type `K`, in its owned or borrowed form
+fn serialized_key_size<K: Key>(key: &impl Serialize) -> usize {
+    K::SIZE.unwrap_or_else(|| serialized_size(key))
+}
+
 pub struct BTree<Fd, PB, K, V>
 where
     Fd: AsRawFd + Copy,
     PB: PageBuffer,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     pager_ref: Rc<RefCell<Pager<PB>>>,
@@ -86,7 +141,7 @@ impl<Fd, PB, K, V> BTree<Fd, PB, K, V>
 where
     Fd: AsRawFd + Copy,
     PB: PageBuffer,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     pub fn init(pager_ref: Rc<RefCell<Pager<PB>>>, backing_fd: Fd) -> Result<Self> {
@@ -114,9 +169,11 @@ where
         PagerInfo::new(self.pager_ref.clone(), self.backing_fd)
     }
 
-    pub fn insert(&mut self, key: K, value: V) -> Result<()> {
+    /// Inserts or replaces the value for `key`, returning the value it replaced, like
+    /// `BTreeMap::insert`
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>> {
         let mut pager_info = self.pager_info();
-        let insert_res = self.root.insert(key, value, &mut pager_info)?;
+        let (insert_res, old_value) = self.root.insert(key, value, &mut pager_info)?;
         if let InsertResult::Split(split_key, new_page_id_right) = insert_res {
             // get a new page to move data to, representing the left side of the split
             let new_page_left_ref = pager_info.new_page(self.root.page_kind())?;
@@ -137,9 +194,16 @@ where
             root_page.insert_cell(1, &to_bytes(&split_key)?)?;
             root_page.insert_cell(2, &to_bytes(&new_page_id_right)?)?;
 
+            let was_leaf = root_page.kind() == PageKind::BTreeLeaf;
             root_page.set_kind(PageKind::BTreeNode);
+            drop(root_page);
+            if was_leaf {
+                // the right page was split off the root, whose keys are now on the left page
+                let mut new_right: Node<PB, K, V> = pager_info.page_node(new_page_id_right)?;
+                new_right.leaf_replace_left_sibling(&new_page_id_left)?;
+            }
         }
-        Ok(())
+        Ok(old_value)
     }
 
     pub fn get(&self, key: &K) -> Result<Option<V>> {
@@ -147,6 +211,11 @@ where
         self.root.get(key, &mut pager_info)
     }
 
+    pub fn contains(&self, key: &K) -> Result<bool> {
+        let mut pager_info = self.pager_info();
+        self.root.contains(key, &mut pager_info)
+    }
+
     pub fn remove(&mut self, key: &K) -> Result<Option<V>> {
         let mut pager_info = self.pager_info();
         let res = self.root.remove(key, &mut pager_info)?;
@@ -182,6 +251,39 @@ where
         min_key: KeyLimit<K>,
         max_key: KeyLimit<K>,
     ) -> Result<BTreeIter<PB, Fd, K, V>> {
+        Ok(BTreeIter {
+            cursor: self.leaf_cursor(min_key, max_key)?,
+        })
+    }
+
+    /// Like `iter`, but only the keys are read, leaving the values' bytes untouched
+    pub fn keys_in_range(
+        &self,
+        min_key: KeyLimit<K>,
+        max_key: KeyLimit<K>,
+    ) -> Result<BTreeKeys<PB, Fd, K, V>> {
+        Ok(BTreeKeys {
+            cursor: self.leaf_cursor(min_key, max_key)?,
+        })
+    }
+
+    /// Like `iter`, but only the values are returned. Keys are still read to check the
+    /// upper limit, but are never turned into owned `K`s.
+    pub fn values_in_range(
+        &self,
+        min_key: KeyLimit<K>,
+        max_key: KeyLimit<K>,
+    ) -> Result<BTreeValues<PB, Fd, K, V>> {
+        Ok(BTreeValues {
+            cursor: self.leaf_cursor(min_key, max_key)?,
+        })
+    }
+
+    fn leaf_cursor(
+        &self,
+        min_key: KeyLimit<K>,
+        max_key: KeyLimit<K>,
+    ) -> Result<LeafCursor<PB, Fd, K, V>> {
         let mut pager_info = self.pager_info();
         let mut node: Node<PB, K, V> = pager_info.page_node(self.root.page_id())?;
         while !node.is_leaf() {
@@ -203,8 +305,12 @@ where
             },
         };
 
-        let iter = BTreeIter::new(node, starting_pos, max_key, pager_info);
-        Ok(iter)
+        Ok(LeafCursor {
+            leaf: node,
+            logical_pos: starting_pos,
+            max_key,
+            pager_info,
+        })
     }
 }
 
@@ -234,7 +340,7 @@ impl<PB: PageBuffer, Fd: AsRawFd + Copy> PagerInfo<PB, Fd> {
 
     fn page_node<K, V>(&mut self, page_id: PageId) -> Result<Node<PB, K, V>>
     where
-        K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+        K: Key,
         V: Serialize + DeserializeOwned,
     {
         let page = self.get_page(page_id)?;
@@ -258,11 +364,12 @@ pub enum KeyLimit<K: Ord> {
     Exclusive(K),
 }
 
-pub struct BTreeIter<PB, Fd, K, V>
+/// Walks leaf entries in key order, following right siblings from one leaf to the next
+struct LeafCursor<PB, Fd, K, V>
 where
     PB: PageBuffer,
     Fd: AsRawFd + Copy,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     leaf: Node<PB, K, V>,
@@ -270,73 +377,150 @@ where
     max_key: KeyLimit<K>,
     pager_info: PagerInfo<PB, Fd>,
 }
-impl<PB, Fd, K, V> BTreeIter<PB, Fd, K, V>
+impl<PB, Fd, K, V> LeafCursor<PB, Fd, K, V>
 where
     PB: PageBuffer,
     Fd: AsRawFd + Copy,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
-    fn new(
-        leftmost_leaf: Node<PB, K, V>,
-        starting_pos: u16,
-        max_key: KeyLimit<K>,
-        pager_info: PagerInfo<PB, Fd>,
-    ) -> Self {
-        BTreeIter {
-            leaf: leftmost_leaf,
-            logical_pos: starting_pos,
-            max_key,
-            pager_info,
+    /// Moves onto the next leaf once this one has been read, returning false if there
+    /// isn't one
+    fn advance_leaf(&mut self) -> Result<bool> {
+        if self.logical_pos == self.leaf.key_count() {
+            let next_page_id = self.leaf.leaf_right_sibling()?;
+            if next_page_id == 0 {
+                return Ok(false);
+            }
+            self.leaf = self.pager_info.page_node(next_page_id)?;
+            self.logical_pos = 0;
         }
+        Ok(true)
     }
+
+    fn past_max_key(&self, key: &BorrowedKey<K>) -> bool {
+        match &self.max_key {
+            KeyLimit::Exclusive(max) => key.cmp(max).is_ge(),
+            KeyLimit::Inclusive(max) => key.cmp(max).is_gt(),
+            KeyLimit::None => false,
+        }
+    }
+}
+
+pub struct BTreeIter<PB, Fd, K, V>
+where
+    PB: PageBuffer,
+    Fd: AsRawFd + Copy,
+    K: Key,
+    V: Serialize + DeserializeOwned,
+{
+    cursor: LeafCursor<PB, Fd, K, V>,
 }
 
 impl<PB, Fd, K, V> Iterator for BTreeIter<PB, Fd, K, V>
 where
     PB: PageBuffer,
     Fd: AsRawFd + Copy,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     type Item = Result<(K, V)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.logical_pos == self.leaf.key_count() {
-            // replace with next leaf
-            let next_page_id = match self.leaf.leaf_right_sibling() {
-                Ok(id) => id,
-                Err(err) => return Some(Err(err)),
-            };
-            if next_page_id == 0 {
-                return None;
-            }
-            self.leaf = match self.pager_info.page_node(next_page_id) {
-                Ok(node) => node,
-                Err(err) => return Some(Err(err)),
-            };
-            self.logical_pos = 0;
+        match self.cursor.advance_leaf() {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
         }
-        let leaf_page = self.leaf.page_ref.borrow();
-        let (key, val) = match self.leaf.leaf_kv_at_pos(self.logical_pos, &leaf_page) {
+        let cursor = &mut self.cursor;
+        let leaf_page = cursor.leaf.page_ref.borrow();
+        let (key, val) = match cursor.leaf.leaf_kv_at_pos(cursor.logical_pos, &leaf_page) {
             Ok((k, v)) => (k, v),
             Err(err) => return Some(Err(err)),
         };
-        match &self.max_key {
-            KeyLimit::Exclusive(max) => {
-                if key.key.as_ref() >= max {
-                    return None;
-                }
-            }
-            KeyLimit::Inclusive(max) => {
-                if key.key.as_ref() > max {
-                    return None;
-                }
-            }
-            KeyLimit::None => {}
+        if cursor.past_max_key(&key) {
+            return None;
+        }
+        cursor.logical_pos += 1;
+        Some(Ok((key.to_owned(), val)))
+    }
+}
+
+pub struct BTreeKeys<PB, Fd, K, V>
+where
+    PB: PageBuffer,
+    Fd: AsRawFd + Copy,
+    K: Key,
+    V: Serialize + DeserializeOwned,
+{
+    cursor: LeafCursor<PB, Fd, K, V>,
+}
+
+impl<PB, Fd, K, V> Iterator for BTreeKeys<PB, Fd, K, V>
+where
+    PB: PageBuffer,
+    Fd: AsRawFd + Copy,
+    K: Key,
+    V: Serialize + DeserializeOwned,
+{
+    type Item = Result<K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.cursor.advance_leaf() {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
+        }
+        let cursor = &mut self.cursor;
+        let leaf_page = cursor.leaf.page_ref.borrow();
+        let key = match cursor.leaf.key_from_leaf(cursor.logical_pos, &leaf_page) {
+            Ok(k) => k,
+            Err(err) => return Some(Err(err)),
+        };
+        if cursor.past_max_key(&key) {
+            return None;
+        }
+        cursor.logical_pos += 1;
+        Some(Ok(key.to_owned()))
+    }
+}
+
+pub struct BTreeValues<PB, Fd, K, V>
+where
+    PB: PageBuffer,
+    Fd: AsRawFd + Copy,
+    K: Key,
+    V: Serialize + DeserializeOwned,
+{
+    cursor: LeafCursor<PB, Fd, K, V>,
+}
+
+impl<PB, Fd, K, V> Iterator for BTreeValues<PB, Fd, K, V>
+where
+    PB: PageBuffer,
+    Fd: AsRawFd + Copy,
+    K: Key,
+    V: Serialize + DeserializeOwned,
+{
+    type Item = Result<V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.cursor.advance_leaf() {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
         }
-        self.logical_pos += 1;
-        Some(Ok((key.key.into_owned(), val)))
+        let cursor = &mut self.cursor;
+        let leaf_page = cursor.leaf.page_ref.borrow();
+        let (key, val) = match cursor.leaf.leaf_kv_at_pos(cursor.logical_pos, &leaf_page) {
+            Ok((k, v)) => (k, v),
+            Err(err) => return Some(Err(err)),
+        };
+        if cursor.past_max_key(&key) {
+            return None;
+        }
+        cursor.logical_pos += 1;
+        Some(Ok(val))
     }
 }
 
@@ -352,16 +536,23 @@ enum SplitDetermination {
     DontInsert(u16),
 }
 
-#[derive(Deserialize)]
-struct BorrowedKey<'a, K: Clone> {
-    #[serde(borrow)]
-    key: Cow<'a, K>,
+struct BorrowedKey<'a, K: Key> {
+    key: K::Borrowed<'a>,
+}
+impl<K: Key> BorrowedKey<'_, K> {
+    fn cmp(&self, other: &K) -> Ordering {
+        K::cmp_borrowed(&self.key, other)
+    }
+
+    fn to_owned(&self) -> K {
+        K::from_borrowed(&self.key)
+    }
 }
 
 struct Node<PB, K, V>
 where
     PB: PageBuffer,
-    K: Ord + Serialize + Debug + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     page_ref: PageRef<PB>,
@@ -371,7 +562,7 @@ where
 impl<PB, K, V> Node<PB, K, V>
 where
     PB: PageBuffer,
-    K: Ord + Debug + Serialize + DeserializeOwned + Clone,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     fn new(page_ref: PageRef<PB>) -> Self {
@@ -409,7 +600,8 @@ where
 
     fn can_fit_leaf(&self, key: &K, value: &V) -> bool {
         assert!(self.is_leaf());
-        let needed_space: usize = serialized_size(&(key, value)) + CELL_POINTER_SIZE as usize;
+        let needed_space: usize =
+            serialized_key_size::<K>(key) + serialized_size(value) + CELL_POINTER_SIZE as usize;
         assert!(needed_space <= u16::MAX.into());
         let page = self.page_ref.borrow();
         page.can_fit_data(needed_space as u16)
@@ -417,9 +609,9 @@ where
 
     fn can_fit_node(&self, key: &K) -> bool {
         assert!(self.is_node());
-        let dummy_id: PageId = 42;
-        let needed_space =
-            serialized_size(&key) + serialized_size(&dummy_id) + (2 * CELL_POINTER_SIZE as usize);
+        let needed_space = serialized_key_size::<K>(key)
+            + PageId::SERIALIZED_SIZE
+            + (2 * CELL_POINTER_SIZE as usize);
         assert!(needed_space <= u16::MAX.into());
         let page = self.page_ref.borrow();
         page.can_fit_data(needed_space as u16)
@@ -462,8 +654,9 @@ where
     ) -> Result<BorrowedKey<'page, K>> {
         assert!(self.is_leaf());
         let pos = Self::logical_leaf_key_pos_to_physical_pos(logical_pos);
-        let (key, _): (BorrowedKey<'page, K>, V) = from_bytes(page.cell_bytes(pos))?;
-        Ok(key)
+        // the value comes after the key, so it doesn't need to be read
+        let (key, _) = from_bytes_prefix::<K::Borrowed<'page>>(page.cell_bytes(pos))?;
+        Ok(BorrowedKey { key })
     }
 
     fn value_from_leaf(&self, logical: u16) -> Result<V> {
@@ -481,11 +674,10 @@ where
     ) -> Result<(BorrowedKey<'page, K>, V)> {
         assert!(self.is_leaf());
         let pos = Self::logical_leaf_key_pos_to_physical_pos(logical);
-        let kv = from_bytes(page.cell_bytes(pos))?;
-        Ok(kv)
+        let (key, val) = from_bytes(page.cell_bytes(pos))?;
+        Ok((BorrowedKey { key }, val))
     }
 
-    #[allow(dead_code)]
     fn leaf_left_sibling(&self) -> Result<PageId> {
         assert!(self.is_leaf());
         let page = self.page_ref.borrow();
@@ -542,7 +734,7 @@ where
         assert!(self.is_node());
         let pos = Self::logical_node_key_pos_to_physical_pos(key_pos);
         let key = from_bytes(page.cell_bytes(pos))?;
-        Ok(key)
+        Ok(BorrowedKey { key })
     }
 
     fn page_id_from_inner_node(&self, id_pos: u16) -> Result<PageId> {
@@ -575,7 +767,7 @@ where
         while low < high {
             let mid = (low + high) / 2; // TODO: Rework to prevent overflow
             let cell_key = self.key_at_pos(mid, &page).unwrap();
-            match cell_key.key.as_ref().cmp(key) {
+            match cell_key.cmp(key) {
                 Ordering::Less => {
                     low = mid + 1;
                 }
@@ -584,7 +776,7 @@ where
             }
         }
         let cell_key = self.key_at_pos(low, &page).unwrap();
-        match cell_key.key.as_ref().cmp(key) {
+        match cell_key.cmp(key) {
             Ordering::Greater => Err(low),
             Ordering::Equal => Ok(low),
             Ordering::Less => Err(low + 1),
@@ -678,10 +870,9 @@ where
         key_to_be_inserted: &K,
         logical_insertion_pos: u16,
     ) -> Result<SplitDetermination> {
-        let dummy_id: PageId = 0;
-        let id_size = serialized_size(&dummy_id) as u16;
+        let id_size = PageId::SERIALIZED_SIZE as u16;
         let id_used_space = id_size + CELL_POINTER_SIZE;
-        let key_size = serialized_size(key_to_be_inserted) as u16;
+        let key_size = serialized_key_size::<K>(key_to_be_inserted) as u16;
         let insertion_size = key_size + id_size + (CELL_POINTER_SIZE * 2);
         let mut used_space = 0;
 
@@ -707,7 +898,7 @@ where
             }
 
             let this_key_used_space =
-                serialized_size(self.key_at_pos(i, &self.page_ref.borrow())?.key.as_ref()) as u16
+                serialized_key_size::<K>(&self.key_at_pos(i, &self.page_ref.borrow())?.key) as u16
                     + CELL_POINTER_SIZE;
             let space_used_minus_this_key = self.page_used_space() - this_key_used_space;
             let size_goal = (space_used_minus_this_key + insertion_size) / 2;
@@ -747,8 +938,7 @@ where
         let (split_key, move_start_logical_pos, move_offset) = match split_determination {
             SplitDetermination::InsertLeft(pos) | SplitDetermination::InsertRight(pos) => (
                 self.key_from_inner_node(pos, &self.page_ref.borrow())?
-                    .key
-                    .into_owned(),
+                    .to_owned(),
                 pos + 1,
                 0,
             ),
@@ -798,8 +988,7 @@ where
     }
 
     fn leaf_siblings_space_used() -> u16 {
-        let dummy_id: PageId = 0;
-        (serialized_size(&dummy_id) as u16 + CELL_POINTER_SIZE) * 2
+        (PageId::SERIALIZED_SIZE as u16 + CELL_POINTER_SIZE) * 2
     }
 
     fn split_leaf_and_insert<Fd: AsRawFd + Copy>(
@@ -809,21 +998,24 @@ where
         pager_info: &mut PagerInfo<PB, Fd>,
     ) -> Result<(K, Node<PB, K, V>)> {
         println!("splitting leaf");
-        let insertion_size = serialized_size(&(&key, &value)) as u16 + CELL_POINTER_SIZE;
-        let size_goal_fn = |this_key: &K, _: &V| match key.cmp(this_key) {
+        let insertion_size =
+            (serialized_key_size::<K>(&key) + serialized_size(&value)) as u16 + CELL_POINTER_SIZE;
+        let size_goal_fn = |this_key: &BorrowedKey<K>, _: &V| match this_key.cmp(&key).reverse() {
             Ordering::Less => (self.leaf_space_used_ignoring_siblings() - insertion_size) / 2,
             Ordering::Equal => unreachable!("Existing keys shouldn't be inserted here"),
             Ordering::Greater => self.leaf_space_used_ignoring_siblings() / 2,
         };
 
-        let split_key_pos = self
-            .leaf_find_logical_position_meeting_size_goal(0, size_goal_fn)?
-            .unwrap();
+        let split_key_pos = match self.leaf_sequential_split_pos(&key, insertion_size)? {
+            Some(pos) => pos,
+            None => self
+                .leaf_find_logical_position_meeting_size_goal(0, size_goal_fn)?
+                .unwrap(),
+        };
 
         let split_key = self
             .key_from_leaf(split_key_pos, &self.page_ref.borrow())?
-            .key
-            .into_owned();
+            .to_owned();
 
         // get new page
         let mut new_node = Self::init_leaf(pager_info)?;
@@ -832,6 +1024,10 @@ where
         let old_right = self.leaf_replace_right_sibling(&new_node.page_id())?;
         new_node.leaf_replace_left_sibling(&self.page_id())?;
         new_node.leaf_replace_right_sibling(&old_right)?;
+        if old_right != 0 {
+            let mut old_right_node: Self = pager_info.page_node(old_right)?;
+            old_right_node.leaf_replace_left_sibling(&new_node.page_id())?;
+        }
 
         // copy cells to new page and remove cells from old page
         let key_count = self.key_count();
@@ -847,25 +1043,86 @@ where
         Ok((split_key, new_node))
     }
 
+    /// Inserting ascending keys always lands past the end of the rightmost leaf, and
+    /// descending ones before the start of the leftmost. Splitting those in half would leave
+    /// every page half empty, so instead the page the keys are moving away from keeps all but
+    /// the third of a page the other one needs. Returns `None` for any other insert, or if
+    /// the pages wouldn't both be at least a third full.
+    fn leaf_sequential_split_pos(&self, key: &K, insertion_size: u16) -> Result<Option<u16>> {
+        let key_count = self.key_count();
+        let page = self.page_ref.borrow();
+        let ascending = self.leaf_right_sibling()? == 0
+            && self.key_from_leaf(key_count - 1, &page)?.cmp(key) == Ordering::Less;
+        let descending = !ascending
+            && self.leaf_left_sibling()? == 0
+            && self.key_from_leaf(0, &page)?.cmp(key) == Ordering::Greater;
+        if !ascending && !descending {
+            return Ok(None);
+        }
+
+        let cell_size = |pos: u16| {
+            let physical_pos = Self::logical_leaf_key_pos_to_physical_pos(pos);
+            page.cell_bytes(physical_pos).len() as u16 + CELL_POINTER_SIZE
+        };
+        let min_size = PB::buffer_size() / 3;
+        // what the page getting the new key needs on top of it
+        let needed = min_size.saturating_sub(Self::leaf_siblings_space_used() + insertion_size);
+        let mut split_pos;
+        let mut taken = 0;
+        if ascending {
+            // the new page on the right takes keys off the end
+            split_pos = key_count - 1;
+            while taken < needed && split_pos > 0 {
+                taken += cell_size(split_pos);
+                split_pos -= 1;
+            }
+        } else {
+            // this page keeps keys from the start, and the rest move right
+            split_pos = 0;
+            taken += cell_size(0);
+            while taken < needed && split_pos < key_count - 1 {
+                split_pos += 1;
+                taken += cell_size(split_pos);
+            }
+        }
+
+        let keys_size = self.leaf_space_used_ignoring_siblings();
+        let with_new_key = Self::leaf_siblings_space_used() + taken + insertion_size;
+        let without_new_key = Self::leaf_siblings_space_used() + keys_size - taken;
+        if with_new_key < min_size || with_new_key > PB::buffer_size() || without_new_key < min_size
+        {
+            return Ok(None);
+        }
+        Ok(Some(split_pos))
+    }
+
     fn insert_as_leaf<Fd: AsRawFd + Copy>(
         &mut self,
         key: K,
         value: V,
         pager_info: &mut PagerInfo<PB, Fd>,
-    ) -> Result<InsertResult<K>> {
+    ) -> Result<(InsertResult<K>, Option<V>)> {
         assert!(self.is_leaf());
         // if the key already exists, remove that entry before doing anything
         let existing_key_pos = self.binary_search_keys(&key);
-        if let Ok(pos) = existing_key_pos {
-            let physical_pos = Self::logical_leaf_key_pos_to_physical_pos(pos);
-            let mut page = self.page_ref.borrow_mut();
-            page.remove_cell(physical_pos);
-        }
+        let old_value = match existing_key_pos {
+            Ok(pos) => {
+                let old_value = self.value_from_leaf(pos)?;
+                let physical_pos = Self::logical_leaf_key_pos_to_physical_pos(pos);
+                let mut page = self.page_ref.borrow_mut();
+                page.remove_cell(physical_pos);
+                Some(old_value)
+            }
+            Err(_) => None,
+        };
 
         if !self.can_fit_leaf(&key, &value) {
             let (split_key, new_node) = self.split_leaf_and_insert(key, value, pager_info)?;
             assert!(new_node.is_leaf());
-            Ok(InsertResult::Split(split_key, new_node.page_id()))
+            Ok((
+                InsertResult::Split(split_key, new_node.page_id()),
+                old_value,
+            ))
         } else {
             let logical_pos = match existing_key_pos {
                 Ok(logical_pos) => logical_pos,
@@ -874,7 +1131,7 @@ where
             let physical_pos = Self::logical_leaf_key_pos_to_physical_pos(logical_pos);
             let mut page = self.page_ref.borrow_mut();
             page.insert_cell(physical_pos, &to_bytes(&(key, value))?)?;
-            Ok(InsertResult::Done)
+            Ok((InsertResult::Done, old_value))
         }
     }
 
@@ -943,8 +1200,7 @@ where
         let old_key = if logical_key_pos < self.key_count() {
             Some(
                 self.key_from_inner_node(logical_key_pos, &self.page_ref.borrow())?
-                    .key
-                    .into_owned(),
+                    .to_owned(),
             )
         } else {
             None
@@ -963,35 +1219,35 @@ where
         key: K,
         value: V,
         pager_info: &mut PagerInfo<PB, Fd>,
-    ) -> Result<InsertResult<K>> {
+    ) -> Result<(InsertResult<K>, Option<V>)> {
         assert!(self.is_node());
         let (logical_pos, mut child_node) = self.get_descendent_by_key(&key, pager_info)?;
-        if let InsertResult::Split(split_key, new_page_id) =
-            child_node.insert(key, value, pager_info)?
-        {
+        let (child_res, old_value) = child_node.insert(key, value, pager_info)?;
+        if let InsertResult::Split(split_key, new_page_id) = child_res {
             if !self.can_fit_node(&split_key) {
                 let (parent_split_key, parent_new_node) =
                     self.split_node_and_insert(pager_info, logical_pos, &split_key, new_page_id)?;
                 assert!(parent_new_node.is_node());
-                Ok(InsertResult::Split(
-                    parent_split_key,
-                    parent_new_node.page_id(),
+                Ok((
+                    InsertResult::Split(parent_split_key, parent_new_node.page_id()),
+                    old_value,
                 ))
             } else {
                 self.insert_split_key_and_page_id_into_node(logical_pos, &split_key, new_page_id)?;
-                Ok(InsertResult::Done)
+                Ok((InsertResult::Done, old_value))
             }
         } else {
-            Ok(InsertResult::Done)
+            Ok((InsertResult::Done, old_value))
         }
     }
 
+    /// Also returns the value the key had before, if it had one
     fn insert<Fd: AsRawFd + Copy>(
         &mut self,
         key: K,
         value: V,
         pager_info: &mut PagerInfo<PB, Fd>,
-    ) -> Result<InsertResult<K>> {
+    ) -> Result<(InsertResult<K>, Option<V>)> {
         println!("inserting {key:?}");
         if self.is_leaf() {
             self.insert_as_leaf(key, value, pager_info)
@@ -1017,6 +1273,21 @@ where
         }
     }
 
+    /// Like `get`, but stops once the key is found, without deserializing the value
+    fn contains<Fd: AsRawFd + Copy>(
+        &self,
+        key: &K,
+        pager_info: &mut PagerInfo<PB, Fd>,
+    ) -> Result<bool> {
+        if self.is_leaf() {
+            Ok(self.binary_search_keys(key).is_ok())
+        } else {
+            assert!(self.is_node());
+            let (_, child_node) = self.get_descendent_by_key(key, pager_info)?;
+            child_node.contains(key, pager_info)
+        }
+    }
+
     fn can_fit_via_merge<Fd: AsRawFd + Copy>(
         &self,
         left_child_pos: u16,
@@ -1029,7 +1300,7 @@ where
         let fits = if left_child.is_node() {
             let page = self.page_ref.borrow();
             let merge_key = self.key_at_pos(left_child_pos, &page)?;
-            let key_size = serialized_size(&merge_key.key) as u16 + CELL_POINTER_SIZE;
+            let key_size = serialized_key_size::<K>(&merge_key.key) as u16 + CELL_POINTER_SIZE;
             left_child.page_free_space() >= right_child.page_used_space() + key_size
         } else {
             left_child.page_free_space() >= right_child.page_used_space()
@@ -1072,7 +1343,7 @@ where
             let initial_left_key_count = left_child.key_count();
             let page = self.page_ref.borrow();
             let key = self.key_from_inner_node(left_child_pos, &page)?;
-            left_child.insert_trailing_key(&key.key)?;
+            left_child.insert_trailing_key(&key.to_owned())?;
             initial_left_key_count + 1
         } else {
             left_child.key_count()
@@ -1090,8 +1361,13 @@ where
         Self::move_cells(&mut right_child, &mut left_child, from_range, logical_start)?;
 
         if left_child.is_leaf() {
-            // update right sibling pointer
-            left_child.leaf_replace_right_sibling(&right_child.leaf_right_sibling()?)?;
+            // update sibling pointers, so the next leaf doesn't point back at the dropped page
+            let new_right = right_child.leaf_right_sibling()?;
+            left_child.leaf_replace_right_sibling(&new_right)?;
+            if new_right != 0 {
+                let mut new_right_node: Self = pager_info.page_node(new_right)?;
+                new_right_node.leaf_replace_left_sibling(&left_child.page_id())?;
+            }
         }
 
         // remove right page
@@ -1115,16 +1391,17 @@ where
     fn leaf_find_logical_position_meeting_size_goal(
         &self,
         starting_size: u16,
-        size_goal_fn: impl Fn(&K, &V) -> u16,
+        size_goal_fn: impl Fn(&BorrowedKey<K>, &V) -> u16,
     ) -> Result<Option<u16>> {
         assert!(self.is_leaf());
         let mut used_space = starting_size;
         let page = self.page_ref.borrow();
         for i in 0..self.key_count() {
             let (k, v) = self.leaf_kv_at_pos(i, &page)?;
-            let increment = serialized_size(&(&k.key, &v)) as u16 + CELL_POINTER_SIZE;
+            let increment =
+                (serialized_key_size::<K>(&k.key) + serialized_size(&v)) as u16 + CELL_POINTER_SIZE;
             used_space += increment;
-            if used_space >= size_goal_fn(&k.key, &v) {
+            if used_space >= size_goal_fn(&k, &v) {
                 return Ok(Some(i));
             }
         }
@@ -1134,21 +1411,20 @@ where
     fn node_find_logical_position_meeting_size_goal(
         &self,
         starting_size: u16,
-        size_goal_fn: impl Fn(&K, u16) -> u16, // takes key and index
+        size_goal_fn: impl Fn(&BorrowedKey<K>, u16) -> u16, // takes key and index
     ) -> Result<Option<u16>> {
-        let dummy_id: PageId = 0;
-        let dummy_space_used = serialized_size(&dummy_id) as u16 + CELL_POINTER_SIZE;
+        let id_space_used = PageId::SERIALIZED_SIZE as u16 + CELL_POINTER_SIZE;
 
         assert!(self.is_node());
         let mut used_space = starting_size;
         let page = self.page_ref.borrow();
         for i in 0..self.key_count() {
-            used_space += dummy_space_used;
+            used_space += id_space_used;
             let key = self.key_at_pos(i, &page)?;
-            if used_space >= size_goal_fn(&key.key, i) {
+            if used_space >= size_goal_fn(&key, i) {
                 return Ok(Some(i));
             }
-            used_space += serialized_size(&key.key) as u16 + CELL_POINTER_SIZE;
+            used_space += serialized_key_size::<K>(&key.key) as u16 + CELL_POINTER_SIZE;
         }
         Ok(None)
     }
@@ -1175,8 +1451,8 @@ where
             .expect("Should always have a value");
 
         let left_page = left_child.page_ref.borrow();
-        let new_split_key = left_child.key_at_pos(new_split_pos, &left_page)?;
-        self.replace_inner_node_key(right_child_logical_pos - 1, &new_split_key.key)?;
+        let new_split_key = left_child.key_at_pos(new_split_pos, &left_page)?.to_owned();
+        self.replace_inner_node_key(right_child_logical_pos - 1, &new_split_key)?;
         drop(left_page);
 
         let from_range = new_split_pos + 1..=left_child.key_count() - 1;
@@ -1210,8 +1486,10 @@ where
             .expect("Should always have a value");
 
         let right_page = right_child.page_ref.borrow();
-        let new_split_key = right_child.key_at_pos(new_split_pos, &right_page)?;
-        self.replace_inner_node_key(left_child_logical_pos, &new_split_key.key)?;
+        let new_split_key = right_child
+            .key_at_pos(new_split_pos, &right_page)?
+            .to_owned();
+        self.replace_inner_node_key(left_child_logical_pos, &new_split_key)?;
         drop(right_page);
 
         let from_range = 0..=new_split_pos;
@@ -1234,7 +1512,9 @@ where
         assert!(right_child_logical_pos > 0);
 
         let page = self.page_ref.borrow();
-        let old_split_key = self.key_at_pos(right_child_logical_pos - 1, &page)?;
+        let old_split_key = self
+            .key_at_pos(right_child_logical_pos - 1, &page)?
+            .to_owned();
 
         let mut left_child =
             self.descendent_node_at_logical_pos(right_child_logical_pos - 1, pager_info)?;
@@ -1244,12 +1524,12 @@ where
 
         let combined_size = left_child.page_used_space()
             + right_child.page_used_space()
-            + serialized_size(&old_split_key.key) as u16
+            + serialized_key_size::<K>(&old_split_key) as u16
             + CELL_POINTER_SIZE;
 
         let new_split_pos = left_child
-            .node_find_logical_position_meeting_size_goal(0, |key: &K, _: u16| {
-                let key_space_used = serialized_size(&key) as u16 + CELL_POINTER_SIZE;
+            .node_find_logical_position_meeting_size_goal(0, |key: &BorrowedKey<K>, _: u16| {
+                let key_space_used = serialized_key_size::<K>(&key.key) as u16 + CELL_POINTER_SIZE;
                 (combined_size - key_space_used) / 2
             })?
             .expect("Should always have a value");
@@ -1258,12 +1538,12 @@ where
         Self::move_cells(&mut left_child, &mut right_child, from_range.clone(), 0)?;
 
         let key_insert_pos = from_range.len() - 1;
-        right_child.insert_interior_split_key(key_insert_pos as u16, &old_split_key.key)?;
+        right_child.insert_interior_split_key(key_insert_pos as u16, &old_split_key)?;
         drop(page);
 
         let left_page = left_child.page_ref.borrow();
-        let new_split_key = left_child.key_at_pos(new_split_pos, &left_page)?;
-        self.replace_inner_node_key(right_child_logical_pos - 1, &new_split_key.key)?;
+        let new_split_key = left_child.key_at_pos(new_split_pos, &left_page)?.to_owned();
+        self.replace_inner_node_key(right_child_logical_pos - 1, &new_split_key)?;
         drop(left_page);
 
         left_child.remove_trailing_key(new_split_pos);
@@ -1278,7 +1558,7 @@ where
         assert!(left_child_logical_pos < self.descendent_count() - 1);
 
         let page = self.page_ref.borrow();
-        let old_split_key = self.key_at_pos(left_child_logical_pos, &page)?;
+        let old_split_key = self.key_at_pos(left_child_logical_pos, &page)?.to_owned();
 
         let mut left_child =
             self.descendent_node_at_logical_pos(left_child_logical_pos, pager_info)?;
@@ -1288,28 +1568,34 @@ where
 
         let combined_size = left_child.page_used_space()
             + right_child.page_used_space()
-            + serialized_size(&old_split_key.key) as u16
+            + serialized_key_size::<K>(&old_split_key) as u16
             + CELL_POINTER_SIZE;
 
         let starting_size = left_child.page_used_space()
-            + serialized_size(&old_split_key.key) as u16
+            + serialized_key_size::<K>(&old_split_key) as u16
             + CELL_POINTER_SIZE;
         let new_split_pos = right_child
-            .node_find_logical_position_meeting_size_goal(starting_size, |key: &K, _: u16| {
-                let key_space_used = serialized_size(key) as u16 + CELL_POINTER_SIZE;
-                (combined_size - key_space_used) / 2
-            })?
+            .node_find_logical_position_meeting_size_goal(
+                starting_size,
+                |key: &BorrowedKey<K>, _: u16| {
+                    let key_space_used =
+                        serialized_key_size::<K>(&key.key) as u16 + CELL_POINTER_SIZE;
+                    (combined_size - key_space_used) / 2
+                },
+            )?
             .expect("Should always have a value");
 
         let right_page = right_child.page_ref.borrow();
-        let new_split_key = right_child.key_at_pos(new_split_pos, &right_page)?;
+        let new_split_key = right_child
+            .key_at_pos(new_split_pos, &right_page)?
+            .to_owned();
 
         let from_range = 0..=new_split_pos;
         let left_child_key_count = left_child.key_count();
-        left_child.insert_interior_split_key(left_child_key_count, &old_split_key.key)?;
+        left_child.insert_interior_split_key(left_child_key_count, &old_split_key)?;
         drop(page);
 
-        self.replace_inner_node_key(left_child_logical_pos, &new_split_key.key)?;
+        self.replace_inner_node_key(left_child_logical_pos, &new_split_key)?;
         drop(right_page);
 
         Self::move_cells(
@@ -1418,68 +1704,18 @@ where
 /// - This is the smallest buffer size we can write straitforward manual tests for
 const TEST_BUFFER_SIZE: u16 = 112;
 #[cfg(test)]
-pub struct SmallBuffer {
-    data: [u8; TEST_BUFFER_SIZE as usize],
-}
-#[cfg(test)]
-impl PageBuffer for SmallBuffer {
-    fn new() -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            data: [0; TEST_BUFFER_SIZE as usize],
-        }
-    }
-
-    fn buffer_size() -> u16 {
-        TEST_BUFFER_SIZE
-    }
-
-    fn data(&self) -> &[u8] {
-        &self.data
-    }
-
-    fn data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
-    }
-}
+pub type SmallBuffer = crate::pager::SizedBuffer<{ TEST_BUFFER_SIZE as usize }>;
 
 #[cfg(test)]
 // This is the smallest size you can construct a btree with when using u32 keys and u32 values
 const SMALLEST_BUFFER_SIZE: u16 = 36;
 #[cfg(test)]
-pub struct SmallestBuffer {
-    data: [u8; SMALLEST_BUFFER_SIZE as usize],
-}
-#[cfg(test)]
-impl PageBuffer for SmallestBuffer {
-    fn new() -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            data: [0; SMALLEST_BUFFER_SIZE as usize],
-        }
-    }
-
-    fn buffer_size() -> u16 {
-        SMALLEST_BUFFER_SIZE
-    }
-
-    fn data(&self) -> &[u8] {
-        &self.data
-    }
-
-    fn data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
-    }
-}
+pub type SmallestBuffer = crate::pager::SizedBuffer<{ SMALLEST_BUFFER_SIZE as usize }>;
 
 #[cfg(test)]
 impl<PB: PageBuffer, T> BTree<i32, PB, T, T>
 where
-    T: Ord + Serialize + Debug + FromStr + Clone + DeserializeOwned,
+    T: Key + FromStr,
 {
     /*
      * An example description looks something like this:
@@ -1525,13 +1761,27 @@ where
         assert_eq!(first_page_id, 0);
         drop(root);
 
-        let _root = Node::from_description_lines(
+        let root = Node::from_description_lines(
             &mut pager_info,
             first_line,
             &mut lines,
             first_page_id,
             (0, 0),
-        );
+        )
+        .unwrap();
+
+        // each node only links up its own children, so link leaves across nodes too
+        if root.is_node() {
+            let ids = leaf_page_ids(&root, &mut pager_info);
+            for (i, id) in ids.iter().enumerate() {
+                let mut leaf: Node<SmallBuffer, T, T> = pager_info.page_node(*id).unwrap();
+                let left = if i == 0 { 0 } else { ids[i - 1] };
+                let right = ids.get(i + 1).copied().unwrap_or(0);
+                leaf.leaf_replace_left_sibling(&left).unwrap();
+                leaf.leaf_replace_right_sibling(&right).unwrap();
+            }
+        }
+        drop(root);
 
         let tree = BTree::init(pager_ref, backing_fd).unwrap();
         assert_subtree_valid(&tree.root, &mut pager_info);
@@ -1542,7 +1792,7 @@ where
 #[cfg(test)]
 impl<PB: PageBuffer, T> BTree<i32, PB, T, T>
 where
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     fn to_description(&self) -> String {
         let mut pager_info = self.pager_info();
@@ -1585,7 +1835,7 @@ where
 impl<PB, K, V> Node<PB, K, V>
 where
     PB: PageBuffer,
-    K: Ord + Debug + Serialize + Clone + DeserializeOwned,
+    K: Key,
     V: Serialize + DeserializeOwned,
 {
     #[allow(dead_code)]
@@ -1593,12 +1843,12 @@ where
         if self.is_leaf() {
             let page = self.page_ref.borrow();
             (0..self.key_count())
-                .map(|i| self.key_from_leaf(i, &page).unwrap().key.into_owned())
+                .map(|i| self.key_from_leaf(i, &page).unwrap().to_owned())
                 .collect()
         } else {
             let page = self.page_ref.borrow();
             (0..self.key_count())
-                .map(|i| self.key_from_inner_node(i, &page).unwrap().key.into_owned())
+                .map(|i| self.key_from_inner_node(i, &page).unwrap().to_owned())
                 .collect()
         }
     }
@@ -1645,7 +1895,7 @@ where
 #[cfg(test)]
 impl<T> Node<SmallBuffer, T, T>
 where
-    T: Ord + Serialize + DeserializeOwned + Debug + FromStr + Clone,
+    T: Key + FromStr,
 {
     fn from_description_lines<Fd: AsRawFd + Copy, I: Iterator<Item = DescriptionLine<T>>>(
         pager_info: &mut PagerInfo<SmallBuffer, Fd>,
@@ -1845,7 +2095,7 @@ impl<T: Debug> Display for DescriptionLine<T> {
 fn assert_tree_keys_fully_ordered<PB, T>(root: &Node<PB, T, T>)
 where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     let keys = root.keys();
     let mut sorted_keys = keys.clone();
@@ -1859,7 +2109,7 @@ fn assert_all_node_keys_ordered_and_deduped<PB, T>(
     pager_info: &mut PagerInfo<PB, i32>,
 ) where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     let mut sorted_keys = node.keys();
     sorted_keys.sort();
@@ -1878,7 +2128,7 @@ fn assert_all_keys_in_range<PB, T>(
     max_inclusive: Option<&T>,
 ) where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     let res = match (min_exclusive, max_inclusive) {
         (Some(min), Some(max)) => node.keys().iter().all(|k| k > min && k <= max),
@@ -1895,7 +2145,7 @@ fn assert_all_subnode_keys_ordered_relative_to_node_keys<PB, T>(
     pager_info: &mut PagerInfo<PB, i32>,
 ) where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     if node.is_leaf() {
         return;
@@ -1927,12 +2177,12 @@ fn assert_all_nodes_sized_correctly<PB, T>(
     pager_info: &mut PagerInfo<PB, i32>,
 ) where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     fn correct_cell_count<PB, T>(node: &Node<PB, T, T>) -> bool
     where
         PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+        T: Key,
     {
         if node.is_leaf() {
             true
@@ -1947,7 +2197,7 @@ fn assert_all_nodes_sized_correctly<PB, T>(
         pager_info: &mut PagerInfo<PB, i32>,
     ) where
         PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+        T: Key,
     {
         let third_size = PB::buffer_size() / 3;
         let meets_minimum_size = node.page_used_space() >= third_size;
@@ -1972,11 +2222,47 @@ fn assert_all_nodes_sized_correctly<PB, T>(
         .for_each(|node| assert_all_nodes_sized_correctly_not_root(node, pager_info));
 }
 
+// the ids of every leaf under `node`, in key order
+#[cfg(test)]
+fn leaf_page_ids<PB, T>(node: &Node<PB, T, T>, pager_info: &mut PagerInfo<PB, i32>) -> Vec<PageId>
+where
+    PB: PageBuffer,
+    T: Key,
+{
+    if node.is_leaf() {
+        return vec![node.page_id()];
+    }
+    let children: Vec<_> = node.descendent_iter(pager_info).collect();
+    children
+        .iter()
+        .flat_map(|c| leaf_page_ids(c, pager_info))
+        .collect()
+}
+
+#[cfg(test)]
+fn assert_leaf_siblings_linked<PB, T>(root: &Node<PB, T, T>, pager_info: &mut PagerInfo<PB, i32>)
+where
+    PB: PageBuffer,
+    T: Key,
+{
+    if root.is_leaf() {
+        return;
+    }
+    let ids = leaf_page_ids(root, pager_info);
+    for (i, id) in ids.iter().enumerate() {
+        let leaf: Node<PB, T, T> = pager_info.page_node(*id).unwrap();
+        let left = if i == 0 { 0 } else { ids[i - 1] };
+        let right = ids.get(i + 1).copied().unwrap_or(0);
+        assert_eq!(leaf.leaf_left_sibling().unwrap(), left);
+        assert_eq!(leaf.leaf_right_sibling().unwrap(), right);
+    }
+}
+
 #[cfg(test)]
 fn assert_all_leaves_same_level<PB, T>(root: &Node<PB, T, T>, pager_info: &mut PagerInfo<PB, i32>)
 where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     fn leaf_levels<PB, T>(
         node: &Node<PB, T, T>,
@@ -1985,7 +2271,7 @@ where
     ) -> Vec<usize>
     where
         PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+        T: Key,
     {
         if node.is_leaf() {
             return vec![level];
@@ -2006,13 +2292,14 @@ where
 fn assert_subtree_valid<PB, T>(node: &Node<PB, T, T>, pager_info: &mut PagerInfo<PB, i32>)
 where
     PB: PageBuffer,
-    T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+    T: Key,
 {
     assert_all_nodes_sized_correctly(node, pager_info);
     assert_tree_keys_fully_ordered(node);
     assert_all_node_keys_ordered_and_deduped(node, pager_info);
     assert_all_subnode_keys_ordered_relative_to_node_keys(node, pager_info);
     assert_all_leaves_same_level(node, pager_info);
+    assert_leaf_siblings_linked(node, pager_info);
 }
 
 #[cfg(test)]
@@ -2040,7 +2327,7 @@ mod tests {
 
     use crate::pager::{PageBuffer, PageId, Pager, CELL_POINTER_SIZE};
 
-    use super::{BTree, KeyLimit, SmallBuffer, SmallestBuffer};
+    use super::{BTree, Key, KeyLimit, SmallBuffer, SmallestBuffer};
 
     fn trim_lines(s: &str) -> String {
         s.trim().lines().map(|l| l.trim()).join("\n")
@@ -2062,18 +2349,18 @@ mod tests {
     ) -> BTree<i32, SmallBuffer, u32, u32> {
         let file = open_file(filename);
         let backing_fd = file.as_raw_fd();
-        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file])));
+        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file]).unwrap()));
 
         BTree::<i32, SmallBuffer, u32, u32>::from_description(description, pager_ref, backing_fd)
     }
 
     fn init_tree_in_file<PB: PageBuffer, T>(filename: &str) -> BTree<i32, PB, T, T>
     where
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+        T: Key,
     {
         let file = open_file(filename);
         let backing_fd = file.as_raw_fd();
-        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file])));
+        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file]).unwrap()));
 
         BTree::init(pager_ref, backing_fd).unwrap()
     }
@@ -2081,15 +2368,23 @@ mod tests {
     fn init_tree_in_file_with_pb<PB, T>(filename: &str) -> BTree<i32, PB, T, T>
     where
         PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
+        T: Key,
     {
         let file = open_file(filename);
         let backing_fd = file.as_raw_fd();
-        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file])));
+        let pager_ref = Rc::new(RefCell::new(Pager::new(vec![file]).unwrap()));
 
         BTree::init(pager_ref, backing_fd).unwrap()
     }
 
+    #[test]
+    fn fixed_key_sizes_match_serialized_sizes() {
+        assert_eq!(u32::SIZE, Some(serialized_size(&42u32)));
+        assert_eq!(i64::SIZE, Some(serialized_size(&-42i64)));
+        assert_eq!(char::SIZE, Some(serialized_size(&'x')));
+        assert_eq!(String::SIZE, None);
+    }
+
     #[test]
     fn sizing_proofs() {
         // These constants may change in the future. They're just tested here to prove that my
@@ -2247,11 +2542,38 @@ mod tests {
         let expected_tree = trim_lines("0: L[1, 2, 3, 4]");
 
         let mut t = init_tree_from_description_in_file(filename, &input_tree);
-        t.insert(2, 42).unwrap();
+        assert_eq!(t.insert(2, 42).unwrap(), Some(2));
 
         assert_eq!(t.to_description(), expected_tree);
         assert_eq!(t.get(&2).unwrap(), Some(42));
         assert_subtree_valid(&t.root, &mut t.pager_info());
+
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn repeated_inserts_return_previous_values() {
+        let filename = "repeated_inserts_return_previous_values.test";
+        let mut t: BTree<i32, SmallBuffer, u32, u32> = init_tree_in_file(filename);
+        for i in 0..50 {
+            assert_eq!(t.insert(i, i).unwrap(), None);
+        }
+        // enough keys for the replaced ones to be spread over several levels
+        assert!(t.root.is_node());
+        for round in 1..=3 {
+            for i in 0..50 {
+                assert_eq!(
+                    t.insert(i, i + 100 * round).unwrap(),
+                    Some(i + 100 * (round - 1))
+                );
+            }
+        }
+        assert_eq!(t.get(&7).unwrap(), Some(307));
+        assert_subtree_valid(&t.root, &mut t.pager_info());
+
+        drop(t);
+        fs::remove_file(filename).unwrap();
     }
 
     #[test]
@@ -2316,10 +2638,11 @@ mod tests {
     #[test]
     fn leaf_root_split() {
         let filename = "leaf_root_split.test";
+        // ascending keys leave the left page as full as it can be
         let expected_tree = "
-            0: [4] (2)
-            0->0: L[1, 2, 3, 4]
-            0->1: L[5, 6, 7, 8] 
+            0: [6] (2)
+            0->0: L[1, 2, 3, 4, 5, 6]
+            0->1: L[7, 8]
         ";
         let expected_tree = trim_lines(expected_tree);
 
@@ -2336,6 +2659,30 @@ mod tests {
         fs::remove_file(filename).unwrap();
     }
 
+    #[test]
+    fn descending_leaf_root_split() {
+        let filename = "descending_leaf_root_split.test";
+        // descending keys leave the right page as full as it can be
+        let expected_tree = "
+            0: [2] (2)
+            0->0: L[1, 2]
+            0->1: L[3, 4, 5, 6, 7, 8]
+        ";
+        let expected_tree = trim_lines(expected_tree);
+
+        let mut tree: BTree<i32, SmallBuffer, u32, u32> = init_tree_in_file(filename);
+
+        for i in (1..=8).rev() {
+            tree.insert(i, i).unwrap();
+        }
+
+        assert_eq!(&tree.to_description(), &expected_tree);
+        assert_subtree_valid(&tree.root, &mut tree.pager_info());
+
+        drop(tree);
+        fs::remove_file(filename).unwrap();
+    }
+
     #[test]
     fn node_root_split() {
         let filename = "node_root_split.test";
@@ -2350,17 +2697,18 @@ mod tests {
         ";
         let init_tree = trim_lines(init_tree);
 
+        // 23 goes past the end of the rightmost leaf, so that leaf keeps all it can
         let expected_tree = "
             0: [12] (2)
             0->0: [3, 6, 9] (4)
-            0->1: [15, 19] (3)
+            0->1: [15, 21] (3)
             0->0->0: L[1, 2, 3] 
             0->0->1: L[4, 5, 6] 
             0->0->2: L[7, 8, 9]
             0->0->3: L[10, 11, 12] 
             0->1->0: L[13, 14, 15] 
-            0->1->1: L[16, 17, 18, 19] 
-            0->1->2: L[20, 21, 22, 23]
+            0->1->1: L[16, 17, 18, 19, 20, 21] 
+            0->1->2: L[22, 23]
         ";
         let expected_tree = trim_lines(expected_tree);
 
@@ -2384,6 +2732,34 @@ mod tests {
             ";
         let input_tree = trim_lines(input_tree);
 
+        // 11 goes past the end of the rightmost leaf, so that leaf keeps all it can
+        let output_tree = "
+            0: [3, 9] (3)
+            0->0: L[1, 2, 3] 
+            0->1: L[4, 5, 6, 7, 8, 9] 
+            0->2: L[10, 11] 
+            ";
+        let output_tree = trim_lines(output_tree);
+
+        let mut t = init_tree_from_description_in_file(filename, &input_tree);
+        t.insert(11, 11).unwrap();
+
+        assert_eq!(&t.to_description(), &output_tree);
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn split_as_leaf_insert_inside_rightmost_leaf() {
+        let filename = "split_as_leaf_insert_inside_rightmost_leaf.test";
+        let input_tree = "
+            0: [3] (2)
+            0->0: L[1, 2, 3] 
+            0->1: L[4, 5, 6, 7, 8, 9, 11] 
+            ";
+        let input_tree = trim_lines(input_tree);
+
+        // the key isn't past the end of the leaf, so it's split evenly
         let output_tree = "
             0: [3, 7] (3)
             0->0: L[1, 2, 3] 
@@ -2393,9 +2769,38 @@ mod tests {
         let output_tree = trim_lines(output_tree);
 
         let mut t = init_tree_from_description_in_file(filename, &input_tree);
-        t.insert(11, 11).unwrap();
+        t.insert(10, 10).unwrap();
+
+        assert_eq!(&t.to_description(), &output_tree);
+        assert_subtree_valid(&t.root, &mut t.pager_info());
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn split_as_leaf_insert_before_leftmost_leaf() {
+        let filename = "split_as_leaf_insert_before_leftmost_leaf.test";
+        let input_tree = "
+            0: [8] (2)
+            0->0: L[2, 3, 4, 5, 6, 7, 8] 
+            0->1: L[9, 10, 11] 
+            ";
+        let input_tree = trim_lines(input_tree);
+
+        // 1 goes before the start of the leftmost leaf, so the keys on the right stay packed
+        let output_tree = "
+            0: [2, 8] (3)
+            0->0: L[1, 2] 
+            0->1: L[3, 4, 5, 6, 7, 8] 
+            0->2: L[9, 10, 11] 
+            ";
+        let output_tree = trim_lines(output_tree);
+
+        let mut t = init_tree_from_description_in_file(filename, &input_tree);
+        t.insert(1, 1).unwrap();
 
         assert_eq!(&t.to_description(), &output_tree);
+        assert_subtree_valid(&t.root, &mut t.pager_info());
         drop(t);
         fs::remove_file(filename).unwrap();
     }
@@ -2587,6 +2992,48 @@ mod tests {
         fs::remove_file(filename).unwrap();
     }
 
+    #[test]
+    fn merge_relinks_next_leaf() {
+        let filename = "merge_relinks_next_leaf.test";
+        let input_tree = "
+            0: [2, 6] (3)
+            0->0: L[0, 1, 2]
+            0->1: L[5, 6] 
+            0->2: L[7, 8, 9, 10]
+        ";
+        let input_tree = trim_lines(input_tree);
+
+        let mut t = init_tree_from_description_in_file(filename, &input_tree);
+        let mut pager_info = t.pager_info();
+        let dropped = t
+            .root
+            .descendent_node_at_logical_pos(1, &mut pager_info)
+            .unwrap();
+        let dropped_id = dropped.page_id();
+        drop(dropped);
+        t.remove(&6).unwrap();
+
+        // the leaf after the merged pair points back at the page that's left, not the
+        // dropped one, whose id can be handed out again
+        let left = t
+            .root
+            .descendent_node_at_logical_pos(0, &mut pager_info)
+            .unwrap();
+        let next = t
+            .root
+            .descendent_node_at_logical_pos(1, &mut pager_info)
+            .unwrap();
+        assert_eq!(next.leaf_left_sibling().unwrap(), left.page_id());
+        assert_ne!(next.leaf_left_sibling().unwrap(), dropped_id);
+        assert_eq!(left.leaf_right_sibling().unwrap(), next.page_id());
+
+        drop(left);
+        drop(next);
+        drop(pager_info);
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
     #[test]
     fn merge_right_leaf() {
         let filename = "merge_right_leaf.test";
@@ -3043,6 +3490,25 @@ mod tests {
         fs::remove_file(filename).unwrap();
     }
 
+    #[test]
+    fn contains_agrees_with_get() {
+        let filename = "contains_agrees_with_get.test";
+        let mut t: BTree<i32, SmallBuffer, u32, u32> = init_tree_in_file(filename);
+
+        for i in (0..=100).step_by(2) {
+            t.insert(i, i).unwrap();
+        }
+
+        for i in 0..=110 {
+            let expected = t.get(&i).unwrap().is_some();
+            assert_eq!(t.contains(&i).unwrap(), expected);
+            assert_eq!(expected, i <= 100 && i % 2 == 0);
+        }
+
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
     #[test]
     fn basic_iter_test() {
         let filename = "basic_iter_test.test";
@@ -3111,6 +3577,81 @@ mod tests {
         fs::remove_file(filename).unwrap();
     }
 
+    #[test]
+    fn keys_and_values_in_range() {
+        let filename = "keys_and_values_in_range.test";
+        let mut t: BTree<i32, SmallBuffer, String, String> = init_tree_in_file(filename);
+
+        let mut expected = Vec::new();
+        for i in 0..200 {
+            let key = format!("key{:03}", (i * 37) % 200);
+            t.insert(key.clone(), key.to_uppercase()).unwrap();
+            expected.push((key.clone(), key.to_uppercase()));
+        }
+        expected.sort();
+        expected.retain(|(k, _)| k.as_str() >= "key050" && k.as_str() < "key150");
+
+        let min = || KeyLimit::Inclusive(String::from("key050"));
+        let max = || KeyLimit::Exclusive(String::from("key150"));
+        let keys: Vec<_> = t
+            .keys_in_range(min(), max())
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let values: Vec<_> = t
+            .values_in_range(min(), max())
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let (expected_keys, expected_values): (Vec<_>, Vec<_>) = expected.into_iter().unzip();
+        assert_eq!(keys, expected_keys);
+        assert_eq!(values, expected_values);
+
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn string_keys() {
+        let filename = "string_keys.test";
+        let mut t: BTree<i32, SmallBuffer, String, String> = init_tree_in_file(filename);
+
+        let mut expected = Vec::new();
+        for i in 0..500 {
+            let key = format!("key{:04}", (i * 37) % 500);
+            t.insert(key.clone(), key.to_uppercase()).unwrap();
+            expected.push((key.clone(), key.to_uppercase()));
+        }
+        expected.sort();
+
+        let actual: Vec<_> = t
+            .iter(KeyLimit::None, KeyLimit::None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(actual, expected);
+
+        assert_eq!(
+            t.get(&String::from("key0123")).unwrap(),
+            Some(String::from("KEY0123"))
+        );
+        assert_eq!(t.get(&String::from("key")).unwrap(), None);
+        for i in (0..500).step_by(2) {
+            let key = format!("key{:04}", i);
+            assert_eq!(t.remove(&key).unwrap(), Some(key.to_uppercase()));
+        }
+        expected.retain(|(k, _)| k.ends_with(['1', '3', '5', '7', '9']));
+        let actual: Vec<_> = t
+            .iter(KeyLimit::None, KeyLimit::None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(actual, expected);
+
+        drop(t);
+        fs::remove_file(filename).unwrap();
+    }
+
     /*
      * Proptest stuff below here ---------------------------
      */
@@ -3121,7 +3662,7 @@ mod tests {
     ) -> Option<T>
     where
         PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + PartialEq + Clone,
+        T: Key + PartialEq,
     {
         ref_tree
             .iter()
@@ -3144,10 +3685,7 @@ mod tests {
         ref_tree: BTreeMap<T, T>,
         type_str: String,
     }
-    impl<
-            T: Ord + Serialize + DeserializeOwned + Debug + Clone + Arbitrary + 'static + ToString,
-        > ReferenceStateMachine for ReferenceBTree<T>
-    {
+    impl<T: Key + Arbitrary + 'static + ToString> ReferenceStateMachine for ReferenceBTree<T> {
         type State = Self;
         type Transition = TreeOperation<T>;
 
@@ -3192,25 +3730,18 @@ mod tests {
         }
     }
 
-    pub struct BTreeTestWrapper<
-        PB: PageBuffer,
-        T: Ord + Serialize + DeserializeOwned + Debug + Clone,
-    > {
+    pub struct BTreeTestWrapper<PB: PageBuffer, T: Key> {
         tree: BTree<i32, PB, T, T>,
         filename: String,
     }
-    impl<PB: PageBuffer, T: Ord + Serialize + DeserializeOwned + Debug + Clone>
-        BTreeTestWrapper<PB, T>
-    {
+    impl<PB: PageBuffer, T: Key> BTreeTestWrapper<PB, T> {
         fn new(tree: BTree<i32, PB, T, T>, filename: String) -> Self {
             BTreeTestWrapper { tree, filename }
         }
     }
 
-    impl<
-            PB: PageBuffer,
-            T: Ord + Serialize + DeserializeOwned + Debug + Clone + Arbitrary + 'static + ToString,
-        > StateMachineTest for BTree<i32, PB, T, T>
+    impl<PB: PageBuffer, T: Key + Arbitrary + 'static + ToString> StateMachineTest
+        for BTree<i32, PB, T, T>
     {
         type SystemUnderTest = BTreeTestWrapper<PB, T>;
         type Reference = ReferenceBTree<T>;
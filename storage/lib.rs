@@ -26,7 +26,13 @@ mod btree_disk;
 mod generate; // TODO: This should probably be its own crate??
 mod pager;
 
-pub use btree_disk::BTree;
+pub use btree_disk::{BTree, Key, KeyLimit};
+/// The page size is picked by the `PageBuffer` a `Pager` is created with. Sizes other than
+/// 4K and 16K can be used with `SizedBuffer<{ buffer_size_for(page_size) }>`.
+pub use pager::{
+    buffer_size_for, PageBuffer, PageBuffer16K, PageBuffer4K, PageBufferProd, Pager, PagerError,
+    SizedBuffer,
+};
 
 #[derive(Debug)]
 pub enum StorageError {
@@ -80,8 +86,53 @@ pub struct NumericValue {
     digits: Vec<u16>, // each member is a group of 4 digits. Stored in base 10000. Most significant
                       // to least significant
 }
+impl NumericValue {
+    /// Generates a value that fits a column configured with `cfg`: at most `max_precision`
+    /// digits, of which at most `max_scale` come after the decimal point. The integer
+    /// digits are grouped from the decimal point leftwards and the scale digits from it
+    /// rightwards, with `first_group_weight` counting the groups before the point.
+    fn generate_with_cfg(rng: &mut Rng, cfg: &NumericCfg) -> Self {
+        let total_digits = rng.next_value() as usize % (cfg.max_precision + 1);
+        let scale = rng.next_value() as usize % (cfg.max_scale.min(total_digits) + 1);
+        let integer_digits = total_digits - scale;
+
+        // Groups are 4 digits, except the leading integer group and trailing scale group,
+        // which hold whatever is left over
+        let integer_groups = integer_digits.div_ceil(4);
+        let scale_groups = scale.div_ceil(4);
+        let leading_width = 4 - (4 - integer_digits % 4) % 4;
+        let trailing_width = 4 - (4 - scale % 4) % 4;
+
+        let mut digits = Vec::with_capacity(integer_groups + scale_groups);
+        for i in 0..integer_groups {
+            let width = if i == 0 { leading_width } else { 4 };
+            digits.push(u16::generate(rng) % 10u16.pow(width as u32));
+        }
+        for i in 0..scale_groups {
+            let width = if i == scale_groups - 1 {
+                trailing_width
+            } else {
+                4
+            };
+            let padding = 10u16.pow(4 - width as u32);
+            digits.push(u16::generate(rng) % 10u16.pow(width as u32) * padding);
+        }
+
+        // NaN has no digits to constrain, so only signed values are generated
+        let sign = if rng.next_value() % 2 == 1 {
+            NumericValueSign::Positive
+        } else {
+            NumericValueSign::Negative
+        };
+        NumericValue {
+            total_digits: total_digits as u16,
+            first_group_weight: integer_groups as u16,
+            sign,
+            digits,
+        }
+    }
+}
 impl Generate for NumericValue {
-    // TODO: Make this take into account a NumericCfg
     fn generate(rng: &mut Rng) -> Self {
         let total_digits = u16::generate(rng) % 100; // TODO: Undo this modulo later?
         let first_group_weight = u16::generate(rng);
@@ -149,7 +200,7 @@ impl DbType {
     #[allow(dead_code)]
     fn as_generated_value(&self, rng: &mut Rng) -> DbValue {
         match self {
-            DbType::Numeric(_) => DbValue::Numeric(NumericValue::generate(rng)),
+            DbType::Numeric(cfg) => DbValue::Numeric(NumericValue::generate_with_cfg(rng, cfg)),
             DbType::Integer => DbValue::Integer(i32::generate(rng)),
             DbType::Varchar => DbValue::Varchar(String::generate(rng)),
             DbType::Char(size) => {
@@ -175,3 +226,47 @@ struct Row {
     data: Vec<DbValue>,
     schema: Rc<Schema>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{generate::Rng, DbType, DbValue, NumericCfg, NumericValue, NumericValueSign};
+
+    #[test]
+    fn generated_numerics_fit_their_cfg() {
+        let mut rng = Rng::from_seed(7);
+        for (max_precision, max_scale) in [(0, 0), (1, 0), (5, 2), (10, 10), (38, 9)] {
+            let cfg = NumericCfg {
+                max_precision,
+                max_scale,
+            };
+            for _ in 0..200 {
+                let NumericValue {
+                    total_digits,
+                    first_group_weight,
+                    sign,
+                    digits,
+                } = NumericValue::generate_with_cfg(&mut rng, &cfg);
+                let total_digits = total_digits as usize;
+                let integer_groups = first_group_weight as usize;
+                assert!(total_digits <= max_precision);
+                assert!(digits.len() <= total_digits.div_ceil(4) + 1);
+                assert!(digits.len() - integer_groups <= max_scale.div_ceil(4));
+                assert!(digits.iter().all(|d| *d < 10000));
+                assert_ne!(sign, NumericValueSign::NaN);
+            }
+        }
+
+        let ty = DbType::Numeric(Rc::new(NumericCfg {
+            max_precision: 3,
+            max_scale: 1,
+        }));
+        for _ in 0..50 {
+            let DbValue::Numeric(v) = ty.as_generated_value(&mut rng) else {
+                panic!("expected a numeric value");
+            };
+            assert!(v.total_digits <= 3);
+        }
+    }
+}
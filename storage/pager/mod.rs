@@ -8,20 +8,26 @@ use std::fs::File;
 use std::io::Error as IoError;
 use std::os::unix::fs::MetadataExt;
 use std::rc::Rc;
-use std::{collections::HashMap, os::fd::AsRawFd};
+use std::{
+    collections::{HashMap, HashSet},
+    os::fd::AsRawFd,
+};
 
 pub type PageRef<PB> = Rc<RefCell<Page<PB>>>;
 pub type PageId = page::PageId;
 pub type PageBufferOffset = page::PageBufferOffset;
 
-pub use page::{Page, PageBuffer, PageError, PageKind, CELL_POINTER_SIZE, PAGE_SIZE};
+pub use page::{
+    buffer_size_for, Page, PageBuffer, PageBuffer16K, PageBuffer4K, PageBufferProd, PageError,
+    PageKind, SizedBuffer, CELL_POINTER_SIZE,
+};
 
-use serialize::Error as SerdeError;
+use serialize::{from_bytes, to_bytes, Error as SerdeError};
 
 /*
  * Pager Requirements:
  * - Stores up to MAX_PAGE_COUNT pages in memory
- * - MAX_PAGER_MEMORY is divisible by PAGE_SIZE
+ * - MAX_PAGER_MEMORY is divisible by the page size
  * - The memory use should be basically constant
  *      - Can't use an array because if the total number of pages in the db is less than
  *      MAX_PAGE_COUNT, what do we do with the rest of the slots?
@@ -57,13 +63,23 @@ use serialize::Error as SerdeError;
  */
 
 const MAX_PAGER_MEMORY: usize = 1024 * 1000 * 20; // 20 MB
-const MAX_PAGE_COUNT: usize = MAX_PAGER_MEMORY / PAGE_SIZE as usize;
 
 #[derive(Debug)]
 pub enum PagerError {
     Io(IoError),
     Page(PageError),
     Serde(SerdeError),
+    /// The file's first page isn't a header page, so it was written before they were added
+    MissingFileHeader,
+    /// The page id is past the end of the file, or the page was deleted
+    UnknownPage {
+        fd: RawFd,
+        page_id: PageId,
+    },
+    /// Something outside the pager still held a page it needed to write out or drop
+    PageStillBorrowed,
+    /// The fd isn't one of the files the pager was created with
+    UnknownFd(RawFd),
 }
 impl From<IoError> for PagerError {
     fn from(value: IoError) -> Self {
@@ -87,6 +103,14 @@ impl Display for PagerError {
             Self::Io(error) => error.fmt(f),
             Self::Page(error) => error.fmt(f),
             Self::Serde(error) => error.fmt(f),
+            Self::MissingFileHeader => f.write_str("Pager error: The file has no header page"),
+            Self::UnknownPage { fd, page_id } => {
+                write!(f, "Pager error: File {fd} has no page {page_id}")
+            }
+            Self::PageStillBorrowed => f.write_str(
+                "Pager error: A page was still borrowed when it had to be written out or dropped",
+            ),
+            Self::UnknownFd(fd) => write!(f, "Pager error: File {fd} isn't managed by this pager"),
         }
     }
 }
@@ -97,10 +121,17 @@ type PageLookupKey = (RawFd, PageId);
 struct NextPageId {
     raw_fd: RawFd,
     next_id: PageId,
+    /// A new file's header page is written along with its first page, so this is false
+    /// until then
+    header_written: bool,
 }
 impl NextPageId {
-    fn new(raw_fd: RawFd, next_id: PageId) -> Self {
-        NextPageId { raw_fd, next_id }
+    fn new(raw_fd: RawFd, next_id: PageId, header_written: bool) -> Self {
+        NextPageId {
+            raw_fd,
+            next_id,
+            header_written,
+        }
     }
 
     fn matches_fd<Fd: AsRawFd>(&self, fd: Fd) -> bool {
@@ -173,6 +204,19 @@ impl ClockCacheHandler {
     }
 }
 
+/// How well the page cache is working, for tuning its size
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PagerStats {
+    /// Pages requested that were already in the cache
+    pub hits: u64,
+    /// Pages requested that had to be read from disk
+    pub misses: u64,
+    /// Pages dropped from the cache to make room for another
+    pub evictions: u64,
+    /// Dirty pages written to disk, whether on eviction or by `flush_all`
+    pub flushes: u64,
+}
+
 /*
  * TODO: Put proper documentation of how this works here
  */
@@ -183,47 +227,164 @@ pub struct Pager<PB: PageBuffer> {
     page_locations: HashMap<PageLookupKey, usize>,
     location_fd_mapping: HashMap<usize, RawFd>,
     next_page_ids: Vec<NextPageId>,
+    /// Ids of deleted pages in each file, most recently deleted last
+    free_page_ids: HashMap<RawFd, Vec<PageId>>,
     clock_cache: ClockCacheHandler,
     fd_to_file_mapping: HashMap<RawFd, File>,
+    stats: PagerStats,
 }
 impl<PB: PageBuffer> Pager<PB> {
-    pub fn new(file_refs: Vec<File>) -> Self {
-        Self::with_page_count(file_refs, MAX_PAGE_COUNT)
+    /// Fails if any of the files were written with a different page size than `PB` uses
+    pub fn new(file_refs: Vec<File>) -> Result<Self, PagerError> {
+        Self::with_page_count(file_refs, MAX_PAGER_MEMORY / PB::page_size() as usize)
     }
 
-    fn with_page_count(file_refs: Vec<File>, page_count: usize) -> Self {
-        Pager {
+    fn with_page_count(file_refs: Vec<File>, page_count: usize) -> Result<Self, PagerError> {
+        let mut next_page_ids = Vec::with_capacity(file_refs.len());
+        let mut free_page_ids = HashMap::with_capacity(file_refs.len());
+        for file in file_refs.iter() {
+            let (next_id, free_ids, header_written) = match Self::read_file_header(file)? {
+                Some((next_id, free_ids)) => (next_id, free_ids, true),
+                None => (0, Vec::new(), false),
+            };
+            next_page_ids.push(NextPageId::new(file.as_raw_fd(), next_id, header_written));
+            free_page_ids.insert(file.as_raw_fd(), free_ids);
+        }
+        Ok(Pager {
             pages: (0..page_count)
                 .map(|_| Rc::new(RefCell::new(Page::new(0, PageKind::Uninitialized))))
                 .collect(),
             page_locations: HashMap::with_capacity(page_count),
             location_fd_mapping: HashMap::with_capacity(page_count),
-            next_page_ids: file_refs
-                .iter()
-                .map(|file| {
-                    let next_id = Self::calc_page_count(file).unwrap();
-                    NextPageId::new(file.as_raw_fd(), next_id)
-                })
-                .collect(),
+            next_page_ids,
+            free_page_ids,
             clock_cache: ClockCacheHandler::new(page_count),
             fd_to_file_mapping: file_refs.into_iter().map(|r| (r.as_raw_fd(), r)).collect(),
+            stats: PagerStats::default(),
+        })
+    }
+
+    /// The next page id and the free-list recorded in a file's header page, or `None` for
+    /// an empty file
+    fn read_file_header(file: &File) -> Result<Option<(PageId, Vec<PageId>)>, PagerError> {
+        if file.metadata()?.size() == 0 {
+            return Ok(None);
         }
+        let expected = PB::page_size();
+        let found = Page::<PB>::stored_page_size(file)?;
+        if found != expected {
+            return Err(PageError::PageSizeMismatch { expected, found }.into());
+        }
+        let header = Page::<PB>::read_file_header(file)?;
+        if header.kind() != PageKind::FileHeader {
+            return Err(PagerError::MissingFileHeader);
+        }
+        let next_id = from_bytes(&header.get_cell_owned(0))?;
+        // stored most recently deleted first
+        let mut free_ids = (1..header.cell_count())
+            .map(|pos| from_bytes(&header.get_cell_owned(pos)))
+            .collect::<Result<Vec<PageId>, _>>()?;
+        free_ids.reverse();
+        Ok(Some((next_id, free_ids)))
     }
 
-    fn calc_page_count(file: &File) -> Result<u64, PagerError> {
-        let size = file.metadata()?.size();
-        Ok(size / PAGE_SIZE as u64)
+    /// Writes the header page with the next id and as much of the free-list as fits in it.
+    /// The ids that don't fit are the oldest ones, and are lost after reopening, so their
+    /// pages are never reused.
+    fn write_file_header(
+        file: &mut File,
+        next_id: PageId,
+        free_ids: &[PageId],
+    ) -> Result<(), PagerError> {
+        let mut header = Page::<PB>::new(0, PageKind::FileHeader);
+        header.insert_cell(0, &to_bytes(&next_id)?)?;
+        for id in free_ids.iter().rev() {
+            match header.insert_cell(header.cell_count(), &to_bytes(id)?) {
+                Ok(()) => (),
+                Err(PageError::NotEnoughSpace) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        header.write_as_file_header(file)?;
+        Ok(())
+    }
+
+    // so a page is never on disk without a header page before it
+    fn write_file_header_if_missing(
+        next_page_id: &mut NextPageId,
+        free_ids: &[PageId],
+        file: &mut File,
+    ) -> Result<(), PagerError> {
+        if !next_page_id.header_written {
+            Self::write_file_header(file, next_page_id.next_id, free_ids)?;
+            next_page_id.header_written = true;
+        }
+        Ok(())
     }
 
+    /// Writes out every dirty page, and each file's header page
     pub fn flush_all(&mut self) -> Result<(), PagerError> {
         for location in 0..self.pages.len() {
             self.flush_page(location)?;
         }
+        let fds: Vec<RawFd> = self.next_page_ids.iter().map(|npid| npid.raw_fd).collect();
+        for fd in fds {
+            self.flush_file_header(fd)?;
+        }
+        Ok(())
+    }
+
+    /// Like `flush_all`, but only writes out the dirty pages and header page of `fd`
+    pub fn flush_fd<Fd: AsRawFd>(&mut self, fd: Fd) -> Result<(), PagerError> {
+        let fd = fd.as_raw_fd();
+        self.next_id_for_fd(&fd)?;
+        let locations: Vec<usize> = self
+            .location_fd_mapping
+            .iter()
+            .filter(|(_, location_fd)| **location_fd == fd)
+            .map(|(location, _)| *location)
+            .collect();
+        for location in locations {
+            self.flush_page(location)?;
+        }
+        self.flush_file_header(fd)
+    }
+
+    fn flush_file_header(&mut self, fd: RawFd) -> Result<(), PagerError> {
+        let file = self
+            .fd_to_file_mapping
+            .get_mut(&fd)
+            .ok_or(PagerError::UnknownFd(fd))?;
+        let next_page_id = self
+            .next_page_ids
+            .iter_mut()
+            .find(|npid| npid.matches_fd(fd))
+            .ok_or(PagerError::UnknownFd(fd))?;
+        let free_ids = self
+            .free_page_ids
+            .get(&fd)
+            .ok_or(PagerError::UnknownFd(fd))?;
+        Self::write_file_header(file, next_page_id.next_id, free_ids)?;
+        next_page_id.header_written = true;
         Ok(())
     }
 
+    /// False for a file the pager doesn't manage, and for deleted pages
     pub fn file_has_page<Fd: AsRawFd>(&self, fd: &Fd, page_id: PageId) -> bool {
-        &page_id < self.next_id_for_fd(fd).peek_id()
+        self.check_page_exists(fd.as_raw_fd(), page_id).is_ok()
+    }
+
+    // fails for ids past the end of the file, and for ids on its free-list
+    fn check_page_exists(&self, fd: RawFd, page_id: PageId) -> Result<(), PagerError> {
+        let past_end = &page_id >= self.next_id_for_fd(&fd)?.peek_id();
+        let freed = self
+            .free_page_ids
+            .get(&fd)
+            .is_some_and(|ids| ids.contains(&page_id));
+        if past_end || freed {
+            return Err(PagerError::UnknownPage { fd, page_id });
+        }
+        Ok(())
     }
 
     pub fn get_page<Fd: AsRawFd>(
@@ -231,13 +392,15 @@ impl<PB: PageBuffer> Pager<PB> {
         fd: Fd,
         page_id: PageId,
     ) -> Result<PageRef<PB>, PagerError> {
-        assert!(self.file_has_page(&fd, page_id));
+        self.check_page_exists(fd.as_raw_fd(), page_id)?;
         match self.page_locations.get(&(fd.as_raw_fd(), page_id)) {
             Some(loc) => {
+                self.stats.hits += 1;
                 self.clock_cache.set_use_bit(*loc);
                 Ok(self.pages.get(*loc).unwrap().clone())
             }
             None => {
+                self.stats.misses += 1;
                 let page = self.evict_page_and_replace_with(fd.as_raw_fd(), page_id)?;
                 Ok(page)
             }
@@ -246,47 +409,87 @@ impl<PB: PageBuffer> Pager<PB> {
 
     fn flush_page(&mut self, location: usize) -> Result<(), PagerError> {
         // only flush a page location if it's actually and dirty
-        if self.location_fd_mapping.contains_key(&location) {
+        if let Some(fd) = self.location_fd_mapping.get(&location).copied() {
             let page_ref = self.pages.get(location).unwrap();
-            assert_eq!(Rc::strong_count(page_ref), 1, "The reference owned by the pager should be the only reference that exists when we are about to flush a page");
+            // the reference owned by the pager should be the only one left
+            if Rc::strong_count(page_ref) != 1 {
+                return Err(PagerError::PageStillBorrowed);
+            }
+            let page_ref = page_ref.clone();
             let mut page = page_ref.borrow_mut();
-            let fd = self.location_fd_mapping.get(&location).unwrap();
-            let file = self.fd_to_file_mapping.get_mut(fd).unwrap();
             if page.is_dirty() {
-                page.write_to_disk(file)?;
+                self.write_out(fd, &mut page)?;
             }
         }
         Ok(())
     }
 
-    /// Marks a page for deletion by garbage collection at a later point
+    // writes a dirty page to its file, first writing the file's header if it doesn't have one
+    fn write_out(&mut self, fd: RawFd, page: &mut Page<PB>) -> Result<(), PagerError> {
+        let file = self
+            .fd_to_file_mapping
+            .get_mut(&fd)
+            .ok_or(PagerError::UnknownFd(fd))?;
+        let next_page_id = self
+            .next_page_ids
+            .iter_mut()
+            .find(|npid| npid.matches_fd(fd))
+            .ok_or(PagerError::UnknownFd(fd))?;
+        let free_ids = self
+            .free_page_ids
+            .get(&fd)
+            .ok_or(PagerError::UnknownFd(fd))?;
+        Self::write_file_header_if_missing(next_page_id, free_ids, file)?;
+        page.write_to_disk(file)?;
+        self.stats.flushes += 1;
+        Ok(())
+    }
+
+    /// Drops a page from the cache without writing it out, and adds its id to the file's
+    /// free-list. Every id on the free-list is handed out again by `new_page`, so deleting
+    /// a page twice is an error.
     pub fn delete_page<Fd: AsRawFd>(&mut self, fd: Fd, page_id: PageId) -> Result<(), PagerError> {
-        let location = self.page_locations.get(&(fd.as_raw_fd(), page_id)).unwrap();
-        let page_ref = self.pages.get(*location).unwrap();
-        assert_eq!(Rc::strong_count(page_ref), 1, "The reference owned by the pager should be the only reference that exists when we are about to delete a page");
-        // TODO: Somehow mark for deletion or whatever
+        let key = (fd.as_raw_fd(), page_id);
+        self.check_page_exists(key.0, page_id)?;
+        if let Some(location) = self.page_locations.get(&key).copied() {
+            let page_ref = self.pages.get(location).unwrap();
+            if Rc::strong_count(page_ref) != 1 {
+                return Err(PagerError::PageStillBorrowed);
+            }
+            self.page_locations.remove(&key);
+            self.location_fd_mapping.remove(&location);
+        }
+        self.free_page_ids.get_mut(&key.0).unwrap().push(page_id);
         Ok(())
     }
 
     // evicts a page and returns the location of that now usable page
     fn evict_page(&mut self) -> Result<usize, PagerError> {
         let location = self.clock_cache.advance_to_next_evictable_location();
+        self.evict_location(location)?;
+        Ok(location)
+    }
+
+    fn evict_location(&mut self, location: usize) -> Result<(), PagerError> {
         let page_ref = self.pages.get(location).unwrap();
-        assert_eq!(Rc::strong_count(page_ref), 1, "The reference owned by the pager should be the only reference that exists when we are about to evict a page");
+        // the reference owned by the pager should be the only one left
+        if Rc::strong_count(page_ref) != 1 {
+            return Err(PagerError::PageStillBorrowed);
+        }
+        let page_ref = page_ref.clone();
         let mut page = page_ref.borrow_mut();
 
         // handle old page, which may already be in use yet
-        if self.location_fd_mapping.contains_key(&location) {
-            let fd = self.location_fd_mapping.get(&location).unwrap();
-            let file = self.fd_to_file_mapping.get_mut(fd).unwrap();
+        if let Some(fd) = self.location_fd_mapping.get(&location).copied() {
             if page.is_dirty() {
-                page.write_to_disk(file)?;
+                self.write_out(fd, &mut page)?;
             }
-            self.page_locations.remove(&(*fd, page.id()));
+            self.page_locations.remove(&(fd, page.id()));
             self.location_fd_mapping.remove(&location);
+            self.stats.evictions += 1;
         }
 
-        Ok(location)
+        Ok(())
     }
 
     fn evict_page_and_replace_with<Fd: AsRawFd>(
@@ -295,6 +498,16 @@ impl<PB: PageBuffer> Pager<PB> {
         replacement_page_id: PageId,
     ) -> Result<PageRef<PB>, PagerError> {
         let location = self.evict_page()?;
+        self.load_page_at(location, replacement_fd, replacement_page_id)
+    }
+
+    // reads a page from disk into an already evicted location
+    fn load_page_at<Fd: AsRawFd>(
+        &mut self,
+        location: usize,
+        replacement_fd: Fd,
+        replacement_page_id: PageId,
+    ) -> Result<PageRef<PB>, PagerError> {
         let page_ref = self.pages.get(location).unwrap();
 
         let mut page = page_ref.borrow_mut();
@@ -303,7 +516,7 @@ impl<PB: PageBuffer> Pager<PB> {
         let file = self
             .fd_to_file_mapping
             .get(&replacement_fd.as_raw_fd())
-            .unwrap();
+            .ok_or(PagerError::UnknownFd(replacement_fd.as_raw_fd()))?;
         page.replace_contents(file, replacement_page_id)?;
         self.location_fd_mapping
             .insert(location, replacement_fd.as_raw_fd());
@@ -313,18 +526,18 @@ impl<PB: PageBuffer> Pager<PB> {
         Ok(page_ref.clone())
     }
 
-    fn next_id_for_fd<Fd: AsRawFd>(&self, fd: &Fd) -> &NextPageId {
+    fn next_id_for_fd<Fd: AsRawFd>(&self, fd: &Fd) -> Result<&NextPageId, PagerError> {
         self.next_page_ids
             .iter()
             .find(|npid| npid.matches_fd(fd.as_raw_fd()))
-            .unwrap()
+            .ok_or(PagerError::UnknownFd(fd.as_raw_fd()))
     }
 
-    fn next_id_for_fd_mut<Fd: AsRawFd>(&mut self, fd: &Fd) -> &mut NextPageId {
+    fn next_id_for_fd_mut<Fd: AsRawFd>(&mut self, fd: &Fd) -> Result<&mut NextPageId, PagerError> {
         self.next_page_ids
             .iter_mut()
             .find(|npid| npid.matches_fd(fd.as_raw_fd()))
-            .unwrap()
+            .ok_or(PagerError::UnknownFd(fd.as_raw_fd()))
     }
 
     pub fn new_page<Fd: AsRawFd>(
@@ -332,9 +545,19 @@ impl<PB: PageBuffer> Pager<PB> {
         fd: Fd,
         kind: PageKind,
     ) -> Result<PageRef<PB>, PagerError> {
-        let page_id = self.next_id_for_fd_mut(&fd).use_id();
-
+        self.next_id_for_fd(&fd)?;
+        // so the id isn't used up if there's nothing to evict
         let location = self.evict_page()?;
+        // the most recently freed id is reused first, before the file is extended
+        let page_id = match self
+            .free_page_ids
+            .get_mut(&fd.as_raw_fd())
+            .and_then(|ids| ids.pop())
+        {
+            Some(id) => id,
+            None => self.next_id_for_fd_mut(&fd)?.use_id(),
+        };
+
         let page_ref = self.pages.get(location).unwrap();
         let mut page = page_ref.borrow_mut();
         page.reset(page_id, kind);
@@ -345,16 +568,65 @@ impl<PB: PageBuffer> Pager<PB> {
         Ok(page_ref.clone())
     }
 
+    /// Loads up to `count` pages of `fd`, starting at `start_id`, into the cache ahead of a
+    /// sequential scan, so `get_page` finds them there. Ids past the end of the file are
+    /// skipped, and it stops early rather than evict a borrowed page or one it loaded
+    /// itself. Returns how many of the pages are in the cache afterwards.
+    pub fn prefetch<Fd: AsRawFd>(
+        &mut self,
+        fd: Fd,
+        start_id: PageId,
+        count: usize,
+    ) -> Result<usize, PagerError> {
+        let fd = fd.as_raw_fd();
+        let end_id = start_id
+            .saturating_add(count as u64)
+            .min(*self.next_id_for_fd(&fd)?.peek_id());
+        let mut prefetched = HashSet::new();
+        for page_id in start_id..end_id {
+            if let Some(location) = self.page_locations.get(&(fd, page_id)) {
+                prefetched.insert(*location);
+                continue;
+            }
+            let Some(location) = self.next_unpinned_location(&prefetched) else {
+                break;
+            };
+            self.evict_location(location)?;
+            self.load_page_at(location, fd, page_id)?;
+            prefetched.insert(location);
+        }
+        Ok(prefetched.len())
+    }
+
+    // the next location the clock would evict, passing over borrowed pages and those in `skip`
+    fn next_unpinned_location(&mut self, skip: &HashSet<usize>) -> Option<usize> {
+        // the hand clears use bits as it goes, so two laps reach every location
+        for _ in 0..=2 * self.pages.len() {
+            let location = self.clock_cache.advance_to_next_evictable_location();
+            if Rc::strong_count(&self.pages[location]) == 1 && !skip.contains(&location) {
+                return Some(location);
+            }
+            // so the hand moves past it next time
+            self.clock_cache.set_use_bit(location);
+        }
+        None
+    }
+
     pub fn file_from_fd(&self, fd: RawFd) -> Option<&File> {
         self.fd_to_file_mapping.get(&fd)
     }
+
+    /// Counts of cache hits, misses, evictions and flushes since the pager was created
+    pub fn stats(&self) -> PagerStats {
+        self.stats
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs::{self, OpenOptions};
 
-    use page::PageBufferProd;
+    use page::PAGE_SIZE;
     use serialize::{from_bytes, to_bytes};
 
     use super::*;
@@ -362,6 +634,7 @@ mod tests {
     #[test]
     fn size_proofs() {
         assert!(MAX_PAGER_MEMORY % PAGE_SIZE as usize == 0);
+        assert!(MAX_PAGER_MEMORY.is_multiple_of(PageBuffer4K::page_size() as usize));
     }
 
     fn fill_page(page: &mut Page<PageBufferProd>, starting_at: u64) {
@@ -406,7 +679,7 @@ mod tests {
         let fd0 = table0.as_raw_fd();
         let fd1 = table1.as_raw_fd();
         let fd2 = table2.as_raw_fd();
-        let mut pager = Pager::new(vec![table0, table1, table2]);
+        let mut pager = Pager::new(vec![table0, table1, table2]).unwrap();
 
         // set up table 0
         let page0_ref = pager.new_page(fd0, PageKind::Heap).unwrap();
@@ -521,6 +794,42 @@ mod tests {
         fs::remove_file(file2).unwrap();
     }
 
+    #[test]
+    fn page_size_is_checked_on_reopen() {
+        let file = "page_size_is_checked_on_reopen.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager: Pager<PageBuffer4K> = Pager::new(vec![table]).unwrap();
+        let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+        page_ref.borrow_mut().insert_cell(0, &[1, 2, 3]).unwrap();
+        drop(page_ref);
+        pager.flush_all().unwrap();
+        drop(pager);
+
+        let reopen = || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(file)
+                .unwrap()
+        };
+        let pager: Pager<PageBuffer4K> = Pager::new(vec![reopen()]).unwrap();
+        assert!(pager.file_has_page(&fd, 0));
+        assert!(!pager.file_has_page(&fd, 1));
+        drop(pager);
+
+        let res: Result<Pager<PageBufferProd>, _> = Pager::new(vec![reopen()]);
+        assert!(matches!(
+            res,
+            Err(PagerError::Page(PageError::PageSizeMismatch {
+                expected: PAGE_SIZE,
+                found: 4096
+            }))
+        ));
+
+        fs::remove_file(file).unwrap();
+    }
+
     fn count_pages_in_cache_from_fd(pager: &Pager<PageBufferProd>, fd: RawFd) -> usize {
         pager
             .location_fd_mapping
@@ -537,7 +846,7 @@ mod tests {
         let table1 = open_test_file(file1);
         let fd0 = table0.as_raw_fd();
         let fd1 = table1.as_raw_fd();
-        let mut pager = Pager::with_page_count(vec![table0, table1], 3);
+        let mut pager = Pager::with_page_count(vec![table0, table1], 3).unwrap();
 
         /*
          * Plan:
@@ -639,4 +948,327 @@ mod tests {
         fs::remove_file(file0).unwrap();
         fs::remove_file(file1).unwrap();
     }
+
+    #[test]
+    fn stats() {
+        let file = "pager_stats.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::with_page_count(vec![table], 2).unwrap();
+
+        // the third page evicts one of the first two, writing it out
+        for _ in 0..3 {
+            let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+            fill_page(&mut page_ref.borrow_mut(), 0);
+        }
+        assert_eq!(
+            pager.stats(),
+            PagerStats {
+                hits: 0,
+                misses: 0,
+                evictions: 1,
+                flushes: 1,
+            }
+        );
+
+        // page 0 was the one evicted, so it has to be read back in, evicting another
+        assert_eq!(vec![0, 0, 0], get_first_cell_from_page(&mut pager, fd, 0));
+        assert_eq!(vec![0, 0, 0], get_first_cell_from_page(&mut pager, fd, 0));
+        assert_eq!(
+            pager.stats(),
+            PagerStats {
+                hits: 1,
+                misses: 1,
+                evictions: 2,
+                flushes: 2,
+            }
+        );
+
+        // only the page that hasn't been written out yet is dirty
+        pager.flush_all().unwrap();
+        assert_eq!(pager.stats().flushes, 3);
+
+        drop(pager);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn new_page_reuses_freed_ids_last_in_first_out() {
+        let file = "pager_reuse_freed_ids.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::with_page_count(vec![table], 3).unwrap();
+        for _ in 0..6 {
+            let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+            fill_page(&mut page_ref.borrow_mut(), 0);
+        }
+        pager.flush_all().unwrap();
+        for id in [1, 4, 2] {
+            pager.delete_page(fd, id).unwrap();
+        }
+
+        let mut new_ids = Vec::new();
+        for _ in 0..4 {
+            let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+            new_ids.push(page_ref.borrow().id());
+        }
+        // then the file is extended once the free-list is empty
+        assert_eq!(new_ids, vec![2, 4, 1, 6]);
+
+        // the reused pages were left empty, and that overwrites what was in them before
+        pager.flush_all().unwrap();
+        drop(pager);
+        let table = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(file)
+            .unwrap();
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::with_page_count(vec![table], 3).unwrap();
+        assert!(pager.free_page_ids[&fd].is_empty());
+        for id in [1, 2, 4] {
+            assert_eq!(pager.get_page(fd, id).unwrap().borrow().cell_count(), 0);
+        }
+        assert_eq!(vec![30; 3], get_first_cell_from_page(&mut pager, fd, 3));
+
+        drop(pager);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn freed_ids_are_only_reused_once() {
+        let file = "pager_freed_ids_reused_once.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::with_page_count(vec![table], 3).unwrap();
+        for _ in 0..3 {
+            pager.new_page(fd, PageKind::Heap).unwrap();
+        }
+
+        pager.delete_page(fd, 1).unwrap();
+        assert!(matches!(
+            pager.delete_page(fd, 1),
+            Err(PagerError::UnknownPage { page_id: 1, .. })
+        ));
+        assert!(matches!(
+            pager.delete_page(fd, 3),
+            Err(PagerError::UnknownPage { page_id: 3, .. })
+        ));
+        assert_eq!(pager.free_page_ids[&fd], vec![1]);
+
+        // neither failed delete put an id on the free-list
+        let first = pager.new_page(fd, PageKind::Heap).unwrap();
+        let second = pager.new_page(fd, PageKind::Heap).unwrap();
+        assert_eq!((first.borrow().id(), second.borrow().id()), (1, 3));
+
+        drop(first);
+        drop(second);
+        drop(pager);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn flush_fd() {
+        let file_a = "pager_flush_fd_a.test";
+        let file_b = "pager_flush_fd_b.test";
+        let table_a = open_test_file(file_a);
+        let table_b = open_test_file(file_b);
+        let fd_a = table_a.as_raw_fd();
+        let fd_b = table_b.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::new(vec![table_a, table_b]).unwrap();
+
+        for _ in 0..3 {
+            for fd in [fd_a, fd_b] {
+                let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+                fill_page(&mut page_ref.borrow_mut(), fd as u64);
+            }
+        }
+
+        pager.flush_fd(fd_a).unwrap();
+        assert_eq!(pager.stats().flushes, 3);
+        // three pages after the header page, and nothing written to the other file
+        let page_size = PageBufferProd::page_size() as u64;
+        assert_eq!(fs::metadata(file_a).unwrap().len(), 4 * page_size);
+        assert_eq!(fs::metadata(file_b).unwrap().len(), 0);
+
+        // the pages that were written out are clean now
+        pager.flush_all().unwrap();
+        assert_eq!(pager.stats().flushes, 6);
+        assert_eq!(fs::metadata(file_b).unwrap().len(), 4 * page_size);
+
+        drop(pager);
+        fs::remove_file(file_a).unwrap();
+        fs::remove_file(file_b).unwrap();
+    }
+
+    #[test]
+    fn lookup_and_borrow_errors() {
+        let file = "pager_lookup_and_borrow_errors.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::with_page_count(vec![table], 2).unwrap();
+
+        let unknown_fd = fd + 100;
+        assert!(matches!(
+            pager.get_page(unknown_fd, 0),
+            Err(PagerError::UnknownFd(f)) if f == unknown_fd
+        ));
+        assert!(matches!(
+            pager.new_page(unknown_fd, PageKind::Heap),
+            Err(PagerError::UnknownFd(_))
+        ));
+        assert!(matches!(
+            pager.get_page(fd, 0),
+            Err(PagerError::UnknownPage { fd: f, page_id: 0 }) if f == fd
+        ));
+
+        // with both cache slots held, there's nothing to evict
+        let first = pager.new_page(fd, PageKind::Heap).unwrap();
+        let second = pager.new_page(fd, PageKind::Heap).unwrap();
+        assert!(matches!(
+            pager.new_page(fd, PageKind::Heap),
+            Err(PagerError::PageStillBorrowed)
+        ));
+        assert!(matches!(
+            pager.delete_page(fd, 0),
+            Err(PagerError::PageStillBorrowed)
+        ));
+        assert!(matches!(
+            pager.flush_all(),
+            Err(PagerError::PageStillBorrowed)
+        ));
+
+        // nothing was left half done, so it all works once the pages are let go
+        drop(first);
+        drop(second);
+        pager.delete_page(fd, 1).unwrap();
+        pager.flush_all().unwrap();
+        assert!(pager.get_page(fd, 0).is_ok());
+        assert!(!pager.file_has_page(&fd, 2));
+
+        // a deleted page is gone, and ids that were never handed out can't be deleted
+        assert!(!pager.file_has_page(&fd, 1));
+        assert!(matches!(
+            pager.get_page(fd, 1),
+            Err(PagerError::UnknownPage { page_id: 1, .. })
+        ));
+        assert!(matches!(
+            pager.delete_page(fd, 2),
+            Err(PagerError::UnknownPage { page_id: 2, .. })
+        ));
+        assert!(matches!(
+            pager.delete_page(unknown_fd, 0),
+            Err(PagerError::UnknownFd(_))
+        ));
+
+        drop(pager);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn file_header_survives_reopening() {
+        let file = "pager_file_header.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::with_page_count(vec![table], 3).unwrap();
+        for _ in 0..5 {
+            let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+            fill_page(&mut page_ref.borrow_mut(), 0);
+        }
+        // page 4 is never written, so the file ends at page 3
+        pager.delete_page(fd, 4).unwrap();
+        pager.delete_page(fd, 1).unwrap();
+        pager.flush_all().unwrap();
+        drop(pager);
+
+        let table = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(file)
+            .unwrap();
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::<PageBufferProd>::with_page_count(vec![table], 3).unwrap();
+        assert_eq!(pager.free_page_ids[&fd], vec![4, 1]);
+        // the freed ids aren't pages, but still count towards the next id
+        assert!(!pager.file_has_page(&fd, 4) && pager.file_has_page(&fd, 3));
+        assert_eq!(pager.next_id_for_fd(&fd).unwrap().peek_id(), &5);
+        let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+        assert_eq!(page_ref.borrow().id(), 1);
+        drop(page_ref);
+        for id in [0, 2, 3] {
+            assert_eq!(
+                vec![id * 10; 3],
+                get_first_cell_from_page(&mut pager, fd, id)
+            );
+        }
+        drop(pager);
+
+        // a file whose first page is some other kind of page predates header pages
+        let mut table = open_test_file(file);
+        Page::<PageBufferProd>::new(0, PageKind::Heap)
+            .write_as_file_header(&mut table)
+            .unwrap();
+        assert!(matches!(
+            Pager::<PageBufferProd>::new(vec![table]),
+            Err(PagerError::MissingFileHeader)
+        ));
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn prefetch() {
+        let file = "pager_prefetch.test";
+        let table = open_test_file(file);
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::with_page_count(vec![table], 5).unwrap();
+        for _ in 0..5 {
+            let page_ref = pager.new_page(fd, PageKind::Heap).unwrap();
+            fill_page(&mut page_ref.borrow_mut(), 0);
+        }
+        pager.flush_all().unwrap();
+        drop(pager);
+
+        let table = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(file)
+            .unwrap();
+        let fd = table.as_raw_fd();
+        let mut pager = Pager::with_page_count(vec![table], 3).unwrap();
+        let pinned = pager.get_page(fd, 0).unwrap();
+
+        // only two locations are free, and the pinned page keeps its place
+        assert_eq!(pager.prefetch(fd, 1, 10).unwrap(), 2);
+        assert_eq!(pinned.borrow().id(), 0);
+        assert_eq!(
+            vec![10, 10, 10],
+            get_first_cell_from_page(&mut pager, fd, 1)
+        );
+        assert_eq!(
+            vec![20, 20, 20],
+            get_first_cell_from_page(&mut pager, fd, 2)
+        );
+        assert_eq!(pager.stats().misses, 1);
+        assert_eq!(pager.stats().hits, 2);
+        drop(pinned);
+
+        // ids past the end of the file are left out
+        assert_eq!(pager.prefetch(fd, 3, 10).unwrap(), 2);
+        assert_eq!(
+            vec![30, 30, 30],
+            get_first_cell_from_page(&mut pager, fd, 3)
+        );
+        assert_eq!(
+            vec![40, 40, 40],
+            get_first_cell_from_page(&mut pager, fd, 4)
+        );
+        assert_eq!(pager.stats().misses, 1);
+
+        // a run longer than the cache stops once it's full of the run's own pages
+        assert_eq!(pager.prefetch(fd, 0, 5).unwrap(), 3);
+
+        drop(pager);
+        fs::remove_file(file).unwrap();
+    }
 }
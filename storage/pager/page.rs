@@ -57,10 +57,12 @@ use std::{
 pub type PageId = u64;
 pub type PageBufferOffset = u16;
 
+/// The page size used by `PageBufferProd`. Other sizes can be picked by using a different
+/// `SizedBuffer` as the `PageBuffer`
 pub const PAGE_SIZE: PageBufferOffset = 4096 * 4; // 16KB
-pub const PAGE_BUFFER_SIZE: PageBufferOffset =
-    PAGE_SIZE - mem::size_of::<PageHeader>() as PageBufferOffset;
-const HEADER_VERSION: u8 = 1;
+pub const PAGE_HEADER_SIZE: PageBufferOffset = mem::size_of::<PageHeader>() as PageBufferOffset;
+pub const PAGE_BUFFER_SIZE: PageBufferOffset = PAGE_SIZE - PAGE_HEADER_SIZE;
+const HEADER_VERSION: u8 = 2;
 // the byte values spell PAGE
 const ALIGNMENT_GUARD_VALUE: u32 = u32::from_be_bytes([50, 41, 47, 45]);
 pub const CELL_POINTER_SIZE: u16 = mem::size_of::<CellPointer>() as u16;
@@ -71,6 +73,16 @@ pub enum PageError {
     SerdeError(SerdeError),
     NotEnoughSpace,
     Corrupted,
+    /// The file was written with pages of a different size than the ones being read
+    PageSizeMismatch {
+        expected: PageBufferOffset,
+        found: PageBufferOffset,
+    },
+    /// The page's header uses a layout this version doesn't know how to read
+    UnsupportedVersion {
+        found: u8,
+        supported: u8,
+    },
 }
 impl From<IoError> for PageError {
     fn from(value: IoError) -> Self {
@@ -90,6 +102,14 @@ impl Display for PageError {
             Self::SerdeError(error) => error.fmt(f),
             Self::NotEnoughSpace => f.write_str("Page error: Not enough space"),
             Self::Corrupted => f.write_str("Page error: Corrupted"),
+            Self::PageSizeMismatch { expected, found } => write!(
+                f,
+                "Page error: Expected pages of {expected} bytes, but the file has pages of {found} bytes"
+            ),
+            Self::UnsupportedVersion { found, supported } => write!(
+                f,
+                "Page error: The page header has version {found}, but only version {supported} can be read"
+            ),
         }
     }
 }
@@ -133,6 +153,8 @@ pub enum PageKind {
     Heap,
     BTreeNode,
     BTreeLeaf,
+    /// The first page of a file, where the pager keeps what it needs to reopen it
+    FileHeader,
 }
 
 // TODO: Add CRC check in addition to the checksum
@@ -152,6 +174,9 @@ pub enum PageKind {
 ///
 /// Our max page size will be 16KB, so free_space_start and free_space_end only need to be u16. If
 /// the page size increases, those fields will need to use larger types
+///
+/// page_size is stored so that a file can't be read back with a different page size than it was
+/// written with
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct PageHeader {
@@ -169,11 +194,11 @@ pub struct PageHeader {
     free_space_start: PageBufferOffset,       // 2, 36
     free_space_end: PageBufferOffset,         // 2, 38
     total_free_space: PageBufferOffset,       // 2, 40
+    page_size: PageBufferOffset,              // 2, 42
+    _padding2: u16,                           // 2, 44
+    _padding3: u32,                           // 4, 48
 }
 
-// TODO: Make this a trait that just defines a buffer size, add a production implementation using
-// the default size. Tests elsewhere can define a smaller buffer and use those
-
 pub trait PageBuffer {
     fn new() -> Self
     where
@@ -181,6 +206,11 @@ pub trait PageBuffer {
 
     fn buffer_size() -> u16;
 
+    /// The size of a whole page using this buffer, which is what's read from and written to disk
+    fn page_size() -> PageBufferOffset {
+        PAGE_HEADER_SIZE + Self::buffer_size()
+    }
+
     fn data(&self) -> &[u8];
 
     fn data_mut(&mut self) -> &mut [u8];
@@ -203,20 +233,29 @@ pub trait PageBuffer {
     }
 }
 
+/// The buffer size that makes pages `page_size` bytes long
+pub const fn buffer_size_for(page_size: PageBufferOffset) -> usize {
+    (page_size - PAGE_HEADER_SIZE) as usize
+}
+
+/// A page buffer of `SIZE` bytes. Page sizes should keep this a multiple of 8, so the page
+/// has no padding after the buffer.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
-pub struct PageBufferProd {
-    data: [u8; PAGE_BUFFER_SIZE as usize],
+pub struct SizedBuffer<const SIZE: usize> {
+    data: [u8; SIZE],
 }
-impl PageBuffer for PageBufferProd {
+pub type PageBuffer4K = SizedBuffer<{ buffer_size_for(4096) }>;
+pub type PageBuffer16K = SizedBuffer<{ buffer_size_for(4096 * 4) }>;
+pub type PageBufferProd = PageBuffer16K;
+
+impl<const SIZE: usize> PageBuffer for SizedBuffer<SIZE> {
     fn new() -> Self {
-        PageBufferProd {
-            data: [0; PAGE_BUFFER_SIZE as usize],
-        }
+        SizedBuffer { data: [0; SIZE] }
     }
 
     fn buffer_size() -> u16 {
-        PAGE_BUFFER_SIZE
+        SIZE as u16
     }
 
     fn data(&self) -> &[u8] {
@@ -228,6 +267,17 @@ impl PageBuffer for PageBufferProd {
     }
 }
 
+/// The rest of the header is only laid out as `PageHeader` expects when the version matches
+fn check_header_version(found: u8) -> Result<(), PageError> {
+    if found != HEADER_VERSION {
+        return Err(PageError::UnsupportedVersion {
+            found,
+            supported: HEADER_VERSION,
+        });
+    }
+    Ok(())
+}
+
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct Page<PB: PageBuffer> {
@@ -247,6 +297,9 @@ impl<PB: PageBuffer> Page<PB> {
             free_space_start: 0,
             free_space_end: PB::buffer_size(),
             total_free_space: PB::buffer_size(),
+            page_size: PB::page_size(),
+            _padding2: 0,
+            _padding3: 0,
             page_id: id,
             overflow_page_id: None,
         };
@@ -301,12 +354,34 @@ impl<PB: PageBuffer> Page<PB> {
         source: &F,
         page_id: PageId,
     ) -> Result<(), PageError> {
+        self.read_at(source, Self::file_offset(page_id))
+    }
+
+    /// Reads the header page at the start of `source`, which comes before the page with id 0
+    pub fn read_file_header<F: FileExt>(source: &F) -> Result<Self, PageError> {
+        let mut page = Page::new(0, PageKind::Uninitialized);
+        page.read_at(source, 0)?;
+        Ok(page)
+    }
+
+    // a file starts with its header page, so page ids count from the page after it
+    fn file_offset(page_id: PageId) -> u64 {
+        (page_id + 1) * PB::page_size() as u64
+    }
+
+    fn read_at<F: FileExt>(&mut self, source: &F, offset: u64) -> Result<(), PageError> {
         let buf = self.as_slice_mut();
-        let offset = page_id * PAGE_SIZE as u64;
         // make read all
         Self::read_entire_page(source, buf, offset)?;
 
         // new page should now have values from disk
+        check_header_version(self.header.header_version)?;
+        if self.header.page_size != PB::page_size() {
+            return Err(PageError::PageSizeMismatch {
+                expected: PB::page_size(),
+                found: self.header.page_size,
+            });
+        }
         let checksum = self.calc_checksum();
         if checksum != self.header.checksum {
             return Err(PageError::Corrupted);
@@ -314,12 +389,23 @@ impl<PB: PageBuffer> Page<PB> {
         Ok(())
     }
 
+    /// Reads the page size recorded in the first page of `source`, without needing the rest of
+    /// the page to be the size `PB` expects
+    pub fn stored_page_size<F: FileExt>(source: &F) -> Result<PageBufferOffset, PageError> {
+        let mut page: Page<PB> = Page::new(0, PageKind::Uninitialized);
+        let header = &mut page.as_slice_mut()[..PAGE_HEADER_SIZE as usize];
+        source.read_exact_at(header, 0)?;
+        check_header_version(page.header.header_version)?;
+        Ok(page.header.page_size)
+    }
+
+    // the header and buffer are laid out back to back, so this covers every byte of both
     fn as_slice(&self) -> &[u8] {
-        unsafe { slice::from_raw_parts(self as *const Self as *const u8, PAGE_SIZE.into()) }
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, PB::page_size().into()) }
     }
 
     fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { slice::from_raw_parts_mut(self as *mut Self as *mut u8, PAGE_SIZE.into()) }
+        unsafe { slice::from_raw_parts_mut(self as *mut Self as *mut u8, PB::page_size().into()) }
     }
 
     fn read_entire_page<F: FileExt>(
@@ -327,7 +413,7 @@ impl<PB: PageBuffer> Page<PB> {
         buf: &mut [u8],
         offset: u64,
     ) -> Result<(), PageError> {
-        assert!(buf.len() == PAGE_SIZE as usize);
+        assert!(buf.len() == PB::page_size() as usize);
         source.read_exact_at(buf, offset)?;
         Ok(())
     }
@@ -337,14 +423,22 @@ impl<PB: PageBuffer> Page<PB> {
         buf: &[u8],
         offset: u64,
     ) -> Result<(), PageError> {
-        assert!(buf.len() == PAGE_SIZE as usize);
+        assert!(buf.len() == PB::page_size() as usize);
         dest.write_all_at(buf, offset)?;
         Ok(())
     }
 
     pub fn write_to_disk<F: FileExt>(&mut self, dest: &mut F) -> Result<(), PageError> {
+        self.write_at(dest, Self::file_offset(self.header.page_id))
+    }
+
+    /// Writes this page as the header page at the start of `dest`
+    pub fn write_as_file_header<F: FileExt>(&mut self, dest: &mut F) -> Result<(), PageError> {
+        self.write_at(dest, 0)
+    }
+
+    fn write_at<F: FileExt>(&mut self, dest: &mut F, offset: u64) -> Result<(), PageError> {
         self.defragment()?;
-        let offset = self.header.page_id * PAGE_SIZE as u64;
         // setting dirty flag before slice cast and write to:
         // 1: Make the effects on other vars easier to reason about.
         // 2: By definition the page on disk should be considered clean
@@ -449,6 +543,8 @@ impl<PB: PageBuffer> Page<PB> {
         self.header.cell_count = 0;
         self.header.overflow_page_id = None;
         self.header.flags = PageFlags { flags: 0 };
+        // so a reused page id's old contents are overwritten even if nothing is added
+        self.header.flags.set_dirty(true);
     }
 
     fn make_room_for_pointer(&mut self, cell_position: u16) {
@@ -581,10 +677,12 @@ mod tests {
         assert_eq!(mem::size_of::<PageKind>(), 1);
         assert_eq!(mem::size_of::<Option<PageId>>(), 16);
         assert_eq!(mem::size_of::<Option<u16>>(), 4);
-        assert_eq!(mem::size_of::<PageHeader>(), 40);
+        assert_eq!(mem::size_of::<PageHeader>(), 48);
         assert_eq!(mem::size_of::<PageBufferProd>(), PAGE_BUFFER_SIZE as usize);
         assert_eq!(mem::size_of::<Page<PageBufferProd>>(), PAGE_SIZE as usize);
         assert_eq!(PAGE_BUFFER_SIZE % 8, 0);
+        assert_eq!(mem::size_of::<Page<PageBuffer4K>>(), 4096);
+        assert_eq!(PageBuffer4K::page_size(), 4096);
         assert_eq!(mem::size_of::<CellPointer>(), 4);
         assert_eq!(
             CELL_POINTER_SIZE as usize,
@@ -875,6 +973,44 @@ mod tests {
         fs::remove_file(filename).unwrap();
     }
 
+    #[test]
+    fn header_version_is_checked_on_read() {
+        let filename = "header_version_is_checked_on_read.test";
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(filename)
+            .unwrap();
+
+        let mut page: Page<PageBufferProd> = Page::new(0, PageKind::Heap);
+        page.insert_cell(0, &[1, 2, 3]).unwrap();
+        page.write_as_file_header(&mut file).unwrap();
+        page.write_to_disk(&mut file).unwrap();
+        let offset = mem::offset_of!(PageHeader, header_version) as u64;
+        file.write_all_at(&[HEADER_VERSION + 1], offset).unwrap();
+        file.write_all_at(&[HEADER_VERSION + 1], PAGE_SIZE as u64 + offset)
+            .unwrap();
+
+        let expected_err = |res: Result<_, PageError>| {
+            matches!(
+                res,
+                Err(PageError::UnsupportedVersion { found, supported: HEADER_VERSION })
+                    if found == HEADER_VERSION + 1
+            )
+        };
+        assert!(expected_err(
+            Page::<PageBufferProd>::from_disk(&file, 0).map(|_| ())
+        ));
+        assert!(expected_err(
+            Page::<PageBufferProd>::stored_page_size(&file).map(|_| ())
+        ));
+
+        drop(file);
+        fs::remove_file(filename).unwrap();
+    }
+
     #[test]
     fn to_from_disk_basics() {
         let filename = "to_from_disk_basics.test";
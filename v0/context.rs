@@ -0,0 +1,81 @@
+//! Context frames for errors, so a failed statement can report the chain of
+//! operations from the SQL text down to the error that caused it.
+//!
+//! Frames are recorded in every build. Building one only happens once an error
+//! has occurred, so statements that succeed don't pay for them.
+
+/// An error along with frames describing what was being done when it happened,
+/// innermost first. The frames are kept beside the error rather than wrapping it,
+/// so the error is matched on the same way whether or not it has any.
+#[derive(Debug)]
+pub struct Traced<E> {
+    pub error: E,
+    pub frames: Vec<String>,
+}
+impl<E> Traced<E> {
+    pub fn new(error: E, frame: String) -> Self {
+        Traced {
+            error,
+            frames: vec![frame],
+        }
+    }
+
+    pub fn with_frame(mut self, frame: String) -> Self {
+        self.frames.push(frame);
+        self
+    }
+
+    /// Converts the error, keeping its frames
+    pub fn map<F>(self, f: impl FnOnce(E) -> F) -> Traced<F> {
+        Traced {
+            error: f(self.error),
+            frames: self.frames,
+        }
+    }
+}
+
+/// An error that can be wrapped with a frame describing what was being done
+/// when it happened
+pub trait Contextual: Sized {
+    fn with_frame(self, frame: String) -> Self;
+
+    /// The error with any context frames stripped away
+    fn root(&self) -> &Self;
+
+    fn into_root(self) -> Self;
+}
+
+pub trait ResultExt<T, E> {
+    fn context<F: FnOnce() -> String>(self, frame: F) -> Result<T, E>;
+}
+impl<T, E: Contextual> ResultExt<T, E> for Result<T, E> {
+    fn context<F: FnOnce() -> String>(self, frame: F) -> Result<T, E> {
+        self.map_err(|err| err.with_frame(frame()))
+    }
+}
+
+/// Implements `Contextual` for an error enum with a `Context(String, Box<Self>)` variant
+macro_rules! impl_contextual {
+    ($err:ty) => {
+        impl $crate::context::Contextual for $err {
+            fn with_frame(self, frame: String) -> Self {
+                Self::Context(frame, Box::new(self))
+            }
+
+            fn root(&self) -> &Self {
+                match self {
+                    Self::Context(_, err) => $crate::context::Contextual::root(err.as_ref()),
+                    err => err,
+                }
+            }
+
+            fn into_root(self) -> Self {
+                match self {
+                    Self::Context(_, err) => $crate::context::Contextual::into_root(*err),
+                    err => err,
+                }
+            }
+        }
+    };
+}
+pub(crate) use impl_contextual;
@@ -0,0 +1,127 @@
+//! Writing query results in formats other tools can read
+
+use std::io::{self, Write};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    storage::{Row, Schema},
+    DbValue,
+};
+
+/// Records are separated by CRLF, as RFC 4180 specifies
+const CSV_LINE_END: &str = "\r\n";
+
+/// Quotes a field if it contains anything that would otherwise break up the record,
+/// doubling any quotes inside it
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn csv_record<'a>(
+    writer: &mut impl Write,
+    fields: impl Iterator<Item = &'a str>,
+) -> io::Result<()> {
+    let line: Vec<String> = fields.map(csv_field).collect();
+    writer.write_all(line.join(",").as_bytes())?;
+    writer.write_all(CSV_LINE_END.as_bytes())
+}
+
+pub(crate) fn write_csv_header(writer: &mut impl Write, schema: &Schema) -> io::Result<()> {
+    csv_record(writer, schema.columns().map(|c| c.name.as_str()))
+}
+
+pub(crate) fn write_csv_row(writer: &mut impl Write, row: &Row) -> io::Result<()> {
+    // floats use `Display`, which is the shortest text that parses back to the same value
+    let fields: Vec<String> = row
+        .data
+        .iter()
+        .map(|val| match val {
+            DbValue::String(s) | DbValue::Char(s) => s.clone(),
+            _ => val.to_string(),
+        })
+        .collect();
+    csv_record(writer, fields.iter().map(|f| f.as_str()))
+}
+
+/// A row viewed as a JSON object keyed by its column names
+struct JsonRow<'a> {
+    schema: &'a Schema,
+    row: &'a Row,
+}
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.row.data.len()))?;
+        for (col, val) in self.schema.columns().zip(self.row.data.iter()) {
+            match val {
+                DbValue::String(s) | DbValue::Char(s) => map.serialize_entry(&col.name, s)?,
+                DbValue::Integer(i) => map.serialize_entry(&col.name, i)?,
+                DbValue::UnsignedInt(u) => map.serialize_entry(&col.name, u)?,
+                DbValue::SmallInt(i) => map.serialize_entry(&col.name, i)?,
+                DbValue::Float(f) => map.serialize_entry(&col.name, &f.as_f64())?,
+            }
+        }
+        map.end()
+    }
+}
+
+pub(crate) fn write_json_row(
+    writer: &mut impl Write,
+    schema: &Schema,
+    row: &Row,
+) -> io::Result<()> {
+    serde_json::to_writer(writer, &JsonRow { schema, row }).map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Column, DbFloat, DbType};
+
+    #[test]
+    fn csv_quotes_fields_when_needed() {
+        let schema = Schema::new(vec![
+            Column::new(String::from("name"), DbType::String),
+            Column::new(String::from("a,b"), DbType::Float),
+        ]);
+        let row = Row::new(vec![
+            DbValue::String(String::from("say \"hi\", then leave")),
+            DbValue::Float(DbFloat::new(0.1)),
+        ]);
+
+        let mut out = Vec::new();
+        write_csv_header(&mut out, &schema).unwrap();
+        write_csv_row(&mut out, &row).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,\"a,b\"\r\n\"say \"\"hi\"\", then leave\",0.1\r\n"
+        );
+    }
+
+    #[test]
+    fn json_rows_are_keyed_by_column() {
+        let schema = Schema::new(vec![
+            Column::new(String::from("name"), DbType::String),
+            Column::new(String::from("n"), DbType::Integer),
+            Column::new(String::from("u"), DbType::UnsignedInt),
+            Column::new(String::from("f"), DbType::Float),
+        ]);
+        let row = Row::new(vec![
+            DbValue::String(String::from("a \"quote\"")),
+            DbValue::Integer(-3),
+            DbValue::UnsignedInt(u64::MAX),
+            DbValue::Float(DbFloat::new(0.5)),
+        ]);
+
+        let mut out = Vec::new();
+        write_json_row(&mut out, &schema, &row).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"name":"a \"quote\"","n":-3,"u":18446744073709551615,"f":0.5}"#
+        );
+    }
+}
@@ -7,18 +7,31 @@ use crate::DbFloat;
 
 pub struct RNG {
     rng: ChaCha8Rng,
+    seed: u64,
 }
 impl RNG {
     /// Creates a new generator using a random seed.
     pub fn new() -> Self {
         let seed: u64 = rand::random();
-        RNG::from_seed(seed)
+        RNG::with_seed(seed)
+    }
+
+    /// Creates a new generator using the provided seed. Generators with the same
+    /// seed produce the same values.
+    pub fn with_seed(seed: u64) -> Self {
+        let rng = ChaCha8Rng::seed_from_u64(seed);
+        RNG { rng, seed }
     }
 
     /// Creates a new generator using the provided seed
+    #[deprecated(note = "use `RNG::with_seed`")]
     pub fn from_seed(seed: u64) -> Self {
-        let rng = ChaCha8Rng::seed_from_u64(seed);
-        RNG { rng }
+        RNG::with_seed(seed)
+    }
+
+    /// The seed this generator was created with, so a run can be replayed with `with_seed`
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
     pub fn next_value(&mut self) -> u32 {
@@ -115,7 +128,7 @@ impl Generate for char {
             .expect("Failed a u32->char conversion that should have already been proven to work.")
     }
 }
-const DISSALOWED_CHARS: [char; 9] = ['*', ',', ';', '=', '(', ')', '<', '>', '\''];
+const DISSALOWED_CHARS: [char; 8] = ['*', ',', ';', '=', '(', ')', '<', '>'];
 fn to_useful_char(n: u32) -> Option<char> {
     let ch = char::from_u32(n)?;
     if ch.is_control() || DISSALOWED_CHARS.contains(&ch) {
@@ -124,3 +137,21 @@ fn to_useful_char(n: u32) -> Option<char> {
         Some(ch)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Schema;
+
+    #[test]
+    fn same_seed_generates_same_values() {
+        let mut original = RNG::new();
+        let mut replay = RNG::with_seed(original.seed());
+
+        let schema = Schema::generate(&mut original);
+        assert_eq!(schema, Schema::generate(&mut replay));
+        for _ in 0..10 {
+            assert_eq!(schema.gen_row(&mut original), schema.gen_row(&mut replay));
+        }
+    }
+}
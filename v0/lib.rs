@@ -2,28 +2,46 @@ use std::{
     borrow::Cow,
     collections::HashSet,
     fmt,
-    hash::Hash,
+    hash::{Hash, Hasher},
+    io,
     path::Path,
-    sync::{Mutex, MutexGuard, PoisonError},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
 };
 
+use context::{impl_contextual, Contextual, ResultExt, Traced};
 use generate::Generate;
-use query::{QueryError, QueryResult, ResultRows};
+use query::{tokenize::Position, PreparedQuery, QueryError, QueryResult, ResultRows};
 use serde::{self, Deserialize, Serialize};
-use storage::{Row, Schema, StorageError, StorageLayer};
+use storage::{
+    Collation, Column, IntegrityProblem, PrimaryKey, Row, Schema, StorageError, StorageLayer,
+    TableInfo,
+};
 
+pub mod context;
+mod export;
 pub mod generate;
 pub mod query;
 pub mod repl;
 pub mod storage;
+pub mod trawler;
 
-const DB_TYPE_COUNT: u32 = 4;
+const DB_TYPE_COUNT: u32 = 7;
+const CHAR_GEN_SIZE_MAX: u32 = 20;
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum DbType {
     String,
     Integer,
     Float,
     UnsignedInt,
+    /// A 32-bit integer, for columns whose values are known to be small
+    SmallInt,
+    /// A string of exactly this many characters. Shorter strings are padded with spaces.
+    Char(u32),
+    /// A string of at most this many characters
+    Varchar(u32),
 }
 impl DbType {
     pub fn generate_val(&self, rng: &mut generate::RNG) -> DbValue {
@@ -32,42 +50,68 @@ impl DbType {
             Self::Integer => DbValue::Integer(i64::generate(rng)),
             Self::String => DbValue::String(String::generate(rng)),
             Self::UnsignedInt => DbValue::UnsignedInt(u64::generate(rng)),
+            Self::SmallInt => DbValue::SmallInt(i32::generate(rng)),
+            Self::Char(size) => {
+                DbValue::padded_char(truncated(String::generate(rng), *size), *size)
+            }
+            Self::Varchar(size) => DbValue::String(truncated(String::generate(rng), *size)),
         }
     }
 
+    /// Numeric types can be coerced to each other, as can string types, though a
+    /// value may not fit in the type it's coerced to
     pub fn coerceable_to(&self, other: &DbType) -> bool {
-        matches!(
-            (self, other),
-            (DbType::Float, DbType::Float)
-                | (DbType::Float, DbType::Integer)
-                | (DbType::Float, DbType::UnsignedInt)
-                | (DbType::Integer, DbType::Float)
-                | (DbType::Integer, DbType::Integer)
-                | (DbType::Integer, DbType::UnsignedInt)
-                | (DbType::UnsignedInt, DbType::Float)
-                | (DbType::UnsignedInt, DbType::Integer)
-                | (DbType::UnsignedInt, DbType::UnsignedInt)
-                | (DbType::String, DbType::String)
-        )
+        self.is_string() == other.is_string()
+    }
+
+    /// Whether values of this type are strings, rather than numbers
+    pub fn is_string(&self) -> bool {
+        matches!(self, DbType::String | DbType::Char(_) | DbType::Varchar(_))
     }
 }
 impl Generate for DbType {
     fn generate(rng: &mut generate::RNG) -> Self {
-        assert_eq!(DB_TYPE_COUNT, 4);
+        assert_eq!(DB_TYPE_COUNT, 7);
         let choice = rng.next_value() % DB_TYPE_COUNT;
         match choice {
             0 => Self::String,
             1 => Self::Integer,
             2 => Self::Float,
             3 => Self::UnsignedInt,
+            4 => Self::SmallInt,
+            5 => Self::Char(rng.next_value() % CHAR_GEN_SIZE_MAX + 1),
+            6 => Self::Varchar(rng.next_value() % CHAR_GEN_SIZE_MAX + 1),
             _ => panic!("Somehow got a number out of range!"),
         }
     }
 }
+impl DbType {
+    /// The type name as written in a CREATE TABLE statement
+    pub fn as_sql_type_str(&self) -> Cow<'static, str> {
+        match self {
+            Self::String => Cow::Borrowed("STRING"),
+            Self::Integer => Cow::Borrowed("INTEGER"),
+            Self::Float => Cow::Borrowed("FLOAT"),
+            Self::UnsignedInt => Cow::Borrowed("UNSIGNED INT"),
+            Self::SmallInt => Cow::Borrowed("SMALLINT"),
+            Self::Char(size) => Cow::Owned(format!("CHAR({size})")),
+            Self::Varchar(size) => Cow::Owned(format!("VARCHAR({size})")),
+        }
+    }
+}
+
+/// `s` cut down to at most `size` characters
+fn truncated(mut s: String, size: u32) -> String {
+    if let Some((i, _)) = s.char_indices().nth(size as usize) {
+        s.truncate(i);
+    }
+    s
+}
 
 /// Gaurantees that this float is finite, which means we
 /// can enforce equality and total order on it.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, PartialOrd)]
+#[serde(try_from = "UncheckedDbFloat")]
 struct PrivateDbFloat {
     f: f64,
 }
@@ -78,16 +122,48 @@ impl PrivateDbFloat {
     }
 }
 
+/// Has the same serialized form as `PrivateDbFloat`, so that deserialized
+/// floats can be checked for finiteness
+#[derive(Deserialize)]
+struct UncheckedDbFloat {
+    f: f64,
+}
+impl TryFrom<UncheckedDbFloat> for PrivateDbFloat {
+    type Error = &'static str;
+
+    fn try_from(value: UncheckedDbFloat) -> std::result::Result<Self, Self::Error> {
+        if value.f.is_finite() {
+            Ok(PrivateDbFloat { f: value.f })
+        } else {
+            Err("a stored float was not finite")
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DbFloat {
     inner: PrivateDbFloat,
 }
 impl DbFloat {
+    /// Panics if `f` isn't finite. Use `try_new` for floats that come from user input.
     pub fn new(f: f64) -> Self {
         DbFloat {
             inner: PrivateDbFloat::new(f),
         }
     }
+
+    /// Returns `None` if `f` is NaN or infinite
+    pub fn try_new(f: f64) -> Option<Self> {
+        if f.is_finite() {
+            Some(DbFloat::new(f))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.inner.f
+    }
 }
 impl fmt::Display for DbFloat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -97,6 +173,17 @@ impl fmt::Display for DbFloat {
 impl Eq for DbFloat {
     fn assert_receiver_is_total_eq(&self) {}
 }
+impl Hash for DbFloat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // 0.0 and -0.0 are equal, so they need to hash the same
+        let f = if self.inner.f == 0.0 {
+            0.0
+        } else {
+            self.inner.f
+        };
+        f.to_bits().hash(state);
+    }
+}
 #[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for DbFloat {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
@@ -117,12 +204,15 @@ impl fmt::LowerExp for DbFloat {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, PartialOrd, Eq, Ord)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, PartialOrd, Eq, Ord, Hash)]
 pub enum DbValue {
     String(String),
     Integer(i64),
     Float(DbFloat),
     UnsignedInt(u64),
+    SmallInt(i32),
+    /// The value of a `CHAR(n)` column, already padded to its length
+    Char(String),
 }
 impl DbValue {
     pub fn db_type(&self) -> DbType {
@@ -131,27 +221,60 @@ impl DbValue {
             Self::Integer(_) => DbType::Integer,
             Self::String(_) => DbType::String,
             Self::UnsignedInt(_) => DbType::UnsignedInt,
+            Self::SmallInt(_) => DbType::SmallInt,
+            Self::Char(s) => DbType::Char(s.chars().count() as u32),
         }
     }
 
+    /// Pads `s` with spaces to `size` characters. Longer strings are left as they
+    /// are, for inserts to reject.
+    fn padded_char(mut s: String, size: u32) -> Self {
+        let padding = (size as usize).saturating_sub(s.chars().count());
+        s.extend(std::iter::repeat_n(' ', padding));
+        DbValue::Char(s)
+    }
+
     pub fn as_insertable_sql_str(&self) -> String {
         match self {
             Self::Float(v) => format!("{v:}"),
             Self::Integer(v) => format!("{v}"),
-            Self::String(v) => format!("'{v}'"),
+            Self::String(v) => quoted_str(v),
             Self::UnsignedInt(v) => format!("{v}"),
+            Self::SmallInt(v) => format!("{v}"),
+            Self::Char(v) => quoted_str(v),
         }
     }
 
     /// Returns Some(_) if the coercion is possible,
-    /// otherwise returns None. This coercion may be lossy.
+    /// otherwise returns None. This coercion may be lossy, except to `SmallInt`,
+    /// which returns None for values out of its range. Strings aren't checked
+    /// against the length of `Char` and `Varchar`, which is left to inserts.
     /// Does not coerce non-strings to strings
     fn coerced_to(&self, t: DbType) -> Option<Self> {
         match (t, self) {
+            (DbType::Char(size), DbValue::String(s) | DbValue::Char(s)) => {
+                Some(DbValue::padded_char(s.clone(), size))
+            }
+            (DbType::String | DbType::Varchar(_), DbValue::String(s) | DbValue::Char(s)) => {
+                Some(DbValue::String(s.clone()))
+            }
+            (DbType::SmallInt, DbValue::SmallInt(_)) => Some(self.clone()),
+            (DbType::SmallInt, DbValue::Integer(i)) => {
+                i32::try_from(*i).ok().map(DbValue::SmallInt)
+            }
+            (DbType::SmallInt, DbValue::UnsignedInt(i)) => {
+                i32::try_from(*i).ok().map(DbValue::SmallInt)
+            }
+            (DbType::SmallInt, DbValue::Float(f)) => {
+                let f = f.inner.f.trunc();
+                let in_range = f >= i32::MIN as f64 && f <= i32::MAX as f64;
+                in_range.then_some(DbValue::SmallInt(f as i32))
+            }
+            (_, DbValue::SmallInt(i)) => DbValue::Integer(i64::from(*i)).coerced_to(t),
             (DbType::Float, DbValue::Float(_)) => Some(self.clone()),
-            (DbType::Float, DbValue::Integer(i)) => Some(DbValue::Float(DbFloat::new(*i as f64))),
+            (DbType::Float, DbValue::Integer(i)) => DbFloat::try_new(*i as f64).map(DbValue::Float),
             (DbType::Float, DbValue::UnsignedInt(i)) => {
-                Some(DbValue::Float(DbFloat::new(*i as f64)))
+                DbFloat::try_new(*i as f64).map(DbValue::Float)
             }
             (DbType::Integer, DbValue::Float(f)) => Some(DbValue::Integer(f.inner.f as i64)),
             (DbType::Integer, DbValue::Integer(_)) => Some(self.clone()),
@@ -161,7 +284,6 @@ impl DbValue {
             }
             (DbType::UnsignedInt, DbValue::Integer(i)) => Some(DbValue::UnsignedInt(*i as u64)),
             (DbType::UnsignedInt, DbValue::UnsignedInt(_)) => Some(self.clone()),
-            (DbType::String, DbValue::String(_)) => Some(self.clone()),
             _ => None,
         }
     }
@@ -172,10 +294,15 @@ impl fmt::Display for DbValue {
             Self::Float(v) => v.fmt(f),
             Self::Integer(v) => v.fmt(f),
             Self::String(v) => {
-                let str = format!("\"{v}\"");
+                let str = format!("\"{}\"", escaped_str(v));
                 str.fmt(f)
             }
             Self::UnsignedInt(v) => v.fmt(f),
+            Self::SmallInt(v) => v.fmt(f),
+            Self::Char(v) => {
+                let str = format!("\"{}\"", escaped_str(v));
+                str.fmt(f)
+            }
         }
     }
 }
@@ -203,24 +330,97 @@ where
 pub enum DatabaseError {
     StorageError(StorageError),
     QueryError(QueryError),
-    MutexError,
+    LockError,
     InvalidTypeMapping,
     RowPositionInvalid,
     QueryDidNotReturnRows,
+    /// The same placeholder was given more than one value
+    DuplicateParameter(String),
+    /// A value was given for a placeholder the statement doesn't have
+    UnknownParameter(String),
+    IoError(io::Error),
+    /// An error with a frame describing what was being done when it happened. The
+    /// frames are only shown by the alternate format, `{:#}`.
+    Context(String, Box<DatabaseError>),
+}
+impl_contextual!(DatabaseError);
+impl DatabaseError {
+    /// Where in the statement's text the error happened, if known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::QueryError(err) => err.position(),
+            Self::Context(_, err) => err.position(),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(err) => err.fmt(f),
+            Self::QueryError(err) => err.fmt(f),
+            Self::LockError => f.write_str("The database lock was poisoned"),
+            Self::InvalidTypeMapping => {
+                f.write_str("The value cannot be converted to the requested type")
+            }
+            Self::RowPositionInvalid => f.write_str("No value exists at that position in the row"),
+            Self::QueryDidNotReturnRows => f.write_str("The query did not return rows"),
+            Self::DuplicateParameter(name) => {
+                write!(f, "The parameter {name} was given more than once")
+            }
+            Self::UnknownParameter(name) => {
+                write!(f, "The statement has no placeholder named {name}")
+            }
+            Self::IoError(err) => err.fmt(f),
+            Self::Context(frame, err) if f.alternate() => write!(f, "{err:#}\n  while {frame}"),
+            Self::Context(_, err) => err.fmt(f),
+        }
+    }
+}
+impl std::error::Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StorageError(err) => Some(err),
+            Self::QueryError(err) => Some(err),
+            Self::IoError(err) => Some(err),
+            Self::Context(_, err) => err.source(),
+            _ => None,
+        }
+    }
 }
 impl From<StorageError> for DatabaseError {
     fn from(value: StorageError) -> Self {
         Self::StorageError(value)
     }
 }
+impl From<io::Error> for DatabaseError {
+    fn from(value: io::Error) -> Self {
+        Self::IoError(value)
+    }
+}
 impl From<QueryError> for DatabaseError {
     fn from(value: QueryError) -> Self {
         Self::QueryError(value)
     }
 }
-impl From<PoisonError<MutexGuard<'_, StorageLayer>>> for DatabaseError {
-    fn from(_: PoisonError<MutexGuard<'_, StorageLayer>>) -> Self {
-        Self::MutexError
+impl From<Traced<QueryError>> for DatabaseError {
+    fn from(value: Traced<QueryError>) -> Self {
+        value
+            .frames
+            .into_iter()
+            .fold(Self::QueryError(value.error), |err, frame| {
+                err.with_frame(frame)
+            })
+    }
+}
+impl From<PoisonError<RwLockReadGuard<'_, StorageLayer>>> for DatabaseError {
+    fn from(_: PoisonError<RwLockReadGuard<'_, StorageLayer>>) -> Self {
+        Self::LockError
+    }
+}
+impl From<PoisonError<RwLockWriteGuard<'_, StorageLayer>>> for DatabaseError {
+    fn from(_: PoisonError<RwLockWriteGuard<'_, StorageLayer>>) -> Self {
+        Self::LockError
     }
 }
 
@@ -229,66 +429,265 @@ type Result<T> = std::result::Result<T, DatabaseError>;
 pub trait TableKnowledge {
     fn table_exists(&self, name: &str) -> bool;
     fn table_schema(&self, name: &str) -> Result<Schema>;
+    fn table_names(&self) -> Vec<String>;
+    /// The table's schema, primary key and row count, or `None` if there's no such table
+    fn table_info(&self, name: &str) -> Option<TableInfo>;
 }
 
+/// Statements that only read, through `read_transaction` and `prepare_read`, share
+/// access to the database and can run concurrently. Anything else holds exclusive access.
+///
+/// Each `Database` is one connection to the storage. More can be made with `connect`,
+/// which share the same storage but have their own transaction state, so each thread
+/// can hold its own.
 pub struct Database {
-    storage: Mutex<StorageLayer>,
+    storage: Arc<RwLock<StorageLayer>>,
+    /// Tells this connection's statements apart from other connections' in storage
+    connection: usize,
+    /// The last connection id handed out, shared by every connection to the storage
+    connections: Arc<AtomicUsize>,
 }
+
+/// A handle made by `Database::connect`. Every `Database` is a connection, this just
+/// names the ones made for sharing.
+pub type Connection = Database;
+
 impl Database {
+    fn new(storage: StorageLayer) -> Self {
+        Database {
+            storage: Arc::new(RwLock::new(storage)),
+            connection: 0,
+            connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
     pub fn init(db_file: &Path) -> Result<Self> {
-        let storage = StorageLayer::init(db_file)?;
-        Ok(Database {
-            storage: Mutex::new(storage),
-        })
+        let storage = StorageLayer::init(db_file)
+            .map_err(DatabaseError::from)
+            .context(|| format!("opening {}", db_file.display()))?;
+        Ok(Database::new(storage))
     }
 
-    pub fn execute(&mut self, command: &str) -> Result<usize> {
+    /// Opens an existing database without write access, so several processes can
+    /// query the same file. Statements that would modify it return an error.
+    pub fn init_read_only(db_file: &Path) -> Result<Self> {
+        let storage = StorageLayer::open_read_only(db_file)
+            .map_err(DatabaseError::from)
+            .context(|| format!("opening {} read-only", db_file.display()))?;
+        Ok(Database::new(storage))
+    }
+
+    /// Creates an empty database that only lives in memory. Committing keeps a copy
+    /// of the tables in memory, which aborting goes back to.
+    pub fn in_memory() -> Self {
+        Database::new(StorageLayer::in_memory())
+    }
+
+    /// Another connection to the same storage, with its own transaction state. While
+    /// one connection has a transaction open, changes from any other fail with
+    /// `StorageError::Busy` until it's committed or rolled back.
+    pub fn connect(&self) -> Connection {
+        Database {
+            storage: self.storage.clone(),
+            connection: self.connections.fetch_add(1, Ordering::Relaxed) + 1,
+            connections: self.connections.clone(),
+        }
+    }
+
+    /// Takes exclusive access to the storage on behalf of this connection
+    fn write(&self) -> Result<RwLockWriteGuard<'_, StorageLayer>> {
+        let mut lock = self.storage.write()?;
+        lock.set_session(self.connection);
+        Ok(lock)
+    }
+
+    pub fn execute(&self, command: &str) -> Result<usize> {
         let affected = self.prepare(command)?.execute([])?;
         Ok(affected)
     }
 
-    pub fn transaction(&mut self) -> Result<Transaction> {
-        let lock = self.storage.lock()?;
+    pub fn transaction(&self) -> Result<Transaction<'_>> {
+        let lock = self.write()?;
         Ok(Transaction { storage: lock })
     }
 
-    pub fn commit(&mut self) -> Result<()> {
-        self.storage.lock()?.flush()?;
+    pub fn read_transaction(&self) -> Result<ReadTransaction<'_>> {
+        let lock = self.storage.read()?;
+        Ok(ReadTransaction { storage: lock })
+    }
+
+    pub fn commit(&self) -> Result<()> {
+        self.write()?
+            .flush()
+            .map_err(DatabaseError::from)
+            .context(|| String::from("committing"))?;
         Ok(())
     }
 
-    pub fn abort(&mut self) -> Result<()> {
-        self.storage.lock()?.reload()?;
+    pub fn abort(&self) -> Result<()> {
+        self.write()?.reload()?;
         Ok(())
     }
 
-    pub fn prepare<'a>(&'a mut self, stmt: &'a str) -> Result<PreparedStatement<'a>> {
+    /// Whether this connection started a transaction with `BEGIN` that hasn't been
+    /// committed or rolled back yet. Until it is, statements executed on the database
+    /// aren't flushed.
+    pub fn in_transaction(&self) -> Result<bool> {
+        Ok(self.storage.read()?.transaction_owner() == Some(self.connection))
+    }
+
+    pub fn prepare<'a>(&'a self, stmt: &'a str) -> Result<PreparedStatement<'a>> {
         Ok(PreparedStatement {
-            storage: MaybeLockedStorage::HoldingLock(self.storage.lock()?),
-            statement: stmt,
+            query: PreparedQuery::parse(stmt)?,
+            storage: MaybeLockedStorage::HoldingLock(self.write()?),
         })
     }
+
+    pub fn prepare_read<'a>(&'a self, stmt: &'a str) -> Result<ReadPreparedStatement<'a>> {
+        Ok(ReadPreparedStatement {
+            query: PreparedQuery::parse(stmt)?,
+            storage: MaybeReadLockedStorage::HoldingLock(self.storage.read()?),
+        })
+    }
+
+    /// Starts building a table named `name`, as an alternative to running `CREATE TABLE`
+    pub fn create_table(&self, name: &str) -> TableBuilder<'_> {
+        TableBuilder {
+            db: self,
+            name: name.to_string(),
+            columns: Vec::new(),
+            primary_key: None,
+        }
+    }
+
+    /// Up to `size` rows of `table` in rowid order, starting after the row with rowid
+    /// `after`, or from the first row if it's `None`. Pass the page's cursor back in to
+    /// get the next one. Only the rows of the page are read, the same as with
+    /// `SELECT ... WHERE rowid > :after ORDER BY rowid LIMIT n`.
+    pub fn page_after(&self, table: &str, after: Option<usize>, size: usize) -> Result<Page> {
+        let storage = self.storage.read()?;
+        let scan = match after {
+            Some(after) => storage.table_scan_after(table, after, true),
+            None => storage.table_scan(table, true),
+        };
+        let mut rows = scan
+            .map_err(DatabaseError::from)
+            .context(|| format!("paging through {table}"))?;
+        let mut page = Vec::new();
+        let mut last_id = None;
+        for row in rows.by_ref().take(size) {
+            let mut row = row.into_owned();
+            last_id = match row.data.pop() {
+                Some(DbValue::UnsignedInt(id)) => Some(id as usize),
+                _ => unreachable!("Rows scanned with their rowid end with it"),
+            };
+            page.push(row);
+        }
+        let cursor = last_id.filter(|_| rows.next().is_some());
+        Ok(Page { rows: page, cursor })
+    }
+
+    /// Scans every table for rows that don't match its schema, primary keys that
+    /// don't match its key set, and rowids at or past its next rowid, like SQLite's
+    /// `PRAGMA integrity_check`. An empty list means nothing was found.
+    pub fn check_integrity(&self) -> Result<Vec<IntegrityProblem>> {
+        Ok(self.storage.read()?.check_integrity())
+    }
+}
+
+/// Rows of a table returned by `Database::page_after`
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub rows: Vec<Row>,
+    /// The rowid of the page's last row, to get the next page with, or `None` if there
+    /// are no rows after it
+    pub cursor: Option<usize>,
+}
+/// A table being built by `Database::create_table`. It's checked the same way as
+/// `CREATE TABLE` when it's built.
+pub struct TableBuilder<'a> {
+    db: &'a Database,
+    name: String,
+    columns: Vec<Column>,
+    primary_key: Option<String>,
+}
+impl TableBuilder<'_> {
+    pub fn column(mut self, name: &str, _type: DbType) -> Self {
+        self.columns.push(Column::new(name.to_string(), _type));
+        self
+    }
+
+    /// Compares the last column added with `collation`, like `COLLATE` does
+    pub fn collated(mut self, collation: Collation) -> Self {
+        if let Some(col) = self.columns.pop() {
+            self.columns.push(col.collated(collation));
+        }
+        self
+    }
+
+    /// Makes `name` the primary key instead of the rowid
+    pub fn primary_key(mut self, name: &str) -> Self {
+        self.primary_key = Some(name.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<()> {
+        let schema = Schema::new(self.columns);
+        let primary_key = match self.primary_key {
+            Some(name) => match schema.column(&name) {
+                Some(col) => PrimaryKey::column(col.clone()),
+                None => return Err(StorageError::UnknownColumnName(name).into()),
+            },
+            None => PrimaryKey::Rowid,
+        };
+        let mut storage = self.db.write()?;
+        storage.create_table(self.name, schema, primary_key, Vec::new(), Vec::new())?;
+        // like statements, it's written out unless it's part of a transaction
+        if storage.transaction_owner().is_none() {
+            storage.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Database {
+    /// A transaction left open would keep every other connection from making changes
+    fn drop(&mut self) {
+        if let Ok(mut storage) = self.write() {
+            if storage.transaction_owner() == Some(self.connection) {
+                _ = storage.rollback();
+            }
+        }
+    }
 }
 impl TableKnowledge for Database {
     fn table_exists(&self, name: &str) -> bool {
-        self.storage.lock().unwrap().table_exists(name)
+        self.storage.read().unwrap().table_exists(name)
     }
 
     fn table_schema(&self, name: &str) -> Result<Schema> {
-        let schema = self.storage.lock().unwrap().table_schema(name)?.clone();
+        let schema = self.storage.read().unwrap().table_schema(name)?.clone();
         Ok(schema)
     }
+
+    fn table_names(&self) -> Vec<String> {
+        self.storage.read().unwrap().table_names()
+    }
+
+    fn table_info(&self, name: &str) -> Option<TableInfo> {
+        self.storage.read().unwrap().table_info(name)
+    }
 }
 
 pub struct Transaction<'tx> {
-    storage: MutexGuard<'tx, StorageLayer>,
+    storage: RwLockWriteGuard<'tx, StorageLayer>,
 }
 impl<'tx> Transaction<'tx> {
-    pub fn prepare<'a>(&'a mut self, stmt: &'a str) -> PreparedStatement<'a> {
-        PreparedStatement {
+    pub fn prepare<'a>(&'a mut self, stmt: &'a str) -> Result<PreparedStatement<'a>> {
+        Ok(PreparedStatement {
+            query: PreparedQuery::parse(stmt)?,
             storage: MaybeLockedStorage::NotHoldingLock(&mut self.storage),
-            statement: stmt,
-        }
+        })
     }
 
     pub fn commit(mut self) -> Result<()> {
@@ -296,15 +695,39 @@ impl<'tx> Transaction<'tx> {
         Ok(())
     }
 
+    /// Flushes the changes made so far without ending the transaction or giving up
+    /// its lock, so a long bulk load can persist its progress. Rows flushed this way
+    /// stay on disk after a crash, and aborting only goes back to the last checkpoint.
+    /// Fails on a read-only database, where there'd be nothing to persist.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if self.storage.is_read_only() {
+            return Err(StorageError::ReadOnly.into());
+        }
+        self.storage.flush()?;
+        Ok(())
+    }
+
     pub fn abort(mut self) -> Result<()> {
         self.storage.reload()?;
         Ok(())
     }
 
     pub fn execute(&mut self, command: &str) -> Result<usize> {
-        let affected = self.prepare(command).execute([])?;
+        let affected = self.prepare(command)?.execute([])?;
         Ok(affected)
     }
+
+    /// Returns the statements needed to recreate every table and its rows.
+    /// See `StorageLayer::dump`.
+    pub fn dump(&self) -> Vec<String> {
+        self.storage.dump()
+    }
+
+    /// Describes a table's columns and which of them is the primary key
+    pub fn table_schema_info(&self, name: &str) -> Result<String> {
+        let info = self.storage.table_schema_info(name)?;
+        Ok(info)
+    }
 }
 impl TableKnowledge for Transaction<'_> {
     fn table_exists(&self, name: &str) -> bool {
@@ -315,13 +738,42 @@ impl TableKnowledge for Transaction<'_> {
         let schema = self.storage.table_schema(name)?;
         Ok(schema.clone())
     }
+
+    fn table_names(&self) -> Vec<String> {
+        self.storage.table_names()
+    }
+
+    fn table_info(&self, name: &str) -> Option<TableInfo> {
+        self.storage.table_info(name)
+    }
 }
 
 enum RowContents<'a> {
     Filled(ResultRows<'a>),
+    /// The statement changed rows rather than returning them
+    Affected(usize),
+    /// The statement was `EXPLAIN`ed, so only its plan was produced
+    Plan(String),
+    /// The statement freed this many bytes of space
+    Reclaimed(u64),
     Empty,
 }
+impl<'a> RowContents<'a> {
+    /// The contents of a result that isn't rows, so nothing is borrowed from the database
+    fn without_rows(res: QueryResult) -> Self {
+        match res {
+            QueryResult::NothingToDo => RowContents::Empty,
+            QueryResult::Ok(affected) => RowContents::Affected(affected),
+            QueryResult::Explain(plan) => RowContents::Plan(plan),
+            QueryResult::Reclaimed(bytes) => RowContents::Reclaimed(bytes),
+            QueryResult::Rows(_) => unreachable!("Only used for commands that don't return rows"),
+        }
+    }
+}
 
+/// The result of a query. Rows are read from the database as they're iterated over
+/// rather than all at once, so they borrow the statement they came from, which keeps
+/// its hold on the database until they're dropped.
 pub struct Rows<'a> {
     rows: RowContents<'a>,
 }
@@ -330,17 +782,100 @@ impl<'a> Rows<'a> {
         Rows { rows }
     }
 
+    fn from_result(res: QueryResult<'a>) -> Self {
+        match res {
+            QueryResult::NothingToDo => Rows::new(RowContents::Empty),
+            QueryResult::Ok(affected) => Rows::new(RowContents::Affected(affected)),
+            QueryResult::Rows(rows) => Rows::new(RowContents::Filled(rows)),
+            QueryResult::Explain(plan) => Rows::new(RowContents::Plan(plan)),
+            QueryResult::Reclaimed(bytes) => Rows::new(RowContents::Reclaimed(bytes)),
+        }
+    }
+
     pub fn mapped<F>(self, map_fn: F) -> MappedResults<'a, F> {
         MappedResults::new(self.rows, map_fn)
     }
+
+    /// Converts each row into `T`, usually a tuple of the selected columns' types
+    pub fn mapped_into<T: FromRow>(self) -> MappedResults<'a, fn(&Row) -> Result<T>> {
+        MappedResults::new(self.rows, T::from_row)
+    }
+
+    /// The columns of the returned rows, if the statement returned any
+    pub fn schema(&self) -> Option<Cow<'a, Schema>> {
+        match &self.rows {
+            RowContents::Filled(rows) => Some(rows.schema()),
+            _ => None,
+        }
+    }
+
+    /// The description of the query plan, if the statement was an `EXPLAIN`
+    pub fn plan(&self) -> Option<&str> {
+        match &self.rows {
+            RowContents::Plan(plan) => Some(plan),
+            _ => None,
+        }
+    }
+
+    /// Writes the rows as CSV, after a header record of the column names.
+    /// Returns the number of rows written.
+    pub fn to_csv(self, writer: &mut impl io::Write) -> Result<usize> {
+        let rows = match self.rows {
+            RowContents::Filled(rows) => rows,
+            _ => return Err(DatabaseError::QueryDidNotReturnRows),
+        };
+        export::write_csv_header(writer, &rows.schema())?;
+        let mut written = 0;
+        for row in rows {
+            let row = row.map_err(QueryError::from)?;
+            export::write_csv_row(writer, &row)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Writes the rows as a JSON array of objects keyed by column name.
+    /// Returns the number of rows written.
+    pub fn to_json(self, writer: &mut impl io::Write) -> Result<usize> {
+        let rows = match self.rows {
+            RowContents::Filled(rows) => rows,
+            _ => return Err(DatabaseError::QueryDidNotReturnRows),
+        };
+        let schema = rows.schema();
+        writer.write_all(b"[")?;
+        let mut written = 0;
+        for row in rows {
+            let row = row.map_err(QueryError::from)?;
+            if written > 0 {
+                writer.write_all(b",")?;
+            }
+            export::write_json_row(writer, &schema, &row)?;
+            written += 1;
+        }
+        writer.write_all(b"]")?;
+        Ok(written)
+    }
+
+    /// The number of bytes freed, if the statement was a `VACUUM`
+    pub fn bytes_reclaimed(&self) -> Option<u64> {
+        match &self.rows {
+            RowContents::Reclaimed(bytes) => Some(*bytes),
+            _ => None,
+        }
+    }
 }
 impl<'a> Iterator for Rows<'a> {
-    type Item = Cow<'a, Row>;
+    type Item = Result<Cow<'a, Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match &mut self.rows {
-            RowContents::Empty => None,
-            RowContents::Filled(rows) => rows.next(),
+            RowContents::Empty
+            | RowContents::Affected(_)
+            | RowContents::Plan(_)
+            | RowContents::Reclaimed(_) => None,
+            RowContents::Filled(rows) => rows
+                .next()
+                .map(|r| r.map_err(|e| QueryError::from(e).into())),
         }
     }
 }
@@ -362,97 +897,280 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         match &mut self.rows {
-            RowContents::Empty => None,
-            RowContents::Filled(rows) => rows.next().map(|r| (self.map_fn)(&r)),
+            RowContents::Empty
+            | RowContents::Affected(_)
+            | RowContents::Plan(_)
+            | RowContents::Reclaimed(_) => None,
+            RowContents::Filled(rows) => rows.next().map(|r| match r {
+                Ok(r) => (self.map_fn)(&r),
+                Err(e) => Err(QueryError::from(e).into()),
+            }),
         }
     }
 }
 
+/// A transaction that can only read, so it can be held alongside other readers
+pub struct ReadTransaction<'tx> {
+    storage: RwLockReadGuard<'tx, StorageLayer>,
+}
+impl ReadTransaction<'_> {
+    pub fn prepare<'a>(&'a self, stmt: &'a str) -> Result<ReadPreparedStatement<'a>> {
+        Ok(ReadPreparedStatement {
+            query: PreparedQuery::parse(stmt)?,
+            storage: MaybeReadLockedStorage::NotHoldingLock(&self.storage),
+        })
+    }
+}
+impl TableKnowledge for ReadTransaction<'_> {
+    fn table_exists(&self, name: &str) -> bool {
+        self.storage.table_exists(name)
+    }
+
+    fn table_schema(&self, name: &str) -> Result<Schema> {
+        let schema = self.storage.table_schema(name)?;
+        Ok(schema.clone())
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.storage.table_names()
+    }
+
+    fn table_info(&self, name: &str) -> Option<TableInfo> {
+        self.storage.table_info(name)
+    }
+}
+
 enum MaybeLockedStorage<'stmt> {
-    HoldingLock(MutexGuard<'stmt, StorageLayer>),
+    HoldingLock(RwLockWriteGuard<'stmt, StorageLayer>),
     NotHoldingLock(&'stmt mut StorageLayer),
 }
 
+/// A statement parsed once when it's prepared, so it can be executed repeatedly
+/// with different parameters
 pub struct PreparedStatement<'stmt> {
     storage: MaybeLockedStorage<'stmt>,
-    statement: &'stmt str,
+    query: PreparedQuery<'stmt>,
 }
 impl PreparedStatement<'_> {
     pub fn execute<P: Params>(&mut self, params: P) -> Result<usize> {
-        let bound_statement = params.bind_to(self.statement);
-        match &mut self.storage {
-            MaybeLockedStorage::HoldingLock(lock) => {
-                let res = match query::execute(&bound_statement, lock)? {
-                    QueryResult::NothingToDo => 0,
-                    QueryResult::Ok(affected) => affected,
-                    QueryResult::Rows(_) => 0,
-                };
+        let affected = self.execute_bound(&params)?;
+        self.flush()?;
+        Ok(affected)
+    }
+
+    /// Executes the statement once for each set of parameters. Returns the total
+    /// number of rows affected.
+    pub fn execute_batch<P, I>(&mut self, params_iter: I) -> Result<usize>
+    where
+        P: Params,
+        I: IntoIterator<Item = P>,
+    {
+        let mut affected = 0;
+        for params in params_iter {
+            affected += self.execute_bound(&params)?;
+        }
+        self.flush()?;
+        Ok(affected)
+    }
+
+    fn execute_bound(&mut self, params: &impl Params) -> Result<usize> {
+        let values = checked_bindings(params, &self.query)?;
+        let res = match &mut self.storage {
+            MaybeLockedStorage::HoldingLock(lock) => self.query.execute(&values, lock)?,
+            MaybeLockedStorage::NotHoldingLock(storage) => self.query.execute(&values, storage)?,
+        };
+        let affected = match res {
+            QueryResult::NothingToDo => 0,
+            QueryResult::Ok(affected) => affected,
+            QueryResult::Rows(_) | QueryResult::Explain(_) | QueryResult::Reclaimed(_) => 0,
+        };
+        Ok(affected)
+    }
+
+    /// Statements that hold their own lock aren't part of a transaction, so their
+    /// changes are written out straight away, unless they're inside a `BEGIN`
+    fn flush(&mut self) -> Result<()> {
+        if let MaybeLockedStorage::HoldingLock(lock) = &mut self.storage {
+            if lock.transaction_owner().is_none() && !self.query.ends_transaction() {
                 lock.flush()?;
-                Ok(res)
-            }
-            MaybeLockedStorage::NotHoldingLock(storage) => {
-                match query::execute(&bound_statement, storage)? {
-                    QueryResult::NothingToDo => Ok(0),
-                    QueryResult::Ok(affected) => Ok(affected),
-                    QueryResult::Rows(_) => Ok(0),
-                }
             }
         }
+        Ok(())
+    }
+
+    /// The rowid of the last row inserted through this database, or `None` if no
+    /// row has been inserted yet. When several rows are inserted by one execution,
+    /// this is the id of the final one.
+    pub fn last_insert_rowid(&self) -> Option<usize> {
+        self.storage.storage().last_insert_rowid()
     }
 
     pub fn query(&mut self) -> Result<Rows<'_>> {
-        let res = match &mut self.storage {
-            MaybeLockedStorage::HoldingLock(lock) => query::execute(self.statement, lock)?,
-            MaybeLockedStorage::NotHoldingLock(storage) => query::execute(self.statement, storage)?,
-        };
-        match res {
-            QueryResult::NothingToDo => Ok(Rows::new(RowContents::Empty)),
-            QueryResult::Ok(_) => Ok(Rows::new(RowContents::Empty)),
-            QueryResult::Rows(rows) => Ok(Rows::new(RowContents::Filled(rows))),
+        self.query_with([])
+    }
+
+    /// Executes the statement and maps its first row with `map_fn`, or returns `None`
+    /// if there are no rows
+    pub fn query_row<T, F>(&mut self, map_fn: F) -> Result<Option<T>>
+    where
+        F: Fn(&Row) -> Result<T>,
+    {
+        self.query()?.mapped(map_fn).next().transpose()
+    }
+
+    /// Like `query`, with each value bound to the placeholder of the same name.
+    /// Changes are flushed the same as with `execute`, unless the command returns rows,
+    /// which are still being read from the database.
+    pub fn query_with<P: Params>(&mut self, params: P) -> Result<Rows<'_>> {
+        let values = checked_bindings(&params, &self.query)?;
+        if self.query.returns_rows() {
+            let res = match &mut self.storage {
+                MaybeLockedStorage::HoldingLock(lock) => self.query.execute(&values, lock)?,
+                MaybeLockedStorage::NotHoldingLock(storage) => {
+                    self.query.execute(&values, storage)?
+                }
+            };
+            return Ok(Rows::from_result(res));
         }
+        let contents = {
+            let res = match &mut self.storage {
+                MaybeLockedStorage::HoldingLock(lock) => self.query.execute(&values, lock)?,
+                MaybeLockedStorage::NotHoldingLock(storage) => {
+                    self.query.execute(&values, storage)?
+                }
+            };
+            RowContents::without_rows(res)
+        };
+        self.flush()?;
+        Ok(Rows::new(contents))
     }
 }
 impl TableKnowledge for PreparedStatement<'_> {
     fn table_exists(&self, name: &str) -> bool {
-        match &self.storage {
-            MaybeLockedStorage::HoldingLock(lock) => lock.table_exists(name),
-            MaybeLockedStorage::NotHoldingLock(storage) => storage.table_exists(name),
+        self.storage.storage().table_exists(name)
+    }
+
+    fn table_schema(&self, name: &str) -> Result<Schema> {
+        let schema = self.storage.storage().table_schema(name)?;
+        Ok(schema.clone())
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.storage.storage().table_names()
+    }
+
+    fn table_info(&self, name: &str) -> Option<TableInfo> {
+        self.storage.storage().table_info(name)
+    }
+}
+
+enum MaybeReadLockedStorage<'stmt> {
+    HoldingLock(RwLockReadGuard<'stmt, StorageLayer>),
+    NotHoldingLock(&'stmt StorageLayer),
+}
+impl MaybeLockedStorage<'_> {
+    fn storage(&self) -> &StorageLayer {
+        match self {
+            Self::HoldingLock(lock) => lock,
+            Self::NotHoldingLock(storage) => storage,
         }
     }
+}
+
+impl MaybeReadLockedStorage<'_> {
+    fn storage(&self) -> &StorageLayer {
+        match self {
+            Self::HoldingLock(lock) => lock,
+            Self::NotHoldingLock(storage) => storage,
+        }
+    }
+}
+
+/// A statement that can only read from the database. Executing anything that
+/// would modify it returns an error.
+pub struct ReadPreparedStatement<'stmt> {
+    storage: MaybeReadLockedStorage<'stmt>,
+    query: PreparedQuery<'stmt>,
+}
+impl ReadPreparedStatement<'_> {
+    pub fn query(&self) -> Result<Rows<'_>> {
+        self.query_with([])
+    }
+
+    /// See `PreparedStatement::query_row`
+    pub fn query_row<T, F>(&self, map_fn: F) -> Result<Option<T>>
+    where
+        F: Fn(&Row) -> Result<T>,
+    {
+        self.query()?.mapped(map_fn).next().transpose()
+    }
+
+    /// Like `query`, with each value bound to the placeholder of the same name
+    pub fn query_with<P: Params>(&self, params: P) -> Result<Rows<'_>> {
+        let values = checked_bindings(&params, &self.query)?;
+        let res = self.query.execute_read(&values, self.storage.storage())?;
+        Ok(Rows::from_result(res))
+    }
+}
+impl TableKnowledge for ReadPreparedStatement<'_> {
+    fn table_exists(&self, name: &str) -> bool {
+        self.storage.storage().table_exists(name)
+    }
 
     fn table_schema(&self, name: &str) -> Result<Schema> {
-        let schema = match &self.storage {
-            MaybeLockedStorage::HoldingLock(lock) => lock.table_schema(name)?,
-            MaybeLockedStorage::NotHoldingLock(storage) => storage.table_schema(name)?,
-        };
+        let schema = self.storage.storage().table_schema(name)?;
         Ok(schema.clone())
     }
+
+    fn table_names(&self) -> Vec<String> {
+        self.storage.storage().table_names()
+    }
+
+    fn table_info(&self, name: &str) -> Option<TableInfo> {
+        self.storage.storage().table_info(name)
+    }
 }
 
 pub trait Params {
-    fn bind_to(&self, target: &str) -> String;
+    /// The value to bind to each placeholder, keyed by the placeholder's name
+    fn bindings(&self) -> Result<Vec<(&str, DbValue)>>;
 }
-impl<T: ToSql> Params for &[(&str, T)] {
-    fn bind_to(&self, target: &str) -> String {
-        let mut bound = target.to_string();
-        for (target, replacement) in *self {
-            bound = bound.replace(target, replacement.to_sql().as_ref());
+
+/// The bindings of `params`, checked to give each of the query's placeholders at
+/// most one value, and no value to a placeholder it doesn't have
+fn checked_bindings<'p>(
+    params: &'p impl Params,
+    query: &PreparedQuery,
+) -> Result<Vec<(&'p str, DbValue)>> {
+    let values = params.bindings()?;
+    let mut seen = HashSet::new();
+    for (name, _) in &values {
+        if !query.has_placeholder(name) {
+            return Err(DatabaseError::UnknownParameter(name.to_string()));
+        }
+        if !seen.insert(*name) {
+            return Err(DatabaseError::DuplicateParameter(name.to_string()));
         }
-        bound
+    }
+    Ok(values)
+}
+impl<T: ToSql> Params for &[(&str, T)] {
+    fn bindings(&self) -> Result<Vec<(&str, DbValue)>> {
+        self.iter()
+            .map(|(name, val)| Ok((*name, val.to_sql()?)))
+            .collect()
     }
 }
 impl Params for &[(&str, &dyn ToSql)] {
-    fn bind_to(&self, target: &str) -> String {
-        let mut bound = target.to_string();
-        for (target, replacement) in *self {
-            bound = bound.replace(target, replacement.to_sql().as_ref());
-        }
-        bound
+    fn bindings(&self) -> Result<Vec<(&str, DbValue)>> {
+        self.iter()
+            .map(|(name, val)| Ok((*name, val.to_sql()?)))
+            .collect()
     }
 }
 impl Params for [&dyn ToSql; 0] {
-    fn bind_to(&self, target: &str) -> String {
-        target.to_string()
+    fn bindings(&self) -> Result<Vec<(&str, DbValue)>> {
+        Ok(Vec::new())
     }
 }
 // TODO: Figure out how to write a macro to generate code for abitrary tuple sizes
@@ -463,52 +1181,71 @@ where
     V: ToSql,
     W: ToSql,
 {
-    fn bind_to(&self, target: &str) -> String {
-        // TODO: Do this in a smarter way with a smarter type/function
-        let mut bound = target.replace(self.0 .0, self.0 .1.to_sql().as_ref());
-        bound = bound.replace(self.1 .0, self.1 .1.to_sql().as_ref());
-        bound = bound.replace(self.2 .0, self.2 .1.to_sql().as_ref());
-        bound = bound.replace(self.3 .0, self.3 .1.to_sql().as_ref());
-        bound
+    fn bindings(&self) -> Result<Vec<(&str, DbValue)>> {
+        Ok(vec![
+            (self.0 .0, self.0 .1.to_sql()?),
+            (self.1 .0, self.1 .1.to_sql()?),
+            (self.2 .0, self.2 .1.to_sql()?),
+            (self.3 .0, self.3 .1.to_sql()?),
+        ])
     }
 }
 
 trait ToSql {
-    fn to_sql(&self) -> String;
+    fn to_sql(&self) -> Result<DbValue>;
 }
 impl ToSql for String {
-    fn to_sql(&self) -> String {
-        format!("\"{}\"", escape_str(self))
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::String(self.clone()))
     }
 }
 impl ToSql for &String {
-    fn to_sql(&self) -> String {
-        format!("\"{}\"", escape_str(self))
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::String(self.to_string()))
     }
 }
 impl ToSql for &str {
-    fn to_sql(&self) -> String {
-        format!("\"{}\"", escape_str(self))
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::String(self.to_string()))
     }
 }
 impl ToSql for f64 {
-    fn to_sql(&self) -> String {
-        format!("{:}", self)
+    /// Fails for NaN and infinities, which can't be stored
+    fn to_sql(&self) -> Result<DbValue> {
+        DbFloat::try_new(*self)
+            .map(DbValue::Float)
+            .ok_or(DatabaseError::InvalidTypeMapping)
     }
 }
 impl ToSql for i64 {
-    fn to_sql(&self) -> String {
-        self.to_string()
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::Integer(*self))
+    }
+}
+impl ToSql for i32 {
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::SmallInt(*self))
     }
 }
 impl ToSql for u64 {
-    fn to_sql(&self) -> String {
-        self.to_string()
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::UnsignedInt(*self))
     }
 }
 impl ToSql for usize {
-    fn to_sql(&self) -> String {
-        self.to_string()
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::UnsignedInt(*self as u64))
+    }
+}
+impl ToSql for bool {
+    /// There's no boolean type, so like SQLite's `TRUE` and `FALSE` these are 1 and 0
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(DbValue::SmallInt(*self as i32))
+    }
+}
+impl ToSql for DbValue {
+    fn to_sql(&self) -> Result<DbValue> {
+        Ok(self.clone())
     }
 }
 
@@ -518,7 +1255,7 @@ pub trait FromSql: Sized {
 impl FromSql for String {
     fn from_sql(sql_val: &DbValue) -> Result<Self> {
         match sql_val {
-            DbValue::String(s) => Ok(s.clone()),
+            DbValue::String(s) | DbValue::Char(s) => Ok(s.clone()),
             _ => Err(DatabaseError::InvalidTypeMapping),
         }
     }
@@ -547,6 +1284,14 @@ impl FromSql for i64 {
         }
     }
 }
+impl FromSql for i32 {
+    fn from_sql(sql_val: &DbValue) -> Result<Self> {
+        match sql_val {
+            DbValue::SmallInt(i) => Ok(*i),
+            _ => Err(DatabaseError::InvalidTypeMapping),
+        }
+    }
+}
 impl FromSql for usize {
     fn from_sql(sql_val: &DbValue) -> Result<Self> {
         match sql_val {
@@ -558,58 +1303,1207 @@ impl FromSql for usize {
 
 pub trait DataAccess {
     fn get<T: FromSql>(&self, idx: usize) -> Result<T>;
+
+    /// Like `get`, but finds the column by name in the schema the row was returned with
+    fn get_by_name<T: FromSql>(&self, name: &str, schema: &Schema) -> Result<T>;
+
+    /// The value as it's stored, for when its type isn't known ahead of time
+    fn value(&self, idx: usize) -> Result<&DbValue>;
+
+    /// Like `value`, but finds the column by name in the schema the row was returned with
+    fn value_by_name(&self, name: &str, schema: &Schema) -> Result<&DbValue>;
 }
 impl DataAccess for Row {
     fn get<T: FromSql>(&self, idx: usize) -> Result<T> {
-        match self.data.get(idx) {
+        T::from_sql(self.value(idx)?)
+    }
+
+    fn get_by_name<T: FromSql>(&self, name: &str, schema: &Schema) -> Result<T> {
+        match schema.column_position(name) {
+            None => Err(DatabaseError::RowPositionInvalid),
+            Some(idx) => self.get(idx),
+        }
+    }
+
+    fn value(&self, idx: usize) -> Result<&DbValue> {
+        self.data.get(idx).ok_or(DatabaseError::RowPositionInvalid)
+    }
+
+    fn value_by_name(&self, name: &str, schema: &Schema) -> Result<&DbValue> {
+        match schema.column_position(name) {
             None => Err(DatabaseError::RowPositionInvalid),
-            Some(v) => T::from_sql(v),
+            Some(idx) => self.value(idx),
         }
     }
 }
 
-fn escape_str(input: &str) -> String {
-    let mut lookbehind = '\0';
-    let mut parts = input.split(|c| {
-        let mut res = false;
-        if c == '"' && lookbehind != '\\' {
-            res = true;
+/// Conversion from a whole row, taking each column in order through `FromSql`
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self>;
+}
+macro_rules! impl_from_row_for_tuple {
+    ($len:expr; $($idx:tt $t:ident),+) => {
+        impl<$($t: FromSql),+> FromRow for ($($t,)+) {
+            fn from_row(row: &Row) -> Result<Self> {
+                if row.data.len() != $len {
+                    return Err(DatabaseError::InvalidTypeMapping);
+                }
+                Ok(($(row.get::<$t>($idx)?,)+))
+            }
         }
-        lookbehind = c;
-        res
-    });
+    };
+}
+impl_from_row_for_tuple!(1; 0 A);
+impl_from_row_for_tuple!(2; 0 A, 1 B);
+impl_from_row_for_tuple!(3; 0 A, 1 B, 2 C);
+impl_from_row_for_tuple!(4; 0 A, 1 B, 2 C, 3 D);
+impl_from_row_for_tuple!(5; 0 A, 1 B, 2 C, 3 D, 4 E);
+impl_from_row_for_tuple!(6; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_from_row_for_tuple!(7; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_from_row_for_tuple!(8; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
 
-    let mut escaped = String::with_capacity(input.len());
+/// Escapes quotes and backslashes in `input` for writing it in double quotes
+fn escaped_str(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('"', "\\\"")
+}
 
-    // add first part so we can add escaped quotes between the rest easily
-    escaped += match parts.next() {
-        Some(part) => part,
-        None => return escaped,
-    };
-    parts.fold(escaped, |mut accum, part| {
-        accum += "\\\"";
-        accum += part;
-        accum
-    })
+/// Writes `input` as a single-quoted string literal, doubling any quotes in it
+fn quoted_str(input: &str) -> String {
+    format!("'{}'", input.replace('\'', "''"))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::*;
 
     #[test]
-    fn escape_str_escapes() {
-        let input = "a \" b \" c \" d";
-        let expected = "a \\\" b \\\" c \\\" d";
-        let actual = escape_str(input);
-        assert_eq!(expected, actual);
+    fn quoted_str_doubles_quotes() {
+        assert_eq!(quoted_str("it's 'quoted'"), "'it''s ''quoted'''");
+        // other quotes and backslashes are written as they are
+        assert_eq!(quoted_str("a \" b \\"), "'a \" b \\'");
+    }
+
+    #[test]
+    fn rows_can_be_put_in_sets() {
+        let row = |f: f64, s: &str| {
+            Row::new(vec![
+                DbValue::Float(DbFloat::new(f)),
+                DbValue::String(String::from(s)),
+                DbValue::Integer(1),
+            ])
+        };
+        let rows = [
+            row(1.5, "a"),
+            row(1.5, "a"),
+            row(1.5, "b"),
+            row(2.5, "a"),
+            // equal to 0.0, so it has to hash the same
+            row(-0.0, "a"),
+            row(0.0, "a"),
+        ];
+        let set: HashSet<Row> = rows.iter().cloned().collect();
+        assert_eq!(set.len(), 4);
+        assert!(set.contains(&row(0.0, "a")));
+        assert!(set.contains(&row(-0.0, "a")));
+        assert!(!set.contains(&row(1.5, "c")));
+
+        // a value's type is part of it, so equal numbers of different types are different
+        let values: HashSet<DbValue> = [
+            DbValue::Integer(1),
+            DbValue::UnsignedInt(1),
+            DbValue::SmallInt(1),
+            DbValue::Float(DbFloat::new(1.0)),
+            DbValue::Integer(1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(values.len(), 4);
+    }
+
+    #[test]
+    fn generated_strings_round_trip() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (id INTEGER, s STRING, c CHAR(3));")
+            .unwrap();
+        let mut rng = generate::RNG::with_seed(3);
+        let mut strings: Vec<String> = ["it's", "'", "''", "\"'\"", "back\\", "'; DROP TABLE t;"]
+            .map(String::from)
+            .to_vec();
+        strings.extend((0..200).map(|_| String::generate(&mut rng)));
+        assert!(strings[6..].iter().any(|s| s.contains('\'')));
+
+        let chars: Vec<DbValue> = strings
+            .iter()
+            .map(|_| DbType::Char(3).generate_val(&mut rng))
+            .collect();
+        let inserts: String = strings
+            .iter()
+            .zip(&chars)
+            .enumerate()
+            .map(|(id, (s, c))| {
+                format!(
+                    "INSERT INTO t (id, s, c) VALUES ({id}, {}, {});",
+                    DbValue::String(s.clone()).as_insertable_sql_str(),
+                    c.as_insertable_sql_str()
+                )
+            })
+            .collect();
+        db.execute(&inserts).unwrap();
+
+        let stmt = db.prepare_read("SELECT s, c FROM t ORDER BY id;").unwrap();
+        let stored: Vec<(String, DbValue)> = stmt
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| Ok((r.get::<String>(0)?, r.value(1)?.clone())))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected: Vec<(String, DbValue)> = strings.into_iter().zip(chars).collect();
+        assert_eq!(stored, expected);
+    }
+
+    #[test]
+    fn dump_round_trips_names_that_need_quoting() {
+        let source_file = Path::new("dump_round_trips_names_that_need_quoting_source.test");
+        let target_file = Path::new("dump_round_trips_names_that_need_quoting_target.test");
+        let source = Database::init(source_file).unwrap();
+        source
+            .execute(
+                "CREATE TABLE `my t` (`my col` INTEGER PRIMARY KEY, `select` STRING, \
+                CONSTRAINT `positive col` CHECK (`my col` > 0));",
+            )
+            .unwrap();
+        source
+            .execute(
+                "CREATE TABLE `from` (id INTEGER, `t-id` INTEGER, \
+                FOREIGN KEY (`t-id`) REFERENCES `my t`(`my col`));",
+            )
+            .unwrap();
+        source
+            .execute("INSERT INTO `my t` (`my col`, `select`) VALUES (1, 'a');")
+            .unwrap();
+        source
+            .execute("INSERT INTO `from` (id, `t-id`) VALUES (2, 1);")
+            .unwrap();
+
+        let tx = source.transaction().unwrap();
+        let dump = tx.dump();
+        drop(tx);
+        assert!(dump[0].starts_with("CREATE TABLE `my t` (`my col` INTEGER PRIMARY KEY"));
+
+        let target = Database::init(target_file).unwrap();
+        target.execute(&dump.join("\n")).unwrap();
+
+        let tx = target.transaction().unwrap();
+        assert_eq!(tx.dump(), dump);
+        drop(tx);
+        // the constraints came across too
+        assert!(target
+            .execute("INSERT INTO `my t` (`my col`, `select`) VALUES (0, 'b');")
+            .is_err());
+        assert!(target
+            .execute("INSERT INTO `from` (id, `t-id`) VALUES (3, 5);")
+            .is_err());
+
+        drop(source);
+        drop(target);
+        fs::remove_file(source_file).unwrap();
+        fs::remove_file(target_file).unwrap();
+    }
+
+    #[test]
+    fn dump_round_trips() {
+        let source_file = Path::new("dump_round_trips_source.test");
+        let target_file = Path::new("dump_round_trips_target.test");
+        let source = Database::init(source_file).unwrap();
+        source
+            .execute("CREATE TABLE t (name STRING PRIMARY KEY, count INTEGER, size FLOAT, id UNSIGNED INT);")
+            .unwrap();
+        source
+            .execute("INSERT INTO t (name, count, size, id) VALUES (\"a \\\" b\", -3, 1.5, 7);")
+            .unwrap();
+        source
+            .execute(
+                "INSERT INTO t (name, count, size, id) VALUES (\"c\", 4, 2, 18446744073709551615);",
+            )
+            .unwrap();
+
+        let tx = source.transaction().unwrap();
+        let dump = tx.dump();
+        drop(tx);
+
+        let target = Database::init(target_file).unwrap();
+        target.execute(&dump.join("\n")).unwrap();
+
+        let tx = target.transaction().unwrap();
+        assert_eq!(tx.dump(), dump);
+        assert!(dump[0].contains("name STRING PRIMARY KEY"));
+        drop(tx);
+
+        drop(source);
+        drop(target);
+        fs::remove_file(source_file).unwrap();
+        fs::remove_file(target_file).unwrap();
+    }
+
+    #[test]
+    fn table_schema_info_marks_primary_key() {
+        let file = Path::new("table_schema_info_marks_primary_key.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE a (foo STRING, bar INTEGER PRIMARY KEY);")
+            .unwrap();
+        db.execute("CREATE TABLE b (foo STRING);").unwrap();
+
+        let tx = db.transaction().unwrap();
+        assert_eq!(tx.table_names(), vec!["a", "b"]);
+        assert_eq!(
+            tx.table_schema_info("a").unwrap(),
+            "a: [foo (String), bar (Integer)]\n  primary key: bar"
+        );
+        assert_eq!(
+            tx.table_schema_info("b").unwrap(),
+            "b: [foo (String)]\n  primary key: rowid"
+        );
+        assert!(tx.table_schema_info("c").is_err());
+        drop(tx);
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn tables_can_be_built() {
+        let file = Path::new("tables_can_be_built.test");
+        let db = Database::init(file).unwrap();
+        db.create_table("users")
+            .column("id", DbType::UnsignedInt)
+            .column("email", DbType::Varchar(50))
+            .collated(Collation::NoCase)
+            .column("age", DbType::SmallInt)
+            .primary_key("email")
+            .build()
+            .unwrap();
+        db.execute("INSERT INTO users (id, email, age) VALUES (1, 'a@b.c', 30);")
+            .unwrap();
+        // the primary key compares with the collation it was given
+        assert!(db
+            .execute("INSERT INTO users (id, email, age) VALUES (2, 'A@B.C', 31);")
+            .is_err());
+        drop(db);
+
+        // built tables are written out like created ones
+        let db = Database::init(file).unwrap();
+        let info = db.table_info("users").unwrap();
+        assert_eq!(info.primary_key, "email");
+        assert_eq!(info.row_count, 1);
+        assert_eq!(info.schema.columns().count(), 3);
+
+        let error = |builder: TableBuilder| builder.build().unwrap_err().root().to_string();
+        assert_eq!(
+            error(db.create_table("users").column("a", DbType::Integer)),
+            "Table already exists"
+        );
+        assert_eq!(
+            error(
+                db.create_table("t")
+                    .column("a", DbType::Integer)
+                    .column("a", DbType::String)
+            ),
+            "Duplicate column names found"
+        );
+        assert_eq!(
+            error(db.create_table("t").column("RowId", DbType::Integer)),
+            "A column using a reserved name was provided"
+        );
+        assert_eq!(
+            error(
+                db.create_table("t")
+                    .column("a", DbType::Integer)
+                    .primary_key("b")
+            ),
+            "Unknown column name provided: 'b'"
+        );
+        assert!(!db.table_exists("t"));
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn table_info_describes_each_table() {
+        let file = Path::new("table_info_describes_each_table.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE a (foo STRING, bar INTEGER PRIMARY KEY);")
+            .unwrap();
+        db.execute("CREATE TABLE b (foo STRING);").unwrap();
+        db.execute("INSERT INTO a (foo, bar) VALUES (\"x\", 1);")
+            .unwrap();
+        db.execute("INSERT INTO a (foo, bar) VALUES (\"y\", 2);")
+            .unwrap();
+
+        assert_eq!(db.table_names(), vec!["a", "b"]);
+        let info = db.table_info("a").unwrap();
+        assert_eq!(info.name, "a");
+        assert_eq!(info.primary_key, "bar");
+        assert_eq!(info.row_count, 2);
+        assert_eq!(info.schema.columns().count(), 2);
+
+        let info = db.table_info("b").unwrap();
+        assert_eq!(info.primary_key, "rowid");
+        assert_eq!(info.row_count, 0);
+        assert!(db.table_info("c").is_none());
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn last_insert_rowid_tracks_inserts() {
+        let file = Path::new("last_insert_rowid_tracks_inserts.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER PRIMARY KEY);")
+            .unwrap();
+
+        let mut tx = db.transaction().unwrap();
+        assert_eq!(
+            tx.prepare("SELECT a FROM t;").unwrap().last_insert_rowid(),
+            None
+        );
+        let id = {
+            let mut stmt = tx
+                .prepare("INSERT INTO t (a) VALUES (1); INSERT INTO t (a) VALUES (2);")
+                .unwrap();
+            stmt.execute([]).unwrap();
+            stmt.last_insert_rowid().unwrap()
+        };
+
+        // skipped inserts leave the last id alone
+        {
+            let mut stmt = tx
+                .prepare("INSERT INTO t (a) VALUES (2) ON CONFLICT (a) DO NOTHING;")
+                .unwrap();
+            stmt.execute([]).unwrap();
+            assert_eq!(stmt.last_insert_rowid(), Some(id));
+        }
+
+        {
+            let mut stmt = tx.prepare("SELECT rowid FROM t WHERE a = 2;").unwrap();
+            let rowids: Vec<usize> = stmt
+                .query()
+                .unwrap()
+                .mapped(|r: &Row| r.get::<usize>(0))
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(rowids, vec![id]);
+        }
+        drop(tx);
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn read_only_database_rejects_changes() {
+        let file = Path::new("read_only_database_rejects_changes.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        db.execute("INSERT INTO t (a) VALUES (1);").unwrap();
+        drop(db);
+        let contents = fs::read(file).unwrap();
+
+        let db = Database::init_read_only(file).unwrap();
+        let rows: Vec<i64> = {
+            let mut stmt = db.prepare("SELECT a FROM t;").unwrap();
+            stmt.query()
+                .unwrap()
+                .mapped(|r: &Row| r.get::<i64>(0))
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+        assert_eq!(rows, vec![1]);
+
+        for stmt in [
+            "INSERT INTO t (a) VALUES (2);",
+            "DELETE FROM t WHERE a = 1;",
+            "CREATE TABLE u (a INTEGER);",
+            "DESTROY TABLE t;",
+        ] {
+            assert!(db.execute(stmt).is_err());
+        }
+        db.commit().unwrap();
+        let mut tx = db.transaction().unwrap();
+        assert!(matches!(
+            tx.checkpoint(),
+            Err(DatabaseError::StorageError(StorageError::ReadOnly))
+        ));
+        drop(tx);
+        drop(db);
+        assert_eq!(fs::read(file).unwrap(), contents);
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn readers_share_access() {
+        let file = Path::new("readers_share_access.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        db.execute("INSERT INTO t (a) VALUES (1);").unwrap();
+
+        let first = db.read_transaction().unwrap();
+        let second = db.prepare_read("SELECT a FROM t;").unwrap();
+        let query = first.prepare("SELECT a FROM t;").unwrap();
+        for stmt in [&query, &second] {
+            let rows: Vec<i64> = stmt
+                .query()
+                .unwrap()
+                .mapped(|r: &Row| r.get::<i64>(0))
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(rows, vec![1]);
+        }
+        assert!(first
+            .prepare("INSERT INTO t (a) VALUES (2);")
+            .unwrap()
+            .query()
+            .is_err());
+        drop(query);
+        drop(first);
+        drop(second);
+
+        let db = std::sync::Arc::new(db);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    let stmt = db.prepare_read("SELECT a FROM t;").unwrap();
+                    let count = stmt.query().unwrap().count();
+                    count
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 1);
+        }
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn in_memory_database() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        db.execute("INSERT INTO t (a) VALUES (1);").unwrap();
+        db.commit().unwrap();
+
+        let stmt = db.prepare_read("SELECT a FROM t;").unwrap();
+        let rows: Vec<i64> = stmt
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| r.get::<i64>(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows, vec![1]);
+        drop(stmt);
+
+        // aborting goes back to what was last committed, the same as with a file
+        let mut tx = db.transaction().unwrap();
+        tx.execute("INSERT INTO t (a) VALUES (2);").unwrap();
+        tx.abort().unwrap();
+        let count: usize = db
+            .prepare_read("SELECT COUNT(*) FROM t;")
+            .unwrap()
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| r.get::<usize>(0))
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn checkpoints_flush_without_ending_the_transaction() {
+        let file = Path::new("checkpoints_flush_without_ending_the_transaction.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        db.commit().unwrap();
+        let count = |db: &Database| -> usize {
+            db.prepare_read("SELECT COUNT(*) FROM t;")
+                .unwrap()
+                .query()
+                .unwrap()
+                .mapped(|r: &Row| r.get::<usize>(0))
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+
+        let mut tx = db.transaction().unwrap();
+        tx.execute("INSERT INTO t (a) VALUES (1);").unwrap();
+        tx.checkpoint().unwrap();
+        // the checkpointed row is on disk while the transaction is still open
+        assert_eq!(count(&Database::init_read_only(file).unwrap()), 1);
+
+        tx.execute("INSERT INTO t (a) VALUES (2);").unwrap();
+        tx.abort().unwrap();
+        assert_eq!(count(&db), 1);
+
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn newer_file_versions_are_rejected() {
+        let file = Path::new("newer_file_versions_are_rejected.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        db.commit().unwrap();
+        drop(db);
+
+        // the database header's version follows the lengths of the storage layer
+        // and its header, which are both written as sequences, so writing just those
+        // and the version finds where it is
+        #[derive(Serialize)]
+        struct Prefix {
+            db_header: Version,
+        }
+        #[derive(Serialize)]
+        struct Version {
+            header_version: u16,
+        }
+        let mut prefix = Vec::new();
+        let version = Version { header_version: 0 };
+        storage::write::to_writer(&mut prefix, &Prefix { db_header: version }).unwrap();
+        let offset = prefix.len() - std::mem::size_of::<u16>();
+
+        let mut bytes = fs::read(file).unwrap();
+        bytes[offset..prefix.len()].copy_from_slice(&u16::MAX.to_le_bytes());
+        fs::write(file, bytes).unwrap();
+
+        let result = Database::init(file);
+        fs::remove_file(file).unwrap();
+        let err = match result.map_err(DatabaseError::into_root) {
+            Err(DatabaseError::StorageError(err)) => err,
+            Err(err) => panic!("Unexpected error: {err}"),
+            Ok(_) => panic!("Opened a file from a newer version"),
+        };
+        assert!(
+            matches!(
+                err,
+                StorageError::UnsupportedVersion {
+                    found: u16::MAX,
+                    supported: 0
+                }
+            ),
+            "Unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn booleans_are_bound_as_integers() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (id INTEGER, flag SMALLINT);")
+            .unwrap();
+        let mut insert = db
+            .prepare("INSERT INTO t (id, flag) VALUES (:id, :flag);")
+            .unwrap();
+        for (id, flag) in [(1, true), (2, false), (3, true)] {
+            insert
+                .execute(&[(":id", &(id as i64) as &dyn ToSql), (":flag", &flag)][..])
+                .unwrap();
+        }
+        drop(insert);
+
+        let stmt = db
+            .prepare_read("SELECT id FROM t WHERE flag = :flag;")
+            .unwrap();
+        let ids: Vec<i64> = stmt
+            .query_with(&[(":flag", true)][..])
+            .unwrap()
+            .mapped(|r: &Row| r.get::<i64>(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(true.to_sql().unwrap(), DbValue::SmallInt(1));
+        assert_eq!(false.to_sql().unwrap(), DbValue::SmallInt(0));
+    }
+
+    #[test]
+    fn non_finite_floats_are_rejected() {
+        assert!(DbFloat::try_new(f64::NAN).is_none());
+        assert!(DbFloat::try_new(f64::INFINITY).is_none());
+        assert_eq!(DbFloat::try_new(1.5), Some(DbFloat::new(1.5)));
+
+        let mut bytes = Vec::new();
+        storage::write::to_writer(&mut bytes, &DbFloat::new(1.5)).unwrap();
+        let float: DbFloat = storage::read::from_bytes(&bytes).unwrap();
+        assert_eq!(float, DbFloat::new(1.5));
+
+        // a corrupted float is an error rather than a panic
+        let len = bytes.len();
+        bytes[len - 8..].copy_from_slice(&f64::NAN.to_le_bytes());
+        assert!(storage::read::from_bytes::<DbFloat>(&bytes).is_err());
+
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a FLOAT);").unwrap();
+        assert!(db.execute("INSERT INTO t (a) VALUES (1.0e999);").is_err());
+    }
+
+    const BATCH_INSERT: &str =
+        "INSERT INTO t (id, name, score, n) VALUES (:id, :name, :score, :n);";
+
+    fn batch_params(i: i64) -> impl Params {
+        (
+            (":id", i),
+            (":name", format!("\"row\", {i}")),
+            (":score", i as f64 / 2.0),
+            (":n", i as u64),
+        )
+    }
+
+    fn batch_table() -> Database {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (id INTEGER, name STRING, score FLOAT, n UNSIGNED INT);")
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn execute_batch() {
+        let db = batch_table();
+        let affected = db
+            .prepare(BATCH_INSERT)
+            .unwrap()
+            .execute_batch((0..100).map(batch_params))
+            .unwrap();
+        assert_eq!(affected, 100);
+
+        let stmt = db
+            .prepare_read("SELECT id, name, score FROM t WHERE n = 7;")
+            .unwrap();
+        let rows: Vec<(i64, String, f64)> = stmt
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .collect::<Result<_>>()
+            .unwrap();
+        // bound strings are stored as is, without any escaping
+        assert_eq!(rows, vec![(7, String::from("\"row\", 7"), 3.5)]);
+        drop(stmt);
+
+        let err = db.execute("SELECT :id FROM t;").unwrap_err();
+        assert!(err.to_string().starts_with("No value was bound to ':id'"));
+    }
+
+    #[test]
+    fn rows_map_into_tuples() {
+        let db = batch_table();
+        db.prepare(BATCH_INSERT)
+            .unwrap()
+            .execute_batch((0..3).map(batch_params))
+            .unwrap();
+
+        let stmt = db
+            .prepare_read("SELECT name, id, n FROM t ORDER BY id;")
+            .unwrap();
+        let rows: Vec<(String, i64, usize)> = stmt
+            .query()
+            .unwrap()
+            .mapped_into()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows[2], (String::from("\"row\", 2"), 2, 2));
+        assert_eq!(rows.len(), 3);
+
+        // columns in the wrong order, or the wrong number of them, don't map
+        let res: Result<Vec<(i64, String, usize)>> = stmt.query().unwrap().mapped_into().collect();
+        assert!(matches!(res, Err(DatabaseError::InvalidTypeMapping)));
+        let res: Result<Vec<(String, i64)>> = stmt.query().unwrap().mapped_into().collect();
+        assert!(matches!(res, Err(DatabaseError::InvalidTypeMapping)));
+    }
+
+    #[test]
+    fn get_columns_by_name() {
+        let db = batch_table();
+        db.prepare(BATCH_INSERT)
+            .unwrap()
+            .execute_batch((0..3).map(batch_params))
+            .unwrap();
+
+        let stmt = db
+            .prepare_read("SELECT n, name AS title FROM t WHERE id = 1;")
+            .unwrap();
+        let mut rows = stmt.query().unwrap();
+        let schema = rows.schema().unwrap();
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(
+            row.get_by_name::<String>("title", &schema).unwrap(),
+            "\"row\", 1"
+        );
+        assert_eq!(row.get_by_name::<u64>("n", &schema).unwrap(), 1);
+        assert!(matches!(
+            row.get_by_name::<String>("name", &schema),
+            Err(DatabaseError::RowPositionInvalid)
+        ));
+    }
+
+    #[test]
+    fn get_raw_values() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER, b FLOAT, c CHAR(3));")
+            .unwrap();
+        db.execute("INSERT INTO t (a, b, c) VALUES (-1, 1.5, 'x');")
+            .unwrap();
+
+        let stmt = db.prepare_read("SELECT a, b, c AS code FROM t;").unwrap();
+        let mut rows = stmt.query().unwrap();
+        let schema = rows.schema().unwrap();
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.value(0).unwrap(), &DbValue::Integer(-1));
+        assert_eq!(row.value(1).unwrap(), &DbValue::Float(DbFloat::new(1.5)));
+        // values are returned without being converted, so CHAR keeps its padding
+        assert_eq!(
+            row.value_by_name("code", &schema).unwrap(),
+            &DbValue::Char(String::from("x  "))
+        );
+        assert!(matches!(
+            row.value(3),
+            Err(DatabaseError::RowPositionInvalid)
+        ));
+        assert!(matches!(
+            row.value_by_name("c", &schema),
+            Err(DatabaseError::RowPositionInvalid)
+        ));
+    }
+
+    #[test]
+    fn prepare_reports_parse_errors() {
+        let db = batch_table();
+        assert!(db.prepare("INSERT INTO t (id) VALUES (;").is_err());
+        assert!(db.prepare_read("SELECT FROM t;").is_err());
+        let mut tx = db.transaction().unwrap();
+        assert!(tx.prepare("DELETE t;").is_err());
+    }
+
+    #[test]
+    fn rows_are_read_as_they_are_iterated() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        for a in [1, 2, 0, 4] {
+            db.execute(&format!("INSERT INTO t (a) VALUES ({a});"))
+                .unwrap();
+        }
+
+        // the rows before the one that fails are returned before it's reached
+        let stmt = db.prepare_read("SELECT 12 / a FROM t;").unwrap();
+        let mut rows = stmt.query().unwrap().mapped(|r: &Row| r.get::<i64>(0));
+        assert_eq!(rows.next().unwrap().unwrap(), 12);
+        assert_eq!(rows.next().unwrap().unwrap(), 6);
+        assert!(rows.next().unwrap().is_err());
+
+        // the statement holds the lock for as long as its rows are borrowed
+        assert!(db.storage.try_write().is_err());
+        drop(rows);
+        drop(stmt);
+        assert!(db.storage.try_write().is_ok());
+
+        let mut stmt = db.prepare("SELECT a FROM t WHERE a > :min;").unwrap();
+        let rows: Vec<i64> = stmt
+            .query_with(&[(":min", 1i64)][..])
+            .unwrap()
+            .mapped(|r: &Row| r.get(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows, vec![2, 4]);
+    }
+
+    #[test]
+    fn keyset_pagination() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        for a in 0..7 {
+            db.execute(&format!("INSERT INTO t (a) VALUES ({a});"))
+                .unwrap();
+        }
+        db.execute("DELETE FROM t WHERE a = 3;").unwrap();
+
+        let mut pages = Vec::new();
+        let mut after = None;
+        loop {
+            let page = db.page_after("t", after, 2).unwrap();
+            let values: Vec<i64> = page.rows.iter().map(|r| r.get(0).unwrap()).collect();
+            pages.push(values);
+            after = page.cursor;
+            if after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, vec![vec![0, 1], vec![2, 4], vec![5, 6]]);
+        assert_eq!(
+            db.page_after("t", Some(6), 2).unwrap(),
+            Page {
+                rows: Vec::new(),
+                cursor: None
+            }
+        );
+        assert!(db.page_after("missing", None, 2).is_err());
+
+        // the same pages, through a query
+        let stmt = db
+            .prepare_read("SELECT a FROM t WHERE rowid > :after ORDER BY rowid LIMIT 2;")
+            .unwrap();
+        let page: Vec<i64> = stmt
+            .query_with(&[(":after", 2i64)][..])
+            .unwrap()
+            .mapped(|r: &Row| r.get(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(page, vec![4, 5]);
+    }
+
+    #[test]
+    fn parameters_are_checked() {
+        let db = batch_table();
+        let mut stmt = db.prepare(BATCH_INSERT).unwrap();
+        let err = stmt
+            .execute(((":id", 1), (":name", "a"), (":id", 2.5), (":n", 3u64)))
+            .unwrap_err();
+        assert!(matches!(&err, DatabaseError::DuplicateParameter(name) if name == ":id"));
+        let err = stmt
+            .execute(((":id", 1), (":name", "a"), (":scor", 2.5), (":n", 3u64)))
+            .unwrap_err();
+        assert!(matches!(&err, DatabaseError::UnknownParameter(name) if name == ":scor"));
+        assert_eq!(
+            err.to_string(),
+            "The statement has no placeholder named :scor"
+        );
+        drop(stmt);
+
+        // a placeholder used twice in the statement takes one value for both
+        let stmt = db
+            .prepare_read("SELECT :id + 1 FROM t WHERE id = :id;")
+            .unwrap();
+        assert!(stmt.query_with(&[(":id", 1i64)][..]).is_ok());
+        assert!(matches!(
+            stmt.query_with(&[(":id", 1i64), (":id", 2i64)][..]),
+            Err(DatabaseError::DuplicateParameter(_))
+        ));
+        assert!(matches!(
+            stmt.query_with(&[(":other", 1i64)][..]),
+            Err(DatabaseError::UnknownParameter(_))
+        ));
+    }
+
+    #[test]
+    fn escaped_strings_round_trip() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (id INTEGER, s STRING);")
+            .unwrap();
+        db.execute(
+            "INSERT INTO t (id, s) VALUES (1, \"a \\\" b \\\\ c\"); \
+             INSERT INTO t (id, s) VALUES (2, 'it''s \\ \"quoted\"');",
+        )
+        .unwrap();
+        let strings = [
+            "a \" b \\ c",
+            "it's \\ \"quoted\"",
+            "\\\"",
+            "\\",
+            "'\"'",
+            "ends with \\",
+        ];
+        let mut insert = db
+            .prepare("INSERT INTO t (id, s) VALUES (:id, :s);")
+            .unwrap();
+        for (id, s) in strings.iter().enumerate().skip(2) {
+            insert
+                .execute(&[(":id", &(id as i64 + 1) as &dyn ToSql), (":s", s)][..])
+                .unwrap();
+        }
+        drop(insert);
+
+        let stmt = db.prepare_read("SELECT s FROM t ORDER BY id;").unwrap();
+        let stored: Vec<String> = stmt
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| r.get::<String>(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(stored, strings);
+
+        // literals compare against the unescaped values
+        let stmt = db
+            .prepare_read("SELECT id FROM t WHERE s = \"a \\\" b \\\\ c\";")
+            .unwrap();
+        assert_eq!(stmt.query_row(|r: &Row| r.get::<i64>(0)).unwrap(), Some(1));
+        let stmt = db
+            .prepare_read("SELECT id FROM t WHERE s = 'it''s \\ \"quoted\"';")
+            .unwrap();
+        assert_eq!(stmt.query_row(|r: &Row| r.get::<i64>(0)).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn query_single_rows() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE _metadata (version UNSIGNED INT);")
+            .unwrap();
+        let mut stmt = db
+            .prepare("SELECT version FROM _metadata ORDER BY version DESC LIMIT 1;")
+            .unwrap();
+        assert_eq!(stmt.query_row(|r: &Row| r.get::<u64>(0)).unwrap(), None);
+        drop(stmt);
+
+        db.execute("INSERT INTO _metadata (version) VALUES (1);")
+            .unwrap();
+        db.execute("INSERT INTO _metadata (version) VALUES (2);")
+            .unwrap();
+        let stmt = db
+            .prepare_read("SELECT version FROM _metadata ORDER BY version DESC;")
+            .unwrap();
+        assert_eq!(stmt.query_row(|r: &Row| r.get::<u64>(0)).unwrap(), Some(2));
+        assert!(matches!(
+            stmt.query_row(|r: &Row| r.get::<String>(0)),
+            Err(DatabaseError::InvalidTypeMapping)
+        ));
+    }
+
+    #[test]
+    fn sql_transactions() {
+        let file = Path::new("sql_transactions.test");
+        let db = Database::init(file).unwrap();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        let count = |db: &Database| {
+            db.prepare_read("SELECT count(*) FROM t;")
+                .unwrap()
+                .query_row(|r: &Row| r.get::<u64>(0))
+                .unwrap()
+                .unwrap()
+        };
+
+        db.execute("BEGIN;").unwrap();
+        assert!(db.in_transaction().unwrap());
+        db.execute("INSERT INTO t (a) VALUES (1);").unwrap();
+        db.execute("INSERT INTO t (a) VALUES (2);").unwrap();
+        assert_eq!(count(&db), 2);
+        db.execute("ROLLBACK;").unwrap();
+        assert!(!db.in_transaction().unwrap());
+        assert_eq!(count(&db), 0);
+
+        db.execute("BEGIN; INSERT INTO t (a) VALUES (3);").unwrap();
+        let err = db.execute("BEGIN;").unwrap_err();
+        assert_eq!(
+            err.root().to_string(),
+            "A transaction is already open, and they can't be nested"
+        );
+        db.prepare("COMMIT;").unwrap().query().unwrap();
+        assert!(db.execute("COMMIT;").is_err());
+        assert!(db.execute("ROLLBACK;").is_err());
+        // statements outside a transaction are still written out as they run
+        db.prepare("INSERT INTO t (a) VALUES (4);")
+            .unwrap()
+            .query()
+            .unwrap();
+        drop(db);
+
+        // only what was committed was written to the file
+        let db = Database::init(file).unwrap();
+        assert_eq!(count(&db), 2);
+        drop(db);
+        fs::remove_file(file).unwrap();
     }
 
     #[test]
-    fn escaped_str_doesnt_escaped_already_escaped() {
-        let input = "foo \\\" bar";
-        let expected = "foo \\\" bar";
-        let actual = escape_str(input);
-        assert_eq!(expected, actual);
+    fn connections_share_storage() {
+        let file = Path::new("connections_share_storage.test");
+        let first = Database::init(file).unwrap();
+        first.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        let second = first.connect();
+        let count = |db: &Database| {
+            db.prepare_read("SELECT count(*) FROM t;")
+                .unwrap()
+                .query_row(|r: &Row| r.get::<u64>(0))
+                .unwrap()
+                .unwrap()
+        };
+
+        first
+            .execute("BEGIN; INSERT INTO t (a) VALUES (1);")
+            .unwrap();
+        assert!(first.in_transaction().unwrap());
+        assert!(!second.in_transaction().unwrap());
+        // reads see the open transaction's changes, writes wait for it to end
+        assert_eq!(count(&second), 1);
+        let busy = "The database is in use by another session's transaction";
+        for stmt in [
+            "INSERT INTO t (a) VALUES (2);",
+            "BEGIN;",
+            "COMMIT;",
+            "ROLLBACK;",
+        ] {
+            let err = second.execute(stmt).unwrap_err();
+            assert_eq!(err.root().to_string(), busy);
+        }
+        first.execute("COMMIT;").unwrap();
+        second.execute("INSERT INTO t (a) VALUES (2);").unwrap();
+        assert_eq!(count(&first), 2);
+
+        // a connection dropped with a transaction open rolls it back
+        second
+            .execute("BEGIN; INSERT INTO t (a) VALUES (3);")
+            .unwrap();
+        drop(second);
+        assert_eq!(count(&first), 2);
+        first.execute("INSERT INTO t (a) VALUES (4);").unwrap();
+        assert_eq!(count(&first), 3);
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let conn = first.connect();
+                std::thread::spawn(move || {
+                    let stmt = format!("INSERT INTO t (a) VALUES ({i});");
+                    for _ in 0..5 {
+                        conn.execute(&stmt).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(count(&first), 23);
+        drop(first);
+
+        let db = Database::init(file).unwrap();
+        assert_eq!(count(&db), 23);
+        drop(db);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn prepared_statements_are_reused() {
+        let db = batch_table();
+        let mut stmt = db.prepare(BATCH_INSERT).unwrap();
+        for i in 0..3 {
+            assert_eq!(stmt.execute(batch_params(i)).unwrap(), 1);
+        }
+        drop(stmt);
+
+        let stmt = db.prepare_read("SELECT id FROM t ORDER BY id;").unwrap();
+        let ids: Vec<i64> = stmt
+            .query()
+            .unwrap()
+            .mapped(|r: &Row| r.get(0))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn rows_to_csv() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (name STRING, score FLOAT);")
+            .unwrap();
+        db.execute("INSERT INTO t (name, score) VALUES (\"a, b\", 1.5);")
+            .unwrap();
+        db.execute("INSERT INTO t (name, score) VALUES (\"c\", 0.1);")
+            .unwrap();
+
+        let mut out = Vec::new();
+        let written = db
+            .prepare_read("SELECT name, score FROM t;")
+            .unwrap()
+            .query()
+            .unwrap()
+            .to_csv(&mut out)
+            .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,score\r\n\"a, b\",1.5\r\nc,0.1\r\n"
+        );
+
+        let mut stmt = db
+            .prepare("INSERT INTO t (name, score) VALUES (\"d\", 2.0);")
+            .unwrap();
+        assert!(matches!(
+            stmt.query().unwrap().to_csv(&mut Vec::new()),
+            Err(DatabaseError::QueryDidNotReturnRows)
+        ));
+    }
+
+    #[test]
+    fn rows_to_json() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (name STRING, n INTEGER);")
+            .unwrap();
+        let mut out = Vec::new();
+        let written = db
+            .prepare_read("SELECT name, n FROM t;")
+            .unwrap()
+            .query()
+            .unwrap()
+            .to_json(&mut out)
+            .unwrap();
+        assert_eq!((written, out.as_slice()), (0, &b"[]"[..]));
+
+        db.execute("INSERT INTO t (name, n) VALUES (\"a\", 1);")
+            .unwrap();
+        db.execute("INSERT INTO t (name, n) VALUES (\"b\", -2);")
+            .unwrap();
+        let mut out = Vec::new();
+        let written = db
+            .prepare_read("SELECT name, n FROM t;")
+            .unwrap()
+            .query()
+            .unwrap()
+            .to_json(&mut out)
+            .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"[{"name":"a","n":1},{"name":"b","n":-2}]"#
+        );
+    }
+
+    #[test]
+    fn errors_show_context_frames() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        let err = db.execute("SELECT b FROM t;").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "Unknown column name provided: 'b'\n  while selecting from t\n  while executing `SELECT b FROM t;`"
+        );
+        assert!(matches!(
+            err.root(),
+            DatabaseError::QueryError(QueryError::ExecutionError(_))
+        ));
+
+        let err = Database::init(Path::new("missing_dir/errors_show_context_frames.test"))
+            .err()
+            .unwrap();
+        assert!(format!("{err:#}")
+            .ends_with("\n  while opening missing_dir/errors_show_context_frames.test"));
+    }
+
+    #[test]
+    fn errors_display_and_chain() {
+        let db = Database::in_memory();
+        db.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        let err = db.execute("SELECT b FROM t;").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown column name provided: 'b'");
+
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        let mut depth = 0;
+        let mut source = err.source();
+        while let Some(inner) = source {
+            depth += 1;
+            source = inner.source();
+        }
+        // DatabaseError -> QueryError -> ExecutionError
+        assert_eq!(depth, 2);
     }
 }
@@ -11,11 +11,7 @@ use rjsdb::{
 // - fix possible integer overflow during type coercion
 //   - research how other dbs handle this
 // - add tests for parser, execution
-// - transactions in repl
-//   - requires table locks,
 // - host repl on a my website
-// - figure out how to do read-only stuff with unmutable references
-// - "stackable"/"traversable" errors when in dev build
 // - better data structure for representing schema/primary key/etc, to allow:
 //    - showing which col is the pk in repl
 // - Figure out how to manage database connections and accept requests
@@ -53,28 +49,23 @@ fn gen_rows(count: usize, table_name: &str, tx: &mut Transaction, rng: &mut RNG)
 }
 
 fn test_prepare_gen_rows(count: usize, tx: &mut Transaction, rng: &mut RNG) {
-    for _ in 0..count {
-        let stmt = "INSERT INTO the_mf_table (id, foo, bar, baz) VALUES (:id, :foo, :bar, :baz);";
-        // let params = [
-        //     (":id", i64::generate(rng)),
-        //     (":foo", String::generate(rng)),
-        //     (":bar", i64::generate(rng)),
-        //     (":baz", f64::generate(rng)),
-        // ];
-        tx.prepare(stmt)
-            .execute((
-                (":id", i64::generate(rng)),
-                (":foo", String::generate(rng)),
-                (":bar", i64::generate(rng)),
-                (":baz", f64::generate(rng)),
-            ))
-            .unwrap();
-    }
+    let stmt = "INSERT INTO the_mf_table (id, foo, bar, baz) VALUES (:id, :foo, :bar, :baz);";
+    let params = (0..count).map(|_| {
+        (
+            (":id", i64::generate(rng)),
+            (":foo", String::generate(rng)),
+            (":bar", i64::generate(rng)),
+            (":baz", f64::generate(rng)),
+        )
+    });
+    tx.prepare(stmt).unwrap().execute_batch(params).unwrap();
 }
 
 fn main() {
-    let path = Path::new("db.db");
-    let mut db = Database::init(path).unwrap();
+    let mut db = match std::env::args().nth(1) {
+        Some(path) => Database::init(Path::new(&path)).unwrap(),
+        None => Database::in_memory(),
+    };
 
     // let mut rng = RNG::new();
     // if !db.table_exists("the_mf_table") {
@@ -96,15 +87,7 @@ fn main() {
     // let results: Result<Vec<(String, i64, usize)>, DatabaseError> = prepped
     //     .query()
     //     .unwrap()
-    //     .mapped(|r: &Row| {
-    //         let foo = r.get(0);
-    //         println!("foo: {:?}", foo);
-    //         let bar = r.get(1);
-    //         println!("bar: {:?}", bar);
-    //         let id = r.get(2);
-    //         println!("id: {:?}", id);
-    //         Ok((foo?, bar?, id?))
-    //     })
+    //     .mapped_into()
     //     .collect();
     // for row in results.unwrap() {
     //     println!("{:?}", row);
@@ -141,14 +124,8 @@ fn main() {
     // let version: Option<usize> = db
     //     .prepare("SELECT version FROM _metadata ORDER BY version DESC LIMIT 1;")
     //     .unwrap()
-    //     .query()
-    //     .unwrap()
-    //     .mapped(|row: &Row| {
-    //         let version: usize = row.get(0).unwrap();
-    //         Ok(version)
-    //     })
-    //     .flatten()
-    //     .next();
+    //     .query_row(|row: &Row| row.get(0))
+    //     .unwrap();
 
     // println!("version: {version:?}");
 }
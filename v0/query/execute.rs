@@ -1,23 +1,160 @@
-use std::{borrow::Cow, iter::zip};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    iter::zip,
+};
 
 use crate::{
-    storage::{Column, ColumnWithIndex, Row, Rows, Schema, StorageError, StorageLayer},
-    DbType, DbValue,
+    context::Traced,
+    storage::{Collation, Column, ColumnWithIndex, Row, Rows, Schema, StorageError, StorageLayer},
+    DbFloat, DbType, DbValue,
 };
 
 use super::parse::{
-    CreateStatement, DeleteStatement, DestroyStatement, InsertStatement, OrderByClause,
-    ParsingError, SelectColumns, SelectSource, SelectStatement, Statement, WhereClause, WhereCmp,
-    WhereMember,
+    ArithmeticOp, ColumnProjection, ColumnRef, CreateStatement, DeleteStatement, DestroyStatement,
+    Expression, InsertStatement, OrderByClause, Parser, ParsingError, ScalarFunction,
+    SelectColumns, SelectItem, SelectSource, SelectStatement, Statement, UnionStatement,
+    WhereClause, WhereCmp, WhereMember,
 };
+use super::tokenize::{Position, Tokenizer};
 
 #[derive(Debug)]
 pub enum ExecutionError {
     ParsingError(ParsingError),
     StorageError(StorageError),
-    UnknownColumnNameProvided,
+    UnknownColumnName(String),
+    /// A bare column name matched a column in more than one of these tables
+    AmbiguousColumn {
+        name: String,
+        tables: Vec<String>,
+    },
     MismatchedTypeComparision,
     UncoercableValueProvided,
+    NonNumericOperand,
+    DivisionByZero,
+    ArithmeticOverflow,
+    InvalidFunctionArgument(ScalarFunction),
+    InvalidCast {
+        from: DbType,
+        to: DbType,
+    },
+    RequiresWriteAccess,
+    UnboundPlaceholder(String),
+    /// A subquery used as a value selected this many columns instead of one
+    ScalarSubqueryColumns(usize),
+    /// A subquery used as a value returned no rows. There's no NULL to stand in for
+    /// its value, so this is an error rather than a value.
+    ScalarSubqueryNoRows,
+    /// A subquery used as a value returned more than one row
+    ScalarSubqueryRows,
+    /// A subquery used as a value somewhere other than the selected columns
+    MisplacedSubquery,
+    /// An INSERT named a different number of columns than it provided values for
+    ColumnValueCountMismatch {
+        columns: usize,
+        values: usize,
+    },
+    /// An INSERT named this column more than once
+    ColumnListedTwice(String),
+    /// An INSERT left out this column, and there's nothing to fill it with
+    MissingColumnValue(String),
+    /// The selects of a UNION produce different numbers of columns
+    UnionColumnCountMismatch {
+        left: usize,
+        right: usize,
+    },
+    /// A column of a UNION holds strings on one side and numbers on the other. It's
+    /// named after the first select's column.
+    UnionColumnTypeMismatch {
+        column: String,
+        left: DbType,
+        right: DbType,
+    },
+}
+impl ExecutionError {
+    /// Where in the statement's text the error happened, if known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::ParsingError(err) => err.position(),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ParsingError(err) => err.fmt(f),
+            Self::StorageError(err) => err.fmt(f),
+            Self::UnknownColumnName(name) => write!(f, "Unknown column name provided: '{name}'"),
+            Self::AmbiguousColumn { name, tables } => write!(
+                f,
+                "Ambiguous column name provided: '{name}' could be from any of {}",
+                tables.join(", ")
+            ),
+            Self::MismatchedTypeComparision => f.write_str("Cannot compare values of these types"),
+            Self::UncoercableValueProvided => {
+                f.write_str("A value could not be converted to the column's type")
+            }
+            Self::NonNumericOperand => f.write_str("Arithmetic requires numeric operands"),
+            Self::DivisionByZero => f.write_str("Division by zero"),
+            Self::ArithmeticOverflow => f.write_str("Arithmetic overflow"),
+            Self::InvalidFunctionArgument(func) => {
+                write!(f, "An argument of the wrong type was passed to {func}")
+            }
+            Self::InvalidCast { from, to } => write!(
+                f,
+                "Cannot cast {} to {}",
+                from.as_sql_type_str(),
+                to.as_sql_type_str()
+            ),
+            Self::RequiresWriteAccess => f.write_str("The statement requires write access"),
+            Self::UnboundPlaceholder(name) => write!(f, "No value was bound to '{name}'"),
+            Self::ScalarSubqueryColumns(n) => {
+                write!(
+                    f,
+                    "A subquery used as a value must select one column, not {n}"
+                )
+            }
+            Self::ScalarSubqueryNoRows => {
+                f.write_str("A subquery used as a value returned no rows")
+            }
+            Self::ScalarSubqueryRows => {
+                f.write_str("A subquery used as a value returned more than one row")
+            }
+            Self::MisplacedSubquery => {
+                f.write_str("Subqueries can only be used as values in the selected columns")
+            }
+            Self::ColumnValueCountMismatch { columns, values } => {
+                write!(f, "{values} values were provided for {columns} columns")
+            }
+            Self::ColumnListedTwice(name) => write!(f, "Column '{name}' was listed twice"),
+            Self::MissingColumnValue(name) => write!(f, "No value was provided for '{name}'"),
+            Self::UnionColumnCountMismatch { left, right } => write!(
+                f,
+                "The selects of a UNION must have the same number of columns, not {left} and {right}"
+            ),
+            Self::UnionColumnTypeMismatch {
+                column,
+                left,
+                right,
+            } => write!(
+                f,
+                "Column '{column}' of a UNION can't be {} on one side and {} on the other",
+                left.as_sql_type_str(),
+                right.as_sql_type_str()
+            ),
+        }
+    }
+}
+impl std::error::Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ParsingError(err) => Some(err),
+            Self::StorageError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 impl From<StorageError> for ExecutionError {
     fn from(value: StorageError) -> Self {
@@ -31,11 +168,16 @@ impl From<ParsingError> for ExecutionError {
 }
 
 type Result<T> = std::result::Result<T, ExecutionError>;
+type TracedResult<T> = std::result::Result<T, Traced<ExecutionError>>;
 
 pub enum QueryResult<'a> {
     Ok(usize),
     NothingToDo,
     Rows(ResultRows<'a>),
+    /// A description of the plan for an `EXPLAIN`ed statement
+    Explain(String),
+    /// The number of bytes freed by a `VACUUM`
+    Reclaimed(u64),
 }
 
 pub struct ResultRows<'a> {
@@ -51,7 +193,7 @@ impl<'a> ResultRows<'a> {
     }
 }
 impl<'a> Iterator for ResultRows<'a> {
-    type Item = Cow<'a, Row>;
+    type Item = Result<Cow<'a, Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.source.next()
@@ -68,40 +210,70 @@ impl ExecutablePlan {
     }
 
     fn build_select_source_rows<'strg>(
-        &self,
         select_source: &SelectSource,
-        storage: &'strg mut StorageLayer,
+        storage: &'strg StorageLayer,
         uses_rowid: bool,
     ) -> Result<RowsSource<'strg>> {
         let source = match select_source {
             SelectSource::Table(name) => {
                 let rows = storage.table_scan(name, uses_rowid)?;
-                RowsSource::Table(rows)
+                RowsSource::Table {
+                    name: name.clone(),
+                    rows,
+                    after: None,
+                }
+            }
+            SelectSource::Expression(inner_stmt) => Self::compose_select(inner_stmt, storage)?,
+            SelectSource::CrossJoin { left, right } => {
+                let left_table = left.table();
+                let left = Self::build_select_source_rows(left, storage, uses_rowid)?;
+                let right_rows = storage.table_scan(right, uses_rowid)?;
+                RowsSource::Join(Box::new(JoinRowsIter::cross(
+                    left, left_table, right_rows, right,
+                )))
+            }
+            SelectSource::NoTable => RowsSource::SingleRow { done: false },
+            SelectSource::Join { left, right, on } => {
+                let left_table = left.table();
+                let left = Self::build_select_source_rows(left, storage, uses_rowid)?;
+                let right_rows = storage.table_scan(right, uses_rowid)?;
+                let cross = JoinRowsIter::cross(left, left_table, right_rows, right);
+                match HashJoinRowsIter::join_keys(on, &cross)? {
+                    Some(keys) => {
+                        RowsSource::HashJoin(Box::new(HashJoinRowsIter::new(cross, on, keys)))
+                    }
+                    // anything else is checked against every pair of rows
+                    None => {
+                        let cross = RowsSource::Join(Box::new(cross));
+                        RowsSource::Filter(Box::new(FilterRowsIter::build(
+                            cross, on, None, storage,
+                        )?))
+                    }
+                }
             }
-            SelectSource::Expression(inner_stmt) => self.compose_select(inner_stmt, storage)?,
         };
         Ok(source)
     }
 
     fn compose_select<'strg>(
-        &self,
         select_stmt: &SelectStatement,
-        storage: &'strg mut StorageLayer,
+        storage: &'strg StorageLayer,
     ) -> Result<RowsSource<'strg>> {
-        let source =
-            self.build_select_source_rows(&select_stmt.source, storage, select_stmt.uses_row_id())?;
-        let source = if let Some(where_clause) = &select_stmt.where_clause {
-            let filter = FilterRowsIter::build(source, where_clause)?;
-            RowsSource::Filter(filter)
-        } else {
-            source
+        let source = match (&select_stmt.columns, select_stmt.source.as_ref()) {
+            (SelectColumns::CountAll { out_name }, SelectSource::Table(name))
+                if select_stmt.where_clause.is_none() =>
+            {
+                // nothing is filtered out, so there's no need to scan the table
+                let count = storage.table_row_count(name)?;
+                RowsSource::Count(CountRowsIter::known(count, name, out_name))
+            }
+            _ => Self::compose_projection(select_stmt, storage)?,
         };
-        let source = if let Some(order_by_clause) = &select_stmt.order_by_clause {
-            RowsSource::Sort(SortRowsIter::build(source, order_by_clause)?)
+        let source = if select_stmt.distinct {
+            RowsSource::Distinct(DistinctRowsIter::new(source))
         } else {
             source
         };
-        let source = RowsSource::Select(SelectRowsIter::new(source, &select_stmt.columns));
         let source = if let Some(limit) = &select_stmt.limit {
             RowsSource::Limit(LimitRowsIter::new(source, limit))
         } else {
@@ -110,16 +282,98 @@ impl ExecutablePlan {
         Ok(source)
     }
 
+    fn compose_projection<'strg>(
+        select_stmt: &SelectStatement,
+        storage: &'strg StorageLayer,
+    ) -> Result<RowsSource<'strg>> {
+        // only a table source's columns can be referred to by a qualified name
+        let table = select_stmt.source.table();
+        let seek = match (table, &select_stmt.where_clause) {
+            (Some(name), Some(where_clause)) => rowid_lower_bound(where_clause, name),
+            _ => None,
+        };
+        let source = match (table, seek) {
+            (Some(name), Some(after)) => RowsSource::Table {
+                name: name.to_string(),
+                rows: storage.table_scan_after(name, after, select_stmt.uses_row_id())?,
+                after: Some(after),
+            },
+            _ => Self::build_select_source_rows(
+                &select_stmt.source,
+                storage,
+                select_stmt.uses_row_id(),
+            )?,
+        };
+        let source = match &select_stmt.where_clause {
+            Some(where_clause) if seek.is_none() => {
+                let filter = FilterRowsIter::build(source, where_clause, table, storage)?;
+                RowsSource::Filter(Box::new(filter))
+            }
+            _ => source,
+        };
+        let source = match (&select_stmt.order_by_clause, table) {
+            // a table's rows are already in rowid order, and filtering keeps them that way
+            (Some(order_by_clause), Some(name))
+                if !order_by_clause.desc() && is_rowid_of(order_by_clause.sort_column(), name) =>
+            {
+                source
+            }
+            (Some(order_by_clause), _) => {
+                RowsSource::Sort(SortRowsIter::build(source, order_by_clause, table)?)
+            }
+            (None, _) => source,
+        };
+        let source = match &select_stmt.columns {
+            SelectColumns::CountAll { out_name } => {
+                RowsSource::Count(CountRowsIter::scan(source, out_name))
+            }
+            SelectColumns::CountDistinct { expr, out_name } => {
+                let values = SelectColumns::Only(vec![SelectItem::Column(ColumnProjection {
+                    expr: expr.as_ref().clone(),
+                    out_name: out_name.clone(),
+                })]);
+                let values =
+                    RowsSource::Select(SelectRowsIter::build(source, &values, table, storage)?);
+                let distinct = RowsSource::Distinct(DistinctRowsIter::new(values));
+                RowsSource::Count(CountRowsIter::scan(distinct, out_name))
+            }
+            columns => RowsSource::Select(SelectRowsIter::build(source, columns, table, storage)?),
+        };
+        Ok(source)
+    }
+
     fn select<'strg>(
         &self,
         select_stmt: &SelectStatement,
-        storage: &'strg mut StorageLayer,
+        storage: &'strg StorageLayer,
     ) -> Result<QueryResult<'strg>> {
-        let source = self.compose_select(select_stmt, storage)?;
+        let source = Self::compose_select(select_stmt, storage)?;
 
         Ok(QueryResult::Rows(ResultRows::new(source)))
     }
 
+    fn compose_union<'strg>(
+        union_stmt: &UnionStatement,
+        storage: &'strg StorageLayer,
+    ) -> Result<RowsSource<'strg>> {
+        let mut source = Self::compose_select(&union_stmt.first, storage)?;
+        for (all, select) in union_stmt.rest.iter() {
+            let next = Self::compose_select(select, storage)?;
+            check_union_schemas(&source.schema(), &next.schema())?;
+            source = RowsSource::Union(Box::new(UnionRowsIter::new(source, next)));
+            if !all {
+                source = RowsSource::Distinct(DistinctRowsIter::new(source));
+            }
+        }
+        if let Some(order_by_clause) = &union_stmt.order_by_clause {
+            source = RowsSource::Sort(SortRowsIter::build(source, order_by_clause, None)?);
+        }
+        if let Some(limit) = &union_stmt.limit {
+            source = RowsSource::Limit(LimitRowsIter::new(source, limit));
+        }
+        Ok(source)
+    }
+
     fn create<'strg>(
         &self,
         create_stmt: &CreateStatement,
@@ -132,16 +386,28 @@ impl ExecutablePlan {
             create_stmt.columns.names.iter(),
             create_stmt.columns.types.iter(),
         );
-        let cols = pairs
-            .map(|(name, _type)| Column::new(name.to_string(), *_type))
+        let cols = zip(pairs, create_stmt.columns.collations.iter())
+            .map(|((name, _type), collation)| {
+                Column::new(name.to_string(), *_type).collated(*collation)
+            })
             .collect();
         let schema = Schema::new(cols);
         let primary_key_col = create_stmt
             .columns
             .primary_key_col
             .as_storage_key_column(&schema)?;
+        // catches checks on unknown columns or mismatched types before the table exists
+        for check in &create_stmt.columns.checks {
+            CheckPredicate::build(&check.predicate, &schema)?;
+        }
 
-        storage.create_table(create_stmt.table.clone(), schema, primary_key_col)?;
+        storage.create_table(
+            create_stmt.table.clone(),
+            schema,
+            primary_key_col,
+            create_stmt.columns.checks.clone(),
+            create_stmt.columns.foreign_keys.clone(),
+        )?;
         Ok(QueryResult::Ok(0))
     }
 
@@ -151,23 +417,46 @@ impl ExecutablePlan {
         storage: &'strg mut StorageLayer,
     ) -> Result<QueryResult<'strg>> {
         let schema = storage.table_schema(&insert_stmt.table)?;
+        let columns: Vec<&str> = if insert_stmt.columns.is_empty() {
+            schema.columns().map(|col| col.name.as_str()).collect()
+        } else {
+            insert_stmt.columns.iter().map(String::as_str).collect()
+        };
+        if columns.len() != insert_stmt.values.len() {
+            return Err(ExecutionError::ColumnValueCountMismatch {
+                columns: columns.len(),
+                values: insert_stmt.values.len(),
+            });
+        }
 
-        let indexed_vals: Result<Vec<(usize, DbType, &DbValue)>> =
-            zip(insert_stmt.columns.iter(), insert_stmt.values.iter())
-                .map(|(name, val)| match schema.get(name) {
-                    Some(ci) if val.db_type().coerceable_to(&ci.column._type) => {
-                        Ok((ci.index, ci.column._type, val))
-                    }
-                    Some(_) => Err(ExecutionError::UncoercableValueProvided),
-                    None => Err(ExecutionError::UnknownColumnNameProvided),
-                })
-                .collect();
-        let mut indexed_vals = indexed_vals?;
-        indexed_vals.sort_by_key(|x| x.0);
-        let vals: Vec<DbValue> = indexed_vals
-            .into_iter()
-            .filter_map(|(_, _type, val)| val.coerced_to(_type))
-            .collect();
+        let values = insert_stmt
+            .values
+            .iter()
+            .map(RowExpression::constant)
+            .collect::<Result<Vec<_>>>()?;
+        // Values are placed by the column's position, so the list can be in any order
+        let mut slots: Vec<Option<DbValue>> = schema.columns().map(|_| None).collect();
+        for (name, val) in zip(columns, values.iter()) {
+            let Some(ci) = schema.get(name) else {
+                return Err(ExecutionError::UnknownColumnName(name.to_string()));
+            };
+            if slots[ci.index].is_some() {
+                return Err(ExecutionError::ColumnListedTwice(name.to_string()));
+            }
+            if !val.db_type().coerceable_to(&ci.column._type) {
+                return Err(ExecutionError::UncoercableValueProvided);
+            }
+            let val = val
+                .coerced_to(ci.column._type)
+                .ok_or(ExecutionError::UncoercableValueProvided)?;
+            slots[ci.index] = Some(val);
+        }
+        // There's no NULL or column default to fill in an unlisted column with
+        let vals: Vec<DbValue> = zip(schema.columns(), slots)
+            .map(|(col, val)| {
+                val.ok_or_else(|| ExecutionError::MissingColumnValue(col.name.clone()))
+            })
+            .collect::<Result<_>>()?;
 
         let rows = vec![Row::new(vals)];
 
@@ -184,6 +473,9 @@ impl ExecutablePlan {
         destroy_stmt: &DestroyStatement,
         storage: &'strg mut StorageLayer,
     ) -> Result<QueryResult<'strg>> {
+        if destroy_stmt.if_exists && !storage.table_exists(&destroy_stmt.table) {
+            return Ok(QueryResult::NothingToDo);
+        }
         let row_count = storage.table_row_count(&destroy_stmt.table)?;
         storage.destroy_table(&destroy_stmt.table)?;
         Ok(QueryResult::Ok(row_count))
@@ -194,21 +486,24 @@ impl ExecutablePlan {
         delete_stmt: &DeleteStatement,
         storage: &'strg mut StorageLayer,
     ) -> Result<QueryResult<'strg>> {
-        //compose select with where clause,
-        let select_stmt = delete_stmt.generated_select_statement();
-        let ids: Vec<usize> = if let QueryResult::Rows(rows) = self.select(&select_stmt, storage)? {
-            rows.map(|r| {
-                let v = r.data.first().expect("Should always have a row id here");
-                match v {
-                    DbValue::UnsignedInt(id) => *id as usize,
-                    _ => panic!("Should never have a row id of another kind"),
-                }
-            })
-            .collect()
-        } else {
-            panic!("this should never happen");
+        let table = &delete_stmt.table;
+        let where_clause = match &delete_stmt.where_clause {
+            Some(clause) => clause,
+            None => return Ok(QueryResult::Ok(storage.truncate_table(table)?)),
         };
-        let deleted = storage.delete_rows(&delete_stmt.table, &ids)?;
+        let with_row_id = where_clause.references_column("rowid");
+        let schema = storage.table_scan(table, with_row_id)?.schema.into_owned();
+        // every row is checked before any are deleted, since a subquery reads from storage
+        // as rows are checked, and a row the predicate fails on should leave the table as it was
+        let predicate = Predicate::build(where_clause, &schema, Some(table), storage)?;
+        let mut matching = HashSet::new();
+        for row in storage.table_scan(table, with_row_id)? {
+            if predicate.row_predicate(&row)? {
+                matching.insert(row.into_owned());
+            }
+        }
+        let deleted =
+            storage.delete_rows_where(table, with_row_id, |row| matching.contains(row))?;
         Ok(QueryResult::Ok(deleted))
     }
 
@@ -219,14 +514,114 @@ impl ExecutablePlan {
     ) -> Result<QueryResult<'strg>> {
         match stmt {
             Statement::Select(s) => self.select(s, storage),
+            Statement::Union(u) => Ok(QueryResult::Rows(ResultRows::new(Self::compose_union(
+                u, storage,
+            )?))),
             Statement::Create(c) => self.create(c, storage),
             Statement::Insert(i) => self.insert(i, storage),
             Statement::Destroy(d) => self.destroy(d, storage),
             Statement::Delete(d) => self.delete(d, storage),
+            Statement::Vacuum(v) => {
+                let reclaimed = storage.vacuum(v.table.as_deref())?;
+                Ok(QueryResult::Reclaimed(reclaimed))
+            }
+            Statement::Explain(inner) => Ok(QueryResult::Explain(self.explain(inner, storage)?)),
+            Statement::Begin => {
+                storage.begin()?;
+                Ok(QueryResult::Ok(0))
+            }
+            Statement::Commit => {
+                storage.commit()?;
+                Ok(QueryResult::Ok(0))
+            }
+            Statement::Rollback => {
+                storage.rollback()?;
+                Ok(QueryResult::Ok(0))
+            }
+        }
+    }
+
+    /// Adds a frame naming what executing `stmt` does to an error it caused
+    fn traced(stmt: &Statement) -> impl FnOnce(ExecutionError) -> Traced<ExecutionError> + '_ {
+        |err| Traced::new(err, Self::operation(stmt))
+    }
+
+    /// A short description of what executing `stmt` does, used as an error context frame
+    fn operation(stmt: &Statement) -> String {
+        match stmt {
+            Statement::Select(s) => match s.source.as_ref() {
+                SelectSource::Expression(_) => String::from("selecting from a subquery"),
+                SelectSource::NoTable => String::from("selecting without a table"),
+                source => format!("selecting from {source}"),
+            },
+            Statement::Union(_) => String::from("selecting a union"),
+            Statement::Create(c) => format!("creating table {}", c.table),
+            Statement::Insert(i) => format!("inserting into {}", i.table),
+            Statement::Destroy(d) => format!("destroying table {}", d.table),
+            Statement::Delete(d) => format!("deleting from {}", d.table),
+            Statement::Vacuum(v) => match &v.table {
+                Some(table) => format!("vacuuming {table}"),
+                None => String::from("vacuuming every table"),
+            },
+            Statement::Explain(inner) => format!("explaining {}", Self::operation(inner)),
+            Statement::Begin => String::from("beginning a transaction"),
+            Statement::Commit => String::from("committing"),
+            Statement::Rollback => String::from("rolling back"),
+        }
+    }
+
+    /// Describes the tree of steps `stmt` would be executed with, one per line,
+    /// with each step's input indented beneath it. The steps are built the same
+    /// way as when executing, but no rows are read.
+    fn explain(&self, stmt: &Statement, storage: &StorageLayer) -> Result<String> {
+        let mut lines = Vec::new();
+        self.describe_stmt(stmt, storage, &mut lines)?;
+        Ok(lines.join("\n"))
+    }
+
+    fn describe_stmt(
+        &self,
+        stmt: &Statement,
+        storage: &StorageLayer,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        match stmt {
+            Statement::Select(s) => Self::compose_select(s, storage)?.describe(0, lines),
+            Statement::Union(u) => Self::compose_union(u, storage)?.describe(0, lines),
+            Statement::Create(c) => lines.push(format!("CREATE TABLE {}", c.table)),
+            Statement::Insert(i) => lines.push(format!("INSERT INTO {}", i.table)),
+            Statement::Destroy(d) => lines.push(format!("DESTROY TABLE {}", d.table)),
+            Statement::Delete(d) => match &d.where_clause {
+                Some(clause) => {
+                    // built only to check the clause refers to columns that exist
+                    let with_row_id = clause.references_column("rowid");
+                    let schema = storage.table_scan(&d.table, with_row_id)?.schema;
+                    Predicate::build(clause, &schema, Some(&d.table), storage)?;
+                    lines.push(format!("DELETE FROM {}", d.table));
+                    lines.push(format!("  FILTER {clause}"));
+                    lines.push(format!("    SCAN {}", d.table));
+                }
+                None => {
+                    storage.table_schema(&d.table)?;
+                    lines.push(format!("TRUNCATE {}", d.table));
+                }
+            },
+            Statement::Vacuum(v) => match &v.table {
+                Some(table) => lines.push(format!("VACUUM {table}")),
+                None => lines.push(String::from("VACUUM")),
+            },
+            Statement::Explain(inner) => self.describe_stmt(inner, storage, lines)?,
+            Statement::Begin => lines.push(String::from("BEGIN")),
+            Statement::Commit => lines.push(String::from("COMMIT")),
+            Statement::Rollback => lines.push(String::from("ROLLBACK")),
         }
+        Ok(())
     }
 
-    pub fn execute<'strg>(&self, storage: &'strg mut StorageLayer) -> Result<QueryResult<'strg>> {
+    pub fn execute<'strg>(
+        &self,
+        storage: &'strg mut StorageLayer,
+    ) -> TracedResult<QueryResult<'strg>> {
         if self.plan.is_empty() {
             return Ok(QueryResult::NothingToDo);
         }
@@ -236,367 +631,3459 @@ impl ExecutablePlan {
             .get(last_idx)
             .expect("There should be an expression here");
         for stmt in self.plan[0..last_idx].iter() {
-            _ = self.execute_stmt(stmt, storage)?;
+            _ = self
+                .execute_stmt(stmt, storage)
+                .map_err(Self::traced(stmt))?;
         }
         self.execute_stmt(last_expr, storage)
+            .map_err(Self::traced(last_expr))
+    }
+
+    fn execute_read_stmt<'strg>(
+        &self,
+        stmt: &Statement,
+        storage: &'strg StorageLayer,
+    ) -> Result<QueryResult<'strg>> {
+        match stmt {
+            Statement::Select(s) => self.select(s, storage),
+            Statement::Union(u) => Ok(QueryResult::Rows(ResultRows::new(Self::compose_union(
+                u, storage,
+            )?))),
+            Statement::Explain(inner) => Ok(QueryResult::Explain(self.explain(inner, storage)?)),
+            _ => Err(ExecutionError::RequiresWriteAccess),
+        }
+    }
+
+    /// Executes the plan with shared access to storage, so it may only contain
+    /// statements that don't modify it
+    pub fn execute_read<'strg>(
+        &self,
+        storage: &'strg StorageLayer,
+    ) -> TracedResult<QueryResult<'strg>> {
+        if self.plan.is_empty() {
+            return Ok(QueryResult::NothingToDo);
+        }
+        let last_idx = self.plan.len() - 1;
+        let last_expr = self
+            .plan
+            .get(last_idx)
+            .expect("There should be an expression here");
+        for stmt in self.plan[0..last_idx].iter() {
+            _ = self
+                .execute_read_stmt(stmt, storage)
+                .map_err(Self::traced(stmt))?;
+        }
+        self.execute_read_stmt(last_expr, storage)
+            .map_err(Self::traced(last_expr))
     }
 }
 
 enum RowsSource<'a> {
-    Table(Rows<'a>),
+    /// Starts after the row with rowid `after`, if there is one
+    Table {
+        name: String,
+        rows: Rows<'a>,
+        after: Option<usize>,
+    },
     Select(SelectRowsIter<'a>),
-    Filter(FilterRowsIter<'a>),
+    Filter(Box<FilterRowsIter<'a>>),
     Sort(SortRowsIter<'a>),
+    Distinct(DistinctRowsIter<'a>),
+    Count(CountRowsIter<'a>),
     Limit(LimitRowsIter<'a>),
+    Join(Box<JoinRowsIter<'a>>),
+    HashJoin(Box<HashJoinRowsIter<'a>>),
+    Union(Box<UnionRowsIter<'a>>),
+    /// One row with no columns, for a `SELECT` without a `FROM` clause
+    SingleRow {
+        done: bool,
+    },
 }
 impl<'a> RowsSource<'a> {
     fn schema(&self) -> Cow<'a, Schema> {
         match self {
-            Self::Table(t) => t.schema.clone(),
+            Self::Table { rows, .. } => rows.schema.clone(),
             Self::Select(s) => s.schema.clone(),
             Self::Filter(f) => f.schema.clone(),
             Self::Sort(s) => s.schema.clone(),
+            Self::Distinct(d) => d.schema.clone(),
+            Self::Count(c) => c.schema.clone(),
             Self::Limit(l) => l.schema.clone(),
+            Self::Join(j) => j.schema.clone(),
+            Self::HashJoin(j) => j.schema.clone(),
+            Self::Union(u) => u.schema.clone(),
+            Self::SingleRow { .. } => Cow::Owned(Schema::new(Vec::new())),
+        }
+    }
+
+    /// Adds a line describing this step to `lines`, followed by its source's
+    fn describe(&self, depth: usize, lines: &mut Vec<String>) {
+        let (step, source) = match self {
+            Self::Table {
+                name,
+                rows: _,
+                after: None,
+            } => (format!("SCAN {name}"), None),
+            Self::Table {
+                name,
+                rows: _,
+                after: Some(after),
+            } => (format!("SCAN {name} AFTER ROWID {after}"), None),
+            Self::Select(s) => (format!("PROJECT {}", s.columns), Some(&s.source)),
+            Self::Filter(f) => (format!("FILTER {}", f.where_clause), Some(&f.source)),
+            Self::Sort(s) => (format!("SORT BY {}", s.sort_clause), s.source.as_ref()),
+            Self::Distinct(d) => (String::from("DISTINCT"), Some(&d.source)),
+            Self::Count(c) => match &c.table {
+                Some(name) => (
+                    format!("COUNT ROWS OF {name} (using row count, no scan)"),
+                    None,
+                ),
+                None => (String::from("COUNT"), c.source.as_ref()),
+            },
+            Self::Limit(l) => (format!("LIMIT {}", l.limit), Some(&l.source)),
+            Self::Join(j) => (String::from("CROSS JOIN"), Some(&j.left)),
+            Self::HashJoin(j) => (format!("HASH JOIN ON {}", j.on), Some(&j.left)),
+            Self::Union(u) => (String::from("UNION ALL"), Some(&u.left)),
+            Self::SingleRow { .. } => (String::from("SINGLE ROW (no table)"), None),
+        };
+        lines.push(format!("{}{step}", "  ".repeat(depth)));
+        if let Some(source) = source {
+            source.describe(depth + 1, lines);
+        }
+        match self {
+            Self::Join(j) => {
+                lines.push(format!("{}SCAN {}", "  ".repeat(depth + 1), j.right_table))
+            }
+            Self::HashJoin(j) => {
+                lines.push(format!("{}SCAN {}", "  ".repeat(depth + 1), j.right_table))
+            }
+            Self::Union(u) => u.right.describe(depth + 1, lines),
+            _ => {}
         }
     }
 }
 impl<'a> Iterator for RowsSource<'a> {
-    type Item = Cow<'a, Row>;
+    type Item = Result<Cow<'a, Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
-            Self::Table(t) => t.next(),
+            Self::Table { rows, .. } => rows.next().map(Ok),
             Self::Select(s) => s.next(),
             Self::Filter(f) => f.next(),
             Self::Sort(s) => s.next(),
+            Self::Distinct(d) => d.next(),
+            Self::Count(c) => c.next(),
             Self::Limit(l) => l.next(),
+            Self::Join(j) => j.next(),
+            Self::HashJoin(j) => j.next(),
+            Self::Union(u) => u.next(),
+            Self::SingleRow { done } => match done {
+                true => None,
+                false => {
+                    *done = true;
+                    Some(Ok(Cow::Owned(Row::new(Vec::new()))))
+                }
+            },
         }
     }
 }
 
-struct SelectRowsIter<'a> {
-    source: Box<RowsSource<'a>>,
-    schema: Cow<'a, Schema>,
-    column_project: Box<dyn Fn(Cow<'a, Row>) -> Cow<'a, Row>>,
-}
-impl<'a> SelectRowsIter<'a> {
-    fn new(source: RowsSource<'a>, columns: &SelectColumns) -> Self {
-        let source_schema = source.schema();
-        match columns {
-            SelectColumns::All => {
-                let mut schema = source_schema.into_owned();
-                if let Some(removed_pos) = schema.column_position("rowid") {
-                    schema.remove("rowid");
-                    SelectRowsIter {
-                        source: Box::new(source),
-                        schema: Cow::Owned(schema),
-                        column_project: Box::new(move |r| {
-                            let mut r = r.into_owned();
-                            r.data.remove(removed_pos);
-                            Cow::Owned(r)
-                        }),
-                    }
-                } else {
-                    SelectRowsIter {
-                        source: Box::new(source),
-                        schema: Cow::Owned(schema),
-                        column_project: Box::new(|r| r.clone()),
-                    }
-                }
+/// Finds the column a reference points to. `table` is the name of the table the
+/// schema belongs to, if any. Columns of a schema combining several tables are
+/// named `table.column`, so a bare name is matched against those as well, and is
+/// ambiguous if more than one table has a column of that name.
+fn resolve_column<'s>(
+    col: &ColumnRef,
+    schema: &'s Schema,
+    table: Option<&str>,
+) -> Result<&'s ColumnWithIndex> {
+    match &col.table {
+        Some(qualifier) if table == Some(qualifier.as_str()) => schema.get(&col.name),
+        Some(qualifier) => schema.get(&format!("{qualifier}.{}", col.name)),
+        None => {
+            if let Some(ci) = schema.get(&col.name) {
+                return Ok(ci);
             }
-            SelectColumns::Only(cols) => {
-                // TODO: Handle situations where column name that doesn't exist in schema is provided
-
-                let columns_with_indexes: Vec<(&ColumnWithIndex, &str)> = cols
-                    .iter()
-                    .filter_map(|col| {
-                        source_schema
-                            .get(&col.in_name)
-                            .map(|c| (c, col.out_name.as_str()))
+            let suffix = format!(".{}", col.name);
+            let matching: Vec<_> = schema
+                .columns()
+                .filter(|c| c.name.ends_with(&suffix))
+                .collect();
+            match matching.as_slice() {
+                [c] => schema.get(&c.name),
+                [] => None,
+                _ => {
+                    return Err(ExecutionError::AmbiguousColumn {
+                        name: col.name.clone(),
+                        tables: matching
+                            .iter()
+                            .map(|c| c.name[..c.name.len() - suffix.len()].to_string())
+                            .collect(),
                     })
-                    .collect();
-                let indices: Vec<usize> =
-                    columns_with_indexes.iter().map(|ci| ci.0.index).collect();
-
-                let columns = columns_with_indexes
-                    .iter()
-                    .map(|ci| ci.0.column.with_name(ci.1.to_string()))
-                    .collect();
-
-                let new_schema = Cow::Owned(Schema::new(columns));
-
-                let projection = move |r: Cow<'a, Row>| {
-                    // TODO: Handle situations where column name that doesn't exist in schema is provided
-                    let data = indices
-                        .iter()
-                        .filter_map(|idx| r.data.get(*idx))
-                        .cloned()
-                        .collect();
-                    Cow::Owned(Row::new(data))
-                };
-
-                SelectRowsIter {
-                    source: Box::new(source),
-                    schema: new_schema,
-                    column_project: Box::new(projection),
                 }
             }
         }
     }
+    .ok_or_else(|| ExecutionError::UnknownColumnName(col.to_string()))
 }
-impl<'a> Iterator for SelectRowsIter<'a> {
-    type Item = Cow<'a, Row>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.source.next().map(|r| (self.column_project)(r.clone()))
-    }
+/// Whether `name` is the rowid of a table, or of one of the tables in a join
+fn is_row_id(name: &str) -> bool {
+    name == "rowid" || name.ends_with(".rowid")
 }
 
-struct LimitRowsIter<'a> {
-    source: Box<RowsSource<'a>>,
-    schema: Cow<'a, Schema>,
-    rows_left: usize,
+/// Whether `col` refers to the rowid of table `table`
+fn is_rowid_of(col: &ColumnRef, table: &str) -> bool {
+    col.name == "rowid" && col.table.as_deref().is_none_or(|t| t == table)
 }
-impl<'a> LimitRowsIter<'a> {
-    fn new(source: RowsSource<'a>, limit: &usize) -> Self {
-        let schema = source.schema();
-        LimitRowsIter {
-            source: Box::new(source),
-            schema,
-            rows_left: *limit,
+
+/// The rowid rows must come after to pass `clause`, if it's `rowid > value` or an
+/// equivalent. The table can then be read starting from there rather than filtered.
+fn rowid_lower_bound(clause: &WhereClause, table: &str) -> Option<usize> {
+    let (col, cmp, val) = match clause {
+        WhereClause::Comparison {
+            left: WhereMember::Column(col),
+            cmp,
+            right: WhereMember::Value(val),
+        } => (col, *cmp, val),
+        WhereClause::Comparison {
+            left: WhereMember::Value(val),
+            cmp,
+            right: WhereMember::Column(col),
+        } => {
+            let cmp = match cmp {
+                WhereCmp::LessThan => WhereCmp::GreaterThan,
+                WhereCmp::LessThanEquals => WhereCmp::GreaterThanEquals,
+                cmp => *cmp,
+            };
+            (col, cmp, val)
         }
+        _ => return None,
+    };
+    if !is_rowid_of(col, table) {
+        return None;
+    }
+    let id = match val.coerced_to(DbType::UnsignedInt)? {
+        DbValue::UnsignedInt(id) => usize::try_from(id).ok()?,
+        _ => return None,
+    };
+    match cmp {
+        WhereCmp::GreaterThan => Some(id),
+        WhereCmp::GreaterThanEquals => id.checked_sub(1),
+        _ => None,
     }
 }
-impl<'a> Iterator for LimitRowsIter<'a> {
-    type Item = Cow<'a, Row>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(row) = self.source.next().filter(|_| self.rows_left > 0) {
-            self.rows_left -= 1;
-            Some(row)
-        } else {
-            None
+/// Replaces each wildcard in a select list with the columns it stands for, in schema
+/// order. As with a lone `*`, the rowid is never included.
+fn expand_wildcards(
+    items: &[SelectItem],
+    schema: &Schema,
+    table: Option<&str>,
+) -> Result<Vec<ColumnProjection>> {
+    let mut cols = Vec::new();
+    for item in items {
+        let names: Vec<&str> = match item {
+            SelectItem::Column(col) => {
+                cols.push(col.clone());
+                continue;
+            }
+            SelectItem::Wildcard(None) => schema.columns().map(|c| c.name.as_str()).collect(),
+            SelectItem::Wildcard(Some(qualifier)) if table == Some(qualifier.as_str()) => {
+                schema.columns().map(|c| c.name.as_str()).collect()
+            }
+            // columns of a schema combining several tables are named `table.column`
+            SelectItem::Wildcard(Some(qualifier)) => {
+                let prefix = format!("{qualifier}.");
+                let names: Vec<&str> = schema
+                    .columns()
+                    .map(|c| c.name.as_str())
+                    .filter(|name| name.starts_with(&prefix))
+                    .collect();
+                if names.is_empty() {
+                    return Err(ExecutionError::UnknownColumnName(item.to_string()));
+                }
+                names
+            }
+        };
+        cols.extend(
+            names
+                .into_iter()
+                .filter(|name| !is_row_id(name))
+                .map(|name| ColumnProjection {
+                    expr: Expression::Column(ColumnRef::new(name.to_string())),
+                    out_name: name.to_string(),
+                }),
+        );
+    }
+    Ok(cols)
+}
+
+/// A double-quoted name refers to a column when there's one by that name, and is a
+/// string otherwise
+fn resolve_quoted_name(name: &str, schema: &Schema, table: Option<&str>) -> Result<WhereMember> {
+    let col = ColumnRef::new(name.to_string());
+    match resolve_column(&col, schema, table) {
+        Ok(_) => Ok(WhereMember::Column(col)),
+        Err(ExecutionError::UnknownColumnName(_)) => {
+            Ok(WhereMember::Value(DbValue::String(name.to_string())))
         }
+        Err(err) => Err(err),
     }
 }
 
+/// An `Expression` with its columns resolved against a schema, so it can
+/// be evaluated against each row
 #[derive(Debug)]
-enum FilterType {
-    ValueValue {
-        left: DbValue,
-        right: DbValue,
-        cmp: WhereCmp,
+enum RowExpression {
+    Value(DbValue),
+    Column {
+        pos: usize,
+        _type: DbType,
     },
-    ColumnValue {
-        col: String,
-        val: DbValue,
-        cmp: WhereCmp,
-        schema: Schema,
+    Arithmetic {
+        left: Box<RowExpression>,
+        op: ArithmeticOp,
+        right: Box<RowExpression>,
+        _type: DbType,
     },
-    ColumnColumn {
-        col1: String,
-        col2: String,
+    Function {
+        func: ScalarFunction,
+        args: Vec<RowExpression>,
         _type: DbType,
-        cmp: WhereCmp,
-        schema: Schema,
+    },
+    Cast {
+        expr: Box<RowExpression>,
+        to: DbType,
     },
 }
-impl FilterType {
-    fn validated_column_against(col: &str, schema: &Schema, against: DbType) -> Result<String> {
-        match schema.column(col) {
-            Some(c) if c._type.coerceable_to(&against) => Ok(col.to_string()),
-            Some(_) => Err(ExecutionError::MismatchedTypeComparision),
-            None => Err(ExecutionError::UnknownColumnNameProvided),
+impl RowExpression {
+    fn build(expr: &Expression, schema: &Schema, table: Option<&str>) -> Result<Self> {
+        match expr {
+            Expression::Value(v) => Ok(Self::Value(v.clone())),
+            Expression::Column(col) => {
+                let ci = resolve_column(col, schema, table)?;
+                Ok(Self::Column {
+                    pos: ci.index,
+                    _type: ci.column._type,
+                })
+            }
+            Expression::Arithmetic { left, op, right } => {
+                let left = RowExpression::build(left, schema, table)?;
+                let right = RowExpression::build(right, schema, table)?;
+                let _type = RowExpression::arithmetic_type(left.db_type(), right.db_type())?;
+                Ok(Self::Arithmetic {
+                    left: Box::new(left),
+                    op: *op,
+                    right: Box::new(right),
+                    _type,
+                })
+            }
+            Expression::Function { func, args } => {
+                let args = args
+                    .iter()
+                    .map(|a| RowExpression::build(a, schema, table))
+                    .collect::<Result<Vec<_>>>()?;
+                let arg_types: Vec<DbType> = args.iter().map(|a| a.db_type()).collect();
+                let _type = RowExpression::function_type(*func, &arg_types)?;
+                Ok(Self::Function {
+                    func: *func,
+                    args,
+                    _type,
+                })
+            }
+            Expression::Cast { expr, to } => {
+                let expr = RowExpression::build(expr, schema, table)?;
+                let from = expr.db_type();
+                // anything can be formatted as a string
+                if !from.coerceable_to(to) && !to.is_string() {
+                    return Err(ExecutionError::InvalidCast { from, to: *to });
+                }
+                Ok(Self::Cast {
+                    expr: Box::new(expr),
+                    to: *to,
+                })
+            }
+            Expression::Placeholder(name) => Err(ExecutionError::UnboundPlaceholder(name.clone())),
+            Expression::QuotedName(name) => {
+                let resolved = resolve_quoted_name(name, schema, table)?.into_expression();
+                RowExpression::build(&resolved, schema, table)
+            }
+            // the select list swaps these for columns of their results before building
+            Expression::Subquery(_) => Err(ExecutionError::MisplacedSubquery),
         }
     }
 
-    fn validated_column_column(
-        col1: &str,
-        col2: &str,
-        schema: &Schema,
-    ) -> Result<(String, String, DbType)> {
-        match (schema.column(col1), schema.column(col2)) {
-            (Some(c1), Some(c2)) if c1._type.coerceable_to(&c2._type) => {
-                Ok((col1.to_string(), col2.to_string(), c1._type))
-            }
-            (Some(_), Some(_)) => Err(ExecutionError::MismatchedTypeComparision),
-            _ => Err(ExecutionError::UnknownColumnNameProvided),
-        }
+    /// Evaluates an expression that doesn't refer to any columns
+    fn constant(expr: &Expression) -> Result<DbValue> {
+        let empty = Schema::new(Vec::new());
+        RowExpression::build(expr, &empty, None)?.evaluate(&Row::new(Vec::new()))
     }
 
-    fn val_to_col_type(val: &DbValue, col: &str, schema: &Schema) -> Result<DbValue> {
-        let _type = match schema.column(col) {
-            Some(c) => c._type,
-            None => return Err(ExecutionError::UnknownColumnNameProvided),
+    fn function_type(func: ScalarFunction, arg_types: &[DbType]) -> Result<DbType> {
+        let is_numeric = |t: &DbType| !t.is_string();
+        let _type = match (func, arg_types) {
+            (ScalarFunction::Upper | ScalarFunction::Lower, [t]) if t.is_string() => DbType::String,
+            (ScalarFunction::Length, [t]) if t.is_string() => DbType::Integer,
+            (ScalarFunction::Abs, [t]) if is_numeric(t) => *t,
+            (ScalarFunction::Round, [t]) if is_numeric(t) => DbType::Float,
+            (
+                ScalarFunction::Round,
+                [t, DbType::Integer | DbType::UnsignedInt | DbType::SmallInt],
+            ) if is_numeric(t) => DbType::Float,
+            _ => return Err(ExecutionError::InvalidFunctionArgument(func)),
         };
-        match val.coerced_to(_type) {
-            Some(v) => Ok(v),
-            None => Err(ExecutionError::MismatchedTypeComparision),
-        }
+        Ok(_type)
     }
 
-    fn build(where_clause: &WhereClause, schema: &Schema) -> Result<Self> {
-        match (&where_clause.left, &where_clause.right) {
-            (WhereMember::Value(val), WhereMember::Column(col)) => Ok(Self::ColumnValue {
-                col: FilterType::validated_column_against(col, schema, val.db_type())?,
-                val: FilterType::val_to_col_type(val, col, schema)?,
-                cmp: where_clause.cmp.inverted(), // predicates assume value was always on the right, so we need to invert the comparison type
-                schema: schema.clone(),
-            }),
-            (WhereMember::Column(col), WhereMember::Value(val)) => Ok(Self::ColumnValue {
-                col: FilterType::validated_column_against(col, schema, val.db_type())?,
-                val: FilterType::val_to_col_type(val, col, schema)?,
-                cmp: where_clause.cmp,
-                schema: schema.clone(),
-            }),
-            (WhereMember::Value(val1), WhereMember::Value(val2)) => {
-                let val2 = val2.coerced_to(val1.db_type());
-                match val2 {
-                    Some(val2) => Ok(FilterType::ValueValue {
-                        left: val1.clone(),
-                        right: val2,
-                        cmp: where_clause.cmp,
-                    }),
-                    None => Err(ExecutionError::MismatchedTypeComparision),
-                }
-            }
-            (WhereMember::Column(col1), WhereMember::Column(col2)) => {
-                let (col1, col2, _type) = FilterType::validated_column_column(col1, col2, schema)?;
-                Ok(Self::ColumnColumn {
-                    col1,
-                    col2,
-                    _type,
-                    cmp: where_clause.cmp,
-                    schema: schema.clone(),
-                })
+    /// Operands are coerced to a common type: float if either side is a float,
+    /// unsigned only if both sides are unsigned, and integer otherwise
+    fn arithmetic_type(left: DbType, right: DbType) -> Result<DbType> {
+        match (left, right) {
+            (left, right) if left.is_string() || right.is_string() => {
+                Err(ExecutionError::NonNumericOperand)
             }
+            (DbType::Float, _) | (_, DbType::Float) => Ok(DbType::Float),
+            (DbType::UnsignedInt, DbType::UnsignedInt) => Ok(DbType::UnsignedInt),
+            _ => Ok(DbType::Integer),
         }
     }
 
-    fn row_predicate(&self, row: &Row) -> bool {
-        let (left, right, cmp) = match self {
-            Self::ColumnColumn {
-                col1,
-                col2,
+    fn db_type(&self) -> DbType {
+        match self {
+            Self::Value(v) => v.db_type(),
+            Self::Column { pos: _, _type } => *_type,
+            Self::Arithmetic {
+                left: _,
+                op: _,
+                right: _,
+                _type,
+            } => *_type,
+            Self::Function {
+                func: _,
+                args: _,
+                _type,
+            } => *_type,
+            Self::Cast { expr: _, to } => *to,
+        }
+    }
+
+    fn evaluate(&self, row: &Row) -> Result<DbValue> {
+        match self {
+            Self::Value(v) => Ok(v.clone()),
+            Self::Column { pos, _type: _ } => Ok(row
+                .data
+                .get(*pos)
+                .expect("Should always have a value")
+                .clone()),
+            Self::Arithmetic {
+                left,
+                op,
+                right,
                 _type,
-                cmp,
-                schema,
             } => {
-                let left = schema
-                    .column_value(col1, row)
-                    .expect("Should always have a value")
-                    .coerced_to(*_type)
-                    .expect("Already validated this conversion works");
-                let right = schema
-                    .column_value(col2, row)
-                    .expect("Should always have a value")
-                    .coerced_to(*_type)
-                    .expect("Already validated this conversion works");
-                (left, right, cmp)
+                let left = RowExpression::operand(left.evaluate(row)?, *_type)?;
+                let right = RowExpression::operand(right.evaluate(row)?, *_type)?;
+                RowExpression::apply(*op, left, right)
             }
-            Self::ColumnValue {
-                col,
-                val,
-                cmp,
-                schema,
+            Self::Function {
+                func,
+                args,
+                _type: _,
             } => {
-                let left = schema
-                    .column_value(col, row)
-                    .expect("Should always have a value")
-                    .clone();
-                (left, val.clone(), cmp)
+                let args = args
+                    .iter()
+                    .map(|a| a.evaluate(row))
+                    .collect::<Result<Vec<_>>>()?;
+                RowExpression::call(*func, args)
+            }
+            Self::Cast { expr, to } => RowExpression::cast(expr.evaluate(row)?, *to),
+        }
+    }
+
+    fn cast(val: DbValue, to: DbType) -> Result<DbValue> {
+        let from = val.db_type();
+        let val = if to.is_string() && !from.is_string() {
+            DbValue::String(val.to_string())
+        } else {
+            val
+        };
+        val.coerced_to(to)
+            .ok_or(ExecutionError::InvalidCast { from, to })
+    }
+
+    fn call(func: ScalarFunction, args: Vec<DbValue>) -> Result<DbValue> {
+        let res = match (func, args.as_slice()) {
+            (ScalarFunction::Upper, [DbValue::String(s) | DbValue::Char(s)]) => {
+                DbValue::String(s.to_uppercase())
+            }
+            (ScalarFunction::Lower, [DbValue::String(s) | DbValue::Char(s)]) => {
+                DbValue::String(s.to_lowercase())
+            }
+            (ScalarFunction::Length, [DbValue::String(s) | DbValue::Char(s)]) => {
+                DbValue::Integer(s.chars().count() as i64)
             }
-            Self::ValueValue { left, right, cmp } => (left.clone(), right.clone(), cmp),
+            (ScalarFunction::Abs, [DbValue::Integer(i)]) => match i.checked_abs() {
+                Some(i) => DbValue::Integer(i),
+                None => return Err(ExecutionError::ArithmeticOverflow),
+            },
+            (ScalarFunction::Abs, [DbValue::UnsignedInt(u)]) => DbValue::UnsignedInt(*u),
+            (ScalarFunction::Abs, [DbValue::SmallInt(i)]) => match i.checked_abs() {
+                Some(i) => DbValue::SmallInt(i),
+                None => return Err(ExecutionError::ArithmeticOverflow),
+            },
+            (ScalarFunction::Abs, [DbValue::Float(f)]) => {
+                DbValue::Float(DbFloat::new(f.as_f64().abs()))
+            }
+            (ScalarFunction::Round, [val]) => RowExpression::round(val, 0)?,
+            (ScalarFunction::Round, [val, DbValue::Integer(digits)]) => {
+                RowExpression::round(val, *digits)?
+            }
+            (ScalarFunction::Round, [val, DbValue::SmallInt(digits)]) => {
+                RowExpression::round(val, i64::from(*digits))?
+            }
+            (ScalarFunction::Round, [val, DbValue::UnsignedInt(digits)]) => {
+                RowExpression::round(val, i64::try_from(*digits).unwrap_or(i64::MAX))?
+            }
+            _ => return Err(ExecutionError::InvalidFunctionArgument(func)),
         };
-        match cmp {
-            WhereCmp::Eq => left == right,
-            WhereCmp::LessThan => left < right,
-            WhereCmp::GreaterThan => left > right,
-            WhereCmp::LessThanEquals => left <= right,
-            WhereCmp::GreaterThanEquals => left >= right,
+        Ok(res)
+    }
+
+    /// Rounds to `digits` places after the decimal point, or before it when negative
+    fn round(val: &DbValue, digits: i64) -> Result<DbValue> {
+        let val = match val.coerced_to(DbType::Float) {
+            Some(DbValue::Float(f)) => f.as_f64(),
+            _ => {
+                return Err(ExecutionError::InvalidFunctionArgument(
+                    ScalarFunction::Round,
+                ))
+            }
+        };
+        // a float can't have more than 308 digits on either side of the decimal point
+        let factor = 10f64.powi(digits.clamp(-308, 308) as i32);
+        let rounded = (val * factor).round() / factor;
+        // scaling can overflow, but only when `val` has no digits that far out to round
+        Ok(DbValue::Float(
+            DbFloat::try_new(rounded).unwrap_or(DbFloat::new(val)),
+        ))
+    }
+
+    /// Coerces an operand to the type its arithmetic is done in
+    fn operand(val: DbValue, _type: DbType) -> Result<DbValue> {
+        match (val, _type) {
+            // unsigned values past i64::MAX would otherwise wrap around to negative
+            (DbValue::UnsignedInt(u), DbType::Integer) => i64::try_from(u)
+                .map(DbValue::Integer)
+                .map_err(|_| ExecutionError::ArithmeticOverflow),
+            (val, _type) => Ok(val
+                .coerced_to(_type)
+                .expect("Already validated this conversion works")),
         }
     }
+
+    fn apply(op: ArithmeticOp, left: DbValue, right: DbValue) -> Result<DbValue> {
+        let res = match (left, right) {
+            (DbValue::Integer(l), DbValue::Integer(r)) => {
+                if op == ArithmeticOp::Divide && r == 0 {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                let res = match op {
+                    ArithmeticOp::Add => l.checked_add(r),
+                    ArithmeticOp::Subtract => l.checked_sub(r),
+                    ArithmeticOp::Multiply => l.checked_mul(r),
+                    ArithmeticOp::Divide => l.checked_div(r),
+                };
+                res.map(DbValue::Integer)
+            }
+            (DbValue::UnsignedInt(l), DbValue::UnsignedInt(r)) => {
+                if op == ArithmeticOp::Divide && r == 0 {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                let res = match op {
+                    ArithmeticOp::Add => l.checked_add(r),
+                    ArithmeticOp::Subtract => l.checked_sub(r),
+                    ArithmeticOp::Multiply => l.checked_mul(r),
+                    ArithmeticOp::Divide => l.checked_div(r),
+                };
+                res.map(DbValue::UnsignedInt)
+            }
+            (DbValue::Float(l), DbValue::Float(r)) => {
+                let (l, r) = (l.as_f64(), r.as_f64());
+                if op == ArithmeticOp::Divide && r == 0.0 {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                let res = match op {
+                    ArithmeticOp::Add => l + r,
+                    ArithmeticOp::Subtract => l - r,
+                    ArithmeticOp::Multiply => l * r,
+                    ArithmeticOp::Divide => l / r,
+                };
+                // DbFloats must be finite, so overflowing to infinity is an error
+                DbFloat::try_new(res).map(DbValue::Float)
+            }
+            _ => panic!("Operands should already be coerced to the same numeric type"),
+        };
+        res.ok_or(ExecutionError::ArithmeticOverflow)
+    }
 }
 
-// TODO: Construct predicate in a more intentional way, probably during physical plan phase
-// when I get that set up
-struct FilterRowsIter<'a> {
+type ColumnProjectFn<'a> = Box<dyn Fn(Cow<'a, Row>) -> Result<Cow<'a, Row>> + 'a>;
+
+struct SelectRowsIter<'a> {
     source: Box<RowsSource<'a>>,
-    predicate: FilterType,
     schema: Cow<'a, Schema>,
+    column_project: ColumnProjectFn<'a>,
+    /// The selected columns, as written
+    columns: String,
 }
-impl<'a> FilterRowsIter<'a> {
-    pub fn build(source: RowsSource<'a>, where_clause: &WhereClause) -> Result<Self> {
-        let schema = source.schema();
-        let predicate = FilterType::build(where_clause, &schema)?;
+impl<'a> SelectRowsIter<'a> {
+    fn build(
+        source: RowsSource<'a>,
+        columns: &SelectColumns,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        let source_schema = source.schema();
+        match columns {
+            SelectColumns::All => {
+                let mut schema = source_schema.into_owned();
+                // a join has a rowid for each of its tables
+                let row_ids: Vec<(usize, String)> = schema
+                    .columns()
+                    .enumerate()
+                    .filter(|(_, c)| is_row_id(&c.name))
+                    .map(|(pos, c)| (pos, c.name.clone()))
+                    .collect();
+                if !row_ids.is_empty() {
+                    row_ids.iter().for_each(|(_, name)| schema.remove(name));
+                    Ok(SelectRowsIter {
+                        source: Box::new(source),
+                        schema: Cow::Owned(schema),
+                        column_project: Box::new(move |r| {
+                            let mut r = r.into_owned();
+                            for (pos, _) in row_ids.iter().rev() {
+                                r.data.remove(*pos);
+                            }
+                            Ok(Cow::Owned(r))
+                        }),
+                        columns: String::from("*"),
+                    })
+                } else {
+                    Ok(SelectRowsIter {
+                        source: Box::new(source),
+                        schema: Cow::Owned(schema),
+                        column_project: Box::new(Ok),
+                        columns: String::from("*"),
+                    })
+                }
+            }
+            SelectColumns::CountAll { out_name: _ }
+            | SelectColumns::CountDistinct {
+                expr: _,
+                out_name: _,
+            } => {
+                panic!("Counts should always be built by CountRowsIter")
+            }
+            SelectColumns::Only(items) => {
+                let cols = expand_wildcards(items, &source_schema, table)?;
+                // each subquery's result is added to the end of the row, and the
+                // expressions using it refer to that column instead
+                let mut subqueries: Vec<SelectStatement> = Vec::new();
+                let mut exprs: Vec<Expression> = cols.iter().map(|c| c.expr.clone()).collect();
+                for expr in exprs.iter_mut() {
+                    expr.replace_subqueries(&mut |subquery| {
+                        let index = match subqueries.iter().position(|s| s == subquery) {
+                            Some(index) => index,
+                            None => {
+                                subqueries.push(subquery.clone());
+                                subqueries.len() - 1
+                            }
+                        };
+                        Expression::Column(ColumnRef::new(ScalarSubquery::column_name(index)))
+                    });
+                }
+                let subqueries = subqueries
+                    .iter()
+                    .map(|s| ScalarSubquery::build(s, &source_schema, table, storage))
+                    .collect::<Result<Vec<_>>>()?;
+                let columns = source_schema
+                    .columns()
+                    .cloned()
+                    .chain(subqueries.iter().enumerate().map(|(i, s)| s.column(i)));
+                let extended_schema = Schema::new(columns.collect());
 
-        Ok(FilterRowsIter {
+                let expressions = exprs
+                    .iter()
+                    .map(|expr| RowExpression::build(expr, &extended_schema, table))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let columns = zip(cols.iter(), expressions.iter())
+                    .map(|(col, expr)| {
+                        let column = Column::new(col.out_name.clone(), expr.db_type());
+                        match expr {
+                            // a column selected as is is still compared the same way
+                            RowExpression::Column { pos, _type: _ } => column.collated(
+                                extended_schema
+                                    .columns()
+                                    .nth(*pos)
+                                    .expect("Resolved against this schema")
+                                    .collation,
+                            ),
+                            _ => column,
+                        }
+                    })
+                    .collect();
+
+                let new_schema = Cow::Owned(Schema::new(columns));
+
+                let projection = move |r: Cow<'a, Row>| {
+                    let r = if subqueries.is_empty() {
+                        r
+                    } else {
+                        let mut extended = r.into_owned();
+                        for subquery in subqueries.iter() {
+                            let val = subquery.evaluate(&extended)?;
+                            extended.data.push(val);
+                        }
+                        Cow::Owned(extended)
+                    };
+                    let data = expressions
+                        .iter()
+                        .map(|expr| expr.evaluate(&r))
+                        .collect::<Result<_>>()?;
+                    Ok(Cow::Owned(Row::new(data)))
+                };
+
+                let columns: Vec<String> = items.iter().map(|c| c.to_string()).collect();
+                Ok(SelectRowsIter {
+                    source: Box::new(source),
+                    schema: new_schema,
+                    column_project: Box::new(projection),
+                    columns: columns.join(", "),
+                })
+            }
+        }
+    }
+}
+impl<'a> Iterator for SelectRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.source
+            .next()
+            .map(|r| r.and_then(|r| (self.column_project)(r)))
+    }
+}
+
+/// Skips rows that have already been returned, keeping the first occurrence of each
+struct DistinctRowsIter<'a> {
+    source: Box<RowsSource<'a>>,
+    schema: Cow<'a, Schema>,
+    /// Each column's collation, so rows are duplicates if they compare equal
+    collations: Vec<Collation>,
+    seen: HashSet<Row>,
+}
+impl<'a> DistinctRowsIter<'a> {
+    fn new(source: RowsSource<'a>) -> Self {
+        let schema = source.schema();
+        let collations = schema.columns().map(|c| c.collation).collect();
+        DistinctRowsIter {
             source: Box::new(source),
-            predicate,
             schema,
+            collations,
+            seen: HashSet::new(),
+        }
+    }
+}
+impl<'a> Iterator for DistinctRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.source.find(|row| match row {
+            Ok(row) => {
+                let key = zip(row.data.iter(), self.collations.iter())
+                    .map(|(val, collation)| collation.key(val).into_owned())
+                    .collect();
+                self.seen.insert(Row::new(key))
+            }
+            Err(_) => true,
         })
     }
 }
-impl<'a> Iterator for FilterRowsIter<'a> {
-    type Item = Cow<'a, Row>;
+
+/// The rows of `left` followed by the rows of `right`, which `UNION` then removes
+/// duplicates from. The schema is `left`'s.
+struct UnionRowsIter<'a> {
+    left: Box<RowsSource<'a>>,
+    right: RowsSource<'a>,
+    schema: Cow<'a, Schema>,
+    left_done: bool,
+}
+impl<'a> UnionRowsIter<'a> {
+    fn new(left: RowsSource<'a>, right: RowsSource<'a>) -> Self {
+        let schema = left.schema();
+        UnionRowsIter {
+            left: Box::new(left),
+            right,
+            schema,
+            left_done: false,
+        }
+    }
+}
+impl<'a> Iterator for UnionRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.source.find(|row| self.predicate.row_predicate(row))
+        if !self.left_done {
+            match self.left.next() {
+                Some(row) => return Some(row),
+                None => self.left_done = true,
+            }
+        }
+        self.right.next()
     }
 }
 
-fn sort_key_fn(clause: &OrderByClause, schema: &Schema) -> Result<impl Fn(&Row) -> Vec<DbValue>> {
-    let pos = match schema.column_position(clause.sort_column()) {
-        Some(pos) => pos,
-        None => return Err(ExecutionError::UnknownColumnNameProvided),
-    };
-    let key_fn = move |r: &Row| {
-        let v = r
-            .data
-            .get(pos)
-            .expect("We've already verified this will exist")
-            .clone();
-        let key = vec![v];
-        key
-    };
-    Ok(key_fn)
+/// Checks the rows of two selects can be combined by a union. Columns only need to
+/// hold the same kind of value, strings or numbers, rather than the same type.
+fn check_union_schemas(left: &Schema, right: &Schema) -> Result<()> {
+    let (left_count, right_count) = (left.columns().count(), right.columns().count());
+    if left_count != right_count {
+        return Err(ExecutionError::UnionColumnCountMismatch {
+            left: left_count,
+            right: right_count,
+        });
+    }
+    for (l, r) in zip(left.columns(), right.columns()) {
+        if !l._type.coerceable_to(&r._type) {
+            return Err(ExecutionError::UnionColumnTypeMismatch {
+                column: l.name.clone(),
+                left: l._type,
+                right: r._type,
+            });
+        }
+    }
+    Ok(())
 }
 
-struct SortRowsIter<'a> {
+/// Produces a single row holding the number of rows in its source. The count
+/// can also be provided up front when it's already known.
+struct CountRowsIter<'a> {
+    source: Option<Box<RowsSource<'a>>>,
     schema: Cow<'a, Schema>,
-    sorted_rows: Vec<Cow<'a, Row>>,
-    cursor: usize,
+    count: Option<usize>,
+    /// The table whose row count was provided up front, if any
+    table: Option<String>,
 }
-impl<'a> SortRowsIter<'a> {
-    pub fn build(source: RowsSource<'a>, sort_clause: &OrderByClause) -> Result<Self> {
-        let schema = source.schema();
-        let mut rows = Vec::new();
-        for row in source {
-            rows.push(row);
+impl<'a> CountRowsIter<'a> {
+    fn count_schema(out_name: &str) -> Cow<'a, Schema> {
+        let column = Column::new(out_name.to_string(), DbType::UnsignedInt);
+        Cow::Owned(Schema::new(vec![column]))
+    }
+
+    fn scan(source: RowsSource<'a>, out_name: &str) -> Self {
+        CountRowsIter {
+            source: Some(Box::new(source)),
+            schema: CountRowsIter::count_schema(out_name),
+            count: None,
+            table: None,
         }
+    }
 
-        let key_fn = sort_key_fn(sort_clause, &schema)?;
-        rows.sort_by_cached_key(|row| key_fn(row));
-        if sort_clause.desc() {
-            rows.reverse();
+    fn known(count: usize, table: &str, out_name: &str) -> Self {
+        CountRowsIter {
+            source: None,
+            schema: CountRowsIter::count_schema(out_name),
+            count: Some(count),
+            table: Some(table.to_string()),
         }
+    }
+}
+impl<'a> Iterator for CountRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
 
-        Ok(SortRowsIter {
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(source) = self.source.take() {
+            let mut count = 0;
+            for row in source {
+                if let Err(err) = row {
+                    return Some(Err(err));
+                }
+                count += 1;
+            }
+            self.count = Some(count);
+        }
+        self.count.take().map(|count| {
+            Ok(Cow::Owned(Row::new(vec![DbValue::UnsignedInt(
+                count as u64,
+            )])))
+        })
+    }
+}
+
+struct LimitRowsIter<'a> {
+    source: Box<RowsSource<'a>>,
+    schema: Cow<'a, Schema>,
+    limit: usize,
+    rows_left: usize,
+}
+impl<'a> LimitRowsIter<'a> {
+    fn new(source: RowsSource<'a>, limit: &usize) -> Self {
+        let schema = source.schema();
+        LimitRowsIter {
+            source: Box::new(source),
             schema,
-            sorted_rows: rows,
-            cursor: 0,
+            limit: *limit,
+            rows_left: *limit,
+        }
+    }
+}
+impl<'a> Iterator for LimitRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(row) = self.source.next().filter(|_| self.rows_left > 0) {
+            self.rows_left -= 1;
+            Some(row)
+        } else {
+            None
+        }
+    }
+}
+
+/// Pairs each row of `left` with each row of `right`, in that order, with the columns
+/// of `right` following those of `left`
+struct JoinRowsIter<'a> {
+    left: Box<RowsSource<'a>>,
+    /// Read in full up front, since it's gone through once for each row of `left`
+    right: Vec<Cow<'a, Row>>,
+    right_table: String,
+    schema: Cow<'a, Schema>,
+    /// The row of `left` being paired, and the position in `right` of the next row to pair it with
+    current: Option<(Cow<'a, Row>, usize)>,
+}
+impl<'a> JoinRowsIter<'a> {
+    fn cross(
+        left: RowsSource<'a>,
+        left_table: Option<&str>,
+        right: Rows<'a>,
+        right_table: &str,
+    ) -> Self {
+        let columns = JoinRowsIter::qualified(&left.schema(), left_table)
+            .chain(JoinRowsIter::qualified(&right.schema, Some(right_table)))
+            .collect();
+        JoinRowsIter {
+            left: Box::new(left),
+            right: right.collect(),
+            right_table: right_table.to_string(),
+            schema: Cow::Owned(Schema::new(columns)),
+            current: None,
+        }
+    }
+
+    /// The columns of `schema`, named `table.column` if they belong to `table`. Columns
+    /// without a table are already qualified by an earlier join.
+    fn qualified<'s>(
+        schema: &'s Schema,
+        table: Option<&'s str>,
+    ) -> impl Iterator<Item = Column> + 's {
+        schema.columns().map(move |c| match table {
+            Some(table) => c.with_name(format!("{table}.{}", c.name)),
+            None => c.clone(),
         })
     }
 }
-impl<'a> Iterator for SortRowsIter<'a> {
-    type Item = Cow<'a, Row>;
+impl<'a> Iterator for JoinRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cursor >= self.sorted_rows.len() {
+        if self.right.is_empty() {
             return None;
         }
-        let row = self.sorted_rows.get(self.cursor);
-        self.cursor += 1;
-        row.cloned()
+        let (left, pos) = match &mut self.current {
+            Some((left, pos)) if *pos < self.right.len() => (left, pos),
+            _ => {
+                let left = match self.left.next()? {
+                    Ok(row) => row,
+                    Err(err) => return Some(Err(err)),
+                };
+                let (left, pos) = self.current.insert((left, 0));
+                (left, pos)
+            }
+        };
+        let mut data = left.data.clone();
+        data.extend(self.right[*pos].data.iter().cloned());
+        *pos += 1;
+        Some(Ok(Cow::Owned(Row::new(data))))
+    }
+}
+
+/// Which column of each side a join matches rows on, and how they're compared
+struct JoinKeys {
+    left: usize,
+    /// Relative to the start of the right side's columns
+    right: usize,
+    _type: DbType,
+    collation: Collation,
+}
+
+/// The rows of a join on `left_col = right_col`, found by building a hash table
+/// of the smaller side's values and looking up each row of the other side in it.
+/// Rows come out in the same order a nested loop over the pairs would give.
+struct HashJoinRowsIter<'a> {
+    left: Box<RowsSource<'a>>,
+    right: Vec<Cow<'a, Row>>,
+    right_table: String,
+    schema: Cow<'a, Schema>,
+    /// The join condition, as written
+    on: String,
+    keys: JoinKeys,
+    /// Read from `left` on the first call to `next`, so nothing is read until rows are asked for
+    left_rows: Vec<Cow<'a, Row>>,
+    /// The positions in `left_rows` and `right` of each matching pair, found along with `left_rows`
+    pairs: Option<std::vec::IntoIter<(usize, usize)>>,
+}
+impl<'a> HashJoinRowsIter<'a> {
+    fn new(cross: JoinRowsIter<'a>, on: &WhereClause, keys: JoinKeys) -> Self {
+        HashJoinRowsIter {
+            left: cross.left,
+            right: cross.right,
+            right_table: cross.right_table,
+            schema: cross.schema,
+            on: on.to_string(),
+            keys,
+            left_rows: Vec::new(),
+            pairs: None,
+        }
+    }
+
+    /// The columns to match on, if `on` is an equality between a column of each side
+    /// of `cross`. Anything else has to be joined with a nested loop.
+    fn join_keys(on: &WhereClause, cross: &JoinRowsIter) -> Result<Option<JoinKeys>> {
+        let (left, right) = match on {
+            WhereClause::Comparison {
+                left,
+                cmp: WhereCmp::Eq,
+                right,
+            } => (left, right),
+            _ => return Ok(None),
+        };
+        let (col1, col2, _type, collation) =
+            match FilterType::build(left, WhereCmp::Eq, right, &cross.schema, None)? {
+                FilterType::ColumnColumn {
+                    col1,
+                    col2,
+                    _type,
+                    cmp: _,
+                    collation,
+                    schema: _,
+                } => (col1, col2, _type, collation),
+                _ => return Ok(None),
+            };
+        let index_of = |name: &str| {
+            cross
+                .schema
+                .get(name)
+                .expect("Already resolved this column")
+                .index
+        };
+        let left_width = cross.left.schema().columns().count();
+        let (left, right) = match (index_of(&col1), index_of(&col2)) {
+            (l, r) if l < left_width && r >= left_width => (l, r - left_width),
+            (r, l) if l < left_width && r >= left_width => (l, r - left_width),
+            // both columns are on the same side
+            _ => return Ok(None),
+        };
+        Ok(Some(JoinKeys {
+            left,
+            right,
+            _type,
+            collation,
+        }))
+    }
+
+    fn key(&self, row: &Row, idx: usize) -> DbValue {
+        let val = row.data[idx]
+            .coerced_to(self.keys._type)
+            .expect("Already validated this conversion works");
+        self.keys.collation.key(&val).into_owned()
+    }
+
+    /// The positions in `left_rows` and `right` of each pair of rows whose keys are equal
+    fn matching_pairs(&self) -> Vec<(usize, usize)> {
+        let left = self.left_rows.as_slice();
+        let build_left = left.len() < self.right.len();
+        let (build, build_key, probe, probe_key) = if build_left {
+            (left, self.keys.left, self.right.as_slice(), self.keys.right)
+        } else {
+            (self.right.as_slice(), self.keys.right, left, self.keys.left)
+        };
+        let mut table: HashMap<DbValue, Vec<usize>> = HashMap::new();
+        for (idx, row) in build.iter().enumerate() {
+            table.entry(self.key(row, build_key)).or_default().push(idx);
+        }
+        let mut pairs = Vec::new();
+        for (probe_idx, row) in probe.iter().enumerate() {
+            if let Some(matches) = table.get(&self.key(row, probe_key)) {
+                pairs.extend(matches.iter().map(|&build_idx| (build_idx, probe_idx)));
+            }
+        }
+        if build_left {
+            // probing went through `right`, so put the pairs back in `left`'s order
+            pairs.sort_unstable();
+        } else {
+            pairs.iter_mut().for_each(|pair| *pair = (pair.1, pair.0));
+        }
+        pairs
+    }
+}
+impl<'a> Iterator for HashJoinRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pairs.is_none() {
+            self.left_rows = match self.left.by_ref().collect::<Result<Vec<_>>>() {
+                Ok(rows) => rows,
+                Err(err) => return Some(Err(err)),
+            };
+            self.pairs = Some(self.matching_pairs().into_iter());
+        }
+        let (l, r) = self.pairs.as_mut().expect("Just filled this in").next()?;
+        let mut data = self.left_rows[l].data.clone();
+        data.extend(self.right[r].data.iter().cloned());
+        Some(Ok(Cow::Owned(Row::new(data))))
+    }
+}
+
+#[derive(Debug)]
+enum FilterType {
+    ValueValue {
+        left: DbValue,
+        right: DbValue,
+        cmp: WhereCmp,
+    },
+    ColumnValue {
+        col: String,
+        val: DbValue,
+        cmp: WhereCmp,
+        collation: Collation,
+        schema: Schema,
+    },
+    ColumnColumn {
+        col1: String,
+        col2: String,
+        _type: DbType,
+        cmp: WhereCmp,
+        collation: Collation,
+        schema: Schema,
+    },
+    /// Used when either side is more than a plain column or value
+    ExpressionExpression {
+        left: RowExpression,
+        right: RowExpression,
+        _type: DbType,
+        cmp: WhereCmp,
+    },
+}
+impl FilterType {
+    fn validated_column_against(col: &str, schema: &Schema, against: DbType) -> Result<String> {
+        match schema.column(col) {
+            Some(c) if c._type.coerceable_to(&against) => Ok(col.to_string()),
+            Some(_) => Err(ExecutionError::MismatchedTypeComparision),
+            None => Err(ExecutionError::UnknownColumnName(col.to_string())),
+        }
+    }
+
+    fn validated_column_column(
+        col1: &str,
+        col2: &str,
+        schema: &Schema,
+    ) -> Result<(String, String, DbType)> {
+        match (schema.column(col1), schema.column(col2)) {
+            (Some(c1), Some(c2)) if c1._type.coerceable_to(&c2._type) => {
+                let _type = match (c1._type, c2._type) {
+                    // strings of different lengths are compared as plain strings
+                    (DbType::Char(_), _) | (_, DbType::Char(_)) => DbType::String,
+                    // a small int is compared as the other column's type, which it always fits in
+                    (DbType::SmallInt, _type) => _type,
+                    (_type, _) => _type,
+                };
+                Ok((col1.to_string(), col2.to_string(), _type))
+            }
+            (Some(_), Some(_)) => Err(ExecutionError::MismatchedTypeComparision),
+            (None, _) => Err(ExecutionError::UnknownColumnName(col1.to_string())),
+            (_, None) => Err(ExecutionError::UnknownColumnName(col2.to_string())),
+        }
+    }
+
+    fn val_to_col_type(val: &DbValue, col: &str, schema: &Schema) -> Result<DbValue> {
+        let _type = match schema.column(col) {
+            Some(c) => c._type,
+            None => return Err(ExecutionError::UnknownColumnName(col.to_string())),
+        };
+        match val.coerced_to(_type) {
+            Some(v) => Ok(v),
+            None => Err(ExecutionError::MismatchedTypeComparision),
+        }
+    }
+
+    fn resolved_member(
+        member: &WhereMember,
+        schema: &Schema,
+        table: Option<&str>,
+    ) -> Result<WhereMember> {
+        match member {
+            WhereMember::Column(col) => {
+                let name = resolve_column(col, schema, table)?.column.name.clone();
+                Ok(WhereMember::Column(ColumnRef::new(name)))
+            }
+            WhereMember::Expression(expr) => match expr.as_ref() {
+                Expression::QuotedName(name) => {
+                    let resolved = resolve_quoted_name(name, schema, table)?;
+                    FilterType::resolved_member(&resolved, schema, table)
+                }
+                _ => Ok(member.clone()),
+            },
+            WhereMember::Value(_) => Ok(member.clone()),
+        }
+    }
+
+    /// A column compared with anything uses the column's collation. When both sides
+    /// are columns, the left one's is used unless only the right one has one.
+    fn collation(left: &str, right: Option<&str>, schema: &Schema) -> Collation {
+        let collation_of = |name: &str| schema.column(name).map(|c| c.collation);
+        match (collation_of(left), right.and_then(collation_of)) {
+            (Some(Collation::Binary) | None, Some(right)) => right,
+            (Some(left), _) => left,
+            (None, None) => Collation::Binary,
+        }
+    }
+
+    /// Both sides are compared as the same type, so they must be coercible to it
+    fn comparison_type(left: DbType, right: DbType) -> Result<DbType> {
+        if left == right {
+            return Ok(left);
+        }
+        if left.is_string() && right.is_string() {
+            return Ok(DbType::String);
+        }
+        match RowExpression::arithmetic_type(left, right) {
+            Ok(_type) => Ok(_type),
+            Err(_) => Err(ExecutionError::MismatchedTypeComparision),
+        }
+    }
+
+    fn build(
+        left: &WhereMember,
+        cmp: WhereCmp,
+        right: &WhereMember,
+        schema: &Schema,
+        table: Option<&str>,
+    ) -> Result<Self> {
+        let left = FilterType::resolved_member(left, schema, table)?;
+        let right = FilterType::resolved_member(right, schema, table)?;
+        match (&left, &right) {
+            (WhereMember::Expression(_), _) | (_, WhereMember::Expression(_)) => {
+                let left = RowExpression::build(&left.into_expression(), schema, table)?;
+                let right = RowExpression::build(&right.into_expression(), schema, table)?;
+                let _type = FilterType::comparison_type(left.db_type(), right.db_type())?;
+                Ok(Self::ExpressionExpression {
+                    left,
+                    right,
+                    _type,
+                    cmp,
+                })
+            }
+            (WhereMember::Value(val), WhereMember::Column(col)) => Ok(Self::ColumnValue {
+                col: FilterType::validated_column_against(&col.name, schema, val.db_type())?,
+                val: FilterType::val_to_col_type(val, &col.name, schema)?,
+                cmp: cmp.inverted(), // predicates assume value was always on the right, so we need to invert the comparison type
+                collation: FilterType::collation(&col.name, None, schema),
+                schema: schema.clone(),
+            }),
+            (WhereMember::Column(col), WhereMember::Value(val)) => Ok(Self::ColumnValue {
+                col: FilterType::validated_column_against(&col.name, schema, val.db_type())?,
+                val: FilterType::val_to_col_type(val, &col.name, schema)?,
+                cmp,
+                collation: FilterType::collation(&col.name, None, schema),
+                schema: schema.clone(),
+            }),
+            (WhereMember::Value(val1), WhereMember::Value(val2)) => {
+                let val2 = val2.coerced_to(val1.db_type());
+                match val2 {
+                    Some(val2) => Ok(FilterType::ValueValue {
+                        left: val1.clone(),
+                        right: val2,
+                        cmp,
+                    }),
+                    None => Err(ExecutionError::MismatchedTypeComparision),
+                }
+            }
+            (WhereMember::Column(col1), WhereMember::Column(col2)) => {
+                let (col1, col2, _type) =
+                    FilterType::validated_column_column(&col1.name, &col2.name, schema)?;
+                let collation = FilterType::collation(&col1, Some(&col2), schema);
+                Ok(Self::ColumnColumn {
+                    col1,
+                    col2,
+                    _type,
+                    cmp,
+                    collation,
+                    schema: schema.clone(),
+                })
+            }
+        }
+    }
+
+    fn row_predicate(&self, row: &Row) -> Result<bool> {
+        let (left, right, cmp, collation) = match self {
+            Self::ColumnColumn {
+                col1,
+                col2,
+                _type,
+                cmp,
+                collation,
+                schema,
+            } => {
+                let left = schema
+                    .column_value(col1, row)
+                    .expect("Should always have a value")
+                    .coerced_to(*_type)
+                    .expect("Already validated this conversion works");
+                let right = schema
+                    .column_value(col2, row)
+                    .expect("Should always have a value")
+                    .coerced_to(*_type)
+                    .expect("Already validated this conversion works");
+                (left, right, cmp, *collation)
+            }
+            Self::ColumnValue {
+                col,
+                val,
+                cmp,
+                collation,
+                schema,
+            } => {
+                let left = schema
+                    .column_value(col, row)
+                    .expect("Should always have a value")
+                    .clone();
+                (left, val.clone(), cmp, *collation)
+            }
+            Self::ValueValue { left, right, cmp } => {
+                (left.clone(), right.clone(), cmp, Collation::Binary)
+            }
+            Self::ExpressionExpression {
+                left,
+                right,
+                _type,
+                cmp,
+            } => {
+                let left = left
+                    .evaluate(row)?
+                    .coerced_to(*_type)
+                    .expect("Already validated this conversion works");
+                let right = right
+                    .evaluate(row)?
+                    .coerced_to(*_type)
+                    .expect("Already validated this conversion works");
+                (left, right, cmp, Collation::Binary)
+            }
+        };
+        let (left, right) = (collation.key(&left), collation.key(&right));
+        let passes = match cmp {
+            WhereCmp::Eq => left == right,
+            WhereCmp::LessThan => left < right,
+            WhereCmp::GreaterThan => left > right,
+            WhereCmp::LessThanEquals => left <= right,
+            WhereCmp::GreaterThanEquals => left >= right,
+        };
+        Ok(passes)
+    }
+}
+
+/// A subquery that may refer to the columns of an outer row, and so is run
+/// again for each row it's used with
+#[derive(Debug)]
+struct CorrelatedSubquery<'a> {
+    subquery: Box<SelectStatement>,
+    /// The columns the subquery refers to that belong to the outer row, and where they are in it
+    correlated: Vec<(ColumnRef, usize)>,
+    /// The schema of the rows the subquery produces
+    schema: Schema,
+    storage: &'a StorageLayer,
+}
+impl<'a> CorrelatedSubquery<'a> {
+    /// A value of the given type, to check the subquery against before there's a row to run it with
+    fn stand_in_value(_type: DbType) -> DbValue {
+        match _type {
+            DbType::String => DbValue::String(String::new()),
+            DbType::Integer => DbValue::Integer(0),
+            DbType::UnsignedInt => DbValue::UnsignedInt(0),
+            DbType::SmallInt => DbValue::SmallInt(0),
+            DbType::Char(size) => DbValue::Char(" ".repeat(size as usize)),
+            DbType::Varchar(_) => DbValue::String(String::new()),
+            DbType::Float => DbValue::Float(DbFloat::new(0.0)),
+        }
+    }
+
+    /// Columns are looked up in the subquery's source first, and only treated as
+    /// belonging to the outer row if it doesn't have them
+    fn build(
+        subquery: &SelectStatement,
+        schema: &Schema,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        let inner_schema = ExecutablePlan::build_select_source_rows(
+            &subquery.source,
+            storage,
+            subquery.uses_row_id(),
+        )?
+        .schema();
+        let inner_table = subquery.source.table();
+
+        let mut correlated = Vec::new();
+        let mut checked = subquery.clone();
+        checked.substitute_columns(&mut |col| {
+            if !matches!(
+                resolve_column(col, &inner_schema, inner_table),
+                Err(ExecutionError::UnknownColumnName(_))
+            ) {
+                return None;
+            }
+            let ci = resolve_column(col, schema, table).ok()?;
+            correlated.push((col.clone(), ci.index));
+            Some(CorrelatedSubquery::stand_in_value(ci.column._type))
+        });
+        // building the subquery once up front reports any errors in it, even if
+        // there turn out to be no rows to run it against
+        let schema = ExecutablePlan::compose_select(&checked, storage)?
+            .schema()
+            .into_owned();
+
+        Ok(CorrelatedSubquery {
+            subquery: Box::new(subquery.clone()),
+            correlated,
+            schema,
+            storage,
+        })
+    }
+
+    /// The subquery's rows when run against `row`
+    fn rows(&self, row: &Row) -> Result<RowsSource<'a>> {
+        let mut subquery = self.subquery.as_ref().clone();
+        subquery.substitute_columns(&mut |col| {
+            let (_, pos) = self.correlated.iter().find(|(c, _)| c == col)?;
+            row.data.get(*pos).cloned()
+        });
+        ExecutablePlan::compose_select(&subquery, self.storage)
+    }
+}
+
+/// A `[NOT] EXISTS` subquery, which passes rows the subquery returns anything for
+#[derive(Debug)]
+struct ExistsPredicate<'a> {
+    subquery: CorrelatedSubquery<'a>,
+    negated: bool,
+}
+impl<'a> ExistsPredicate<'a> {
+    fn build(
+        subquery: &SelectStatement,
+        negated: bool,
+        schema: &Schema,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        Ok(ExistsPredicate {
+            subquery: CorrelatedSubquery::build(subquery, schema, table, storage)?,
+            negated,
+        })
+    }
+
+    fn row_predicate(&self, row: &Row) -> Result<bool> {
+        let exists = match self.subquery.rows(row)?.next() {
+            Some(Ok(_)) => true,
+            Some(Err(err)) => return Err(err),
+            None => false,
+        };
+        Ok(exists != self.negated)
+    }
+}
+
+/// A subquery used as a value, which must produce exactly one row of one column.
+/// Producing no rows is an error, since there's no NULL to use as its value.
+#[derive(Debug)]
+struct ScalarSubquery<'a> {
+    subquery: CorrelatedSubquery<'a>,
+}
+impl<'a> ScalarSubquery<'a> {
+    fn build(
+        subquery: &SelectStatement,
+        schema: &Schema,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        let subquery = CorrelatedSubquery::build(subquery, schema, table, storage)?;
+        match subquery.schema.columns().count() {
+            1 => Ok(ScalarSubquery { subquery }),
+            n => Err(ExecutionError::ScalarSubqueryColumns(n)),
+        }
+    }
+
+    /// The name of the column holding the `index`th subquery's result while projecting.
+    /// Statements can't contain a NUL, so no column a statement names can match it.
+    fn column_name(index: usize) -> String {
+        format!("\0subquery{index}")
+    }
+
+    fn column(&self, index: usize) -> Column {
+        let _type = self
+            .subquery
+            .schema
+            .columns()
+            .next()
+            .expect("Checked there's one column when built")
+            ._type;
+        Column::new(ScalarSubquery::column_name(index), _type)
+    }
+
+    fn evaluate(&self, row: &Row) -> Result<DbValue> {
+        let mut rows = self.subquery.rows(row)?;
+        let first = match rows.next() {
+            Some(first) => first?,
+            None => return Err(ExecutionError::ScalarSubqueryNoRows),
+        };
+        if rows.next().transpose()?.is_some() {
+            return Err(ExecutionError::ScalarSubqueryRows);
+        }
+        Ok(first.into_owned().data.swap_remove(0))
+    }
+}
+
+#[derive(Debug)]
+enum Predicate<'a> {
+    Comparison(FilterType),
+    Exists(ExistsPredicate<'a>),
+}
+impl<'a> Predicate<'a> {
+    fn build(
+        where_clause: &WhereClause,
+        schema: &Schema,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        match where_clause {
+            WhereClause::Comparison { left, cmp, right } => Ok(Self::Comparison(
+                FilterType::build(left, *cmp, right, schema, table)?,
+            )),
+            WhereClause::Exists { negated, subquery } => Ok(Self::Exists(ExistsPredicate::build(
+                subquery, *negated, schema, table, storage,
+            )?)),
+        }
+    }
+
+    fn row_predicate(&self, row: &Row) -> Result<bool> {
+        match self {
+            Self::Comparison(filter) => filter.row_predicate(row),
+            Self::Exists(exists) => exists.row_predicate(row),
+        }
+    }
+}
+
+/// The predicate of a CHECK constraint, ready to test rows of its table against
+pub struct CheckPredicate {
+    filter: FilterType,
+}
+impl CheckPredicate {
+    /// Parses a predicate stored as text, as `Parser::parse_check_predicate` reads it
+    pub fn build(predicate: &str, schema: &Schema) -> Result<Self> {
+        let clause = Parser::build(Tokenizer::new(predicate))?.parse_check_predicate()?;
+        let WhereClause::Comparison { left, cmp, right } = clause else {
+            panic!("Check predicates are only parsed as comparisons");
+        };
+        let filter = FilterType::build(&left, cmp, &right, schema, None)?;
+        Ok(CheckPredicate { filter })
+    }
+
+    pub fn passes(&self, row: &Row) -> Result<bool> {
+        self.filter.row_predicate(row)
+    }
+}
+
+// TODO: Construct predicate in a more intentional way, probably during physical plan phase
+// when I get that set up
+struct FilterRowsIter<'a> {
+    source: Box<RowsSource<'a>>,
+    predicate: Predicate<'a>,
+    schema: Cow<'a, Schema>,
+    /// The clause rows are filtered by, as written
+    where_clause: String,
+}
+impl<'a> FilterRowsIter<'a> {
+    pub fn build(
+        source: RowsSource<'a>,
+        where_clause: &WhereClause,
+        table: Option<&str>,
+        storage: &'a StorageLayer,
+    ) -> Result<Self> {
+        let schema = source.schema();
+        let predicate = Predicate::build(where_clause, &schema, table, storage)?;
+
+        Ok(FilterRowsIter {
+            source: Box::new(source),
+            predicate,
+            schema,
+            where_clause: where_clause.to_string(),
+        })
+    }
+}
+impl<'a> Iterator for FilterRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for row in self.source.by_ref() {
+            let passes = match &row {
+                Ok(row) => self.predicate.row_predicate(row),
+                Err(_) => Ok(true),
+            };
+            match passes {
+                Ok(true) => return Some(row),
+                Ok(false) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+fn sort_key_fn(
+    clause: &OrderByClause,
+    schema: &Schema,
+    table: Option<&str>,
+) -> Result<impl Fn(&Row) -> Vec<DbValue>> {
+    let ci = resolve_column(clause.sort_column(), schema, table)?;
+    let (pos, collation) = (ci.index, ci.column.collation);
+    let key_fn = move |r: &Row| {
+        let v = r
+            .data
+            .get(pos)
+            .expect("We've already verified this will exist");
+        let key = vec![collation.key(v).into_owned()];
+        key
+    };
+    Ok(key_fn)
+}
+
+type SortKeyFn = Box<dyn Fn(&Row) -> Vec<DbValue>>;
+
+/// Returns its source's rows in order. The source is read and sorted when the
+/// first row is asked for.
+struct SortRowsIter<'a> {
+    source: Option<Box<RowsSource<'a>>>,
+    schema: Cow<'a, Schema>,
+    /// The clause rows are sorted by, as written
+    sort_clause: String,
+    desc: bool,
+    key_fn: SortKeyFn,
+    sorted_rows: Vec<Cow<'a, Row>>,
+    cursor: usize,
+}
+impl<'a> SortRowsIter<'a> {
+    pub fn build(
+        source: RowsSource<'a>,
+        sort_clause: &OrderByClause,
+        table: Option<&str>,
+    ) -> Result<Self> {
+        let schema = source.schema();
+        let key_fn = sort_key_fn(sort_clause, &schema, table)?;
+        Ok(SortRowsIter {
+            source: Some(Box::new(source)),
+            schema,
+            sort_clause: sort_clause.to_string(),
+            desc: sort_clause.desc(),
+            key_fn: Box::new(key_fn),
+            sorted_rows: Vec::new(),
+            cursor: 0,
+        })
+    }
+
+    fn sort(&mut self, source: RowsSource<'a>) -> Result<()> {
+        for row in source {
+            self.sorted_rows.push(row?);
+        }
+        self.sorted_rows
+            .sort_by_cached_key(|row| (self.key_fn)(row));
+        if self.desc {
+            self.sorted_rows.reverse();
+        }
+        Ok(())
+    }
+}
+impl<'a> Iterator for SortRowsIter<'a> {
+    type Item = Result<Cow<'a, Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(source) = self.source.take() {
+            if let Err(err) = self.sort(*source) {
+                return Some(Err(err));
+            }
+        }
+        if self.cursor >= self.sorted_rows.len() {
+            return None;
+        }
+        let row = self.sorted_rows.get(self.cursor);
+        self.cursor += 1;
+        row.cloned().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod execution_tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::query::{execute, parse::Parser, tokenize::Tokenizer, QueryError};
+
+    fn select_rows(command: &str, storage: &mut StorageLayer) -> Result<Vec<Row>> {
+        match execute(command, storage) {
+            Ok(QueryResult::Rows(rows)) => rows.map(|r| r.map(|r| r.into_owned())).collect(),
+            Ok(_) => panic!("Expected rows"),
+            Err(QueryError::ExecutionError(err)) => Err(err),
+            Err(err) => panic!("Unexpected error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn cross_joins() {
+        let file = Path::new("cross_joins.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table users (id integer, name string); \
+            create table orders (uid integer, id integer); \
+            insert into users (id, name) values (1, \"ann\"); \
+            insert into users (id, name) values (2, \"bob\"); \
+            insert into orders (uid, id) values (2, 10); \
+            insert into orders (uid, id) values (2, 11); \
+            insert into orders (uid, id) values (1, 12);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows("select * from users, orders;", &mut storage).unwrap();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(
+            rows[0],
+            Row::new(vec![
+                DbValue::Integer(1),
+                DbValue::String(String::from("ann")),
+                DbValue::Integer(2),
+                DbValue::Integer(10),
+            ])
+        );
+        match execute("select *, users.rowid from users, orders;", &mut storage) {
+            Ok(QueryResult::Rows(rows)) => {
+                let names: Vec<String> = rows.schema().columns().map(|c| c.name.clone()).collect();
+                assert_eq!(
+                    names,
+                    vec!["users.id", "users.name", "orders.uid", "orders.id", "rowid"]
+                );
+            }
+            Ok(_) => panic!("Expected rows"),
+            Err(err) => panic!("Unexpected error: {err:?}"),
+        }
+
+        // the where clause joins the tables
+        let rows = select_rows(
+            "select name, orders.id from users, orders where users.id = uid order by orders.id;",
+            &mut storage,
+        )
+        .unwrap();
+        let expected: Vec<Row> = [("bob", 10), ("bob", 11), ("ann", 12)]
+            .into_iter()
+            .map(|(name, id)| {
+                Row::new(vec![
+                    DbValue::String(String::from(name)),
+                    DbValue::Integer(id),
+                ])
+            })
+            .collect();
+        assert_eq!(rows, expected);
+        assert_eq!(
+            select_rows(
+                "select orders.* from users, orders where name = \"ann\";",
+                &mut storage
+            )
+            .unwrap(),
+            vec![
+                Row::new(vec![DbValue::Integer(2), DbValue::Integer(10)]),
+                Row::new(vec![DbValue::Integer(2), DbValue::Integer(11)]),
+                Row::new(vec![DbValue::Integer(1), DbValue::Integer(12)]),
+            ]
+        );
+        let err = select_rows("select id from users, orders;", &mut storage).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Ambiguous column name provided: 'id' could be from any of users, orders"
+        );
+
+        execute("create table empty (x integer);", &mut storage).unwrap();
+        assert!(select_rows("select * from users, empty;", &mut storage)
+            .unwrap()
+            .is_empty());
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn destroy_if_exists() {
+        let mut storage = StorageLayer::in_memory();
+        assert!(matches!(
+            execute("destroy table missing;", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::StorageError(
+                StorageError::TableDoesNotExist
+            )))
+        ));
+        assert!(matches!(
+            execute("destroy table if exists missing;", &mut storage),
+            Ok(QueryResult::NothingToDo)
+        ));
+
+        execute(
+            "create table t (a integer); insert into t (a) values (1);",
+            &mut storage,
+        )
+        .unwrap();
+        assert!(matches!(
+            execute("destroy table if exists t;", &mut storage),
+            Ok(QueryResult::Ok(1))
+        ));
+        assert!(!storage.table_exists("t"));
+    }
+
+    #[test]
+    fn select_without_from() {
+        let mut storage = StorageLayer::in_memory();
+        let mut select = |command: &str| select_rows(command, &mut storage).unwrap();
+        assert_eq!(
+            select("select 1 + 1;"),
+            vec![Row::new(vec![DbValue::Integer(2)])]
+        );
+        assert_eq!(
+            select("select 'hello', upper('x');"),
+            vec![Row::new(vec![
+                DbValue::String(String::from("hello")),
+                DbValue::String(String::from("X")),
+            ])]
+        );
+        assert_eq!(
+            select("select 1;; "),
+            vec![Row::new(vec![DbValue::Integer(1)])]
+        );
+        assert_eq!(
+            select("select count(*);"),
+            vec![Row::new(vec![DbValue::UnsignedInt(1)])]
+        );
+        assert!(select_rows("select a;", &mut storage).is_err());
+    }
+
+    #[test]
+    fn unions() {
+        let mut storage = StorageLayer::in_memory();
+        execute(
+            "create table t (a integer, b string); \
+            create table u (x integer, y string); \
+            insert into t values (1, 'one'); \
+            insert into t values (2, 'two'); \
+            insert into t values (2, 'two'); \
+            insert into u values (2, 'two'); \
+            insert into u values (3, 'three');",
+            &mut storage,
+        )
+        .unwrap();
+        let row = |a: i64, b: &str| Row::new(vec![DbValue::Integer(a), DbValue::String(b.into())]);
+
+        let rows = select_rows(
+            "select a, b from t union all select x, y from u;",
+            &mut storage,
+        );
+        assert_eq!(
+            rows.unwrap(),
+            vec![
+                row(1, "one"),
+                row(2, "two"),
+                row(2, "two"),
+                row(2, "two"),
+                row(3, "three"),
+            ]
+        );
+        let rows = select_rows("select a, b from t union select x, y from u;", &mut storage);
+        assert_eq!(
+            rows.unwrap(),
+            vec![row(1, "one"), row(2, "two"), row(3, "three")]
+        );
+
+        // columns are named after the first select, and the order and limit apply to
+        // every row
+        match execute(
+            "select a as n, b from t union select x, y from u order by n desc limit 2;",
+            &mut storage,
+        ) {
+            Ok(QueryResult::Rows(rows)) => {
+                let names: Vec<String> = rows.schema().columns().map(|c| c.name.clone()).collect();
+                assert_eq!(names, vec!["n", "b"]);
+                let rows: Vec<Row> = rows.map(|r| r.unwrap().into_owned()).collect();
+                assert_eq!(rows, vec![row(3, "three"), row(2, "two")]);
+            }
+            Ok(_) => panic!("Expected rows"),
+            Err(err) => panic!("Unexpected error: {err:?}"),
+        }
+
+        // integers and floats can share a column
+        let rows = select_rows("select 1 union all select 2.5;", &mut storage).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        assert!(matches!(
+            select_rows("select a, b from t union select x from u;", &mut storage),
+            Err(ExecutionError::UnionColumnCountMismatch { left: 2, right: 1 })
+        ));
+        assert!(matches!(
+            select_rows("select a from t union select y from u;", &mut storage),
+            Err(ExecutionError::UnionColumnTypeMismatch { column, .. }) if column == "a"
+        ));
+    }
+
+    #[test]
+    fn empty_statements_do_nothing() {
+        let mut storage = StorageLayer::in_memory();
+        for command in ["", "  ", ";", " ;;; \n"] {
+            assert!(
+                matches!(execute(command, &mut storage), Ok(QueryResult::NothingToDo)),
+                "{command:?} should do nothing"
+            );
+        }
+
+        execute(
+            ";create table t (a integer);; insert into t values (1);;",
+            &mut storage,
+        )
+        .unwrap();
+        let rows = select_rows("select a from t;; ", &mut storage).unwrap();
+        assert_eq!(rows, vec![Row::new(vec![DbValue::Integer(1)])]);
+    }
+
+    #[test]
+    fn insert_value_counts_must_match_columns() {
+        let mut storage = StorageLayer::in_memory();
+        execute("create table t (a integer, b integer);", &mut storage).unwrap();
+        let mut mismatch = |command: &str| match execute(command, &mut storage) {
+            Err(QueryError::ExecutionError(ExecutionError::ColumnValueCountMismatch {
+                columns,
+                values,
+            })) => (columns, values),
+            _ => panic!("Expected a count mismatch"),
+        };
+        assert_eq!(mismatch("insert into t (a, b) values (1);"), (2, 1));
+        assert_eq!(mismatch("insert into t (a) values (1, 2);"), (1, 2));
+        assert_eq!(mismatch("insert into t values (1);"), (2, 1));
+        assert_eq!(mismatch("insert into t values (1, 2, 3);"), (2, 3));
+
+        execute(
+            "insert into t values (1, 2); insert into t (b, a) values (3, 4);",
+            &mut storage,
+        )
+        .unwrap();
+        let rows = select_rows("select * from t;", &mut storage).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::Integer(1), DbValue::Integer(2)]),
+                Row::new(vec![DbValue::Integer(4), DbValue::Integer(3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_columns_in_any_order() {
+        let mut storage = StorageLayer::in_memory();
+        execute(
+            "create table t (a integer, b string, c float); \
+            insert into t (c, a, b) values (1.5, 1, \"x\");",
+            &mut storage,
+        )
+        .unwrap();
+        let rows = select_rows("select * from t;", &mut storage).unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![
+                DbValue::Integer(1),
+                DbValue::String(String::from("x")),
+                DbValue::Float(DbFloat::new(1.5)),
+            ])]
+        );
+
+        let err = |command: &str, storage: &mut StorageLayer| match execute(command, storage) {
+            Err(QueryError::ExecutionError(err)) => err,
+            _ => panic!("Expected an execution error"),
+        };
+        assert!(matches!(
+            err("insert into t (c, a) values (1.5, 1);", &mut storage),
+            ExecutionError::MissingColumnValue(name) if name == "b"
+        ));
+        assert!(matches!(
+            err("insert into t (c, a, d) values (1.5, 1, 2);", &mut storage),
+            ExecutionError::UnknownColumnName(name) if name == "d"
+        ));
+        assert!(matches!(
+            err("insert into t (a, a, c) values (1, 2, 1.5);", &mut storage),
+            ExecutionError::ColumnListedTwice(name) if name == "a"
+        ));
+    }
+
+    #[test]
+    fn rowid_lower_bounds() {
+        let file = Path::new("rowid_lower_bounds.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute("create table t (a integer);", &mut storage).unwrap();
+        for a in 0..6 {
+            execute(&format!("insert into t (a) values ({a});"), &mut storage).unwrap();
+        }
+        execute("delete from t where a = 2;", &mut storage).unwrap();
+
+        let values = |command: &str, storage: &mut StorageLayer| -> Vec<DbValue> {
+            select_rows(command, storage)
+                .unwrap()
+                .into_iter()
+                .map(|mut r| r.data.remove(0))
+                .collect()
+        };
+        let ints =
+            |ints: &[i64]| -> Vec<DbValue> { ints.iter().map(|i| DbValue::Integer(*i)).collect() };
+        assert_eq!(
+            values("select a from t where rowid > 1;", &mut storage),
+            ints(&[3, 4, 5])
+        );
+        assert_eq!(
+            values("select a from t where t.rowid >= 1 limit 2;", &mut storage),
+            ints(&[1, 3])
+        );
+        assert_eq!(
+            values(
+                "select a from t where 3 < rowid order by rowid;",
+                &mut storage
+            ),
+            ints(&[4, 5])
+        );
+        assert_eq!(
+            values("select a from t where rowid >= 0;", &mut storage),
+            ints(&[0, 1, 3, 4, 5])
+        );
+        assert!(values("select a from t where rowid > 9;", &mut storage).is_empty());
+        assert_eq!(
+            values("select a from t order by rowid desc limit 1;", &mut storage),
+            ints(&[5])
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn hash_joins() {
+        let file = Path::new("hash_joins.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table users (id integer, name string collate nocase); \
+            create table orders (uid integer, id integer, who string); \
+            create table tags (name string, tag string); \
+            insert into users (id, name) values (1, \"ann\"); \
+            insert into users (id, name) values (2, \"bob\"); \
+            insert into users (id, name) values (2, \"cat\"); \
+            insert into orders (uid, id, who) values (2, 10, \"BOB\"); \
+            insert into orders (uid, id, who) values (3, 11, \"dan\"); \
+            insert into orders (uid, id, who) values (1, 12, \"ann\"); \
+            insert into orders (uid, id, who) values (2, 13, \"Cat\"); \
+            insert into orders (uid, id, who) values (1, 14, \"ann\"); \
+            insert into tags (name, tag) values (\"ann\", \"new\");",
+            &mut storage,
+        )
+        .unwrap();
+
+        // the same rows in the same order as checking every pair, whichever side is smaller
+        for (join, nested) in [
+            (
+                "select * from users join orders on users.id = uid;",
+                "select * from users, orders where users.id = uid;",
+            ),
+            (
+                "select * from orders join users on users.id = uid;",
+                "select * from orders, users where users.id = uid;",
+            ),
+            (
+                "select name, orders.id from users join orders on name = who;",
+                "select name, orders.id from users, orders where name = who;",
+            ),
+            (
+                "select * from users join orders on uid = users.id join tags on tags.name = who;",
+                "select * from users join orders on uid = users.id, tags where tags.name = who;",
+            ),
+        ] {
+            let joined = select_rows(join, &mut storage).unwrap();
+            assert!(!joined.is_empty());
+            assert_eq!(joined, select_rows(nested, &mut storage).unwrap());
+        }
+        assert_eq!(
+            select_rows(
+                "select orders.id from users join orders on name = who where users.id = 2;",
+                &mut storage
+            )
+            .unwrap(),
+            vec![
+                Row::new(vec![DbValue::Integer(10)]),
+                Row::new(vec![DbValue::Integer(13)]),
+            ]
+        );
+
+        let explain = |command: &str, storage: &mut StorageLayer| match execute(command, storage) {
+            Ok(QueryResult::Explain(plan)) => plan,
+            _ => panic!("Expected a plan"),
+        };
+        assert_eq!(
+            explain(
+                "explain select * from users join orders on users.id = uid;",
+                &mut storage
+            ),
+            "PROJECT *\n  HASH JOIN ON users.id = uid\n    SCAN users\n    SCAN orders"
+        );
+        // anything but an equality between the two sides falls back to a nested loop
+        assert_eq!(
+            explain(
+                "explain select * from users join orders on users.id < uid;",
+                &mut storage
+            ),
+            "PROJECT *\n  FILTER users.id < uid\n    CROSS JOIN\n      SCAN users\n      SCAN orders"
+        );
+        assert_eq!(
+            select_rows(
+                "select users.id, uid from users join orders on users.id < uid;",
+                &mut storage
+            )
+            .unwrap()
+            .len(),
+            5
+        );
+        assert!(matches!(
+            select_rows("select * from users join orders on id = uid;", &mut storage),
+            Err(ExecutionError::AmbiguousColumn { .. })
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn select_wildcards() {
+        let file = Path::new("select_wildcards.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (b integer, a string); \
+            insert into t (b, a) values (2, \"x\");",
+            &mut storage,
+        )
+        .unwrap();
+        let column_names = |command, storage: &mut StorageLayer| match execute(command, storage) {
+            Ok(QueryResult::Rows(rows)) => rows
+                .schema()
+                .columns()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>(),
+            Ok(_) => panic!("Expected rows"),
+            Err(err) => panic!("Unexpected error: {err:?}"),
+        };
+
+        assert_eq!(
+            column_names("select *, b * 2 as double from t;", &mut storage),
+            vec!["b", "a", "double"]
+        );
+        assert_eq!(
+            select_rows("select *, b * 2 as double from t;", &mut storage).unwrap(),
+            vec![Row::new(vec![
+                DbValue::Integer(2),
+                DbValue::String(String::from("x")),
+                DbValue::Integer(4),
+            ])]
+        );
+        // the rowid isn't part of `*`, even when it's selected as well
+        assert_eq!(
+            column_names("select rowid, t.* from t where rowid = 0;", &mut storage),
+            vec!["rowid", "b", "a"]
+        );
+        assert!(matches!(
+            select_rows("select u.* from t;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "u.*"
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn select_arithmetic_expressions() {
+        let file = Path::new("select_arithmetic_expressions.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (price float, quantity integer, id unsigned int); \
+            insert into t (price, quantity, id) values (2.5, 4, 1); \
+            insert into t (price, quantity, id) values (1.5, -2, 2);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select price * quantity, quantity + id as total, (quantity - 1) / 2 from t;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    DbValue::Float(DbFloat::new(10.0)),
+                    DbValue::Integer(5),
+                    DbValue::Integer(1),
+                ]),
+                Row::new(vec![
+                    DbValue::Float(DbFloat::new(-3.0)),
+                    DbValue::Integer(0),
+                    DbValue::Integer(-1),
+                ]),
+            ]
+        );
+
+        match execute(
+            "select price * quantity, quantity + id as total from t;",
+            &mut storage,
+        ) {
+            Ok(QueryResult::Rows(rows)) => {
+                let names: Vec<String> = rows.schema().columns().map(|c| c.name.clone()).collect();
+                assert_eq!(names, vec!["price * quantity", "total"]);
+            }
+            _ => panic!("Expected rows"),
+        }
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn select_arithmetic_errors() {
+        let file = Path::new("select_arithmetic_errors.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer, f float, s string, u unsigned int); \
+            insert into t (a, f, s, u) values (0, 0.0, \"x\", 18446744073709551615);",
+            &mut storage,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            select_rows("select 1 / a from t;", &mut storage),
+            Err(ExecutionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            select_rows("select 1.5 / f from t;", &mut storage),
+            Err(ExecutionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            select_rows("select 9223372036854775807 + 1 from t;", &mut storage),
+            Err(ExecutionError::ArithmeticOverflow)
+        ));
+        assert!(matches!(
+            select_rows("select u - 1 from t;", &mut storage),
+            Err(ExecutionError::ArithmeticOverflow)
+        ));
+        assert!(matches!(
+            select_rows("select s + 1 from t;", &mut storage),
+            Err(ExecutionError::NonNumericOperand)
+        ));
+        assert!(matches!(
+            select_rows("select b + 1 from t;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "b"
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn select_qualified_columns() {
+        let file = Path::new("select_qualified_columns.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer, b integer); \
+            insert into t (a, b) values (1, 20); \
+            insert into t (a, b) values (2, 10);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select t.a, b from t where t.b > 5 order by t.b;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::Integer(2), DbValue::Integer(10)]),
+                Row::new(vec![DbValue::Integer(1), DbValue::Integer(20)]),
+            ]
+        );
+        assert!(matches!(
+            select_rows("select u.a from t;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "u.a"
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn resolve_column_across_tables() {
+        let schema = Schema::new(vec![
+            Column::new(String::from("t.id"), DbType::Integer),
+            Column::new(String::from("t.name"), DbType::String),
+            Column::new(String::from("u.id"), DbType::Integer),
+        ]);
+        let bare = |name: &str| ColumnRef::new(String::from(name));
+        let qualified =
+            |table: &str, name: &str| ColumnRef::qualified(String::from(table), String::from(name));
+
+        assert_eq!(
+            resolve_column(&bare("name"), &schema, None).unwrap().index,
+            1
+        );
+        assert_eq!(
+            resolve_column(&qualified("u", "id"), &schema, None)
+                .unwrap()
+                .index,
+            2
+        );
+        assert!(matches!(
+            resolve_column(&bare("id"), &schema, None),
+            Err(ExecutionError::AmbiguousColumn { name, tables })
+                if name == "id" && tables == ["t", "u"]
+        ));
+        assert!(matches!(
+            resolve_column(&qualified("u", "name"), &schema, None),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "u.name"
+        ));
+    }
+
+    #[test]
+    fn select_distinct() {
+        let file = Path::new("select_distinct.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer, b float); \
+            insert into t (a, b) values (3, 1.0); \
+            insert into t (a, b) values (1, 2.0); \
+            insert into t (a, b) values (3, 1.0); \
+            insert into t (a, b) values (2, 1.0); \
+            insert into t (a, b) values (1, 2.0);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows("select distinct a, b from t;", &mut storage).unwrap();
+        let expected: Vec<Row> = [(3, 1.0), (1, 2.0), (2, 1.0)]
+            .into_iter()
+            .map(|(a, b)| Row::new(vec![DbValue::Integer(a), DbValue::Float(DbFloat::new(b))]))
+            .collect();
+        assert_eq!(rows, expected);
+
+        let rows =
+            select_rows("select distinct b from t order by a limit 2;", &mut storage).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::Float(DbFloat::new(2.0))]),
+                Row::new(vec![DbValue::Float(DbFloat::new(1.0))]),
+            ]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn keywords_ignore_case() {
+        let file = Path::new("keywords_ignore_case.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "CREATE Table IF not EXISTS t (Name STRING, n Integer, u UNSIGNED  Int); \
+            Insert INTO t (Name, n, u) VALUES (\"Mixed Case\", 1, 2); \
+            insert into t (Name, n, u) values (\"lower\", 2, 3);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "SeLeCt Name, ROWID FROM t WHERE n = 1 ORDER BY rowId DESC;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![
+                DbValue::String(String::from("Mixed Case")),
+                DbValue::UnsignedInt(0),
+            ])]
+        );
+        // identifiers keep their case
+        assert!(matches!(
+            select_rows("select name from t;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "name"
+        ));
+        assert!(execute("create table u (RowId integer);", &mut storage).is_err());
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        let file = Path::new("quoted_identifiers.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (`order` integer, `first name` string); \
+            insert into t (`order`, `first name`) values (2, \"b\"); \
+            insert into t (`order`, `first name`) values (1, \"a\");",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select `first name` from t where `order` < 3 order by `order`;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::String(String::from("a"))]),
+                Row::new(vec![DbValue::String(String::from("b"))]),
+            ]
+        );
+        let schema = storage.table_scan("t", false).unwrap().schema;
+        let names: Vec<&str> = schema.columns().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["order", "first name"]);
+        assert!(execute("create table u (`rowid` integer);", &mut storage).is_err());
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn double_quoted_identifiers() {
+        let file = Path::new("double_quoted_identifiers.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table \"my table\" (\"order\" integer, \"first name\" string); \
+            insert into \"my table\" (\"order\", \"first name\") values (2, \"b\"); \
+            insert into \"my table\" (\"order\", \"first name\") values (1, \"a\");",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select \"first name\", \"order\" from \"my table\" where \"order\" < 3 order by \"order\";",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    DbValue::String(String::from("a")),
+                    DbValue::Integer(1)
+                ]),
+                Row::new(vec![
+                    DbValue::String(String::from("b")),
+                    DbValue::Integer(2)
+                ]),
+            ]
+        );
+        let schema = storage.table_scan("my table", false).unwrap().schema;
+        let names: Vec<&str> = schema.columns().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["order", "first name"]);
+
+        // a quoted name that isn't a column is a string
+        let rows = select_rows(
+            "select \"order\", \"missing\" from \"my table\" where \"first name\" = \"b\";",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![
+                DbValue::Integer(2),
+                DbValue::String(String::from("missing"))
+            ])]
+        );
+        assert!(execute("create table u (\"rowid\" integer);", &mut storage).is_err());
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn count_fast_path_agrees_with_scan() {
+        let file = Path::new("count_fast_path_agrees_with_scan.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer); \
+            insert into t (a) values (1); \
+            insert into t (a) values (2); \
+            insert into t (a) values (3);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let plan = |command: &str| {
+            let mut statements = Parser::build(Tokenizer::new(command))
+                .unwrap()
+                .parse()
+                .unwrap();
+            match statements.pop() {
+                Some(Statement::Select(s)) => s,
+                _ => panic!("Expected a select statement"),
+            }
+        };
+        let fast = plan("select count(*) from t;");
+        assert!(matches!(
+            ExecutablePlan::compose_select(&fast, &storage),
+            Ok(RowsSource::Count(CountRowsIter { source: None, .. }))
+        ));
+        let scan = plan("select count(*) from t where 1 = 1;");
+        assert!(matches!(
+            ExecutablePlan::compose_select(&scan, &storage),
+            Ok(RowsSource::Count(CountRowsIter {
+                source: Some(_),
+                ..
+            }))
+        ));
+
+        let count = |n: u64| vec![Row::new(vec![DbValue::UnsignedInt(n)])];
+        let fast = select_rows("select count(*) from t;", &mut storage).unwrap();
+        let scanned = select_rows("select count(*) from t where 1 = 1;", &mut storage).unwrap();
+        assert_eq!(fast, count(3));
+        assert_eq!(scanned, fast);
+        let filtered = select_rows("select count(*) from t where a > 1;", &mut storage).unwrap();
+        assert_eq!(filtered, count(2));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn count_distinct() {
+        let file = Path::new("count_distinct.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute("create table t (a integer, b string);", &mut storage).unwrap();
+        let values = [(1, "x"), (2, "y"), (1, "y"), (3, "x"), (2, "x"), (1, "x")];
+        for (a, b) in values {
+            execute(
+                &format!("insert into t (a, b) values ({a}, \"{b}\");"),
+                &mut storage,
+            )
+            .unwrap();
+        }
+
+        let count = |n: usize| vec![Row::new(vec![DbValue::UnsignedInt(n as u64)])];
+        let distinct_a: HashSet<i64> = values.iter().map(|(a, _)| *a).collect();
+        let rows = select_rows("select count(distinct a) from t;", &mut storage).unwrap();
+        assert_eq!(rows, count(distinct_a.len()));
+
+        let distinct_filtered: HashSet<&str> = values
+            .iter()
+            .filter(|(a, _)| *a > 1)
+            .map(|(_, b)| *b)
+            .collect();
+        let rows =
+            select_rows("select count(distinct b) from t where a > 1;", &mut storage).unwrap();
+        assert_eq!(rows, count(distinct_filtered.len()));
+
+        let distinct_halved: HashSet<i64> = values.iter().map(|(a, _)| a / 2).collect();
+        let rows = select_rows("select count(distinct a / 2) from t;", &mut storage).unwrap();
+        assert_eq!(rows, count(distinct_halved.len()));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn explain_describes_plan() {
+        let file = Path::new("explain_describes_plan.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute("create table t (a integer, b string);", &mut storage).unwrap();
+
+        let explain = |command: &str, storage: &mut StorageLayer| match execute(command, storage) {
+            Ok(QueryResult::Explain(plan)) => plan,
+            _ => panic!("Expected a plan"),
+        };
+        assert_eq!(
+            explain(
+                "explain select distinct a * 2 as c, b from t where a > 1 order by b desc limit 5;",
+                &mut storage
+            ),
+            "LIMIT 5\n  \
+                DISTINCT\n    \
+                  PROJECT a * 2 AS c, b\n      \
+                    SORT BY b DESC\n        \
+                      FILTER a > 1\n          \
+                        SCAN t"
+        );
+        assert_eq!(
+            explain("explain select count(*) from t;", &mut storage),
+            "COUNT ROWS OF t (using row count, no scan)"
+        );
+        assert_eq!(
+            explain(
+                "explain select count(*) from (select a from t) where a = 1;",
+                &mut storage
+            ),
+            "COUNT\n  FILTER a = 1\n    PROJECT a\n      SCAN t"
+        );
+        // rows are stored in rowid order, so a lower bound on it is a seek, not a filter
+        assert_eq!(
+            explain(
+                "explain select a from t where rowid > 4 order by rowid limit 2;",
+                &mut storage
+            ),
+            "LIMIT 2\n  PROJECT a\n    SCAN t AFTER ROWID 4"
+        );
+        assert_eq!(
+            explain(
+                "explain select a from t where rowid < 4 order by rowid desc;",
+                &mut storage
+            ),
+            "PROJECT a\n  SORT BY rowid DESC\n    FILTER rowid < 4\n      SCAN t"
+        );
+        assert_eq!(
+            explain("explain delete from t where b = \"x\";", &mut storage),
+            "DELETE FROM t\n  FILTER b = \"x\"\n    SCAN t"
+        );
+
+        // the plan is built the same as when executing, so it's checked the same way
+        assert!(matches!(
+            execute("explain select zz from t;", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::UnknownColumnName(name))) if name == "zz"
+        ));
+        assert!(matches!(
+            execute("explain delete from t where zz = 1;", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::UnknownColumnName(name))) if name == "zz"
+        ));
+        assert!(execute("explain select a from missing;", &mut storage).is_err());
+
+        // nothing is executed
+        assert_eq!(
+            select_rows("select count(*) from t;", &mut storage).unwrap(),
+            vec![Row::new(vec![DbValue::UnsignedInt(0)])]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn unknown_columns_are_named() {
+        let file = Path::new("unknown_columns_are_named.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute("create table t (a integer, b string);", &mut storage).unwrap();
+
+        assert!(matches!(
+            execute("insert into t (a, c) values (1, \"x\");", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::UnknownColumnName(name))) if name == "c"
+        ));
+        assert!(matches!(
+            select_rows("select a from t where zz > 1;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "zz"
+        ));
+        assert!(matches!(
+            select_rows("select a from t where a = zz;", &mut storage),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "zz"
+        ));
+
+        let schema = storage.table_schema("t").unwrap();
+        let row = Row::new(vec![DbValue::Integer(1), DbValue::Integer(2)]);
+        match schema.check_row(&row) {
+            Err(StorageError::SchemaMismatch {
+                expected,
+                got,
+                column,
+            }) => {
+                assert_eq!(expected, DbType::String);
+                assert_eq!(got, DbType::Integer);
+                assert_eq!(column, "b");
+            }
+            _ => panic!("Expected a schema mismatch"),
+        }
+        assert!(matches!(
+            schema.check_row(&Row::new(vec![DbValue::Integer(1)])),
+            Err(StorageError::ColumnCountMismatch {
+                expected: 2,
+                got: 1
+            })
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn exists_subqueries() {
+        let file = Path::new("exists_subqueries.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table users (id integer primary key, name string); \
+            insert into users (id, name) values (1, \"ann\"); \
+            insert into users (id, name) values (2, \"bob\"); \
+            insert into users (id, name) values (3, \"cat\"); \
+            create table orders (uid integer, total integer); \
+            insert into orders (uid, total) values (1, 10); \
+            insert into orders (uid, total) values (3, 5); \
+            insert into orders (uid, total) values (3, 50);",
+            &mut storage,
+        )
+        .unwrap();
+        let names = |rows: Vec<Row>| -> Vec<DbValue> {
+            rows.into_iter().map(|r| r.data[0].clone()).collect()
+        };
+        let name = |n: &str| DbValue::String(n.to_string());
+
+        let rows = select_rows(
+            "select name from users where exists \
+            (select 1 from orders where orders.uid = users.id);",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(names(rows), vec![name("ann"), name("cat")]);
+        // unqualified names are looked up in the subquery's table first
+        let rows = select_rows(
+            "select name from users where not exists \
+            (select 1 from orders where uid = id);",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(names(rows), vec![name("bob")]);
+
+        // the subquery is checked even when there are no rows to run it against
+        assert!(matches!(
+            select_rows(
+                "select name from (select name from users limit 0) where exists \
+                (select 1 from orders where orders.missing = 1);",
+                &mut storage,
+            ),
+            Err(ExecutionError::UnknownColumnName(name)) if name == "orders.missing"
+        ));
+
+        let deleted = match execute(
+            "delete from users where not exists (select 1 from orders where uid = users.id);",
+            &mut storage,
+        ) {
+            Ok(QueryResult::Ok(deleted)) => deleted,
+            _ => panic!("Expected a count of deleted rows"),
+        };
+        assert_eq!(deleted, 1);
+        let rows = select_rows("select name from users;", &mut storage).unwrap();
+        assert_eq!(names(rows), vec![name("ann"), name("cat")]);
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn failed_delete_leaves_rows() {
+        let file = Path::new("failed_delete_leaves_rows.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (i integer); \
+            insert into t (i) values (1); \
+            insert into t (i) values (-9223372036854775808); \
+            insert into t (i) values (2);",
+            &mut storage,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            execute("delete from t where abs(i) > 0;", &mut storage),
+            Err(QueryError::ExecutionError(
+                ExecutionError::ArithmeticOverflow
+            ))
+        ));
+        assert_eq!(
+            select_rows("select i from t;", &mut storage).unwrap(),
+            vec![
+                Row::new(vec![DbValue::Integer(1)]),
+                Row::new(vec![DbValue::Integer(i64::MIN)]),
+                Row::new(vec![DbValue::Integer(2)]),
+            ]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn small_ints() {
+        let file = Path::new("small_ints.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (s smallint primary key, i integer); \
+            insert into t (s, i) values (1, 10); \
+            insert into t (s, i) values (-2147483648, 2);",
+            &mut storage,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            execute("insert into t (s, i) values (2147483648, 0);", &mut storage),
+            Err(QueryError::ExecutionError(
+                ExecutionError::UncoercableValueProvided
+            ))
+        ));
+        assert!(execute("insert into t (s, i) values (1, 0);", &mut storage).is_err());
+
+        // small ints are widened to compare with and do arithmetic alongside other integers
+        storage.flush().unwrap();
+        storage.reload().unwrap();
+        assert_eq!(
+            select_rows("select s, s + i from t where s < i;", &mut storage).unwrap(),
+            vec![
+                Row::new(vec![DbValue::SmallInt(1), DbValue::Integer(11)]),
+                Row::new(vec![
+                    DbValue::SmallInt(i32::MIN),
+                    DbValue::Integer(-2147483646)
+                ]),
+            ]
+        );
+        assert_eq!(
+            select_rows("select cast(i as int) from t where s = 1;", &mut storage).unwrap(),
+            vec![Row::new(vec![DbValue::SmallInt(10)])]
+        );
+        assert!(matches!(
+            select_rows("select abs(s) from t;", &mut storage),
+            Err(ExecutionError::ArithmeticOverflow)
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn char_columns() {
+        let file = Path::new("char_columns.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (code char(4) primary key, name string); \
+            insert into t (code, name) values (\"ab\", \"short\"); \
+            insert into t (code, name) values (\"abcd\", \"full\");",
+            &mut storage,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            execute("insert into t (code, name) values (\"abcde\", \"long\");", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::StorageError(
+                StorageError::ValueTooLong { column, max: 4 }
+            ))) if column == "code"
+        ));
+        // padding makes this the same key as "ab"
+        assert!(execute(
+            "insert into t (code, name) values (\"ab  \", \"dup\");",
+            &mut storage
+        )
+        .is_err());
+
+        // values are padded, and compared with strings as padded strings
+        storage.flush().unwrap();
+        storage.reload().unwrap();
+        assert_eq!(
+            select_rows(
+                "select code, length(code) from t where code = \"ab\";",
+                &mut storage
+            )
+            .unwrap(),
+            vec![Row::new(vec![
+                DbValue::Char(String::from("ab  ")),
+                DbValue::Integer(4)
+            ])]
+        );
+        assert_eq!(
+            select_rows("select name from t where code < \"abcde\";", &mut storage).unwrap(),
+            vec![
+                Row::new(vec![DbValue::String(String::from("short"))]),
+                Row::new(vec![DbValue::String(String::from("full"))]),
+            ]
+        );
+        assert_eq!(
+            select_rows("select name from t where code = name;", &mut storage).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            select_rows(
+                "select cast(name as char(5)) from t where code = \"abcd\";",
+                &mut storage
+            )
+            .unwrap(),
+            vec![Row::new(vec![DbValue::Char(String::from("full "))])]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn varchar_columns() {
+        let file = Path::new("varchar_columns.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (name varchar(3)); \
+            insert into t (name) values (\"ab\"); \
+            insert into t (name) values (\"abc\");",
+            &mut storage,
+        )
+        .unwrap();
+
+        // the limit is kept with the schema, so it still applies after reopening
+        storage.flush().unwrap();
+        storage.reload().unwrap();
+        assert!(matches!(
+            execute("insert into t (name) values (\"abcd\");", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::StorageError(
+                StorageError::ValueTooLong { column, max: 3 }
+            ))) if column == "name"
+        ));
+        assert_eq!(
+            select_rows("select name from t where name > \"a\";", &mut storage).unwrap(),
+            vec![
+                Row::new(vec![DbValue::String(String::from("ab"))]),
+                Row::new(vec![DbValue::String(String::from("abc"))]),
+            ]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn check_constraints() {
+        let file = Path::new("check_constraints.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (age integer check (age >= 0), name string, \
+                constraint short_name check (length(name) < 4)); \
+            insert into t (age, name) values (0, \"ann\");",
+            &mut storage,
+        )
+        .unwrap();
+        let failed_check = |command, storage: &mut StorageLayer| match execute(command, storage) {
+            Err(QueryError::ExecutionError(ExecutionError::StorageError(
+                StorageError::CheckConstraintFailed(name),
+            ))) => name,
+            Err(err) => panic!("Unexpected error: {err:?}"),
+            Ok(_) => panic!("Expected the check to fail"),
+        };
+        assert_eq!(
+            failed_check(
+                "insert into t (age, name) values (-1, \"bob\");",
+                &mut storage
+            ),
+            "age >= 0"
+        );
+
+        // the checks are stored with the table, so they still apply after reopening
+        storage.flush().unwrap();
+        storage.reload().unwrap();
+        assert_eq!(
+            failed_check(
+                "insert into t (age, name) values (1, \"brenda\");",
+                &mut storage
+            ),
+            "short_name"
+        );
+        assert_eq!(
+            select_rows("select age, name from t;", &mut storage).unwrap(),
+            vec![Row::new(vec![
+                DbValue::Integer(0),
+                DbValue::String(String::from("ann"))
+            ])]
+        );
+
+        assert!(matches!(
+            execute("create table u (a integer check (b > 0));", &mut storage),
+            Err(QueryError::ExecutionError(ExecutionError::UnknownColumnName(name))) if name == "b"
+        ));
+        assert!(!storage.table_exists("u"));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn foreign_keys() {
+        let file = Path::new("foreign_keys.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table users (id integer primary key); \
+            create table orders (uid integer, item string, foreign key (uid) references users(id)); \
+            insert into users (id) values (1); \
+            insert into users (id) values (2); \
+            insert into orders (uid, item) values (1, \"pen\");",
+            &mut storage,
+        )
+        .unwrap();
+        let storage_error = |command, storage: &mut StorageLayer| match execute(command, storage) {
+            Err(QueryError::ExecutionError(ExecutionError::StorageError(err))) => err,
+            Err(err) => panic!("Unexpected error: {err:?}"),
+            Ok(_) => panic!("Expected a storage error"),
+        };
+
+        // the constraint is stored with the table, so it still applies after reopening
+        storage.flush().unwrap();
+        storage.reload().unwrap();
+        assert!(matches!(
+            storage_error("insert into orders (uid, item) values (3, \"ink\");", &mut storage),
+            StorageError::MissingParentKey { table, column, parent_table }
+                if table == "orders" && column == "uid" && parent_table == "users"
+        ));
+        assert!(matches!(
+            storage_error("delete from users where id = 1;", &mut storage),
+            StorageError::ReferencedByChild { table, child_table, child_column }
+                if table == "users" && child_table == "orders" && child_column == "uid"
+        ));
+        assert!(matches!(
+            storage_error("delete from users;", &mut storage),
+            StorageError::ReferencedByChild { .. }
+        ));
+        assert!(matches!(
+            storage_error("destroy table users;", &mut storage),
+            StorageError::ReferencedByChild { .. }
+        ));
+        assert_eq!(storage.table_row_count("users").unwrap(), 2);
+
+        execute(
+            "insert into orders (uid, item) values (1, \"ink\"); \
+            delete from users where id = 2; \
+            delete from orders where uid = 1; \
+            delete from users where id = 1;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(storage.table_row_count("users").unwrap(), 0);
+
+        assert!(matches!(
+            storage_error(
+                "create table bad (name string, foreign key (name) references users(id));",
+                &mut storage
+            ),
+            StorageError::InvalidForeignKey { .. }
+        ));
+        assert!(!storage.table_exists("bad"));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn scalar_subqueries() {
+        let file = Path::new("scalar_subqueries.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table users (id integer primary key); \
+            insert into users (id) values (1); \
+            insert into users (id) values (2); \
+            create table orders (uid integer, total integer); \
+            insert into orders (uid, total) values (2, 10); \
+            insert into orders (uid, total) values (2, 15);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select id, (select count(*) from orders where orders.uid = users.id) as n from users;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::Integer(1), DbValue::UnsignedInt(0)]),
+                Row::new(vec![DbValue::Integer(2), DbValue::UnsignedInt(2)]),
+            ]
+        );
+        let rows = select_rows(
+            "select (select count(*) from orders where uid = id) * 10 from users where id = 2;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(rows, vec![Row::new(vec![DbValue::Integer(20)])]);
+
+        assert!(matches!(
+            select_rows(
+                "select id, (select uid, total from orders) from users;",
+                &mut storage
+            ),
+            Err(ExecutionError::ScalarSubqueryColumns(2))
+        ));
+        assert!(matches!(
+            select_rows(
+                "select id, (select total from orders where uid = id) from users;",
+                &mut storage
+            ),
+            Err(ExecutionError::ScalarSubqueryNoRows)
+        ));
+        assert!(matches!(
+            select_rows(
+                "select id, (select total from orders) from users where id = 2;",
+                &mut storage
+            ),
+            Err(ExecutionError::ScalarSubqueryRows)
+        ));
+
+        // a column can be named after the subquery's text without being mistaken for it
+        execute(
+            "create table named (id integer, `(SELECT COUNT(*) FROM orders)` integer); \
+            insert into named (id, `(SELECT COUNT(*) FROM orders)`) values (1, 7);",
+            &mut storage,
+        )
+        .unwrap();
+        let rows = select_rows(
+            "select `(SELECT COUNT(*) FROM orders)`, (select count(*) from orders) from named;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![DbValue::Integer(7), DbValue::UnsignedInt(2)])]
+        );
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn nocase_collation() {
+        let file = Path::new("nocase_collation.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table people (name string collate nocase primary key, nick string); \
+            insert into people (name, nick) values (\"Cat\", \"cat\"); \
+            insert into people (name, nick) values (\"ann\", \"Ann\"); \
+            insert into people (name, nick) values (\"Bob\", \"bob\");",
+            &mut storage,
+        )
+        .unwrap();
+        let names = |rows: Vec<Row>| -> Vec<DbValue> {
+            rows.into_iter().map(|r| r.data[0].clone()).collect()
+        };
+        let name = |n: &str| DbValue::String(n.to_string());
+
+        let rows = select_rows(
+            "select name from people where name = \"CAT\";",
+            &mut storage,
+        );
+        assert_eq!(names(rows.unwrap()), vec![name("Cat")]);
+        let rows = select_rows(
+            "select name from people where nick = \"ann\";",
+            &mut storage,
+        );
+        assert_eq!(names(rows.unwrap()), vec![]);
+        // the right column's collation is used when the left one has none
+        let rows = select_rows("select name from people where nick = name;", &mut storage);
+        assert_eq!(names(rows.unwrap()).len(), 3);
+        let rows = select_rows("select name from people where name < \"b\";", &mut storage);
+        assert_eq!(names(rows.unwrap()), vec![name("ann")]);
+
+        let rows = select_rows("select name from people order by name;", &mut storage);
+        assert_eq!(
+            names(rows.unwrap()),
+            vec![name("ann"), name("Bob"), name("Cat")]
+        );
+        let rows = select_rows("select nick from people order by nick;", &mut storage);
+        assert_eq!(
+            names(rows.unwrap()),
+            vec![name("Ann"), name("bob"), name("cat")]
+        );
+        // selecting the column keeps its collation for the outer query
+        let rows = select_rows(
+            "select name from (select name from people) where name = \"BOB\";",
+            &mut storage,
+        );
+        assert_eq!(names(rows.unwrap()), vec![name("Bob")]);
+
+        // the primary key is unique regardless of case
+        assert!(execute(
+            "insert into people (name, nick) values (\"ANN\", \"x\");",
+            &mut storage
+        )
+        .is_err());
+        execute("delete from people where name = \"ANN\";", &mut storage).unwrap();
+        execute(
+            "insert into people (name, nick) values (\"ANN\", \"x\");",
+            &mut storage,
+        )
+        .unwrap();
+        let rows = select_rows("select name from people where nick = \"x\";", &mut storage);
+        assert_eq!(names(rows.unwrap()), vec![name("ANN")]);
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn distinct_uses_collation() {
+        let mut storage = StorageLayer::in_memory();
+        execute(
+            "create table t (name string collate nocase, nick string); \
+            insert into t values ('Foo', 'Foo'); \
+            insert into t values ('bar', 'bar'); \
+            insert into t values ('FOO', 'FOO');",
+            &mut storage,
+        )
+        .unwrap();
+        let names = |command: &str, storage: &mut StorageLayer| -> Vec<DbValue> {
+            let rows = select_rows(command, storage).unwrap();
+            rows.into_iter().map(|r| r.data[0].clone()).collect()
+        };
+        let name = |n: &str| DbValue::String(n.to_string());
+
+        // the first of the rows that compare equal is the one kept
+        assert_eq!(
+            names("select distinct name from t;", &mut storage),
+            vec![name("Foo"), name("bar")]
+        );
+        assert_eq!(
+            names("select count(distinct name) from t;", &mut storage),
+            vec![DbValue::UnsignedInt(2)]
+        );
+        assert_eq!(
+            names("select name from t union select name from t;", &mut storage),
+            vec![name("Foo"), name("bar")]
+        );
+        assert_eq!(
+            names("select distinct nick from t;", &mut storage),
+            vec![name("Foo"), name("bar"), name("FOO")]
+        );
+    }
+
+    #[test]
+    fn delete_rows() {
+        let file = Path::new("delete_rows.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer primary key, b string); \
+            insert into t (a, b) values (1, \"x\"); \
+            insert into t (a, b) values (2, \"y\"); \
+            insert into t (a, b) values (3, \"x\"); \
+            insert into t (a, b) values (4, \"z\");",
+            &mut storage,
+        )
+        .unwrap();
+        let delete = |command: &str, storage: &mut StorageLayer| match execute(command, storage) {
+            Ok(QueryResult::Ok(deleted)) => deleted,
+            _ => panic!("Expected a count of deleted rows"),
+        };
+
+        assert_eq!(delete("delete from t where b = \"x\";", &mut storage), 2);
+        // deleted keys can be used again
+        execute("insert into t (a, b) values (1, \"w\");", &mut storage).unwrap();
+
+        // the row ids are 0-based, in insertion order
+        assert_eq!(delete("delete from t where rowid = 1;", &mut storage), 1);
+        let rows = select_rows("select a from t order by a;", &mut storage).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![DbValue::Integer(1)]),
+                Row::new(vec![DbValue::Integer(4)]),
+            ]
+        );
+
+        assert_eq!(delete("delete from t;", &mut storage), 2);
+        assert_eq!(
+            select_rows("select a from t;", &mut storage).unwrap(),
+            vec![]
+        );
+        execute("insert into t (a, b) values (4, \"z\");", &mut storage).unwrap();
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn scalar_functions() {
+        let file = Path::new("scalar_functions.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (s string, i integer, f float); \
+            insert into t (s, i, f) values (\"Hello\", -3, 2.375); \
+            insert into t (s, i, f) values (\"wOrLd!\", 4, -1.5);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select upper(s), lower(s), length(s), abs(i), abs(f), round(f, 2), round(f) from t;",
+            &mut storage,
+        )
+        .unwrap();
+        let float = |f| DbValue::Float(DbFloat::new(f));
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    DbValue::String(String::from("HELLO")),
+                    DbValue::String(String::from("hello")),
+                    DbValue::Integer(5),
+                    DbValue::Integer(3),
+                    float(2.375),
+                    float(2.38),
+                    float(2.0),
+                ]),
+                Row::new(vec![
+                    DbValue::String(String::from("WORLD!")),
+                    DbValue::String(String::from("world!")),
+                    DbValue::Integer(6),
+                    DbValue::Integer(4),
+                    float(1.5),
+                    float(-1.5),
+                    float(-2.0),
+                ]),
+            ]
+        );
+
+        let rows =
+            select_rows("select s from t where upper(s) = \"HELLO\";", &mut storage).unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![DbValue::String(String::from("Hello"))])]
+        );
+        let rows = select_rows("select i from t where abs(i) < f;", &mut storage).unwrap();
+        assert_eq!(rows, vec![]);
+        let rows = select_rows("select i from t where round(f, 300) > 2;", &mut storage).unwrap();
+        assert_eq!(rows, vec![Row::new(vec![DbValue::Integer(-3)])]);
+
+        assert!(matches!(
+            select_rows("select upper(i) from t;", &mut storage),
+            Err(ExecutionError::InvalidFunctionArgument(
+                ScalarFunction::Upper
+            ))
+        ));
+        assert!(matches!(
+            select_rows("select s from t where length(i) = 1;", &mut storage),
+            Err(ExecutionError::InvalidFunctionArgument(
+                ScalarFunction::Length
+            ))
+        ));
+        assert!(matches!(
+            select_rows("select s from t where upper(s) = 1;", &mut storage),
+            Err(ExecutionError::MismatchedTypeComparision)
+        ));
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn cast_expressions() {
+        let file = Path::new("cast_expressions.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (s string, i integer, f float); \
+            insert into t (s, i, f) values (\"a\", -3, 2.5);",
+            &mut storage,
+        )
+        .unwrap();
+
+        let rows = select_rows(
+            "select cast(f as integer), cast(i as float), cast(i as string), cast(f as string), \
+            cast(s as string) from t;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![
+                DbValue::Integer(2),
+                DbValue::Float(DbFloat::new(-3.0)),
+                DbValue::String(String::from("-3")),
+                DbValue::String(String::from("2.5")),
+                DbValue::String(String::from("a")),
+            ])]
+        );
+        let rows = select_rows(
+            "select s from t where cast(f as integer) = 2;",
+            &mut storage,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![DbValue::String(String::from("a"))])]
+        );
+
+        let err = select_rows("select cast(s as integer) from t;", &mut storage).unwrap_err();
+        assert!(matches!(
+            err,
+            ExecutionError::InvalidCast {
+                from: DbType::String,
+                to: DbType::Integer
+            }
+        ));
+        assert_eq!(err.to_string(), "Cannot cast STRING to INTEGER");
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn vacuum_renumbers_rows() {
+        let file = Path::new("vacuum_renumbers_rows.test");
+        let mut storage = StorageLayer::init(file).unwrap();
+        execute(
+            "create table t (a integer); \
+            insert into t (a) values (1); \
+            insert into t (a) values (2); \
+            insert into t (a) values (3); \
+            insert into t (a) values (4);",
+            &mut storage,
+        )
+        .unwrap();
+        storage.flush().unwrap();
+        execute("delete from t where a < 3;", &mut storage).unwrap();
+        let size_before = fs::metadata(file).unwrap().len();
+
+        let reclaimed = match execute("vacuum t;", &mut storage) {
+            Ok(QueryResult::Reclaimed(bytes)) => bytes,
+            _ => panic!("Expected the reclaimed byte count"),
+        };
+        assert!(reclaimed > 0);
+        assert_eq!(fs::metadata(file).unwrap().len(), size_before - reclaimed);
+        execute("insert into t (a) values (5);", &mut storage).unwrap();
+        let rows = select_rows("select rowid, a from t;", &mut storage).unwrap();
+        let expected: Vec<Row> = [(0, 3), (1, 4), (2, 5)]
+            .into_iter()
+            .map(|(id, a)| Row::new(vec![DbValue::UnsignedInt(id), DbValue::Integer(a)]))
+            .collect();
+        assert_eq!(rows, expected);
+
+        assert!(execute("vacuum;", &mut storage).is_ok());
+        assert!(execute("vacuum nope;", &mut storage).is_err());
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
     }
 }
@@ -1,13 +1,20 @@
+use std::{collections::HashSet, fmt};
+
 use execute::{ExecutablePlan, ExecutionError};
-use parse::{Parser, ParsingError};
-use tokenize::Tokenizer;
+use parse::{Parser, ParsingError, Statement};
+use tokenize::{Position, TokenKind, Tokenizer};
 
-use crate::storage::{StorageError, StorageLayer};
+use crate::{
+    context::Traced,
+    storage::{StorageError, StorageLayer},
+    DbValue,
+};
 
 mod execute;
 mod parse;
-pub mod tokenize; // TODO: make not public
+pub mod tokenize;
 
+pub(crate) use execute::CheckPredicate;
 pub use execute::QueryResult;
 pub use execute::ResultRows;
 
@@ -18,6 +25,34 @@ pub enum QueryError {
     ParsingError(ParsingError),
     ExecutionError(ExecutionError),
 }
+impl QueryError {
+    /// Where in the statement's text the error happened, if known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::ParsingError(err) => err.position(),
+            Self::ExecutionError(err) => err.position(),
+            Self::StorageError(_) => None,
+        }
+    }
+}
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(err) => err.fmt(f),
+            Self::ParsingError(err) => err.fmt(f),
+            Self::ExecutionError(err) => err.fmt(f),
+        }
+    }
+}
+impl std::error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StorageError(err) => Some(err),
+            Self::ParsingError(err) => Some(err),
+            Self::ExecutionError(err) => Some(err),
+        }
+    }
+}
 impl From<ParsingError> for QueryError {
     fn from(value: ParsingError) -> Self {
         Self::ParsingError(value)
@@ -38,6 +73,108 @@ pub fn execute<'strg>(
     let tokenizer = Tokenizer::new(command);
     let plan = Parser::build(tokenizer)?.parse()?;
     let executable_plan = ExecutablePlan::new(plan);
-    let res = executable_plan.execute(storage)?;
+    let res = executable_plan.execute(storage).map_err(|t| t.error)?;
+    Ok(res)
+}
+
+/// Like `execute`, but only needs shared access to storage. Any statement
+/// that would modify it fails instead.
+pub fn execute_read<'strg>(
+    command: &str,
+    storage: &'strg StorageLayer,
+) -> Result<QueryResult<'strg>> {
+    let tokenizer = Tokenizer::new(command);
+    let plan = Parser::build(tokenizer)?.parse()?;
+    let executable_plan = ExecutablePlan::new(plan);
+    let res = executable_plan.execute_read(storage).map_err(|t| t.error)?;
     Ok(res)
 }
+
+type TracedResult<T> = std::result::Result<T, Traced<QueryError>>;
+
+/// A parsed command whose placeholders can be bound to different values each
+/// time it's executed. Binding works on a copy of the parsed statements, so each
+/// execution still clones them, which costs about as much as the statements are
+/// long but avoids tokenizing and parsing again.
+pub struct PreparedQuery<'a> {
+    command: &'a str,
+    statements: Vec<Statement>,
+    /// The name of each placeholder in the command, including the leading `:`
+    placeholders: HashSet<&'a str>,
+}
+impl<'a> PreparedQuery<'a> {
+    pub fn parse(command: &'a str) -> TracedResult<Self> {
+        let parse = || Parser::build(Tokenizer::new(command))?.parse();
+        let statements = parse().map_err(|err| {
+            Traced::new(
+                QueryError::from(err),
+                format!("preparing `{}`", command.trim()),
+            )
+        })?;
+        // the command already parsed, so every token is valid
+        let placeholders = Tokenizer::new(command)
+            .tokens()
+            .filter_map(|token| token.ok())
+            .filter(|token| token.kind() == TokenKind::Placeholder)
+            .map(|token| token.contents())
+            .collect();
+        Ok(PreparedQuery {
+            command,
+            statements,
+            placeholders,
+        })
+    }
+
+    /// Whether the command has a placeholder called `name`
+    pub fn has_placeholder(&self, name: &str) -> bool {
+        self.placeholders.contains(name)
+    }
+
+    /// Whether the command's result is the rows of a `SELECT`
+    pub fn returns_rows(&self) -> bool {
+        matches!(
+            self.statements.last(),
+            Some(Statement::Select(_) | Statement::Union(_))
+        )
+    }
+
+    /// Whether the command finishes by committing or rolling back a transaction, which
+    /// leaves no changes to flush
+    pub fn ends_transaction(&self) -> bool {
+        matches!(
+            self.statements.last(),
+            Some(Statement::Commit | Statement::Rollback)
+        )
+    }
+
+    pub fn execute<'strg>(
+        &self,
+        values: &[(&str, DbValue)],
+        storage: &'strg mut StorageLayer,
+    ) -> TracedResult<QueryResult<'strg>> {
+        self.bind(values).execute(storage).map_err(|traced| {
+            traced
+                .map(QueryError::from)
+                .with_frame(format!("executing `{}`", self.command.trim()))
+        })
+    }
+
+    /// Like `execute`, but only needs shared access to storage
+    pub fn execute_read<'strg>(
+        &self,
+        values: &[(&str, DbValue)],
+        storage: &'strg StorageLayer,
+    ) -> TracedResult<QueryResult<'strg>> {
+        self.bind(values).execute_read(storage).map_err(|traced| {
+            traced
+                .map(QueryError::from)
+                .with_frame(format!("executing `{}`", self.command.trim()))
+        })
+    }
+
+    fn bind(&self, values: &[(&str, DbValue)]) -> ExecutablePlan {
+        let mut statements = self.statements.clone();
+        statements.iter_mut().for_each(|s| s.bind(values));
+        ExecutablePlan::new(statements)
+    }
+}
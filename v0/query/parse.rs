@@ -1,24 +1,121 @@
 use std::{
-    collections::BTreeSet,
+    fmt::{self, Write},
     num::{ParseFloatError, ParseIntError},
 };
 
 use crate::{
-    storage::{self, ConflictRule, KeySet, Schema},
+    storage::{self, CheckConstraint, Collation, ConflictRule, ForeignKey, Schema},
     DbFloat, DbType, DbValue,
 };
 
-use super::tokenize::{Token, TokenKind, Tokenizer, TokenizerError, Tokens};
+use super::tokenize::{Position, Span, Token, TokenKind, Tokenizer, TokenizerError, Tokens};
 
 #[derive(Debug)]
 pub enum ParsingError {
-    UnexpectedEndOfStatement,
-    UnexpectedTokenType,
+    /// `expected` lists the tokens that would have been accepted instead, when
+    /// there are few enough of them to be helpful
+    UnexpectedEndOfStatement {
+        expected: Vec<TokenKind>,
+    },
+    UnexpectedToken {
+        expected: Vec<TokenKind>,
+    },
     ParseFloatError(ParseFloatError),
     ParseIntError(ParseIntError),
     TokenizerError(TokenizerError),
     MultiplePrimaryKeys,
     UnknownPrimaryKeyProvided,
+    NonFiniteFloat,
+    UnknownFunction(String),
+    WrongArgumentCount(ScalarFunction),
+    UnknownCollation(String),
+    /// Only string columns can be given a collation
+    NonStringCollation,
+    /// An error with the token it happened at, or `None` if it was at the end of the input
+    At {
+        err: Box<ParsingError>,
+        found: Option<String>,
+        position: Position,
+    },
+}
+impl ParsingError {
+    /// Where in the input the error happened, if known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::At { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
+
+    /// The error without the position attached to it
+    pub fn unlocated(&self) -> &ParsingError {
+        match self {
+            Self::At { err, .. } => err,
+            err => err,
+        }
+    }
+
+    fn write_expected(f: &mut fmt::Formatter<'_>, expected: &[TokenKind]) -> fmt::Result {
+        match expected {
+            [] => Ok(()),
+            [kind] => write!(f, ", expected {kind}"),
+            [first, second] => write!(f, ", expected {first} or {second}"),
+            [kinds @ .., last] => {
+                f.write_str(", expected ")?;
+                for kind in kinds {
+                    write!(f, "{kind}, ")?;
+                }
+                write!(f, "or {last}")
+            }
+        }
+    }
+}
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEndOfStatement { expected } => {
+                f.write_str("Unexpected end of statement")?;
+                ParsingError::write_expected(f, expected)
+            }
+            Self::UnexpectedToken { expected } => {
+                f.write_str("Unexpected token")?;
+                ParsingError::write_expected(f, expected)
+            }
+            Self::ParseFloatError(err) => err.fmt(f),
+            Self::ParseIntError(err) => err.fmt(f),
+            Self::TokenizerError(err) => err.fmt(f),
+            Self::MultiplePrimaryKeys => f.write_str("Multiple primary keys were specified"),
+            Self::UnknownPrimaryKeyProvided => f.write_str("Unknown primary key column provided"),
+            Self::NonFiniteFloat => f.write_str("Float values must be finite"),
+            Self::UnknownFunction(name) => write!(f, "Unknown function: '{name}'"),
+            Self::WrongArgumentCount(func) => {
+                write!(f, "Wrong number of arguments passed to {func}")
+            }
+            Self::UnknownCollation(name) => write!(f, "Unknown collation: '{name}'"),
+            Self::NonStringCollation => f.write_str("Only string columns can have a collation"),
+            Self::At {
+                err,
+                found: Some(found),
+                position,
+            } => write!(f, "{err} (at '{found}', {position})"),
+            Self::At {
+                err,
+                found: None,
+                position,
+            } => write!(f, "{err} (at the end of the input, {position})"),
+        }
+    }
+}
+impl std::error::Error for ParsingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ParseFloatError(err) => Some(err),
+            Self::ParseIntError(err) => Some(err),
+            Self::TokenizerError(err) => Some(err),
+            Self::At { err, .. } => err.source(),
+            _ => None,
+        }
+    }
 }
 impl From<ParseFloatError> for ParsingError {
     fn from(value: ParseFloatError) -> Self {
@@ -38,64 +135,142 @@ impl From<TokenizerError> for ParsingError {
 
 type Result<T> = std::result::Result<T, ParsingError>;
 
+const TYPE_KINDS: [TokenKind; 7] = [
+    TokenKind::TypeString,
+    TokenKind::TypeInteger,
+    TokenKind::TypeFloat,
+    TokenKind::TypeUnsignedInt,
+    TokenKind::TypeSmallInt,
+    TokenKind::TypeChar,
+    TokenKind::TypeVarchar,
+];
+const VALUE_KINDS: [TokenKind; 5] = [
+    TokenKind::String,
+    TokenKind::StringLiteral,
+    TokenKind::Integer,
+    TokenKind::Float,
+    TokenKind::UnsignedInt,
+];
+
 pub struct Parser<'a> {
     tokens: Tokens<'a>,
     lookahead: Option<Token<'a>>,
+    /// The last token consumed, which errors found after consuming it are reported at
+    previous: Option<Token<'a>>,
 }
 impl<'a> Parser<'a> {
     pub fn build(tokenizer: Tokenizer<'a>) -> Result<Self> {
-        let mut tokens = tokenizer.tokens();
-        let lookahead = tokens.next_token()?;
-        Ok(Parser { tokens, lookahead })
+        let mut parser = Parser {
+            tokens: tokenizer.tokens(),
+            lookahead: None,
+            previous: None,
+        };
+        match parser.tokens.next_token() {
+            Ok(lookahead) => parser.lookahead = lookahead,
+            Err(err) => return Err(parser.locate(err.into())),
+        }
+        Ok(parser)
+    }
+
+    /// Attaches the position the parser was at to `err`. Unexpected tokens are
+    /// reported at the lookahead, and anything else at the token that was just consumed.
+    fn locate(&self, err: ParsingError) -> ParsingError {
+        let input = self.tokens.input();
+        let token = match err {
+            ParsingError::At { .. } => return err,
+            ParsingError::TokenizerError(_) => {
+                let offset = self.tokens.offset();
+                let found = input[offset..].chars().next().map(String::from);
+                return ParsingError::At {
+                    err: Box::new(err),
+                    found,
+                    position: Position::locate(input, offset),
+                };
+            }
+            ParsingError::UnexpectedToken { .. }
+            | ParsingError::UnexpectedEndOfStatement { .. } => self.lookahead,
+            _ => self.previous,
+        };
+        let (found, offset) = match token {
+            Some(t) => (
+                Some(input[t.span().start..t.span().end].to_string()),
+                t.span().start,
+            ),
+            None => (None, input.len()),
+        };
+        ParsingError::At {
+            err: Box::new(err),
+            found,
+            position: Position::locate(input, offset),
+        }
     }
 
     fn done_parsing(&self) -> bool {
         self.lookahead.is_none()
     }
 
-    fn consume(&mut self, tk: TokenKind) -> Result<Token<'a>> {
-        let token = self.lookahead.take();
-        match token {
-            Some(t) if t.kind() == tk => {
-                self.lookahead = self.tokens.next_token()?;
-                Ok(t)
-            }
-            Some(_) => Err(ParsingError::UnexpectedTokenType),
-            None => Err(ParsingError::UnexpectedEndOfStatement),
+    /// Moves past the lookahead, returning it
+    fn advance(&mut self) -> Result<Token<'a>> {
+        let token = self
+            .lookahead
+            .take()
+            .ok_or(ParsingError::UnexpectedEndOfStatement { expected: vec![] })?;
+        self.previous = Some(token);
+        self.lookahead = self.tokens.next_token()?;
+        Ok(token)
+    }
+
+    /// The error for a lookahead that isn't any of the `expected` kinds
+    fn unexpected(&self, expected: &[TokenKind]) -> ParsingError {
+        let expected = expected.to_vec();
+        match self.lookahead {
+            Some(_) => ParsingError::UnexpectedToken { expected },
+            None => ParsingError::UnexpectedEndOfStatement { expected },
         }
     }
 
+    fn consume_one_of(&mut self, kinds: &[TokenKind]) -> Result<Token<'a>> {
+        match self.peek_kind() {
+            Some(kind) if kinds.contains(&kind) => self.advance(),
+            _ => Err(self.unexpected(kinds)),
+        }
+    }
+
+    fn consume(&mut self, tk: TokenKind) -> Result<Token<'a>> {
+        self.consume_one_of(&[tk])
+    }
+
     fn consume_type_token(&mut self) -> Result<Token<'a>> {
-        let token = match self.lookahead.take() {
-            Some(t) => t,
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
+        self.consume_one_of(&TYPE_KINDS)
+    }
+
+    fn db_type(&mut self) -> Result<DbType> {
+        let _type = match self.consume_type_token()?.kind() {
+            TokenKind::TypeString => DbType::String,
+            TokenKind::TypeInteger => DbType::Integer,
+            TokenKind::TypeFloat => DbType::Float,
+            TokenKind::TypeUnsignedInt => DbType::UnsignedInt,
+            TokenKind::TypeSmallInt => DbType::SmallInt,
+            TokenKind::TypeChar => DbType::Char(self.type_length()?),
+            TokenKind::TypeVarchar => DbType::Varchar(self.type_length()?),
+            _ => panic!("Got a non-type token!"),
         };
-        if matches!(
-            token.kind(),
-            TokenKind::TypeString
-                | TokenKind::TypeInteger
-                | TokenKind::TypeFloat
-                | TokenKind::TypeUnsignedInt
-        ) {
-            self.lookahead = self.tokens.next_token()?;
-            return Ok(token);
-        }
-        Err(ParsingError::UnexpectedTokenType)
+        Ok(_type)
+    }
+
+    // type_length := '(' integer ')'
+    fn type_length(&mut self) -> Result<u32> {
+        _ = self.consume(TokenKind::LeftParen)?;
+        let length = self
+            .consume(TokenKind::Integer)?
+            .contents()
+            .parse::<u32>()?;
+        _ = self.consume(TokenKind::RightParen)?;
+        Ok(length)
     }
 
     fn consume_value_token(&mut self) -> Result<Token<'a>> {
-        let token = match self.lookahead.take() {
-            Some(t) => t,
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
-        };
-        if matches!(
-            token.kind(),
-            TokenKind::String | TokenKind::Integer | TokenKind::Float | TokenKind::UnsignedInt
-        ) {
-            self.lookahead = self.tokens.next_token()?;
-            return Ok(token);
-        }
-        Err(ParsingError::UnexpectedTokenType)
+        self.consume_one_of(&VALUE_KINDS)
     }
 
     fn peek_kind(&self) -> Option<TokenKind> {
@@ -103,13 +278,30 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse(&mut self) -> Result<Vec<Statement>> {
-        self.statement_list()
+        self.statement_list().map_err(|err| self.locate(err))
+    }
+
+    /// Parses the input as the predicate of a CHECK constraint, as it's stored
+    pub fn parse_check_predicate(&mut self) -> Result<WhereClause> {
+        let parse = |parser: &mut Self| {
+            let predicate = parser.comparison()?;
+            match parser.done_parsing() {
+                true => Ok(predicate),
+                false => Err(parser.unexpected(&[])),
+            }
+        };
+        parse(self).map_err(|err| self.locate(err))
     }
 
     fn statement_list(&mut self) -> Result<Vec<Statement>> {
         let mut statements = Vec::new();
 
         while !self.done_parsing() {
+            // empty statements, like the ones in `;;`, are skipped
+            if self.peek_kind() == Some(TokenKind::Semicolon) {
+                self.end_of_statement()?;
+                continue;
+            }
             statements.push(self.statement()?);
         }
 
@@ -118,15 +310,50 @@ impl<'a> Parser<'a> {
 
     fn statement(&mut self) -> Result<Statement> {
         let expr = match self.peek_kind() {
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
-            Some(TokenKind::Select) => Statement::Select(self.select_statement()?),
+            Some(TokenKind::Explain) => {
+                _ = self.consume(TokenKind::Explain)?;
+                Statement::Explain(Box::new(self.statement_body()?))
+            }
+            _ => self.statement_body()?,
+        };
+        self.end_of_statement()?;
+        Ok(expr)
+    }
+
+    fn statement_body(&mut self) -> Result<Statement> {
+        let expr = match self.peek_kind() {
+            Some(TokenKind::Select) => self.select_or_union()?,
             Some(TokenKind::Create) => Statement::Create(self.create_statement()?),
             Some(TokenKind::Insert) => Statement::Insert(self.insert_statement()?),
             Some(TokenKind::Destroy) => Statement::Destroy(self.destroy_statement()?),
             Some(TokenKind::Delete) => Statement::Delete(self.delete_statement()?),
-            Some(_) => return Err(ParsingError::UnexpectedTokenType),
+            Some(TokenKind::Vacuum) => Statement::Vacuum(self.vacuum_statement()?),
+            Some(TokenKind::Begin) => {
+                _ = self.consume(TokenKind::Begin)?;
+                Statement::Begin
+            }
+            Some(TokenKind::Commit) => {
+                _ = self.consume(TokenKind::Commit)?;
+                Statement::Commit
+            }
+            Some(TokenKind::Rollback) => {
+                _ = self.consume(TokenKind::Rollback)?;
+                Statement::Rollback
+            }
+            _ => {
+                return Err(self.unexpected(&[
+                    TokenKind::Select,
+                    TokenKind::Create,
+                    TokenKind::Insert,
+                    TokenKind::Destroy,
+                    TokenKind::Delete,
+                    TokenKind::Vacuum,
+                    TokenKind::Begin,
+                    TokenKind::Commit,
+                    TokenKind::Rollback,
+                ]))
+            }
         };
-        self.end_of_statement()?;
         Ok(expr)
     }
 
@@ -136,37 +363,352 @@ impl<'a> Parser<'a> {
     }
 
     fn column_name(&mut self) -> Result<String> {
-        let name = self.consume(TokenKind::Identifier)?.contents().to_string();
+        self.name()
+    }
+
+    /// A table or column name, which can be double-quoted to use a reserved word or
+    /// spaces in it
+    fn name(&mut self) -> Result<String> {
+        let kind = match self.peek_kind() {
+            Some(TokenKind::String) => TokenKind::String,
+            _ => TokenKind::Identifier,
+        };
+        let name = self.consume(kind)?.unescaped();
         Ok(name)
     }
 
+    // column_ref := identifier ('.' identifier)?
+    fn column_ref(&mut self) -> Result<ColumnRef> {
+        let first = self.column_name()?;
+        self.column_ref_continued(first)
+    }
+
+    fn column_ref_continued(&mut self, first: String) -> Result<ColumnRef> {
+        if self.peek_kind() == Some(TokenKind::Dot) {
+            _ = self.consume(TokenKind::Dot)?;
+            let name = Parser::normalize_column_name(self.column_name()?);
+            return Ok(ColumnRef::qualified(first, name));
+        }
+        Ok(ColumnRef::new(Parser::normalize_column_name(first)))
+    }
+
+    /// `rowid` is reserved whatever its case, like the keywords, so references to it
+    /// are lowercased to match the column the storage layer provides
+    fn normalize_column_name(name: String) -> String {
+        if name.eq_ignore_ascii_case("rowid") {
+            return name.to_lowercase();
+        }
+        name
+    }
+
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.peek_kind() != Some(TokenKind::As) {
+            return Ok(None);
+        }
+        _ = self.consume(TokenKind::As)?;
+        let alias = self.name()?;
+        Ok(Some(alias))
+    }
+
     fn column_projection(&mut self) -> Result<ColumnProjection> {
-        let in_name = self.column_name()?;
-        if self.peek_kind() == Some(TokenKind::As) {
-            _ = self.consume(TokenKind::As)?;
-            let out_name = self.consume(TokenKind::Identifier)?.contents().to_string();
-            Ok(ColumnProjection::new(in_name, out_name))
-        } else {
-            Ok(ColumnProjection::no_projection(in_name))
+        let expr = self.expression()?;
+        self.column_projection_of(expr)
+    }
+
+    fn column_projection_of(&mut self, expr: Expression) -> Result<ColumnProjection> {
+        let out_name = match (self.alias()?, &expr) {
+            (Some(alias), _) => alias,
+            (None, Expression::Column(col)) => col.name.clone(),
+            (None, Expression::QuotedName(name)) => name.clone(),
+            (None, _) => expr.to_string(),
+        };
+        Ok(ColumnProjection { expr, out_name })
+    }
+
+    fn count(&mut self) -> Result<SelectColumns> {
+        _ = self.consume(TokenKind::Count)?;
+        if self.peek_kind() == Some(TokenKind::Distinct) {
+            _ = self.consume(TokenKind::Distinct)?;
+            let expr = self.expression()?;
+            _ = self.consume(TokenKind::RightParen)?;
+            let out_name = self
+                .alias()?
+                .unwrap_or_else(|| format!("count(DISTINCT {expr})"));
+            return Ok(SelectColumns::CountDistinct {
+                expr: Box::new(expr),
+                out_name,
+            });
+        }
+        _ = self.consume(TokenKind::Star)?;
+        _ = self.consume(TokenKind::RightParen)?;
+        let out_name = self.alias()?.unwrap_or_else(|| String::from("count(*)"));
+        Ok(SelectColumns::CountAll { out_name })
+    }
+
+    /// Parses a value from a literal token. Integers are parsed as the narrowest
+    /// type that can hold them, since the tokenizer doesn't distinguish them.
+    fn literal_value(token: &Token) -> Result<DbValue> {
+        let val = match token.kind() {
+            TokenKind::String | TokenKind::StringLiteral => DbValue::String(token.unescaped()),
+            TokenKind::Float => DbValue::Float(Parser::float_value(token.contents())?),
+            TokenKind::UnsignedInt => DbValue::UnsignedInt(token.contents().parse::<u64>()?),
+            TokenKind::Integer => {
+                // need to try parsing as all value types because all integers will
+                // be picked up by the tokenizer as Integer, even if they should be UnsignedInt
+                // or are too large and should be a float
+                token
+                    .contents()
+                    .parse::<i64>()
+                    .map(DbValue::Integer)
+                    .or_else(|_| token.contents().parse::<u64>().map(DbValue::UnsignedInt))
+                    .or_else(|_| Parser::float_value(token.contents()).map(DbValue::Float))?
+            }
+            _ => {
+                return Err(ParsingError::UnexpectedToken {
+                    expected: VALUE_KINDS.to_vec(),
+                })
+            }
+        };
+        Ok(val)
+    }
+
+    /// Values too large to be represented become infinite when parsed, so those are rejected
+    fn float_value(contents: &str) -> Result<DbFloat> {
+        let f = contents.parse::<f64>()?;
+        DbFloat::try_new(f).ok_or(ParsingError::NonFiniteFloat)
+    }
+
+    // expression := term (('+' | '-') term)*
+    // term := factor (('*' | '/') factor)*
+    // factor := column | literal | '(' expression ')' | '(' select_statement ')'
+    fn expression(&mut self) -> Result<Expression> {
+        let first = self.term()?;
+        self.expression_continued(first)
+    }
+
+    fn expression_continued(&mut self, first: Expression) -> Result<Expression> {
+        let mut expr = first;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Plus) => {
+                    _ = self.consume(TokenKind::Plus)?;
+                    ArithmeticOp::Add
+                }
+                Some(TokenKind::Minus) => {
+                    _ = self.consume(TokenKind::Minus)?;
+                    ArithmeticOp::Subtract
+                }
+                Some(kind @ (TokenKind::Integer | TokenKind::Float))
+                    if self
+                        .lookahead
+                        .as_ref()
+                        .is_some_and(|t| t.contents().starts_with('-')) =>
+                {
+                    // the tokenizer reads `a -1` as an identifier followed by a negative
+                    // number, so treat the sign as a subtraction of the positive number
+                    let token = self.consume(kind)?;
+                    let span = Span {
+                        start: token.span().start + 1,
+                        end: token.span().end,
+                    };
+                    let positive = Token::new(&token.contents()[1..], kind, span);
+                    let first = Expression::Value(Parser::literal_value(&positive)?);
+                    let right = self.term_continued(first)?;
+                    expr = Expression::arithmetic(expr, ArithmeticOp::Subtract, right);
+                    continue;
+                }
+                _ => break,
+            };
+            let right = self.term()?;
+            expr = Expression::arithmetic(expr, op, right);
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expression> {
+        let first = self.factor()?;
+        self.term_continued(first)
+    }
+
+    fn term_continued(&mut self, first: Expression) -> Result<Expression> {
+        let mut expr = first;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Star) => {
+                    _ = self.consume(TokenKind::Star)?;
+                    ArithmeticOp::Multiply
+                }
+                Some(TokenKind::Slash) => {
+                    _ = self.consume(TokenKind::Slash)?;
+                    ArithmeticOp::Divide
+                }
+                _ => break,
+            };
+            let right = self.factor()?;
+            expr = Expression::arithmetic(expr, op, right);
+        }
+        Ok(expr)
+    }
+
+    // function_call := identifier '(' expression (',' expression)* ')'
+    fn column_or_function_call(&mut self) -> Result<Expression> {
+        let first = self.column_name()?;
+        self.column_or_function_call_continued(first)
+    }
+
+    fn column_or_function_call_continued(&mut self, first: String) -> Result<Expression> {
+        if self.peek_kind() != Some(TokenKind::LeftParen) {
+            return Ok(Expression::Column(self.column_ref_continued(first)?));
+        }
+        let func = match ScalarFunction::from_name(&first) {
+            Some(func) => func,
+            None => return Err(ParsingError::UnknownFunction(first)),
+        };
+        _ = self.consume(TokenKind::LeftParen)?;
+        let mut args = vec![self.expression()?];
+        while self.peek_kind() == Some(TokenKind::Comma) {
+            _ = self.consume(TokenKind::Comma)?;
+            args.push(self.expression()?);
+        }
+        _ = self.consume(TokenKind::RightParen)?;
+        if !func.accepts_arg_count(args.len()) {
+            return Err(ParsingError::WrongArgumentCount(func));
+        }
+        Ok(Expression::Function { func, args })
+    }
+
+    // cast := 'cast(' expression 'as' type ')'
+    fn cast(&mut self) -> Result<Expression> {
+        _ = self.consume(TokenKind::Cast)?;
+        let expr = self.expression()?;
+        _ = self.consume(TokenKind::As)?;
+        let to = self.db_type()?;
+        _ = self.consume(TokenKind::RightParen)?;
+        Ok(Expression::Cast {
+            expr: Box::new(expr),
+            to,
+        })
+    }
+
+    /// A double-quoted name is only known to be a column when it's qualified. Otherwise
+    /// it's resolved when executing, like SQLite does, so double quotes still work for
+    /// strings.
+    fn quoted_name(&mut self) -> Result<Expression> {
+        let first = self.consume(TokenKind::String)?.unescaped();
+        if self.peek_kind() == Some(TokenKind::Dot) {
+            return Ok(Expression::Column(self.column_ref_continued(first)?));
+        }
+        Ok(Expression::QuotedName(Parser::normalize_column_name(first)))
+    }
+
+    fn placeholder(&mut self) -> Result<Expression> {
+        let token = self.consume(TokenKind::Placeholder)?;
+        Ok(Expression::Placeholder(token.contents().to_string()))
+    }
+
+    fn factor(&mut self) -> Result<Expression> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier) => self.column_or_function_call(),
+            Some(TokenKind::String) => self.quoted_name(),
+            Some(TokenKind::Cast) => self.cast(),
+            Some(TokenKind::Placeholder) => self.placeholder(),
+            Some(TokenKind::LeftParen) => {
+                _ = self.consume(TokenKind::LeftParen)?;
+                let expr = if self.peek_kind() == Some(TokenKind::Select) {
+                    Expression::Subquery(Box::new(self.select_statement()?))
+                } else {
+                    self.expression()?
+                };
+                _ = self.consume(TokenKind::RightParen)?;
+                Ok(expr)
+            }
+            Some(
+                kind @ (TokenKind::StringLiteral
+                | TokenKind::Integer
+                | TokenKind::Float
+                | TokenKind::UnsignedInt),
+            ) => {
+                let token = self.consume(kind)?;
+                Ok(Expression::Value(Parser::literal_value(&token)?))
+            }
+            _ => Err(self.unexpected(&[])),
         }
     }
 
     fn select_columns(&mut self) -> Result<SelectColumns> {
-        if self.peek_kind() == Some(TokenKind::Star) {
-            _ = self.consume(TokenKind::Star)?;
-            return Ok(SelectColumns::All);
+        if self.peek_kind() == Some(TokenKind::Count) {
+            return self.count();
         }
-        let first = self.column_projection()?;
+        let first = self.select_item()?;
         let mut cols = vec![first];
 
         while self.peek_kind() == Some(TokenKind::Comma) {
             _ = self.consume(TokenKind::Comma)?;
-            cols.push(self.column_projection()?);
+            cols.push(self.select_item()?);
         }
 
+        if cols == [SelectItem::Wildcard(None)] {
+            return Ok(SelectColumns::All);
+        }
         Ok(SelectColumns::Only(cols))
     }
 
+    // select_item := '*' | identifier '.' '*' | column_projection
+    fn select_item(&mut self) -> Result<SelectItem> {
+        match self.peek_kind() {
+            Some(TokenKind::Star) => {
+                _ = self.consume(TokenKind::Star)?;
+                Ok(SelectItem::Wildcard(None))
+            }
+            Some(TokenKind::Identifier) => {
+                // the table name has to be consumed to find out if a wildcard follows it
+                let first = self.column_name()?;
+                let expr = if self.peek_kind() == Some(TokenKind::Dot) {
+                    _ = self.consume(TokenKind::Dot)?;
+                    if self.peek_kind() == Some(TokenKind::Star) {
+                        _ = self.consume(TokenKind::Star)?;
+                        return Ok(SelectItem::Wildcard(Some(first)));
+                    }
+                    let name = Parser::normalize_column_name(self.column_name()?);
+                    Expression::Column(ColumnRef::qualified(first, name))
+                } else {
+                    self.column_or_function_call_continued(first)?
+                };
+                let term = self.term_continued(expr)?;
+                let expr = self.expression_continued(term)?;
+                Ok(SelectItem::Column(self.column_projection_of(expr)?))
+            }
+            _ => Ok(SelectItem::Column(self.column_projection()?)),
+        }
+    }
+
+    // tables := name ((',' name) | ('JOIN' name 'ON' comparison))*
+    fn tables(&mut self) -> Result<SelectSource> {
+        let mut source = SelectSource::Table(self.name()?);
+        loop {
+            source = match self.peek_kind() {
+                Some(TokenKind::Comma) => {
+                    _ = self.consume(TokenKind::Comma)?;
+                    SelectSource::CrossJoin {
+                        left: Box::new(source),
+                        right: self.name()?,
+                    }
+                }
+                Some(TokenKind::Join) => {
+                    _ = self.consume(TokenKind::Join)?;
+                    let right = self.name()?;
+                    _ = self.consume(TokenKind::On)?;
+                    SelectSource::Join {
+                        left: Box::new(source),
+                        right,
+                        on: self.comparison()?,
+                    }
+                }
+                _ => return Ok(source),
+            };
+        }
+    }
+
     fn nested_select_statement(&mut self) -> Result<SelectStatement> {
         _ = self.consume(TokenKind::LeftParen)?;
         let statement = self.select_statement()?;
@@ -174,20 +716,63 @@ impl<'a> Parser<'a> {
         Ok(statement)
     }
 
+    /// A select, or several joined by `UNION`. Only the last select of a union can
+    /// have an `ORDER BY` or `LIMIT`, which apply to the combined rows.
+    fn select_or_union(&mut self) -> Result<Statement> {
+        let first = self.select_statement()?;
+        if self.peek_kind() != Some(TokenKind::Union) {
+            return Ok(Statement::Select(first));
+        }
+        let mut rest = Vec::new();
+        let mut ordered_or_limited = first.order_by_clause.is_some() || first.limit.is_some();
+        while self.peek_kind() == Some(TokenKind::Union) {
+            if ordered_or_limited {
+                return Err(self.unexpected(&[TokenKind::Semicolon]));
+            }
+            _ = self.consume(TokenKind::Union)?;
+            let all = self.peek_kind() == Some(TokenKind::All);
+            if all {
+                _ = self.consume(TokenKind::All)?;
+            }
+            let select = self.select_statement()?;
+            ordered_or_limited = select.order_by_clause.is_some() || select.limit.is_some();
+            rest.push((all, select));
+        }
+        let (_, last) = rest.last_mut().expect("A union has at least two selects");
+        Ok(Statement::Union(UnionStatement {
+            first,
+            order_by_clause: last.order_by_clause.take(),
+            limit: last.limit.take(),
+            rest,
+        }))
+    }
+
     fn select_statement(&mut self) -> Result<SelectStatement> {
         _ = self.consume(TokenKind::Select)?;
 
+        let distinct = self.peek_kind() == Some(TokenKind::Distinct);
+        if distinct {
+            _ = self.consume(TokenKind::Distinct)?;
+        }
         let columns = self.select_columns()?;
 
-        _ = self.consume(TokenKind::From)?;
-        let source = match self.peek_kind() {
-            Some(TokenKind::Identifier) => {
-                let table = self.consume(TokenKind::Identifier)?.contents().to_string();
-                SelectSource::Table(table)
+        // a wildcard needs a table to take its columns from
+        let has_wildcard = match &columns {
+            SelectColumns::All => true,
+            SelectColumns::Only(items) => items.iter().any(|i| i.expr().is_none()),
+            SelectColumns::CountAll { .. } | SelectColumns::CountDistinct { .. } => false,
+        };
+        let source = if has_wildcard || self.peek_kind() == Some(TokenKind::From) {
+            _ = self.consume(TokenKind::From)?;
+            match self.peek_kind() {
+                Some(TokenKind::Identifier | TokenKind::String) => self.tables()?,
+                Some(TokenKind::LeftParen) => {
+                    SelectSource::Expression(self.nested_select_statement()?)
+                }
+                _ => return Err(self.unexpected(&[TokenKind::Identifier, TokenKind::LeftParen])),
             }
-            Some(TokenKind::LeftParen) => SelectSource::Expression(self.nested_select_statement()?),
-            Some(_) => return Err(ParsingError::UnexpectedEndOfStatement),
-            None => return Err(ParsingError::UnexpectedTokenType),
+        } else {
+            SelectSource::NoTable
         };
 
         let where_clause = if self.peek_kind() == Some(TokenKind::Where) {
@@ -208,6 +793,7 @@ impl<'a> Parser<'a> {
 
         Ok(SelectStatement {
             columns,
+            distinct,
             source: Box::new(source),
             where_clause,
             order_by_clause,
@@ -218,36 +804,76 @@ impl<'a> Parser<'a> {
     fn is_where_clause_member_kind(tk: TokenKind) -> bool {
         matches!(
             tk,
-            TokenKind::Identifier | TokenKind::String | TokenKind::Integer | TokenKind::Float
+            TokenKind::StringLiteral | TokenKind::Integer | TokenKind::Float
         )
     }
 
     fn where_token_to_where_member(token: Token) -> Result<WhereMember> {
         match token.kind() {
-            TokenKind::Identifier => Ok(WhereMember::Column(token.contents().to_string())),
-            TokenKind::String => Ok(WhereMember::Value(DbValue::String(
-                token.contents().to_string(),
-            ))),
+            TokenKind::StringLiteral => Ok(WhereMember::Value(DbValue::String(token.unescaped()))),
             TokenKind::Integer => Ok(WhereMember::Value(DbValue::Integer(
                 token.contents().parse::<i64>()?,
             ))),
-            TokenKind::Float => Ok(WhereMember::Value(DbValue::Float(DbFloat::new(
-                token.contents().parse::<f64>()?,
-            )))),
-            _ => Err(ParsingError::UnexpectedTokenType),
+            TokenKind::Float => Ok(WhereMember::Value(DbValue::Float(Parser::float_value(
+                token.contents(),
+            )?))),
+            _ => Err(ParsingError::UnexpectedToken {
+                expected: vec![
+                    TokenKind::StringLiteral,
+                    TokenKind::Integer,
+                    TokenKind::Float,
+                ],
+            }),
         }
     }
 
-    fn where_clause(&mut self) -> Result<WhereClause> {
-        _ = self.consume(TokenKind::Where)?;
-        let left = match self.peek_kind() {
+    fn where_member(&mut self) -> Result<WhereMember> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier) => match self.column_or_function_call()? {
+                Expression::Column(col) => Ok(WhereMember::Column(col)),
+                expr => Ok(WhereMember::Expression(Box::new(expr))),
+            },
+            Some(TokenKind::String) => match self.quoted_name()? {
+                Expression::Column(col) => Ok(WhereMember::Column(col)),
+                expr => Ok(WhereMember::Expression(Box::new(expr))),
+            },
+            Some(TokenKind::Cast) => Ok(WhereMember::Expression(Box::new(self.cast()?))),
+            Some(TokenKind::Placeholder) => {
+                Ok(WhereMember::Expression(Box::new(self.placeholder()?)))
+            }
             Some(k) if Parser::is_where_clause_member_kind(k) => {
                 let token = self.consume(k)?;
-                Parser::where_token_to_where_member(token)?
+                Parser::where_token_to_where_member(token)
             }
-            Some(_) => return Err(ParsingError::UnexpectedTokenType),
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
-        };
+            _ => Err(self.unexpected(&[])),
+        }
+    }
+
+    // exists := 'NOT'? 'EXISTS' '(' select_statement ')'
+    fn exists(&mut self) -> Result<WhereClause> {
+        let negated = self.peek_kind() == Some(TokenKind::Not);
+        if negated {
+            _ = self.consume(TokenKind::Not)?;
+        }
+        _ = self.consume(TokenKind::Exists)?;
+        let subquery = self.nested_select_statement()?;
+        Ok(WhereClause::Exists {
+            negated,
+            subquery: Box::new(subquery),
+        })
+    }
+
+    fn where_clause(&mut self) -> Result<WhereClause> {
+        _ = self.consume(TokenKind::Where)?;
+        if matches!(self.peek_kind(), Some(TokenKind::Not | TokenKind::Exists)) {
+            return self.exists();
+        }
+        self.comparison()
+    }
+
+    // comparison := where_member ('=' | '<' | '>' | '<=' | '>=') where_member
+    fn comparison(&mut self) -> Result<WhereClause> {
+        let left = self.where_member()?;
         let cmp = match self.peek_kind() {
             Some(TokenKind::EqualsSign) => {
                 _ = self.consume(TokenKind::EqualsSign)?;
@@ -269,24 +895,24 @@ impl<'a> Parser<'a> {
                 _ = self.consume(TokenKind::GreaterThanEquals)?;
                 WhereCmp::GreaterThanEquals
             }
-            Some(_) => return Err(ParsingError::UnexpectedTokenType),
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
-        };
-        let right = match self.peek_kind() {
-            Some(k) if Parser::is_where_clause_member_kind(k) => {
-                let token = self.consume(k)?;
-                Parser::where_token_to_where_member(token)?
+            _ => {
+                return Err(self.unexpected(&[
+                    TokenKind::EqualsSign,
+                    TokenKind::LeftAngleBracket,
+                    TokenKind::RightAngleBracket,
+                    TokenKind::LessThanEquals,
+                    TokenKind::GreaterThanEquals,
+                ]))
             }
-            Some(_) => return Err(ParsingError::UnexpectedTokenType),
-            None => return Err(ParsingError::UnexpectedEndOfStatement),
         };
-        Ok(WhereClause { left, cmp, right })
+        let right = self.where_member()?;
+        Ok(WhereClause::Comparison { left, cmp, right })
     }
 
     fn order_by_clause(&mut self) -> Result<OrderByClause> {
         _ = self.consume(TokenKind::Order)?;
         _ = self.consume(TokenKind::By)?;
-        let sort_column = self.column_name()?;
+        let sort_column = self.column_ref()?;
         let desc = self.peek_kind().filter(|k| *k == TokenKind::Desc).is_some();
         if desc {
             _ = self.consume(TokenKind::Desc)?;
@@ -310,7 +936,7 @@ impl<'a> Parser<'a> {
             _ = self.consume(TokenKind::Not)?;
             _ = self.consume(TokenKind::Exists)?;
         }
-        let table = self.consume(TokenKind::Identifier)?.contents().to_string();
+        let table = self.name()?;
         let columns = self.create_columns()?;
 
         Ok(CreateStatement {
@@ -324,15 +950,32 @@ impl<'a> Parser<'a> {
         _ = self.consume(TokenKind::LeftParen)?;
         let mut names = Vec::new();
         let mut types = Vec::new();
+        let mut collations = Vec::new();
+        let mut checks = Vec::new();
+        let mut foreign_keys = Vec::new();
         let mut primary_key_col: Option<String> = None;
         while self.peek_kind().is_some() && self.peek_kind() != Some(TokenKind::RightParen) {
-            let name = self.consume(TokenKind::Identifier)?.contents().to_string();
-            let this_type = match self.consume_type_token()?.kind() {
-                TokenKind::TypeString => DbType::String,
-                TokenKind::TypeInteger => DbType::Integer,
-                TokenKind::TypeFloat => DbType::Float,
-                TokenKind::TypeUnsignedInt => DbType::UnsignedInt,
-                _ => panic!("Got a non-type token!"),
+            let peeked = self.peek_kind();
+            if matches!(
+                peeked,
+                Some(TokenKind::Constraint | TokenKind::Check | TokenKind::Foreign)
+            ) {
+                if peeked == Some(TokenKind::Foreign) {
+                    foreign_keys.push(self.foreign_key()?);
+                } else {
+                    checks.push(self.check_constraint()?);
+                }
+                if self.peek_kind() != Some(TokenKind::RightParen) {
+                    _ = self.consume(TokenKind::Comma)?;
+                }
+                continue;
+            }
+            let name = self.name()?;
+            let this_type = self.db_type()?;
+            let collation = if self.peek_kind() == Some(TokenKind::Collate) {
+                self.collation(this_type)?
+            } else {
+                Collation::Binary
             };
 
             if self.peek_kind() == Some(TokenKind::Primary) {
@@ -344,9 +987,16 @@ impl<'a> Parser<'a> {
                 _ = self.consume(TokenKind::Primary)?;
                 _ = self.consume(TokenKind::Key)?;
             }
+            if matches!(
+                self.peek_kind(),
+                Some(TokenKind::Constraint | TokenKind::Check)
+            ) {
+                checks.push(self.check_constraint()?);
+            }
 
             names.push(name);
             types.push(this_type);
+            collations.push(collation);
 
             if self.peek_kind() != Some(TokenKind::RightParen) {
                 _ = self.consume(TokenKind::Comma)?;
@@ -360,17 +1010,77 @@ impl<'a> Parser<'a> {
         Ok(CreateColumns {
             names,
             types,
+            collations,
+            checks,
+            foreign_keys,
             primary_key_col,
         })
     }
 
+    // foreign_key := 'FOREIGN' 'KEY' '(' identifier ')' 'REFERENCES' identifier '(' identifier ')'
+    fn foreign_key(&mut self) -> Result<ForeignKey> {
+        _ = self.consume(TokenKind::Foreign)?;
+        _ = self.consume(TokenKind::Key)?;
+        _ = self.consume(TokenKind::LeftParen)?;
+        let column = self.name()?;
+        _ = self.consume(TokenKind::RightParen)?;
+        _ = self.consume(TokenKind::References)?;
+        let parent_table = self.name()?;
+        _ = self.consume(TokenKind::LeftParen)?;
+        let parent_column = self.name()?;
+        _ = self.consume(TokenKind::RightParen)?;
+        Ok(ForeignKey {
+            column,
+            parent_table,
+            parent_column,
+        })
+    }
+
+    // check_constraint := ('CONSTRAINT' identifier)? 'CHECK' '(' comparison ')'
+    fn check_constraint(&mut self) -> Result<CheckConstraint> {
+        let name = if self.peek_kind() == Some(TokenKind::Constraint) {
+            _ = self.consume(TokenKind::Constraint)?;
+            Some(self.name()?)
+        } else {
+            None
+        };
+        _ = self.consume(TokenKind::Check)?;
+        _ = self.consume(TokenKind::LeftParen)?;
+        let start = self.lookahead.map(|t| t.span().start).unwrap_or_default();
+        _ = self.comparison()?;
+        let end = self.previous.map(|t| t.span().end).unwrap_or_default();
+        _ = self.consume(TokenKind::RightParen)?;
+
+        // kept as written, so it can be stored and parsed again when the table is read
+        let predicate = self.tokens.input()[start..end].to_string();
+        Ok(CheckConstraint {
+            name: name.unwrap_or_else(|| predicate.clone()),
+            predicate,
+        })
+    }
+
+    // collation := 'COLLATE' ('NOCASE' | 'BINARY')
+    fn collation(&mut self, _type: DbType) -> Result<Collation> {
+        _ = self.consume(TokenKind::Collate)?;
+        let name = self.consume(TokenKind::Identifier)?.contents().to_string();
+        let collation = match name.to_ascii_lowercase().as_str() {
+            "nocase" => Collation::NoCase,
+            "binary" => Collation::Binary,
+            _ => return Err(ParsingError::UnknownCollation(name)),
+        };
+        if !_type.is_string() {
+            return Err(ParsingError::NonStringCollation);
+        }
+        Ok(collation)
+    }
+
     fn conflict_clause(&mut self) -> Result<ConflictClause> {
         _ = self.consume(TokenKind::On)?;
         _ = self.consume(TokenKind::Conflict)?;
         _ = self.consume(TokenKind::LeftParen)?;
         let mut target_columns = Vec::new();
         while self.peek_kind().is_some() && self.peek_kind() != Some(TokenKind::RightParen) {
-            let name = self.consume(TokenKind::Identifier)?.contents().to_string();
+            let name = self.name()?;
             target_columns.push(name);
             if self.peek_kind() != Some(TokenKind::RightParen) {
                 _ = self.consume(TokenKind::Comma)?;
@@ -384,8 +1094,11 @@ impl<'a> Parser<'a> {
                 _ = self.consume(TokenKind::Nothing)?;
                 ConflictAction::Nothing
             }
-            Some(_) => return Err(ParsingError::UnexpectedTokenType),
-            _ => return Err(ParsingError::UnexpectedEndOfStatement),
+            Some(TokenKind::Replace) => {
+                _ = self.consume(TokenKind::Replace)?;
+                ConflictAction::Replace
+            }
+            _ => return Err(self.unexpected(&[TokenKind::Nothing, TokenKind::Replace])),
         };
         Ok(ConflictClause {
             target_columns,
@@ -397,47 +1110,31 @@ impl<'a> Parser<'a> {
         _ = self.consume(TokenKind::Insert)?;
         _ = self.consume(TokenKind::Into)?;
 
-        let table = self.consume(TokenKind::Identifier)?.contents().to_string();
+        let table = self.name()?;
 
         let mut columns = Vec::new();
-        _ = self.consume(TokenKind::LeftParen)?;
-        while self.peek_kind().is_some() && self.peek_kind() != Some(TokenKind::RightParen) {
-            let name = self.consume(TokenKind::Identifier)?.contents().to_string();
-            columns.push(name);
-            if self.peek_kind() != Some(TokenKind::RightParen) {
-                _ = self.consume(TokenKind::Comma)?;
+        if self.peek_kind() != Some(TokenKind::Values) {
+            _ = self.consume(TokenKind::LeftParen)?;
+            while self.peek_kind().is_some() && self.peek_kind() != Some(TokenKind::RightParen) {
+                let name = self.name()?;
+                columns.push(name);
+                if self.peek_kind() != Some(TokenKind::RightParen) {
+                    _ = self.consume(TokenKind::Comma)?;
+                }
             }
+            _ = self.consume(TokenKind::RightParen)?;
         }
-        _ = self.consume(TokenKind::RightParen)?;
 
         _ = self.consume(TokenKind::Values)?;
         let mut values = Vec::new();
         _ = self.consume(TokenKind::LeftParen)?;
         while self.peek_kind().is_some() && self.peek_kind() != Some(TokenKind::RightParen) {
-            let token = self.consume_value_token()?;
-            let val = match token.kind() {
-                TokenKind::String => DbValue::String(token.contents().to_string()),
-                TokenKind::Float => DbValue::Float(DbFloat::new(token.contents().parse::<f64>()?)),
-                TokenKind::UnsignedInt => DbValue::UnsignedInt(token.contents().parse::<u64>()?),
-                TokenKind::Integer => {
-                    // need to try parsing as all value types because all integers will
-                    // be picked up by the tokenizer as Integer, even if they should be UnsignedInt
-                    // or are too large and should be a float
-                    token
-                        .contents()
-                        .parse::<i64>()
-                        .map(DbValue::Integer)
-                        .or_else(|_| token.contents().parse::<u64>().map(DbValue::UnsignedInt))
-                        .or_else(|_| {
-                            token
-                                .contents()
-                                .parse::<f64>()
-                                .map(|f| DbValue::Float(DbFloat::new(f)))
-                        })?
-                }
-                _ => panic!("Should not happen!"),
+            let val = if self.peek_kind() == Some(TokenKind::Placeholder) {
+                self.placeholder()?
+            } else {
+                let token = self.consume_value_token()?;
+                Expression::Value(Parser::literal_value(&token)?)
             };
-
             values.push(val);
             if self.peek_kind() != Some(TokenKind::RightParen) {
                 _ = self.consume(TokenKind::Comma)?;
@@ -462,15 +1159,37 @@ impl<'a> Parser<'a> {
     fn destroy_statement(&mut self) -> Result<DestroyStatement> {
         _ = self.consume(TokenKind::Destroy)?;
         _ = self.consume(TokenKind::Table)?;
-        let table = self.consume(TokenKind::Identifier)?.contents().to_string();
-        Ok(DestroyStatement { table })
+        let if_exists = self.peek_kind() == Some(TokenKind::If);
+        if if_exists {
+            _ = self.consume(TokenKind::If)?;
+            _ = self.consume(TokenKind::Exists)?;
+        }
+        let table = self.name()?;
+        Ok(DestroyStatement { table, if_exists })
+    }
+
+    fn vacuum_statement(&mut self) -> Result<VacuumStatement> {
+        _ = self.consume(TokenKind::Vacuum)?;
+        let table = if matches!(
+            self.peek_kind(),
+            Some(TokenKind::Identifier | TokenKind::String)
+        ) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+        Ok(VacuumStatement { table })
     }
 
     fn delete_statement(&mut self) -> Result<DeleteStatement> {
         _ = self.consume(TokenKind::Delete)?;
         _ = self.consume(TokenKind::From)?;
-        let table = self.consume(TokenKind::Identifier)?.contents().to_string();
-        let where_clause = self.where_clause()?;
+        let table = self.name()?;
+        let where_clause = if self.peek_kind() == Some(TokenKind::Where) {
+            Some(self.where_clause()?)
+        } else {
+            None
+        };
         Ok(DeleteStatement {
             table,
             where_clause,
@@ -478,31 +1197,349 @@ impl<'a> Parser<'a> {
     }
 }
 
-#[derive(PartialEq, Debug)]
-pub struct ColumnProjection {
-    pub in_name: String,
-    pub out_name: String,
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
 }
-impl ColumnProjection {
-    fn new(in_name: String, out_name: String) -> Self {
-        ColumnProjection { in_name, out_name }
+impl ArithmeticOp {
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Add | Self::Subtract => 0,
+            Self::Multiply | Self::Divide => 1,
+        }
     }
-
-    fn no_projection(name: String) -> Self {
-        ColumnProjection {
-            in_name: name.clone(),
-            out_name: name,
+}
+impl fmt::Display for ArithmeticOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => f.write_char('+'),
+            Self::Subtract => f.write_char('-'),
+            Self::Multiply => f.write_char('*'),
+            Self::Divide => f.write_char('/'),
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ScalarFunction {
+    Upper,
+    Lower,
+    Length,
+    Abs,
+    /// Takes the number of digits to round to as an optional second argument
+    Round,
+}
+impl ScalarFunction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "length" => Some(Self::Length),
+            "abs" => Some(Self::Abs),
+            "round" => Some(Self::Round),
+            _ => None,
+        }
+    }
+
+    fn accepts_arg_count(&self, count: usize) -> bool {
+        match self {
+            Self::Round => count == 1 || count == 2,
+            _ => count == 1,
+        }
+    }
+}
+impl fmt::Display for ScalarFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upper => f.write_str("UPPER"),
+            Self::Lower => f.write_str("LOWER"),
+            Self::Length => f.write_str("LENGTH"),
+            Self::Abs => f.write_str("ABS"),
+            Self::Round => f.write_str("ROUND"),
+        }
+    }
+}
+
+/// A column reference, optionally qualified by the table it belongs to
+#[derive(PartialEq, Debug, Clone)]
+pub struct ColumnRef {
+    pub table: Option<String>,
+    pub name: String,
+}
+impl ColumnRef {
+    pub fn new(name: String) -> Self {
+        ColumnRef { table: None, name }
+    }
+
+    pub fn qualified(table: String, name: String) -> Self {
+        ColumnRef {
+            table: Some(table),
+            name,
+        }
+    }
+}
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "{table}.{}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Expression {
+    Value(DbValue),
+    Column(ColumnRef),
+    Arithmetic {
+        left: Box<Expression>,
+        op: ArithmeticOp,
+        right: Box<Expression>,
+    },
+    Function {
+        func: ScalarFunction,
+        args: Vec<Expression>,
+    },
+    Cast {
+        expr: Box<Expression>,
+        to: DbType,
+    },
+    /// A named parameter like `:id`, replaced with a value by `bind`
+    Placeholder(String),
+    /// A double-quoted name, which refers to the column by that name if there is one
+    /// and is a string otherwise
+    QuotedName(String),
+    /// A subquery producing a single value, run once for each row it's used with
+    Subquery(Box<SelectStatement>),
+}
+impl Expression {
+    fn arithmetic(left: Expression, op: ArithmeticOp, right: Expression) -> Self {
+        Expression::Arithmetic {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }
+    }
+
+    pub fn references_column(&self, name: &str) -> bool {
+        match self {
+            Self::Value(_) => false,
+            Self::Column(col) => col.name == name,
+            Self::Arithmetic { left, op: _, right } => {
+                left.references_column(name) || right.references_column(name)
+            }
+            Self::Function { func: _, args } => args.iter().any(|a| a.references_column(name)),
+            Self::Cast { expr, to: _ } => expr.references_column(name),
+            Self::Placeholder(_) => false,
+            Self::QuotedName(quoted) => quoted == name,
+            Self::Subquery(subquery) => subquery.references_column(name),
+        }
+    }
+
+    /// Replaces any placeholders that have a value in `values`
+    pub fn bind(&mut self, values: &[(&str, DbValue)]) {
+        match self {
+            Self::Value(_) | Self::Column(_) | Self::QuotedName(_) => {}
+            Self::Arithmetic { left, op: _, right } => {
+                left.bind(values);
+                right.bind(values);
+            }
+            Self::Function { func: _, args } => args.iter_mut().for_each(|a| a.bind(values)),
+            Self::Cast { expr, to: _ } => expr.bind(values),
+            Self::Placeholder(name) => {
+                if let Some((_, val)) = values.iter().find(|(n, _)| n == name) {
+                    *self = Self::Value(val.clone());
+                }
+            }
+            Self::Subquery(subquery) => subquery.bind(values),
+        }
+    }
+
+    /// Replaces each column `value_of` gives a value for with that value. Subqueries
+    /// are left alone, since their columns are looked up in their own source.
+    pub fn substitute_columns(&mut self, value_of: &mut dyn FnMut(&ColumnRef) -> Option<DbValue>) {
+        match self {
+            Self::Value(_) | Self::Placeholder(_) | Self::Subquery(_) => {}
+            Self::Column(col) => {
+                if let Some(val) = value_of(col) {
+                    *self = Self::Value(val);
+                }
+            }
+            Self::QuotedName(name) => {
+                if let Some(val) = value_of(&ColumnRef::new(name.clone())) {
+                    *self = Self::Value(val);
+                }
+            }
+            Self::Arithmetic { left, op: _, right } => {
+                left.substitute_columns(value_of);
+                right.substitute_columns(value_of);
+            }
+            Self::Function { func: _, args } => {
+                args.iter_mut().for_each(|a| a.substitute_columns(value_of))
+            }
+            Self::Cast { expr, to: _ } => expr.substitute_columns(value_of),
+        }
+    }
+
+    /// Replaces each subquery with the expression `replacement` gives for it
+    pub fn replace_subqueries(
+        &mut self,
+        replacement: &mut dyn FnMut(&SelectStatement) -> Expression,
+    ) {
+        match self {
+            Self::Value(_) | Self::Column(_) | Self::Placeholder(_) | Self::QuotedName(_) => {}
+            Self::Arithmetic { left, op: _, right } => {
+                left.replace_subqueries(replacement);
+                right.replace_subqueries(replacement);
+            }
+            Self::Function { func: _, args } => args
+                .iter_mut()
+                .for_each(|a| a.replace_subqueries(replacement)),
+            Self::Cast { expr, to: _ } => expr.replace_subqueries(replacement),
+            Self::Subquery(subquery) => *self = replacement(subquery),
+        }
+    }
+
+    /// Whether this needs parentheses to keep its meaning when it's an operand of `parent_op`
+    fn needs_parens_under(&self, parent_op: ArithmeticOp, is_right: bool) -> bool {
+        match self {
+            Self::Arithmetic {
+                left: _,
+                op,
+                right: _,
+            } => {
+                op.precedence() < parent_op.precedence()
+                    || (is_right && op.precedence() == parent_op.precedence())
+            }
+            _ => false,
+        }
+    }
+}
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(v) => v.fmt(f),
+            Self::Column(col) => col.fmt(f),
+            Self::Arithmetic { left, op, right } => {
+                if left.needs_parens_under(*op, false) {
+                    write!(f, "({left})")?;
+                } else {
+                    write!(f, "{left}")?;
+                }
+                write!(f, " {op} ")?;
+                if right.needs_parens_under(*op, true) {
+                    write!(f, "({right})")
+                } else {
+                    write!(f, "{right}")
+                }
+            }
+            Self::Function { func, args } => {
+                write!(f, "{func}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_char(')')
+            }
+            Self::Cast { expr, to } => write!(f, "CAST({expr} AS {})", to.as_sql_type_str()),
+            Self::Placeholder(name) => f.write_str(name),
+            Self::QuotedName(name) => write!(f, "\"{}\"", crate::escaped_str(name)),
+            Self::Subquery(subquery) => write!(f, "({subquery})"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ColumnProjection {
+    pub expr: Expression,
+    pub out_name: String,
+}
+impl ColumnProjection {
+    #[cfg(test)]
+    fn new(in_name: String, out_name: String) -> Self {
+        ColumnProjection {
+            expr: Expression::Column(ColumnRef::new(in_name)),
+            out_name,
+        }
+    }
+
+    #[cfg(test)]
+    fn no_projection(name: String) -> Self {
+        ColumnProjection {
+            expr: Expression::Column(ColumnRef::new(name.clone())),
+            out_name: name,
+        }
+    }
+}
+impl fmt::Display for ColumnProjection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let renamed = match &self.expr {
+            Expression::Column(col) => col.name != self.out_name,
+            expr => expr.to_string() != self.out_name,
+        };
+        if renamed {
+            write!(f, "{} AS {}", self.expr, self.out_name)
+        } else {
+            self.expr.fmt(f)
+        }
+    }
+}
+
+/// One of the comma-separated entries of a select list
+#[derive(PartialEq, Debug, Clone)]
+pub enum SelectItem {
+    /// `*`, or `t.*` for only the columns of table `t`
+    Wildcard(Option<String>),
+    Column(ColumnProjection),
+}
+impl SelectItem {
+    /// The selected expression, unless this is a wildcard
+    fn expr(&self) -> Option<&Expression> {
+        match self {
+            Self::Wildcard(_) => None,
+            Self::Column(col) => Some(&col.expr),
+        }
+    }
+
+    fn expr_mut(&mut self) -> Option<&mut Expression> {
+        match self {
+            Self::Wildcard(_) => None,
+            Self::Column(col) => Some(&mut col.expr),
+        }
+    }
+}
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard(None) => f.write_char('*'),
+            Self::Wildcard(Some(table)) => write!(f, "{table}.*"),
+            Self::Column(col) => col.fmt(f),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum SelectColumns {
+    /// A lone `*`
     All,
-    Only(Vec<ColumnProjection>),
+    Only(Vec<SelectItem>),
+    /// `COUNT(*)`, which produces a single row holding the number of rows selected
+    CountAll {
+        out_name: String,
+    },
+    /// `COUNT(DISTINCT expr)`, which counts the different values `expr` takes
+    CountDistinct {
+        expr: Box<Expression>,
+        out_name: String,
+    },
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum KeyColumn {
     Rowid,
     Column(String),
@@ -511,48 +1548,140 @@ impl KeyColumn {
     pub fn as_storage_key_column(&self, schema: &Schema) -> Result<storage::PrimaryKey> {
         match self {
             Self::Rowid => Ok(storage::PrimaryKey::Rowid),
-            Self::Column(name) => {
-                let col = match schema.column(name) {
-                    Some(col) => col.clone(),
-                    None => return Err(ParsingError::UnknownPrimaryKeyProvided),
-                };
-                let keyset = match col._type {
-                    DbType::Float => KeySet::Floats(BTreeSet::new()),
-                    DbType::Integer => KeySet::Integers(BTreeSet::new()),
-                    DbType::String => KeySet::Strings(BTreeSet::new()),
-                    DbType::UnsignedInt => KeySet::UnsignedInts(BTreeSet::new()),
-                };
-                Ok(storage::PrimaryKey::Column { col, keyset })
-            }
+            Self::Column(name) => match schema.column(name) {
+                Some(col) => Ok(storage::PrimaryKey::column(col.clone())),
+                None => Err(ParsingError::UnknownPrimaryKeyProvided),
+            },
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct CreateColumns {
     pub names: Vec<String>,
     pub types: Vec<DbType>,
+    pub collations: Vec<Collation>,
+    /// Both those written with a column and those written on their own
+    pub checks: Vec<CheckConstraint>,
+    pub foreign_keys: Vec<ForeignKey>,
     pub primary_key_col: KeyColumn,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Statement {
     Select(SelectStatement),
+    Union(UnionStatement),
     Create(CreateStatement),
     Insert(InsertStatement),
     Destroy(DestroyStatement),
     Delete(DeleteStatement),
+    Vacuum(VacuumStatement),
+    /// Describes how the inner statement would be executed, without executing it
+    Explain(Box<Statement>),
+    /// Starts a transaction, which lasts until the next `COMMIT` or `ROLLBACK`
+    Begin,
+    Commit,
+    Rollback,
+}
+impl Statement {
+    /// Replaces placeholders with the values bound to them. Any placeholder
+    /// without a value is left as is, and fails when executed.
+    pub fn bind(&mut self, values: &[(&str, DbValue)]) {
+        match self {
+            Self::Select(select_stmt) => select_stmt.bind(values),
+            Self::Union(union_stmt) => {
+                union_stmt.first.bind(values);
+                for (_, select) in union_stmt.rest.iter_mut() {
+                    select.bind(values);
+                }
+            }
+            Self::Insert(insert_stmt) => insert_stmt.values.iter_mut().for_each(|v| v.bind(values)),
+            Self::Delete(delete_stmt) => {
+                if let Some(clause) = &mut delete_stmt.where_clause {
+                    clause.bind(values);
+                }
+            }
+            Self::Explain(inner) => inner.bind(values),
+            Self::Create(_)
+            | Self::Destroy(_)
+            | Self::Vacuum(_)
+            | Self::Begin
+            | Self::Commit
+            | Self::Rollback => {}
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum SelectSource {
     Table(String),
     Expression(SelectStatement),
+    /// `FROM a, b`, which pairs every row of `left` with every row of table `right`.
+    /// Each table's columns are named `table.column` in the rows produced.
+    CrossJoin {
+        left: Box<SelectSource>,
+        right: String,
+    },
+    /// `FROM a JOIN b ON ...`, the rows of a cross join for which `on` holds
+    Join {
+        left: Box<SelectSource>,
+        right: String,
+        on: WhereClause,
+    },
+    /// No `FROM` clause, so the columns are evaluated once, against a single row with
+    /// no columns
+    NoTable,
+}
+impl SelectSource {
+    /// The table the source's columns belong to, if they all belong to one
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            Self::Table(name) => Some(name),
+            Self::Expression(_) | Self::CrossJoin { .. } | Self::Join { .. } | Self::NoTable => {
+                None
+            }
+        }
+    }
+
+    fn references_column(&self, name: &str) -> bool {
+        match self {
+            Self::Table(_) | Self::Expression(_) | Self::NoTable => false,
+            Self::CrossJoin { left, right: _ } => left.references_column(name),
+            Self::Join { left, right: _, on } => {
+                left.references_column(name) || on.references_column(name)
+            }
+        }
+    }
+
+    fn bind(&mut self, values: &[(&str, DbValue)]) {
+        match self {
+            Self::Table(_) | Self::NoTable => {}
+            Self::Expression(inner) => inner.bind(values),
+            Self::CrossJoin { left, right: _ } => left.bind(values),
+            Self::Join { left, right: _, on } => {
+                left.bind(values);
+                on.bind(values);
+            }
+        }
+    }
+}
+impl fmt::Display for SelectSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table(name) => f.write_str(name),
+            Self::Expression(inner) => write!(f, "({inner})"),
+            Self::CrossJoin { left, right } => write!(f, "{left}, {right}"),
+            Self::Join { left, right, on } => write!(f, "{left} JOIN {right} ON {on}"),
+            // only ever shown as a missing `FROM` clause
+            Self::NoTable => Ok(()),
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct SelectStatement {
     pub columns: SelectColumns,
+    pub distinct: bool,
     pub source: Box<SelectSource>,
     pub where_clause: Option<WhereClause>,
     pub order_by_clause: Option<OrderByClause>,
@@ -560,64 +1689,232 @@ pub struct SelectStatement {
 }
 impl SelectStatement {
     pub fn uses_row_id(&self) -> bool {
-        if let SelectColumns::Only(cols) = &self.columns {
-            if cols.iter().any(|p| p.in_name == "rowid") {
-                return true;
+        self.references_column("rowid")
+    }
+
+    pub fn references_column(&self, name: &str) -> bool {
+        match &self.columns {
+            SelectColumns::Only(cols) => {
+                if cols
+                    .iter()
+                    .filter_map(SelectItem::expr)
+                    .any(|expr| expr.references_column(name))
+                {
+                    return true;
+                }
             }
+            SelectColumns::CountDistinct { expr, out_name: _ } => {
+                if expr.references_column(name) {
+                    return true;
+                }
+            }
+            SelectColumns::All | SelectColumns::CountAll { out_name: _ } => {}
         }
-        if let Some(WhereClause {
-            left: WhereMember::Column(col),
-            cmp: _,
-            right: _,
-        }) = &self.where_clause
-        {
-            if col == "rowid" {
-                return true;
-            };
+        if self.source.references_column(name) {
+            return true;
         }
-        if let Some(WhereClause {
-            left: _,
-            cmp: _,
-            right: WhereMember::Column(col),
-        }) = &self.where_clause
-        {
-            if col == "rowid" {
+        if let Some(clause) = &self.where_clause {
+            if clause.references_column(name) {
                 return true;
-            };
+            }
         }
         if let Some(clause) = &self.order_by_clause {
-            if clause.sort_column() == "rowid" {
+            if clause.sort_column().name == name {
                 return true;
             }
         }
         false
     }
+
+    /// Replaces the columns `value_of` gives a value for in the selected columns
+    /// and where clause. Used to run a correlated subquery against an outer row.
+    pub fn substitute_columns(&mut self, value_of: &mut dyn FnMut(&ColumnRef) -> Option<DbValue>) {
+        match &mut self.columns {
+            SelectColumns::Only(cols) => cols
+                .iter_mut()
+                .filter_map(SelectItem::expr_mut)
+                .for_each(|expr| expr.substitute_columns(value_of)),
+            SelectColumns::CountDistinct { expr, out_name: _ } => expr.substitute_columns(value_of),
+            SelectColumns::All | SelectColumns::CountAll { out_name: _ } => {}
+        }
+        if let Some(clause) = &mut self.where_clause {
+            clause.substitute_columns(value_of);
+        }
+    }
+
+    fn bind(&mut self, values: &[(&str, DbValue)]) {
+        match &mut self.columns {
+            SelectColumns::Only(cols) => cols
+                .iter_mut()
+                .filter_map(SelectItem::expr_mut)
+                .for_each(|expr| expr.bind(values)),
+            SelectColumns::CountDistinct { expr, out_name: _ } => expr.bind(values),
+            SelectColumns::All | SelectColumns::CountAll { out_name: _ } => {}
+        }
+        self.source.bind(values);
+        if let Some(clause) = &mut self.where_clause {
+            clause.bind(values);
+        }
+    }
+}
+
+impl fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SELECT ")?;
+        if self.distinct {
+            f.write_str("DISTINCT ")?;
+        }
+        match &self.columns {
+            SelectColumns::All => f.write_char('*')?,
+            SelectColumns::Only(cols) => {
+                for (i, col) in cols.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{col}")?;
+                }
+            }
+            SelectColumns::CountAll { out_name } => {
+                f.write_str("COUNT(*)")?;
+                if out_name != "count(*)" {
+                    write!(f, " AS {out_name}")?;
+                }
+            }
+            SelectColumns::CountDistinct { expr, out_name } => {
+                write!(f, "COUNT(DISTINCT {expr})")?;
+                if *out_name != format!("count(DISTINCT {expr})") {
+                    write!(f, " AS {out_name}")?;
+                }
+            }
+        }
+        if *self.source != SelectSource::NoTable {
+            write!(f, " FROM {}", self.source)?;
+        }
+        if let Some(clause) = &self.where_clause {
+            write!(f, " WHERE {clause}")?;
+        }
+        if let Some(clause) = &self.order_by_clause {
+            write!(f, " ORDER BY {clause}")?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Selects whose rows are combined by `UNION`, which drops duplicate rows, or
+/// `UNION ALL`, which keeps them. The combined rows take their column names from
+/// the first select.
+#[derive(PartialEq, Debug, Clone)]
+pub struct UnionStatement {
+    pub first: SelectStatement,
+    /// Each following select, and whether it was joined by `UNION ALL`. They're
+    /// combined from left to right, so a `UNION` drops duplicates of every row before it.
+    pub rest: Vec<(bool, SelectStatement)>,
+    pub order_by_clause: Option<OrderByClause>,
+    pub limit: Option<usize>,
+}
+impl fmt::Display for UnionStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (all, select) in self.rest.iter() {
+            match all {
+                true => write!(f, " UNION ALL {select}")?,
+                false => write!(f, " UNION {select}")?,
+            }
+        }
+        if let Some(clause) = &self.order_by_clause {
+            write!(f, " ORDER BY {clause}")?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct CreateStatement {
     pub table: String,
     pub if_not_exists: bool,
     pub columns: CreateColumns,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct InsertStatement {
     pub table: String,
+    /// Empty when the statement has no column list, meaning every column in order
     pub columns: Vec<String>,
-    pub values: Vec<DbValue>,
+    /// Literal values, or placeholders for values bound before execution
+    pub values: Vec<Expression>,
     pub conflict_clause: Option<ConflictClause>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct DestroyStatement {
     pub table: String,
+    /// A missing table is left alone rather than being an error
+    pub if_exists: bool,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct VacuumStatement {
+    /// When missing, every table is vacuumed
+    pub table: Option<String>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum WhereMember {
     Value(DbValue),
-    Column(String),
+    Column(ColumnRef),
+    /// Anything more involved than a column or value, like a function call
+    Expression(Box<Expression>),
+}
+impl WhereMember {
+    pub fn into_expression(self) -> Expression {
+        match self {
+            Self::Value(v) => Expression::Value(v),
+            Self::Column(col) => Expression::Column(col),
+            Self::Expression(expr) => *expr,
+        }
+    }
+
+    fn bind(&mut self, values: &[(&str, DbValue)]) {
+        if let Self::Expression(expr) = self {
+            expr.bind(values);
+            // a bound placeholder can be compared like any other value
+            if let Expression::Value(val) = expr.as_ref() {
+                *self = Self::Value(val.clone());
+            }
+        }
+    }
+
+    fn substitute_columns(&mut self, value_of: &mut dyn FnMut(&ColumnRef) -> Option<DbValue>) {
+        match self {
+            Self::Value(_) => {}
+            Self::Column(col) => {
+                if let Some(val) = value_of(col) {
+                    *self = Self::Value(val);
+                }
+            }
+            Self::Expression(expr) => {
+                expr.substitute_columns(value_of);
+                if let Expression::Value(val) = expr.as_ref() {
+                    *self = Self::Value(val.clone());
+                }
+            }
+        }
+    }
+}
+impl fmt::Display for WhereMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(v) => v.fmt(f),
+            Self::Column(col) => col.fmt(f),
+            Self::Expression(expr) => expr.fmt(f),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -639,21 +1936,106 @@ impl WhereCmp {
         }
     }
 }
+impl fmt::Display for WhereCmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eq => f.write_char('='),
+            Self::LessThan => f.write_char('<'),
+            Self::GreaterThan => f.write_char('>'),
+            Self::LessThanEquals => f.write_str("<="),
+            Self::GreaterThanEquals => f.write_str(">="),
+        }
+    }
+}
 
 #[derive(PartialEq, Debug, Clone)]
-pub struct WhereClause {
-    pub left: WhereMember,
-    pub cmp: WhereCmp,
-    pub right: WhereMember,
+pub enum WhereClause {
+    Comparison {
+        left: WhereMember,
+        cmp: WhereCmp,
+        right: WhereMember,
+    },
+    /// `[NOT] EXISTS (subquery)`, which holds when the subquery returns any rows. The
+    /// subquery can refer to columns of the row being checked.
+    Exists {
+        negated: bool,
+        subquery: Box<SelectStatement>,
+    },
 }
+impl WhereClause {
+    pub fn references_column(&self, name: &str) -> bool {
+        match self {
+            Self::Comparison {
+                left,
+                cmp: _,
+                right,
+            } => [left, right].into_iter().any(|member| match member {
+                WhereMember::Value(_) => false,
+                WhereMember::Column(col) => col.name == name,
+                WhereMember::Expression(expr) => expr.references_column(name),
+            }),
+            Self::Exists {
+                negated: _,
+                subquery,
+            } => subquery.references_column(name),
+        }
+    }
 
-#[derive(PartialEq, Debug)]
+    pub fn bind(&mut self, values: &[(&str, DbValue)]) {
+        match self {
+            Self::Comparison {
+                left,
+                cmp: _,
+                right,
+            } => {
+                left.bind(values);
+                right.bind(values);
+            }
+            Self::Exists {
+                negated: _,
+                subquery,
+            } => subquery.bind(values),
+        }
+    }
+
+    fn substitute_columns(&mut self, value_of: &mut dyn FnMut(&ColumnRef) -> Option<DbValue>) {
+        match self {
+            Self::Comparison {
+                left,
+                cmp: _,
+                right,
+            } => {
+                left.substitute_columns(value_of);
+                right.substitute_columns(value_of);
+            }
+            Self::Exists {
+                negated: _,
+                subquery,
+            } => subquery.substitute_columns(value_of),
+        }
+    }
+}
+impl fmt::Display for WhereClause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Comparison { left, cmp, right } => write!(f, "{left} {cmp} {right}"),
+            Self::Exists { negated, subquery } => {
+                if *negated {
+                    f.write_str("NOT ")?;
+                }
+                write!(f, "EXISTS ({subquery})")
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct OrderByClause {
-    sort_column: String,
+    sort_column: ColumnRef,
     desc: bool,
 }
 impl OrderByClause {
-    pub fn sort_column(&self) -> &str {
+    pub fn sort_column(&self) -> &ColumnRef {
         &self.sort_column
     }
 
@@ -661,20 +2043,31 @@ impl OrderByClause {
         self.desc
     }
 }
+impl fmt::Display for OrderByClause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.sort_column.fmt(f)?;
+        if self.desc {
+            f.write_str(" DESC")?;
+        }
+        Ok(())
+    }
+}
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum ConflictAction {
     Nothing,
+    Replace,
 }
 impl ConflictAction {
     pub fn as_storage_conflict_action(&self) -> storage::ConflictAction {
         match self {
             Self::Nothing => storage::ConflictAction::Nothing,
+            Self::Replace => storage::ConflictAction::Replace,
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct ConflictClause {
     target_columns: Vec<String>,
     action: ConflictAction,
@@ -688,78 +2081,552 @@ impl ConflictClause {
             action: self.action.as_storage_conflict_action(),
         }
     }
-}
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct DeleteStatement {
+    pub table: String,
+    /// When missing, every row is deleted
+    pub where_clause: Option<WhereClause>,
+}
+
+#[cfg(test)]
+mod parser_tests {
+    use super::*;
+
+    fn only(cols: Vec<ColumnProjection>) -> SelectColumns {
+        SelectColumns::Only(cols.into_iter().map(SelectItem::Column).collect())
+    }
+
+    fn parse_error(input: &str) -> ParsingError {
+        let tokens = Tokenizer::new(input);
+        Parser::build(tokens).unwrap().parse().unwrap_err()
+    }
+
+    #[test]
+    fn select_from_several_tables() {
+        let stmt = "select * from a, b, \"c d\" where a.x = b.y;";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let select = match &actual[0] {
+            Statement::Select(select) => select,
+            _ => panic!("Expected a select statement"),
+        };
+        assert_eq!(
+            *select.source,
+            SelectSource::CrossJoin {
+                left: Box::new(SelectSource::CrossJoin {
+                    left: Box::new(SelectSource::Table(String::from("a"))),
+                    right: String::from("b"),
+                }),
+                right: String::from("c d"),
+            }
+        );
+        assert_eq!(select.source.table(), None);
+        // only tables can be joined, since a subquery's columns have no table to qualify them
+        assert!(matches!(
+            parse_error("select * from (select x from a), b;").unlocated(),
+            ParsingError::UnexpectedToken { .. }
+        ));
+    }
+
+    #[test]
+    fn select_from_joined_tables() {
+        let stmt = "select * from a join b on a.x = y, c;";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let select = match &actual[0] {
+            Statement::Select(select) => select,
+            _ => panic!("Expected a select statement"),
+        };
+        assert_eq!(
+            *select.source,
+            SelectSource::CrossJoin {
+                left: Box::new(SelectSource::Join {
+                    left: Box::new(SelectSource::Table(String::from("a"))),
+                    right: String::from("b"),
+                    on: WhereClause::Comparison {
+                        left: WhereMember::Column(ColumnRef::qualified(
+                            String::from("a"),
+                            String::from("x")
+                        )),
+                        cmp: WhereCmp::Eq,
+                        right: WhereMember::Column(ColumnRef::new(String::from("y"))),
+                    },
+                }),
+                right: String::from("c"),
+            }
+        );
+        assert_eq!(select.source.to_string(), "a JOIN b ON a.x = y, c");
+        assert!(select.references_column("x"));
+        assert!(matches!(
+            parse_error("select * from a join b;").unlocated(),
+            ParsingError::UnexpectedToken { .. }
+        ));
+    }
+
+    #[test]
+    fn select_wildcards() {
+        let stmt = "select *, t.*, t.a, abs(b) + 1 from t;";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let select = match &actual[0] {
+            Statement::Select(select) => select,
+            _ => panic!("Expected a select statement"),
+        };
+        assert_eq!(
+            select.columns,
+            SelectColumns::Only(vec![
+                SelectItem::Wildcard(None),
+                SelectItem::Wildcard(Some(String::from("t"))),
+                SelectItem::Column(ColumnProjection {
+                    expr: Expression::Column(ColumnRef::qualified(
+                        String::from("t"),
+                        String::from("a")
+                    )),
+                    out_name: String::from("a"),
+                }),
+                SelectItem::Column(ColumnProjection {
+                    expr: Expression::arithmetic(
+                        Expression::Function {
+                            func: ScalarFunction::Abs,
+                            args: vec![Expression::Column(ColumnRef::new(String::from("b")))],
+                        },
+                        ArithmeticOp::Add,
+                        Expression::Value(DbValue::Integer(1)),
+                    ),
+                    out_name: String::from("ABS(b) + 1"),
+                }),
+            ])
+        );
+        assert_eq!(select.to_string(), "SELECT *, t.*, t.a, ABS(b) + 1 FROM t");
+
+        let actual = Parser::build(Tokenizer::new("select * from t;"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            &actual[0],
+            Statement::Select(SelectStatement {
+                columns: SelectColumns::All,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn consume() {
+        let stmt = "\"that\" this";
+        let tokens = Tokenizer::new(stmt);
+        let mut parser = Parser::build(tokens).unwrap();
+
+        assert_eq!(
+            parser.consume(TokenKind::String).unwrap(),
+            Token::new("that", TokenKind::String, Span { start: 0, end: 6 })
+        );
+
+        let res = parser.consume(TokenKind::String);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn basic_select() {
+        let stmt = "select foo, bar from the_data;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![
+                ColumnProjection::no_projection(String::from("foo")),
+                ColumnProjection::no_projection(String::from("bar")),
+            ]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("the_data"))),
+            where_clause: None,
+            order_by_clause: None,
+            limit: None,
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn select_with_as() {
+        let stmt = "select a as b, bar, c as d from the_data;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![
+                ColumnProjection::new(String::from("a"), String::from("b")),
+                ColumnProjection::no_projection(String::from("bar")),
+                ColumnProjection::new(String::from("c"), String::from("d")),
+            ]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("the_data"))),
+            where_clause: None,
+            order_by_clause: None,
+            limit: None,
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn select_with_arithmetic() {
+        let stmt = "select price * quantity, a + b * 2 as c, (a + b) / -2, a -1 from the_data;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let col = |name: &str| Expression::Column(ColumnRef::new(String::from(name)));
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        col("price"),
+                        ArithmeticOp::Multiply,
+                        col("quantity"),
+                    ),
+                    out_name: String::from("price * quantity"),
+                },
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        col("a"),
+                        ArithmeticOp::Add,
+                        Expression::arithmetic(
+                            col("b"),
+                            ArithmeticOp::Multiply,
+                            Expression::Value(DbValue::Integer(2)),
+                        ),
+                    ),
+                    out_name: String::from("c"),
+                },
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        Expression::arithmetic(col("a"), ArithmeticOp::Add, col("b")),
+                        ArithmeticOp::Divide,
+                        Expression::Value(DbValue::Integer(-2)),
+                    ),
+                    out_name: String::from("(a + b) / -2"),
+                },
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        col("a"),
+                        ArithmeticOp::Subtract,
+                        Expression::Value(DbValue::Integer(1)),
+                    ),
+                    out_name: String::from("a - 1"),
+                },
+            ]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("the_data"))),
+            where_clause: None,
+            order_by_clause: None,
+            limit: None,
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn select_with_qualified_columns() {
+        let stmt = "select t.a, t.b * 2 from t where t.a > 1 order by t.b;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let col = |name: &str| ColumnRef::qualified(String::from("t"), String::from(name));
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![
+                ColumnProjection {
+                    expr: Expression::Column(col("a")),
+                    out_name: String::from("a"),
+                },
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        Expression::Column(col("b")),
+                        ArithmeticOp::Multiply,
+                        Expression::Value(DbValue::Integer(2)),
+                    ),
+                    out_name: String::from("t.b * 2"),
+                },
+            ]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("t"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Column(col("a")),
+                cmp: WhereCmp::GreaterThan,
+                right: WhereMember::Value(DbValue::Integer(1)),
+            }),
+            order_by_clause: Some(OrderByClause {
+                sort_column: col("b"),
+                desc: false,
+            }),
+            limit: None,
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn select_without_from() {
+        let stmt = "select 1 + 1 as two;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![ColumnProjection {
+                expr: Expression::arithmetic(
+                    Expression::Value(DbValue::Integer(1)),
+                    ArithmeticOp::Add,
+                    Expression::Value(DbValue::Integer(1)),
+                ),
+                out_name: String::from("two"),
+            }]),
+            distinct: false,
+            source: Box::new(SelectSource::NoTable),
+            where_clause: None,
+            order_by_clause: None,
+            limit: None,
+        })];
+        let Statement::Select(select) = &actual[0] else {
+            unreachable!()
+        };
+        assert_eq!(select.to_string(), "SELECT 1 + 1 AS two");
+        assert_eq!(actual, expected);
+
+        // there are no columns for a wildcard to select
+        for stmt in ["select *;", "select 1, *;"] {
+            assert!(Parser::build(Tokenizer::new(stmt))
+                .unwrap()
+                .parse()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn unions() {
+        let stmt =
+            "select a from t union all select b from u union select 1 order by a desc limit 2;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let Statement::Union(union) = &actual[0] else {
+            panic!("Expected a union, got {actual:?}")
+        };
+        assert_eq!(
+            union.rest.iter().map(|(all, _)| *all).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        // the order and limit belong to the whole union, not its last select
+        assert!(union.order_by_clause.as_ref().is_some_and(|c| c.desc()));
+        assert_eq!(union.limit, Some(2));
+        assert_eq!(union.rest[1].1.order_by_clause, None);
+        assert_eq!(union.rest[1].1.limit, None);
+        assert_eq!(
+            union.to_string(),
+            "SELECT a FROM t UNION ALL SELECT b FROM u UNION SELECT 1 ORDER BY a DESC LIMIT 2"
+        );
+
+        for stmt in [
+            "select a from t order by a union select b from u;",
+            "select a from t limit 1 union select b from u;",
+            "select a from t union;",
+        ] {
+            assert!(
+                Parser::build(Tokenizer::new(stmt))
+                    .unwrap()
+                    .parse()
+                    .is_err(),
+                "{stmt:?} should not parse"
+            );
+        }
+    }
 
-#[derive(PartialEq, Debug)]
-pub struct DeleteStatement {
-    pub table: String,
-    pub where_clause: WhereClause,
-}
-impl DeleteStatement {
-    pub fn generated_select_statement(&self) -> SelectStatement {
-        SelectStatement {
-            columns: SelectColumns::Only(vec![ColumnProjection::no_projection(String::from(
-                "rowid",
-            ))]),
-            source: Box::new(SelectSource::Table(self.table.clone())),
-            where_clause: Some(self.where_clause.clone()),
+    #[test]
+    fn select_with_exists() {
+        let stmt = "select a from t where not exists (select 1 from u where u.a = t.a);";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let subquery = SelectStatement {
+            columns: only(vec![ColumnProjection {
+                expr: Expression::Value(DbValue::Integer(1)),
+                out_name: String::from("1"),
+            }]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("u"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Column(ColumnRef::qualified(
+                    String::from("u"),
+                    String::from("a"),
+                )),
+                cmp: WhereCmp::Eq,
+                right: WhereMember::Column(ColumnRef::qualified(
+                    String::from("t"),
+                    String::from("a"),
+                )),
+            }),
             order_by_clause: None,
             limit: None,
-        }
-    }
-}
+        };
+        assert_eq!(subquery.to_string(), "SELECT 1 FROM u WHERE u.a = t.a");
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![ColumnProjection::no_projection(String::from("a"))]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("t"))),
+            where_clause: Some(WhereClause::Exists {
+                negated: true,
+                subquery: Box::new(subquery),
+            }),
+            order_by_clause: None,
+            limit: None,
+        })];
 
-#[cfg(test)]
-mod parser_tests {
-    use super::*;
+        assert_eq!(actual, expected);
+    }
 
     #[test]
-    fn consume() {
-        let stmt = "\"that\" this";
-        let tokens = Tokenizer::new(stmt);
-        let mut parser = Parser::build(tokens).unwrap();
+    fn select_with_scalar_subquery() {
+        let stmt = "select id, (select count(*) from orders where uid = id) + 1 from users;";
 
-        assert_eq!(
-            parser.consume(TokenKind::String).unwrap(),
-            Token::new("that", TokenKind::String)
-        );
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let subquery = SelectStatement {
+            columns: SelectColumns::CountAll {
+                out_name: String::from("count(*)"),
+            },
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("orders"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Column(ColumnRef::new(String::from("uid"))),
+                cmp: WhereCmp::Eq,
+                right: WhereMember::Column(ColumnRef::new(String::from("id"))),
+            }),
+            order_by_clause: None,
+            limit: None,
+        };
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![
+                ColumnProjection::no_projection(String::from("id")),
+                ColumnProjection {
+                    expr: Expression::arithmetic(
+                        Expression::Subquery(Box::new(subquery)),
+                        ArithmeticOp::Add,
+                        Expression::Value(DbValue::Integer(1)),
+                    ),
+                    out_name: String::from("(SELECT COUNT(*) FROM orders WHERE uid = id) + 1"),
+                },
+            ]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("users"))),
+            where_clause: None,
+            order_by_clause: None,
+            limit: None,
+        })];
 
-        let res = parser.consume(TokenKind::String);
-        assert!(res.is_err());
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn basic_select() {
-        let stmt = "select foo, bar from the_data;";
+    fn select_with_function_calls() {
+        let stmt = "select upper(a), round(b * 2, 1) from t where length(a) > 3;";
 
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let col = |name: &str| Expression::Column(ColumnRef::new(String::from(name)));
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
-                ColumnProjection::no_projection(String::from("foo")),
-                ColumnProjection::no_projection(String::from("bar")),
+            columns: only(vec![
+                ColumnProjection {
+                    expr: Expression::Function {
+                        func: ScalarFunction::Upper,
+                        args: vec![col("a")],
+                    },
+                    out_name: String::from("UPPER(a)"),
+                },
+                ColumnProjection {
+                    expr: Expression::Function {
+                        func: ScalarFunction::Round,
+                        args: vec![
+                            Expression::arithmetic(
+                                col("b"),
+                                ArithmeticOp::Multiply,
+                                Expression::Value(DbValue::Integer(2)),
+                            ),
+                            Expression::Value(DbValue::Integer(1)),
+                        ],
+                    },
+                    out_name: String::from("ROUND(b * 2, 1)"),
+                },
             ]),
-            source: Box::new(SelectSource::Table(String::from("the_data"))),
-            where_clause: None,
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("t"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Expression(Box::new(Expression::Function {
+                    func: ScalarFunction::Length,
+                    args: vec![col("a")],
+                })),
+                cmp: WhereCmp::GreaterThan,
+                right: WhereMember::Value(DbValue::Integer(3)),
+            }),
             order_by_clause: None,
             limit: None,
         })];
+        assert_eq!(actual, expected);
+
+        assert!(matches!(
+            parse_error("select nope(a) from t;").unlocated(),
+            ParsingError::UnknownFunction(name) if name == "nope"
+        ));
+        assert!(matches!(
+            parse_error("select upper(a, b) from t;").unlocated(),
+            ParsingError::WrongArgumentCount(ScalarFunction::Upper)
+        ));
+    }
 
+    #[test]
+    fn select_with_cast() {
+        let stmt = "select cast(a as unsigned int) as b from t where cast(a as string) = \"1\";";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let cast = |to| Expression::Cast {
+            expr: Box::new(Expression::Column(ColumnRef::new(String::from("a")))),
+            to,
+        };
+        let expected = vec![Statement::Select(SelectStatement {
+            columns: only(vec![ColumnProjection {
+                expr: cast(DbType::UnsignedInt),
+                out_name: String::from("b"),
+            }]),
+            distinct: false,
+            source: Box::new(SelectSource::Table(String::from("t"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Expression(Box::new(cast(DbType::String))),
+                cmp: WhereCmp::Eq,
+                right: WhereMember::Expression(Box::new(Expression::QuotedName(String::from("1")))),
+            }),
+            order_by_clause: None,
+            limit: None,
+        })];
         assert_eq!(actual, expected);
+        assert_eq!(
+            cast(DbType::UnsignedInt).to_string(),
+            "CAST(a AS UNSIGNED INT)"
+        );
     }
 
     #[test]
-    fn select_with_as() {
-        let stmt = "select a as b, bar, c as d from the_data;";
+    fn select_distinct() {
+        let stmt = "select distinct a, b from the_data;";
 
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
-                ColumnProjection::new(String::from("a"), String::from("b")),
-                ColumnProjection::no_projection(String::from("bar")),
-                ColumnProjection::new(String::from("c"), String::from("d")),
+            columns: only(vec![
+                ColumnProjection::no_projection(String::from("a")),
+                ColumnProjection::no_projection(String::from("b")),
             ]),
+            distinct: true,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: None,
@@ -769,6 +2636,75 @@ mod parser_tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn select_count() {
+        let stmt = "select count(*) from the_data; \
+            select COUNT(*) as total from the_data where a = 1; \
+            select count(distinct a) from the_data;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![
+            Statement::Select(SelectStatement {
+                columns: SelectColumns::CountAll {
+                    out_name: String::from("count(*)"),
+                },
+                distinct: false,
+                source: Box::new(SelectSource::Table(String::from("the_data"))),
+                where_clause: None,
+                order_by_clause: None,
+                limit: None,
+            }),
+            Statement::Select(SelectStatement {
+                columns: SelectColumns::CountAll {
+                    out_name: String::from("total"),
+                },
+                distinct: false,
+                source: Box::new(SelectSource::Table(String::from("the_data"))),
+                where_clause: Some(WhereClause::Comparison {
+                    left: WhereMember::Column(ColumnRef::new(String::from("a"))),
+                    cmp: WhereCmp::Eq,
+                    right: WhereMember::Value(DbValue::Integer(1)),
+                }),
+                order_by_clause: None,
+                limit: None,
+            }),
+            Statement::Select(SelectStatement {
+                columns: SelectColumns::CountDistinct {
+                    expr: Box::new(Expression::Column(ColumnRef::new(String::from("a")))),
+                    out_name: String::from("count(DISTINCT a)"),
+                },
+                distinct: false,
+                source: Box::new(SelectSource::Table(String::from("the_data"))),
+                where_clause: None,
+                order_by_clause: None,
+                limit: None,
+            }),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn explain() {
+        let stmt = "explain select a from the_data;";
+
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Explain(Box::new(Statement::Select(
+            SelectStatement {
+                columns: only(vec![ColumnProjection::no_projection(String::from("a"))]),
+                distinct: false,
+                source: Box::new(SelectSource::Table(String::from("the_data"))),
+                where_clause: None,
+                order_by_clause: None,
+                limit: None,
+            },
+        )))];
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn basic_select_star() {
         let stmt = "select * from the_data;";
@@ -777,6 +2713,7 @@ mod parser_tests {
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
             columns: SelectColumns::All,
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: None,
@@ -793,15 +2730,18 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
-            where_clause: Some(WhereClause {
-                left: WhereMember::Column(String::from("that")),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Column(ColumnRef::new(String::from("that"))),
                 cmp: WhereCmp::Eq,
-                right: WhereMember::Value(DbValue::String(String::from("this"))),
+                right: WhereMember::Expression(Box::new(Expression::QuotedName(String::from(
+                    "this",
+                )))),
             }),
             order_by_clause: None,
             limit: None,
@@ -817,12 +2757,13 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
-            where_clause: Some(WhereClause {
+            where_clause: Some(WhereClause::Comparison {
                 left: WhereMember::Value(DbValue::Integer(1)),
                 cmp: WhereCmp::LessThan,
                 right: WhereMember::Value(DbValue::Integer(2)),
@@ -841,12 +2782,13 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
-            where_clause: Some(WhereClause {
+            where_clause: Some(WhereClause::Comparison {
                 left: WhereMember::Value(DbValue::Integer(1)),
                 cmp: WhereCmp::GreaterThan,
                 right: WhereMember::Value(DbValue::Integer(2)),
@@ -865,14 +2807,15 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: Some(OrderByClause {
-                sort_column: String::from("baz"),
+                sort_column: ColumnRef::new(String::from("baz")),
                 desc: false,
             }),
             limit: None,
@@ -888,14 +2831,15 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: Some(OrderByClause {
-                sort_column: String::from("baz"),
+                sort_column: ColumnRef::new(String::from("baz")),
                 desc: true,
             }),
             limit: None,
@@ -912,6 +2856,7 @@ mod parser_tests {
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
             columns: SelectColumns::All,
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: None,
@@ -928,18 +2873,21 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
-            where_clause: Some(WhereClause {
-                left: WhereMember::Value(DbValue::String(String::from("this"))),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Expression(Box::new(Expression::QuotedName(String::from(
+                    "this",
+                )))),
                 cmp: WhereCmp::Eq,
-                right: WhereMember::Column(String::from("that")),
+                right: WhereMember::Column(ColumnRef::new(String::from("that"))),
             }),
             order_by_clause: Some(OrderByClause {
-                sort_column: String::from("baz"),
+                sort_column: ColumnRef::new(String::from("baz")),
                 desc: true,
             }),
             limit: Some(5),
@@ -955,10 +2903,11 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::no_projection(String::from("rowid")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: None,
@@ -975,10 +2924,11 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Select(SelectStatement {
-            columns: SelectColumns::Only(vec![
+            columns: only(vec![
                 ColumnProjection::no_projection(String::from("foo")),
                 ColumnProjection::new(String::from("rowid"), String::from("bar")),
             ]),
+            distinct: false,
             source: Box::new(SelectSource::Table(String::from("the_data"))),
             where_clause: None,
             order_by_clause: None,
@@ -999,6 +2949,9 @@ mod parser_tests {
             columns: CreateColumns {
                 names: vec![String::from("foo")],
                 types: vec![DbType::String],
+                collations: vec![Collation::Binary; 1],
+                checks: vec![],
+                foreign_keys: vec![],
                 primary_key_col: KeyColumn::Rowid,
             },
         })];
@@ -1017,6 +2970,9 @@ mod parser_tests {
             columns: CreateColumns {
                 names: vec![String::from("foo")],
                 types: vec![DbType::String],
+                collations: vec![Collation::Binary; 1],
+                checks: vec![],
+                foreign_keys: vec![],
                 primary_key_col: KeyColumn::Rowid,
             },
         })];
@@ -1035,10 +2991,118 @@ mod parser_tests {
             columns: CreateColumns {
                 names: vec![String::from("foo"), String::from("bar")],
                 types: vec![DbType::String, DbType::Integer],
+                collations: vec![Collation::Binary; 2],
+                checks: vec![],
+                foreign_keys: vec![],
+                primary_key_col: KeyColumn::Column(String::from("foo")),
+            },
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn create_with_collation() {
+        let stmt = "create table the_data (foo string collate nocase primary key, bar string);";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Create(CreateStatement {
+            table: String::from("the_data"),
+            if_not_exists: false,
+            columns: CreateColumns {
+                names: vec![String::from("foo"), String::from("bar")],
+                types: vec![DbType::String, DbType::String],
+                collations: vec![Collation::NoCase, Collation::Binary],
+                checks: vec![],
+                foreign_keys: vec![],
                 primary_key_col: KeyColumn::Column(String::from("foo")),
             },
         })];
+        assert_eq!(actual, expected);
+
+        let parse = |stmt| Parser::build(Tokenizer::new(stmt)).unwrap().parse();
+        assert!(matches!(
+            parse("create table t (foo integer collate nocase);")
+                .unwrap_err()
+                .unlocated(),
+            ParsingError::NonStringCollation
+        ));
+        assert!(matches!(
+            parse("create table t (foo string collate rtrim);")
+                .unwrap_err()
+                .unlocated(),
+            ParsingError::UnknownCollation(name) if name == "rtrim"
+        ));
+    }
+
+    #[test]
+    fn create_with_checks() {
+        let stmt = "create table t (a integer primary key check (a>=0), b integer, \
+            constraint ordered check ( a < b ));";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Create(CreateStatement {
+            table: String::from("t"),
+            if_not_exists: false,
+            columns: CreateColumns {
+                names: vec![String::from("a"), String::from("b")],
+                types: vec![DbType::Integer, DbType::Integer],
+                collations: vec![Collation::Binary; 2],
+                checks: vec![
+                    CheckConstraint {
+                        name: String::from("a>=0"),
+                        predicate: String::from("a>=0"),
+                    },
+                    CheckConstraint {
+                        name: String::from("ordered"),
+                        predicate: String::from("a < b"),
+                    },
+                ],
+                foreign_keys: vec![],
+                primary_key_col: KeyColumn::Column(String::from("a")),
+            },
+        })];
+        assert_eq!(actual, expected);
+
+        let parse_check = |input| {
+            Parser::build(Tokenizer::new(input))
+                .unwrap()
+                .parse_check_predicate()
+        };
+        assert!(parse_check("a < b").is_ok());
+        assert!(parse_check("exists (select a from t)").is_err());
+        assert!(parse_check("a < b c").is_err());
+    }
 
+    #[test]
+    fn create_with_foreign_keys() {
+        let stmt = "create table t (a integer, b string, foreign key (a) references p(id), \
+            foreign key(b) references q (name));";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Create(CreateStatement {
+            table: String::from("t"),
+            if_not_exists: false,
+            columns: CreateColumns {
+                names: vec![String::from("a"), String::from("b")],
+                types: vec![DbType::Integer, DbType::String],
+                collations: vec![Collation::Binary; 2],
+                checks: vec![],
+                foreign_keys: vec![
+                    ForeignKey {
+                        column: String::from("a"),
+                        parent_table: String::from("p"),
+                        parent_column: String::from("id"),
+                    },
+                    ForeignKey {
+                        column: String::from("b"),
+                        parent_table: String::from("q"),
+                        parent_column: String::from("name"),
+                    },
+                ],
+                primary_key_col: KeyColumn::Rowid,
+            },
+        })];
         assert_eq!(actual, expected);
     }
 
@@ -1048,14 +3112,14 @@ mod parser_tests {
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse();
         assert!(matches!(
-            actual.unwrap_err(),
+            actual.unwrap_err().unlocated(),
             ParsingError::MultiplePrimaryKeys
         ));
     }
 
     #[test]
     fn create_table_all_types() {
-        let stmt = "create table the_data (foo string, bar integer, baz float);";
+        let stmt = "create table the_data (foo string, bar integer, baz float, qux char(8));";
         let tokens = Tokenizer::new(stmt);
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Create(CreateStatement {
@@ -1066,8 +3130,17 @@ mod parser_tests {
                     String::from("foo"),
                     String::from("bar"),
                     String::from("baz"),
+                    String::from("qux"),
+                ],
+                types: vec![
+                    DbType::String,
+                    DbType::Integer,
+                    DbType::Float,
+                    DbType::Char(8),
                 ],
-                types: vec![DbType::String, DbType::Integer, DbType::Float],
+                collations: vec![Collation::Binary; 4],
+                checks: vec![],
+                foreign_keys: vec![],
                 primary_key_col: KeyColumn::Rowid,
             },
         })];
@@ -1088,14 +3161,30 @@ mod parser_tests {
                 String::from("baz"),
             ],
             values: vec![
-                DbValue::String(String::from("thing")),
-                DbValue::Integer(42),
-                DbValue::Float(DbFloat::new(5.25)),
+                Expression::Value(DbValue::String(String::from("thing"))),
+                Expression::Value(DbValue::Integer(42)),
+                Expression::Value(DbValue::Float(DbFloat::new(5.25))),
             ],
             conflict_clause: None,
         })];
 
         assert_eq!(actual, expected);
+
+        let stmt = "insert into the_data values (1, 2);";
+        let actual = Parser::build(Tokenizer::new(stmt))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let expected = vec![Statement::Insert(InsertStatement {
+            table: String::from("the_data"),
+            columns: Vec::new(),
+            values: vec![
+                Expression::Value(DbValue::Integer(1)),
+                Expression::Value(DbValue::Integer(2)),
+            ],
+            conflict_clause: None,
+        })];
+        assert_eq!(actual, expected);
     }
 
     #[test]
@@ -1111,9 +3200,9 @@ mod parser_tests {
                 String::from("baz"),
             ],
             values: vec![
-                DbValue::String(String::from("thing")),
-                DbValue::Integer(42),
-                DbValue::Float(DbFloat::new(5.25)),
+                Expression::Value(DbValue::String(String::from("thing"))),
+                Expression::Value(DbValue::Integer(42)),
+                Expression::Value(DbValue::Float(DbFloat::new(5.25))),
             ],
             conflict_clause: Some(ConflictClause {
                 target_columns: vec![String::from("foo"), String::from("bar")],
@@ -1124,6 +3213,52 @@ mod parser_tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn placeholders_are_bound() {
+        let stmt = "insert into t (a, b) values (:a, 1); select a + :b from t where a = :a;";
+        let tokens = Tokenizer::new(stmt);
+        let mut actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let placeholder = |name: &str| Expression::Placeholder(String::from(name));
+        match &actual[0] {
+            Statement::Insert(insert_stmt) => assert_eq!(
+                insert_stmt.values,
+                vec![placeholder(":a"), Expression::Value(DbValue::Integer(1))]
+            ),
+            _ => panic!("Expected an insert statement"),
+        }
+
+        let values = [(":a", DbValue::Integer(5))];
+        actual.iter_mut().for_each(|s| s.bind(&values));
+        let a = || Expression::Column(ColumnRef::new(String::from("a")));
+        let expected = vec![
+            Statement::Insert(InsertStatement {
+                table: String::from("t"),
+                columns: vec![String::from("a"), String::from("b")],
+                values: vec![
+                    Expression::Value(DbValue::Integer(5)),
+                    Expression::Value(DbValue::Integer(1)),
+                ],
+                conflict_clause: None,
+            }),
+            Statement::Select(SelectStatement {
+                columns: only(vec![ColumnProjection {
+                    expr: Expression::arithmetic(a(), ArithmeticOp::Add, placeholder(":b")),
+                    out_name: String::from("a + :b"),
+                }]),
+                distinct: false,
+                source: Box::new(SelectSource::Table(String::from("t"))),
+                where_clause: Some(WhereClause::Comparison {
+                    left: WhereMember::Column(ColumnRef::new(String::from("a"))),
+                    cmp: WhereCmp::Eq,
+                    right: WhereMember::Value(DbValue::Integer(5)),
+                }),
+                order_by_clause: None,
+                limit: None,
+            }),
+        ];
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn destroy() {
         let stmt = "destroy table the_data;";
@@ -1131,9 +3266,19 @@ mod parser_tests {
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Destroy(DestroyStatement {
             table: String::from("the_data"),
+            if_exists: false,
         })];
 
         assert_eq!(actual, expected);
+
+        let stmt = "destroy table if exists the_data;";
+        let tokens = Tokenizer::new(stmt);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Destroy(DestroyStatement {
+            table: String::from("the_data"),
+            if_exists: true,
+        })];
+        assert_eq!(actual, expected);
     }
 
     #[test]
@@ -1148,11 +3293,15 @@ mod parser_tests {
                 columns: CreateColumns {
                     names: vec![String::from("foo"), String::from("bar")],
                     types: vec![DbType::String, DbType::Integer],
+                    collations: vec![Collation::Binary; 2],
+                    checks: vec![],
+                    foreign_keys: vec![],
                     primary_key_col: KeyColumn::Rowid,
                 },
             }),
             Statement::Select(SelectStatement {
                 columns: SelectColumns::All,
+                distinct: false,
                 source: Box::new(SelectSource::Table(String::from("the_data"))),
                 where_clause: None,
                 order_by_clause: None,
@@ -1170,16 +3319,62 @@ mod parser_tests {
         let actual = Parser::build(tokens).unwrap().parse().unwrap();
         let expected = vec![Statement::Delete(DeleteStatement {
             table: String::from("the_data"),
-            where_clause: WhereClause {
-                left: WhereMember::Column(String::from("a")),
+            where_clause: Some(WhereClause::Comparison {
+                left: WhereMember::Column(ColumnRef::new(String::from("a"))),
                 cmp: WhereCmp::Eq,
-                right: WhereMember::Value(DbValue::String(String::from("thing"))),
-            },
+                right: WhereMember::Expression(Box::new(Expression::QuotedName(String::from(
+                    "thing",
+                )))),
+            }),
+        })];
+
+        assert_eq!(actual, expected);
+
+        let input = "delete from the_data;";
+        let tokens = Tokenizer::new(input);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![Statement::Delete(DeleteStatement {
+            table: String::from("the_data"),
+            where_clause: None,
         })];
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn vacuum() {
+        let input = "vacuum the_data; VACUUM;";
+        let tokens = Tokenizer::new(input);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![
+            Statement::Vacuum(VacuumStatement {
+                table: Some(String::from("the_data")),
+            }),
+            Statement::Vacuum(VacuumStatement { table: None }),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn transaction_statements() {
+        let input = "BEGIN; delete from t; commit; begin; Rollback;";
+        let tokens = Tokenizer::new(input);
+        let actual = Parser::build(tokens).unwrap().parse().unwrap();
+        let expected = vec![
+            Statement::Begin,
+            Statement::Delete(DeleteStatement {
+                table: String::from("t"),
+                where_clause: None,
+            }),
+            Statement::Commit,
+            Statement::Begin,
+            Statement::Rollback,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn nested_quotes_are_safe() {
         let input = "INSERT INTO posts(link, title, date, author) VALUES(\"http://thorstenball.com/blog/2019/04/09/learn-more-programming-languages/\", \"Learn more programming languages, even if you won't use them\", \"2019-04-09T08:30:00+00:00\", \"Thorsten Ball\") ON CONFLICT(link) DO NOTHING;";
@@ -1202,6 +3397,45 @@ mod parser_tests {
         _ = Parser::build(tokens).unwrap().parse().unwrap();
     }
 
+    #[test]
+    fn errors_report_where_they_happened() {
+        let err = parse_error("select a\nfrom t where a = ;");
+        assert_eq!(
+            err.position(),
+            Some(Position {
+                line: 2,
+                column: 18
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Unexpected token (at ';', line 2, column 18)"
+        );
+
+        let err = parse_error("select a from t");
+        assert_eq!(
+            err.to_string(),
+            "Unexpected end of statement, expected ';' (at the end of the input, line 1, column 16)"
+        );
+
+        // errors found after a token is consumed are reported at that token
+        let err = parse_error("select nope(a) from t;");
+        assert_eq!(err.position(), Some(Position { line: 1, column: 8 }));
+    }
+
+    #[test]
+    fn errors_list_the_expected_tokens() {
+        let err = parse_error("select a from t where a ! 1;");
+        assert!(err
+            .to_string()
+            .starts_with("Unexpected token, expected '=', '<', '>', '<=', or '>='"));
+
+        let err = parse_error("select a from 1;");
+        assert!(err
+            .to_string()
+            .starts_with("Unexpected token, expected a name or '('"));
+    }
+
     // TODO:
     // - versions of missing parts returning errors
 }
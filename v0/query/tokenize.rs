@@ -1,26 +1,68 @@
+//! Splits SQL text into tokens. This is public so tools like syntax highlighters
+//! can work from the same tokens the parser sees.
+
+use std::{borrow::Cow, fmt};
+
 use regex::Regex;
 
 #[derive(Debug)]
 pub enum TokenizerError {
     UntokenizableInput,
+    /// A string or quoted name is missing its closing quote
+    UnterminatedQuote,
+    /// Statements can't contain NUL characters, which are kept for names only used
+    /// internally
+    NulCharacter,
+}
+impl fmt::Display for TokenizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UntokenizableInput => f.write_str("Input could not be tokenized"),
+            Self::UnterminatedQuote => f.write_str("A quote was never closed"),
+            Self::NulCharacter => f.write_str("Input can't contain a NUL character"),
+        }
+    }
 }
+impl std::error::Error for TokenizerError {}
 
 type Result<T> = std::result::Result<T, TokenizerError>;
 
+/// What a token is. More kinds will be added as the dialect grows, so code
+/// outside this crate that matches on them needs a wildcard arm.
+#[non_exhaustive]
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum TokenKind {
-    // for things like whitespace, etc.
+    /// Whitespace, which is skipped rather than produced as a token
     None,
 
     // composite kinds
+    /// A table or column name, or any other word that isn't reserved. Names quoted
+    /// in backticks may be reserved words or contain spaces. Unquoted names end at
+    /// operators, so names containing `+`, `-`, `/` or `.` (allowed before arithmetic
+    /// was supported) must now be quoted, like `` `my-name` `` or `"my-name"`.
     Identifier,
+    /// Anything in double quotes. The parser reads it as a name where one is expected,
+    /// and elsewhere as a column if there's one by that name or a string literal if not.
+    /// A quote or backslash inside it is escaped with a backslash, like `"a \" b"`.
     String,
+    /// Anything in single quotes, which is always a string value. A quote inside it is
+    /// written twice, like `'it''s'`.
+    StringLiteral,
+    /// An integer literal, which may be negative
     Integer,
+    /// A float literal like `1.5` or `-2.0e10`
     Float,
+    /// Not produced by the tokenizer, which reads every integer as `Integer`
     UnsignedInt,
+    /// A named parameter like `:id`
+    Placeholder,
 
-    // reserved words
+    // reserved words, which are matched case-insensitively
+    Explain,
     Select,
+    Distinct,
+    Union,
+    All,
     Where,
     From,
     Order,
@@ -38,19 +80,44 @@ pub enum TokenKind {
     Limit,
     As,
     On,
+    Join,
     Conflict,
     Do,
     Nothing,
+    Replace,
+    Constraint,
+    Check,
+    Foreign,
+    References,
     Primary,
     Key,
     Delete,
+    Vacuum,
+    Begin,
+    Commit,
+    Rollback,
+    Collate,
+    /// `count(`, including the paren so `count` can still name a column
+    Count,
+    /// `cast(`, including the paren so `cast` can still name a column
+    Cast,
     TypeString,
     TypeInteger,
     TypeFloat,
     TypeUnsignedInt,
+    /// `SMALLINT` or `INT`
+    TypeSmallInt,
+    /// `CHAR`, followed by its length in parens
+    TypeChar,
+    /// `VARCHAR`, followed by its maximum length in parens
+    TypeVarchar,
 
     // known symbols
     Star,
+    Plus,
+    Minus,
+    Slash,
+    Dot,
     Comma,
     Semicolon,
     EqualsSign,
@@ -61,29 +128,136 @@ pub enum TokenKind {
     LessThanEquals,
     GreaterThanEquals,
 }
+impl fmt::Display for TokenKind {
+    /// Describes the kind the way it would be written in a command
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::None => "whitespace",
+            Self::Identifier => "a name",
+            Self::String | Self::StringLiteral => "a string",
+            Self::Integer | Self::UnsignedInt => "an integer",
+            Self::Float => "a float",
+            Self::Placeholder => "a placeholder",
+            Self::Count => "COUNT(",
+            Self::Cast => "CAST(",
+            Self::TypeString => "STRING",
+            Self::TypeInteger => "INTEGER",
+            Self::TypeFloat => "FLOAT",
+            Self::TypeUnsignedInt => "UNSIGNED INT",
+            Self::TypeSmallInt => "SMALLINT",
+            Self::TypeChar => "CHAR",
+            Self::TypeVarchar => "VARCHAR",
+            Self::Star => "'*'",
+            Self::Plus => "'+'",
+            Self::Minus => "'-'",
+            Self::Slash => "'/'",
+            Self::Dot => "'.'",
+            Self::Comma => "','",
+            Self::Semicolon => "';'",
+            Self::EqualsSign => "'='",
+            Self::LeftParen => "'('",
+            Self::RightParen => "')'",
+            Self::LeftAngleBracket => "'<'",
+            Self::RightAngleBracket => "'>'",
+            Self::LessThanEquals => "'<='",
+            Self::GreaterThanEquals => "'>='",
+            // every other kind is a keyword named after itself
+            keyword => return f.write_str(&format!("{keyword:?}").to_uppercase()),
+        };
+        f.write_str(symbol)
+    }
+}
 
-#[derive(PartialEq, Debug)]
+/// A line and column in the input, both counted from 1. Columns count characters
+/// rather than bytes.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+impl Position {
+    /// The position of the byte at `offset` in `input`
+    pub fn locate(input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Where a token was found, as byte offsets into the tokenized input
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Token<'a> {
     contents: &'a str,
     kind: TokenKind,
+    span: Span,
 }
 impl<'a> Token<'a> {
-    pub fn new(contents: &'a str, kind: TokenKind) -> Self {
-        Token { contents, kind }
+    pub fn new(contents: &'a str, kind: TokenKind, span: Span) -> Self {
+        Token {
+            contents,
+            kind,
+            span,
+        }
     }
 
     pub fn kind(&self) -> TokenKind {
         self.kind
     }
 
+    /// The text of the token. A string literal's contents don't include its quotes,
+    /// and any escapes in it are left as written.
     pub fn contents(&self) -> &'a str {
         self.contents
     }
+
+    /// The contents with escapes resolved: `\"` and `\\` in double-quoted strings, and
+    /// `''` in single-quoted ones. A backslash before anything else is kept.
+    pub fn unescaped(&self) -> String {
+        match self.kind {
+            TokenKind::String => {
+                let mut unescaped = String::with_capacity(self.contents.len());
+                let mut chars = self.contents.chars().peekable();
+                while let Some(c) = chars.next() {
+                    match (c, chars.peek()) {
+                        ('\\', Some(&next @ ('"' | '\\'))) => {
+                            unescaped.push(next);
+                            _ = chars.next();
+                        }
+                        _ => unescaped.push(c),
+                    }
+                }
+                unescaped
+            }
+            TokenKind::StringLiteral => self.contents.replace("''", "'"),
+            _ => self.contents.to_string(),
+        }
+    }
+
+    /// The part of the input the token was read from. For a string literal, this
+    /// includes its quotes.
+    pub fn span(&self) -> Span {
+        self.span
+    }
 }
 
 struct SpecItem(TokenKind, Regex);
 
-const TOKEN_SPEC_LEN: usize = 41;
+const TOKEN_SPEC_LEN: usize = 66;
+/// Reads tokens from a SQL command, skipping whitespace
 pub struct Tokenizer<'a> {
     input: &'a str,
     cursor: usize,
@@ -98,8 +272,19 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    fn token_quoted_identifier(input: &str) -> Option<&str> {
+        let pattern = Regex::new(r"^`[^`]*`").unwrap();
+        pattern.find(input).map(|m| m.as_str())
+    }
+
+    /// A single-quoted string, where `''` is a quote rather than its end
+    fn token_string_literal(input: &str) -> Option<&str> {
+        let pattern = Regex::new(r"^'(?:[^']|'')*'").unwrap();
+        pattern.find(input).map(|m| m.as_str())
+    }
+
     fn token_identifier(input: &str) -> Option<&str> {
-        let pattern = Regex::new(r"^[^\s*,;=\(\)<>]+").unwrap();
+        let pattern = Regex::new(r"^[^\s*,;=\(\)<>+\-/.]+").unwrap();
         if let Some(m) = pattern.find(input) {
             return Some(m.as_str());
         }
@@ -112,6 +297,9 @@ impl<'a> Tokenizer<'a> {
             SpecItem(TokenKind::None, Regex::new(r"^\s+").unwrap()),
             // single chars
             SpecItem(TokenKind::Star, Regex::new(r"^\*").unwrap()),
+            SpecItem(TokenKind::Plus, Regex::new(r"^\+").unwrap()),
+            SpecItem(TokenKind::Slash, Regex::new(r"^/").unwrap()),
+            SpecItem(TokenKind::Dot, Regex::new(r"^\.").unwrap()),
             SpecItem(TokenKind::Comma, Regex::new(r"^,").unwrap()),
             SpecItem(TokenKind::Semicolon, Regex::new(r"^;").unwrap()),
             SpecItem(TokenKind::EqualsSign, Regex::new(r"^=").unwrap()),
@@ -122,7 +310,11 @@ impl<'a> Tokenizer<'a> {
             SpecItem(TokenKind::LeftAngleBracket, Regex::new(r"^<").unwrap()),
             SpecItem(TokenKind::RightAngleBracket, Regex::new(r"^>").unwrap()),
             // keywords
+            SpecItem(TokenKind::Explain, Regex::new(r"^(?i)explain\b").unwrap()),
             SpecItem(TokenKind::Select, Regex::new(r"^(?i)select\b").unwrap()),
+            SpecItem(TokenKind::Distinct, Regex::new(r"^(?i)distinct\b").unwrap()),
+            SpecItem(TokenKind::Union, Regex::new(r"^(?i)union\b").unwrap()),
+            SpecItem(TokenKind::All, Regex::new(r"^(?i)all\b").unwrap()),
             SpecItem(TokenKind::Where, Regex::new(r"^(?i)where\b").unwrap()),
             SpecItem(TokenKind::From, Regex::new(r"^(?i)from\b").unwrap()),
             SpecItem(TokenKind::Order, Regex::new(r"^(?i)order\b").unwrap()),
@@ -140,12 +332,32 @@ impl<'a> Tokenizer<'a> {
             SpecItem(TokenKind::Limit, Regex::new(r"^(?i)limit\b").unwrap()),
             SpecItem(TokenKind::As, Regex::new(r"^(?i)as\b").unwrap()),
             SpecItem(TokenKind::On, Regex::new(r"^(?i)on\b").unwrap()),
+            SpecItem(TokenKind::Join, Regex::new(r"^(?i)join\b").unwrap()),
             SpecItem(TokenKind::Conflict, Regex::new(r"^(?i)conflict\b").unwrap()),
             SpecItem(TokenKind::Do, Regex::new(r"^(?i)do\b").unwrap()),
             SpecItem(TokenKind::Nothing, Regex::new(r"^(?i)nothing\b").unwrap()),
+            SpecItem(TokenKind::Replace, Regex::new(r"^(?i)replace\b").unwrap()),
+            SpecItem(
+                TokenKind::Constraint,
+                Regex::new(r"^(?i)constraint\b").unwrap(),
+            ),
+            SpecItem(TokenKind::Check, Regex::new(r"^(?i)check\b").unwrap()),
+            SpecItem(TokenKind::Foreign, Regex::new(r"^(?i)foreign\b").unwrap()),
+            SpecItem(
+                TokenKind::References,
+                Regex::new(r"^(?i)references\b").unwrap(),
+            ),
             SpecItem(TokenKind::Primary, Regex::new(r"^(?i)primary\b").unwrap()),
             SpecItem(TokenKind::Key, Regex::new(r"^(?i)key\b").unwrap()),
             SpecItem(TokenKind::Delete, Regex::new(r"^(?i)delete\b").unwrap()),
+            SpecItem(TokenKind::Vacuum, Regex::new(r"^(?i)vacuum\b").unwrap()),
+            SpecItem(TokenKind::Begin, Regex::new(r"^(?i)begin\b").unwrap()),
+            SpecItem(TokenKind::Commit, Regex::new(r"^(?i)commit\b").unwrap()),
+            SpecItem(TokenKind::Rollback, Regex::new(r"^(?i)rollback\b").unwrap()),
+            SpecItem(TokenKind::Collate, Regex::new(r"^(?i)collate\b").unwrap()),
+            // includes the paren, so `count` can still be used as a column name
+            SpecItem(TokenKind::Count, Regex::new(r"^(?i)count\s*\(").unwrap()),
+            SpecItem(TokenKind::Cast, Regex::new(r"^(?i)cast\s*\(").unwrap()),
             SpecItem(TokenKind::TypeString, Regex::new(r"^(?i)string\b").unwrap()),
             SpecItem(TokenKind::TypeFloat, Regex::new(r"^(?i)float\b").unwrap()),
             SpecItem(
@@ -154,41 +366,36 @@ impl<'a> Tokenizer<'a> {
             ),
             SpecItem(
                 TokenKind::TypeUnsignedInt,
-                Regex::new(r"^(?i)unsigned int\b").unwrap(),
+                Regex::new(r"^(?i)unsigned\s+int\b").unwrap(),
+            ),
+            SpecItem(
+                TokenKind::TypeSmallInt,
+                Regex::new(r"^(?i)(smallint|int)\b").unwrap(),
+            ),
+            SpecItem(TokenKind::TypeChar, Regex::new(r"^(?i)char\b").unwrap()),
+            SpecItem(
+                TokenKind::TypeVarchar,
+                Regex::new(r"^(?i)varchar\b").unwrap(),
             ),
             // composites
+            SpecItem(
+                TokenKind::Placeholder,
+                Regex::new(r"^:[A-Za-z_]\w*").unwrap(),
+            ),
             SpecItem(
                 TokenKind::Float,
                 Regex::new(r"^-?\d+\.\d+(e-*\d+)*").unwrap(),
             ),
             SpecItem(TokenKind::Integer, Regex::new(r"^-?\d+").unwrap()),
+            // after the numeric composites, so negative literals keep their sign
+            SpecItem(TokenKind::Minus, Regex::new(r"^-").unwrap()),
         ]
     }
 
+    /// A double-quoted string, where a backslash escapes the character after it
     fn token_string(input: &str) -> Option<&str> {
-        if input.is_empty() || !input.starts_with('"') {
-            return None;
-        }
-        let mut stop;
-        let mut lookbehind = '"';
-        let mut found = false;
-        // skip first quote
-        let mut iter = input.char_indices();
-        iter.next();
-        for (i, c) in iter {
-            stop = i;
-            if found {
-                return Some(&input[0..stop]);
-            }
-            if c == '"' && lookbehind != '\\' {
-                found = true;
-            }
-            lookbehind = c;
-        }
-        if found {
-            return Some(input);
-        }
-        None
+        let pattern = Regex::new(r#"^(?s)"(?:[^"\\]|\\.)*""#).unwrap();
+        pattern.find(input).map(|m| m.as_str())
     }
 
     fn next_token(&mut self) -> Result<Option<Token<'a>>> {
@@ -196,7 +403,11 @@ impl<'a> Tokenizer<'a> {
             return Ok(None);
         }
 
-        let input = &self.input[self.cursor..];
+        let start = self.cursor;
+        if start == 0 && self.input.contains('\0') {
+            return Err(TokenizerError::NulCharacter);
+        }
+        let input = &self.input[start..];
 
         for SpecItem(kind, regex) in &self.spec {
             if let Some(m) = regex.find(input) {
@@ -205,21 +416,54 @@ impl<'a> Tokenizer<'a> {
                 if matches!(kind, TokenKind::None) {
                     return self.next_token();
                 }
+                let span = Span {
+                    start,
+                    end: self.cursor,
+                };
                 if matches!(kind, TokenKind::String) {
                     let s = &m.as_str()[1..m.len() - 1];
-                    return Ok(Some(Token::new(s, *kind)));
+                    return Ok(Some(Token::new(s, *kind, span)));
                 }
-                return Ok(Some(Token::new(m.as_str(), *kind)));
+                return Ok(Some(Token::new(m.as_str(), *kind, span)));
             }
         }
+        if let Some(slice) = Tokenizer::token_quoted_identifier(input) {
+            self.cursor += slice.len();
+            let span = Span {
+                start,
+                end: self.cursor,
+            };
+            let name = &slice[1..slice.len() - 1];
+            return Ok(Some(Token::new(name, TokenKind::Identifier, span)));
+        }
         if let Some(slice) = Tokenizer::token_string(input) {
             self.cursor += slice.len();
             let s = &slice[1..slice.len() - 1];
-            return Ok(Some(Token::new(s, TokenKind::String)));
+            let span = Span {
+                start,
+                end: self.cursor,
+            };
+            return Ok(Some(Token::new(s, TokenKind::String, span)));
+        }
+        if let Some(slice) = Tokenizer::token_string_literal(input) {
+            self.cursor += slice.len();
+            let s = &slice[1..slice.len() - 1];
+            let span = Span {
+                start,
+                end: self.cursor,
+            };
+            return Ok(Some(Token::new(s, TokenKind::StringLiteral, span)));
+        }
+        if input.starts_with(['"', '`', '\'']) {
+            return Err(TokenizerError::UnterminatedQuote);
         }
         if let Some(slice) = Tokenizer::token_identifier(input) {
             self.cursor += slice.len();
-            return Ok(Some(Token::new(slice, TokenKind::Identifier)));
+            let span = Span {
+                start,
+                end: self.cursor,
+            };
+            return Ok(Some(Token::new(slice, TokenKind::Identifier, span)));
         }
         // This should never happen. Everything should at least match against a known
         // symbol or the Identifier TokenKind.
@@ -235,6 +479,16 @@ pub struct Tokens<'a> {
     tokenizer: Tokenizer<'a>,
 }
 impl<'a> Tokens<'a> {
+    /// The whole input being tokenized
+    pub fn input(&self) -> &'a str {
+        self.tokenizer.input
+    }
+
+    /// The byte offset of the input that hasn't been tokenized yet
+    pub fn offset(&self) -> usize {
+        self.tokenizer.cursor
+    }
+
     pub fn to_vec(&mut self) -> Result<Vec<Token<'a>>> {
         let mut tokens = Vec::new();
         while let Some(token) = self.next_token()? {
@@ -247,19 +501,70 @@ impl<'a> Tokens<'a> {
         Ok(res)
     }
 }
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.tokenizer.next_token();
+        if res.is_err() {
+            // there's no telling where the next token starts, so stop here
+            self.tokenizer.cursor = self.tokenizer.input.len();
+        }
+        res.transpose()
+    }
+}
+
+/// `name` as it has to be written in a statement to be read back as the same name.
+/// It's quoted in backticks if it's a reserved word, or has characters that would
+/// end an unquoted name early.
+pub fn quote_identifier(name: &str) -> Cow<'_, str> {
+    let mut tokens = Tokenizer::new(name).tokens();
+    match (tokens.next(), tokens.next()) {
+        (Some(Ok(t)), None) if t.kind() == TokenKind::Identifier && t.contents() == name => {
+            Cow::Borrowed(name)
+        }
+        _ => Cow::Owned(format!("`{name}`")),
+    }
+}
 
 #[cfg(test)]
 mod tokenizer_tests {
     use super::*;
 
+    /// The tokens of `input`, without their spans
+    fn contents_and_kinds(input: &str) -> Vec<(&str, TokenKind)> {
+        Tokenizer::new(input)
+            .tokens()
+            .map(|t| {
+                let t = t.unwrap();
+                (t.contents(), t.kind())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identifiers_are_quoted_when_needed() {
+        for name in ["a", "test_table", "count", "Name1"] {
+            assert_eq!(quote_identifier(name), name);
+        }
+        for name in ["my t", "select", "FROM", "a-b", "a.b", "1", "x,y", ""] {
+            let quoted = quote_identifier(name);
+            assert_eq!(quoted, format!("`{name}`"));
+            assert_eq!(
+                contents_and_kinds(&quoted),
+                vec![(name, TokenKind::Identifier)]
+            );
+        }
+    }
+
     #[test]
     fn whitespace_splitting() {
         let input = "a * b";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("a", TokenKind::Identifier),
-            Token::new("*", TokenKind::Star),
-            Token::new("b", TokenKind::Identifier),
+            ("a", TokenKind::Identifier),
+            ("*", TokenKind::Star),
+            ("b", TokenKind::Identifier),
         ];
         assert_eq!(res, expected);
     }
@@ -267,13 +572,13 @@ mod tokenizer_tests {
     #[test]
     fn basic_select() {
         let input = "select * from test_table;";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("select", TokenKind::Select),
-            Token::new("*", TokenKind::Star),
-            Token::new("from", TokenKind::From),
-            Token::new("test_table", TokenKind::Identifier),
-            Token::new(";", TokenKind::Semicolon),
+            ("select", TokenKind::Select),
+            ("*", TokenKind::Star),
+            ("from", TokenKind::From),
+            ("test_table", TokenKind::Identifier),
+            (";", TokenKind::Semicolon),
         ];
 
         assert_eq!(res, expected);
@@ -283,11 +588,11 @@ mod tokenizer_tests {
     #[test]
     fn merges_whitespace() {
         let input = "a  * \t\n b";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("a", TokenKind::Identifier),
-            Token::new("*", TokenKind::Star),
-            Token::new("b", TokenKind::Identifier),
+            ("a", TokenKind::Identifier),
+            ("*", TokenKind::Star),
+            ("b", TokenKind::Identifier),
         ];
         assert_eq!(res, expected);
     }
@@ -296,37 +601,248 @@ mod tokenizer_tests {
     #[test]
     fn trims_whitespace() {
         let input = "  a*b  ";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("a", TokenKind::Identifier),
-            Token::new("*", TokenKind::Star),
-            Token::new("b", TokenKind::Identifier),
+            ("a", TokenKind::Identifier),
+            ("*", TokenKind::Star),
+            ("b", TokenKind::Identifier),
         ];
         assert_eq!(res, expected);
 
         let input = "  a*b";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         assert_eq!(res, expected);
 
         let input = "a*b  ";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         assert_eq!(res, expected);
 
         let input = "a*b";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let input = "a+b - c*d/e-1 - -2";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("a", TokenKind::Identifier),
+            ("+", TokenKind::Plus),
+            ("b", TokenKind::Identifier),
+            ("-", TokenKind::Minus),
+            ("c", TokenKind::Identifier),
+            ("*", TokenKind::Star),
+            ("d", TokenKind::Identifier),
+            ("/", TokenKind::Slash),
+            ("e", TokenKind::Identifier),
+            ("-1", TokenKind::Integer),
+            ("-", TokenKind::Minus),
+            ("-2", TokenKind::Integer),
+        ];
         assert_eq!(res, expected);
     }
 
+    #[test]
+    fn qualified_column_names() {
+        let input = "t.a, t.b*1.5";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("t", TokenKind::Identifier),
+            (".", TokenKind::Dot),
+            ("a", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("t", TokenKind::Identifier),
+            (".", TokenKind::Dot),
+            ("b", TokenKind::Identifier),
+            ("*", TokenKind::Star),
+            ("1.5", TokenKind::Float),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn count_is_only_reserved_as_a_call() {
+        let input = "count, COUNT (*)";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("count", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("COUNT (", TokenKind::Count),
+            ("*", TokenKind::Star),
+            (")", TokenKind::RightParen),
+        ];
+        assert_eq!(res, expected);
+
+        let input = "cast, CAST(cast AS string)";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("cast", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("CAST(", TokenKind::Cast),
+            ("cast", TokenKind::Identifier),
+            ("AS", TokenKind::As),
+            ("string", TokenKind::TypeString),
+            (")", TokenKind::RightParen),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn placeholders() {
+        let input = "a = :a_1, :b)";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("a", TokenKind::Identifier),
+            ("=", TokenKind::EqualsSign),
+            (":a_1", TokenKind::Placeholder),
+            (",", TokenKind::Comma),
+            (":b", TokenKind::Placeholder),
+            (")", TokenKind::RightParen),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn keywords_only_match_whole_words() {
+        let input = "selection fromage Select";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("selection", TokenKind::Identifier),
+            ("fromage", TokenKind::Identifier),
+            ("Select", TokenKind::Select),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        let input = "select `order`, `first name` from `t`";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("select", TokenKind::Select),
+            ("order", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("first name", TokenKind::Identifier),
+            ("from", TokenKind::From),
+            ("t", TokenKind::Identifier),
+        ];
+        assert_eq!(res, expected);
+
+        // operators split unquoted names, but not quoted ones
+        let res = contents_and_kinds("my-name, `my-name`, `a.b/c+d`");
+        let expected = vec![
+            ("my", TokenKind::Identifier),
+            ("-", TokenKind::Minus),
+            ("name", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("my-name", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("a.b/c+d", TokenKind::Identifier),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_errors() {
+        for input in [
+            "select `order from t",
+            "select \"order from t",
+            "select a from t where b = \"",
+            "select a from t where b = 'it''s",
+        ] {
+            assert!(
+                matches!(
+                    Tokenizer::new(input).tokens().to_vec(),
+                    Err(TokenizerError::UnterminatedQuote)
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_type_names() {
+        let res = contents_and_kinds("int smallint integer unsigned int intx");
+        let expected = vec![
+            ("int", TokenKind::TypeSmallInt),
+            ("smallint", TokenKind::TypeSmallInt),
+            ("integer", TokenKind::TypeInteger),
+            ("unsigned int", TokenKind::TypeUnsignedInt),
+            ("intx", TokenKind::Identifier),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn nul_characters_are_errors() {
+        for input in ["select a\0 from t", "select a from t where b = \"\0\""] {
+            assert!(
+                matches!(
+                    Tokenizer::new(input).tokens().to_vec(),
+                    Err(TokenizerError::NulCharacter)
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn keywords_ignore_case_but_keep_their_text() {
+        let input = "SELECT Name FROM T where \"MiXed\" UNSIGNED  Int";
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("SELECT", TokenKind::Select),
+            ("Name", TokenKind::Identifier),
+            ("FROM", TokenKind::From),
+            ("T", TokenKind::Identifier),
+            ("where", TokenKind::Where),
+            ("MiXed", TokenKind::String),
+            ("UNSIGNED  Int", TokenKind::TypeUnsignedInt),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn spans_locate_tokens_in_the_input() {
+        let input = "select \"a \\\" b\" ,\n  -1.5";
+        let tokens = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let spans: Vec<&str> = tokens
+            .iter()
+            .map(|t| &input[t.span().start..t.span().end])
+            .collect();
+        assert_eq!(spans, vec!["select", "\"a \\\" b\"", ",", "-1.5"]);
+        assert_eq!(tokens[1].contents(), "a \\\" b");
+        assert_eq!(tokens[3].span(), Span { start: 20, end: 24 });
+    }
+
+    #[test]
+    fn positions_count_lines_and_characters() {
+        let input = "select\n  \"é\", b\r\nfrom";
+        assert_eq!(Position::locate(input, 0), Position { line: 1, column: 1 });
+        let b = input.find('b').unwrap();
+        assert_eq!(Position::locate(input, b), Position { line: 2, column: 8 });
+        let from = input.find("from").unwrap();
+        assert_eq!(
+            Position::locate(input, from),
+            Position { line: 3, column: 1 }
+        );
+        assert_eq!(
+            Position::locate(input, input.len()).to_string(),
+            "line 3, column 5"
+        );
+    }
+
     #[test]
     fn case_insensitive_on_reserved_words() {
         let input = "sElEcT * FrOm test_table;";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("sElEcT", TokenKind::Select),
-            Token::new("*", TokenKind::Star),
-            Token::new("FrOm", TokenKind::From),
-            Token::new("test_table", TokenKind::Identifier),
-            Token::new(";", TokenKind::Semicolon),
+            ("sElEcT", TokenKind::Select),
+            ("*", TokenKind::Star),
+            ("FrOm", TokenKind::From),
+            ("test_table", TokenKind::Identifier),
+            (";", TokenKind::Semicolon),
         ];
 
         assert_eq!(res, expected);
@@ -335,11 +851,11 @@ mod tokenizer_tests {
     #[test]
     fn greedy_with_strings() {
         let input = "\"string1\" then \"string2\"";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("string1", TokenKind::String),
-            Token::new("then", TokenKind::Identifier),
-            Token::new("string2", TokenKind::String),
+            ("string1", TokenKind::String),
+            ("then", TokenKind::Identifier),
+            ("string2", TokenKind::String),
         ];
 
         assert_eq!(res, expected);
@@ -349,80 +865,114 @@ mod tokenizer_tests {
     fn respects_escaped_strings() {
         // end-of-string
         let input = "\"this then \\\" that\"";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
-        let expected = vec![Token::new("this then \\\" that", TokenKind::String)];
+        let res = contents_and_kinds(input);
+        let expected = vec![("this then \\\" that", TokenKind::String)];
 
         assert_eq!(res, expected);
 
         // not end-of-string
         let input = "\"this then \\\" that\" foo";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+        let res = contents_and_kinds(input);
+        let expected = vec![
+            ("this then \\\" that", TokenKind::String),
+            ("foo", TokenKind::Identifier),
+        ];
+
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn escaped_backslashes_dont_escape_quotes() {
+        let tokens: Vec<Token> = Tokenizer::new("\"a \\\\\" b \"\\\" \\\\ \\n\" 'it''s'")
+            .tokens()
+            .collect::<Result<_>>()
+            .unwrap();
+        let unescaped: Vec<(String, TokenKind)> =
+            tokens.iter().map(|t| (t.unescaped(), t.kind())).collect();
         let expected = vec![
-            Token::new("this then \\\" that", TokenKind::String),
-            Token::new("foo", TokenKind::Identifier),
+            (String::from("a \\"), TokenKind::String),
+            (String::from("b"), TokenKind::Identifier),
+            (String::from("\" \\ \\n"), TokenKind::String),
+            (String::from("it's"), TokenKind::StringLiteral),
         ];
+        assert_eq!(unescaped, expected);
+    }
 
+    #[test]
+    fn single_quoted_strings() {
+        let res = contents_and_kinds("'it''s' 'a \"b\" \\' '' b's");
+        let expected = vec![
+            ("it''s", TokenKind::StringLiteral),
+            ("a \"b\" \\", TokenKind::StringLiteral),
+            ("", TokenKind::StringLiteral),
+            ("b's", TokenKind::Identifier),
+        ];
         assert_eq!(res, expected);
     }
 
     #[test]
     fn all_tokens_in_a_string() {
         let input =
-            "select foo, bar, baz from test_table where bar=\"that thing\" order by foo) desc; -12, -12.3 create table if not ( exists string integer float insert into values destroy -5.134e11 4.122e-38 limit <> <= >= as on conflict do nothing primary key rowid delete unsigned int;";
-        let res: Vec<Token> = Tokenizer::new(input).tokens().to_vec().unwrap();
+            "select foo, bar, baz from test_table where bar=\"that thing\" order by foo) desc; -12, -12.3 create table if not ( exists string integer float insert into values destroy -5.134e11 4.122e-38 limit <> <= >= as on join conflict do nothing replace primary key rowid delete unsigned int begin commit rollback;";
+        let res = contents_and_kinds(input);
         let expected = vec![
-            Token::new("select", TokenKind::Select),
-            Token::new("foo", TokenKind::Identifier),
-            Token::new(",", TokenKind::Comma),
-            Token::new("bar", TokenKind::Identifier),
-            Token::new(",", TokenKind::Comma),
-            Token::new("baz", TokenKind::Identifier),
-            Token::new("from", TokenKind::From),
-            Token::new("test_table", TokenKind::Identifier),
-            Token::new("where", TokenKind::Where),
-            Token::new("bar", TokenKind::Identifier),
-            Token::new("=", TokenKind::EqualsSign),
-            Token::new("that thing", TokenKind::String),
-            Token::new("order", TokenKind::Order),
-            Token::new("by", TokenKind::By),
-            Token::new("foo", TokenKind::Identifier),
-            Token::new(")", TokenKind::RightParen),
-            Token::new("desc", TokenKind::Desc),
-            Token::new(";", TokenKind::Semicolon),
-            Token::new("-12", TokenKind::Integer),
-            Token::new(",", TokenKind::Comma),
-            Token::new("-12.3", TokenKind::Float),
-            Token::new("create", TokenKind::Create),
-            Token::new("table", TokenKind::Table),
-            Token::new("if", TokenKind::If),
-            Token::new("not", TokenKind::Not),
-            Token::new("(", TokenKind::LeftParen),
-            Token::new("exists", TokenKind::Exists),
-            Token::new("string", TokenKind::TypeString),
-            Token::new("integer", TokenKind::TypeInteger),
-            Token::new("float", TokenKind::TypeFloat),
-            Token::new("insert", TokenKind::Insert),
-            Token::new("into", TokenKind::Into),
-            Token::new("values", TokenKind::Values),
-            Token::new("destroy", TokenKind::Destroy),
-            Token::new("-5.134e11", TokenKind::Float),
-            Token::new("4.122e-38", TokenKind::Float),
-            Token::new("limit", TokenKind::Limit),
-            Token::new("<", TokenKind::LeftAngleBracket),
-            Token::new(">", TokenKind::RightAngleBracket),
-            Token::new("<=", TokenKind::LessThanEquals),
-            Token::new(">=", TokenKind::GreaterThanEquals),
-            Token::new("as", TokenKind::As),
-            Token::new("on", TokenKind::On),
-            Token::new("conflict", TokenKind::Conflict),
-            Token::new("do", TokenKind::Do),
-            Token::new("nothing", TokenKind::Nothing),
-            Token::new("primary", TokenKind::Primary),
-            Token::new("key", TokenKind::Key),
-            Token::new("rowid", TokenKind::Identifier),
-            Token::new("delete", TokenKind::Delete),
-            Token::new("unsigned int", TokenKind::TypeUnsignedInt),
-            Token::new(";", TokenKind::Semicolon),
+            ("select", TokenKind::Select),
+            ("foo", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("bar", TokenKind::Identifier),
+            (",", TokenKind::Comma),
+            ("baz", TokenKind::Identifier),
+            ("from", TokenKind::From),
+            ("test_table", TokenKind::Identifier),
+            ("where", TokenKind::Where),
+            ("bar", TokenKind::Identifier),
+            ("=", TokenKind::EqualsSign),
+            ("that thing", TokenKind::String),
+            ("order", TokenKind::Order),
+            ("by", TokenKind::By),
+            ("foo", TokenKind::Identifier),
+            (")", TokenKind::RightParen),
+            ("desc", TokenKind::Desc),
+            (";", TokenKind::Semicolon),
+            ("-12", TokenKind::Integer),
+            (",", TokenKind::Comma),
+            ("-12.3", TokenKind::Float),
+            ("create", TokenKind::Create),
+            ("table", TokenKind::Table),
+            ("if", TokenKind::If),
+            ("not", TokenKind::Not),
+            ("(", TokenKind::LeftParen),
+            ("exists", TokenKind::Exists),
+            ("string", TokenKind::TypeString),
+            ("integer", TokenKind::TypeInteger),
+            ("float", TokenKind::TypeFloat),
+            ("insert", TokenKind::Insert),
+            ("into", TokenKind::Into),
+            ("values", TokenKind::Values),
+            ("destroy", TokenKind::Destroy),
+            ("-5.134e11", TokenKind::Float),
+            ("4.122e-38", TokenKind::Float),
+            ("limit", TokenKind::Limit),
+            ("<", TokenKind::LeftAngleBracket),
+            (">", TokenKind::RightAngleBracket),
+            ("<=", TokenKind::LessThanEquals),
+            (">=", TokenKind::GreaterThanEquals),
+            ("as", TokenKind::As),
+            ("on", TokenKind::On),
+            ("join", TokenKind::Join),
+            ("conflict", TokenKind::Conflict),
+            ("do", TokenKind::Do),
+            ("nothing", TokenKind::Nothing),
+            ("replace", TokenKind::Replace),
+            ("primary", TokenKind::Primary),
+            ("key", TokenKind::Key),
+            ("rowid", TokenKind::Identifier),
+            ("delete", TokenKind::Delete),
+            ("unsigned int", TokenKind::TypeUnsignedInt),
+            ("begin", TokenKind::Begin),
+            ("commit", TokenKind::Commit),
+            ("rollback", TokenKind::Rollback),
+            (";", TokenKind::Semicolon),
         ];
 
         assert_eq!(res, expected);
@@ -1,13 +1,20 @@
 use std::{
     cmp::max,
-    io::{Error as IoError, Write},
+    fs,
+    io::{self, Error as IoError, Write},
     iter::zip,
+    path::PathBuf,
     string::FromUtf8Error,
+    time::{Duration, Instant},
 };
 
 use console::{Key, Term};
 
-use crate::{query::ResultRows, storage::Row, Database, DatabaseError, DbValue, RowContents, Rows};
+use crate::{
+    export,
+    storage::{Row, Schema},
+    Database, DatabaseError, DbValue, RowContents, Rows, TableKnowledge,
+};
 
 #[derive(Debug)]
 pub enum ReplError {
@@ -115,11 +122,105 @@ impl DisplayState {
     }
 }
 
+const DEFAULT_MAX_HISTORY: usize = 1000;
+const HISTORY_FILE_NAME: &str = ".rjsdb_history";
+
+fn default_history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE_NAME))
+}
+
+/// Entered lines, oldest first, capped at `max_len` entries. When backed by
+/// a file, the file is rewritten after every entry so history survives restarts.
+struct History {
+    entries: Vec<String>,
+    max_len: usize,
+    file: Option<PathBuf>,
+}
+impl History {
+    fn load(file: Option<PathBuf>, max_len: usize) -> Self {
+        let entries = file
+            .as_ref()
+            .and_then(|f| fs::read_to_string(f).ok())
+            .map(|contents| contents.lines().map(|l| l.to_string()).collect())
+            .unwrap_or_default();
+        let mut history = History {
+            entries,
+            max_len,
+            file,
+        };
+        history.truncate();
+        history
+    }
+
+    fn truncate(&mut self) {
+        if self.entries.len() > self.max_len {
+            let excess = self.entries.len() - self.max_len;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// Adds an entry, keeping it in memory even if the file can't be written
+    fn push(&mut self, entry: String) -> io::Result<()> {
+        if entry.is_empty() {
+            return Ok(());
+        }
+        self.entries.push(entry);
+        self.truncate();
+        if let Some(file) = &self.file {
+            let mut contents = self.entries.join("\n");
+            contents.push('\n');
+            fs::write(file, contents)?;
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&self, idx: usize) -> Option<&String> {
+        self.entries.get(idx)
+    }
+}
+
+/// Returns the byte position of the first `;` that ends a statement,
+/// ignoring any that appear inside string literals.
+fn statement_end(input: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some('"'), _) if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (Some(q), _) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// How result rows are printed
+enum OutputMode {
+    Table,
+    Csv,
+}
+
 pub struct Repl {
-    history: Vec<String>,
+    history: History,
     history_cursor: usize,
+    /// Whether a failure to save history has been reported yet
+    history_warned: bool,
     term: Term,
     display: DisplayState,
+    continuing_statement: bool,
+    timer: bool,
+    mode: OutputMode,
 }
 impl Default for Repl {
     fn default() -> Self {
@@ -127,22 +228,45 @@ impl Default for Repl {
     }
 }
 impl Repl {
+    /// Creates a repl that persists history to `~/.rjsdb_history`
     pub fn new() -> Self {
+        Repl::with_history(default_history_file(), DEFAULT_MAX_HISTORY)
+    }
+
+    /// Creates a repl that persists up to `max_len` history entries to `file`,
+    /// or keeps them only in memory if no file is given
+    pub fn with_history(file: Option<PathBuf>, max_len: usize) -> Self {
+        let history = History::load(file, max_len);
+        let history_cursor = history.len();
         Repl {
-            history: Vec::new(),
-            history_cursor: 0,
+            history,
+            history_cursor,
+            history_warned: false,
             term: Term::buffered_stdout(),
             display: DisplayState::new(),
+            continuing_statement: false,
+            timer: false,
+            mode: OutputMode::Table,
+        }
+    }
+
+    fn prompt_str(&self) -> &'static str {
+        if self.continuing_statement {
+            "...> "
+        } else {
+            "> "
         }
     }
 
     fn prompt(&mut self) -> Result<()> {
         // self.term
         // .write_fmt(format_args!("{}", self.display.cursor))?;
-        self.term.write_all("> ".as_bytes())?;
+        let prompt = self.prompt_str();
+        self.term.write_all(prompt.as_bytes())?;
         self.term.write_all(self.display.display_line.as_bytes())?;
         self.term.move_cursor_left(usize::MAX)?;
-        self.term.move_cursor_right(self.display.cursor + 2)?;
+        self.term
+            .move_cursor_right(self.display.cursor + prompt.len())?;
         self.term.flush()?;
         self.display.should_rerender = false;
         Ok(())
@@ -192,12 +316,26 @@ impl Repl {
         Ok(())
     }
 
-    fn get_user_input(&mut self) -> Result<String> {
+    /// Reads one line of input, returning `None` once input is exhausted
+    /// (EOF when piped, or Ctrl-D on an empty line at a terminal).
+    fn get_user_input(&mut self) -> Result<Option<String>> {
+        if !self.term.is_term() {
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line));
+        }
+
         self.display.reset();
         self.prompt()?;
         loop {
             let key = self.term.read_key()?;
             match key {
+                Key::Char('\u{4}') if self.display.display_line.is_empty() => {
+                    self.echo_char('\n')?;
+                    return Ok(None);
+                }
                 Key::ArrowUp => self.show_previous_line()?,
                 Key::ArrowDown => self.show_next_line()?,
                 Key::ArrowLeft => {
@@ -236,52 +374,232 @@ impl Repl {
                 _ => (),
             }
         }
-        self.history
-            .push(self.display.display_line.trim().to_string());
+        if let Err(err) = self
+            .history
+            .push(self.display.display_line.trim().to_string())
+        {
+            // history is a convenience, so losing it shouldn't end the session
+            if !self.history_warned {
+                println!("Warning: history couldn't be saved: {err}");
+                self.history_warned = true;
+            }
+        }
         self.history_cursor = self.history.len();
-        Ok(self.display.display_line.clone())
+        Ok(Some(self.display.display_line.clone()))
     }
 
+    /// Each statement's changes are written out as it runs, unless it's between a
+    /// `BEGIN` and a `COMMIT` or `ROLLBACK`
     pub fn run(&mut self, db: &mut Database) -> Result<()> {
-        let mut tx = db.transaction()?;
-        loop {
-            let line = self.get_user_input()?;
-            if line.trim() == "exit;" {
-                break;
-            }
-            match tx.prepare(&line).query() {
-                Err(err) => println!("{err:?}"),
-                Ok(Rows {
-                    rows: RowContents::Empty,
-                }) => println!("ok"),
-                Ok(Rows {
-                    rows: RowContents::Filled(res_rows),
-                }) => Repl::display_rows(res_rows),
+        // input is buffered until a terminating `;` is seen, so statements can span lines
+        let mut buffer = String::new();
+        'input: loop {
+            self.continuing_statement = !buffer.trim().is_empty();
+            let line = match self.get_user_input()? {
+                Some(line) => line,
+                None => {
+                    if !buffer.trim().is_empty() {
+                        println!(
+                            "Incomplete input, missing a terminating `;`: {}",
+                            buffer.trim()
+                        );
+                    }
+                    break;
+                }
             };
+            if !self.continuing_statement && line.trim().starts_with('.') {
+                if let Err(err) = self.run_meta_command(line.trim(), db) {
+                    println!("{err:?}");
+                }
+                continue;
+            }
+            buffer.push_str(&line);
+
+            while let Some(end) = statement_end(&buffer) {
+                let statement: String = buffer.drain(..=end).collect();
+                if statement.trim() == "exit;" {
+                    break 'input;
+                }
+                // nothing but the `;`, so there's nothing to run or report
+                if statement.trim().trim_end_matches(';').trim().is_empty() {
+                    continue;
+                }
+                self.run_statement(statement.trim_start(), db);
+            }
+        }
+        if db.in_transaction()? {
+            println!("Rolling back the transaction that was left open");
+            db.execute("ROLLBACK;")?;
         }
-        tx.commit()?;
         Ok(())
     }
 
-    fn value_len(val: &DbValue) -> usize {
-        match val {
-            DbValue::Float(f) => format!("| {:+<e} ", f).len(),
-            _ => format!("| {} ", val).len(),
+    fn run_statement(&self, statement: &str, db: &mut Database) {
+        let start = Instant::now();
+        let mut prepared = match db.prepare(statement) {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                Repl::print_error(statement, &err);
+                return;
+            }
+        };
+        match prepared.query() {
+            Err(err) => Repl::print_error(statement, &err),
+            Ok(Rows {
+                rows: RowContents::Empty,
+            }) => {
+                let elapsed = start.elapsed();
+                println!("ok");
+                self.report_timing(elapsed, None);
+            }
+            Ok(Rows {
+                rows: RowContents::Affected(affected),
+            }) => {
+                let elapsed = start.elapsed();
+                println!("ok");
+                self.report_timing(elapsed, Some(format!("{affected} rows affected")));
+            }
+            Ok(Rows {
+                rows: RowContents::Plan(plan),
+            }) => println!("{plan}"),
+            Ok(Rows {
+                rows: RowContents::Reclaimed(bytes),
+            }) => {
+                let elapsed = start.elapsed();
+                println!("ok");
+                self.report_timing(elapsed, Some(format!("{bytes} bytes reclaimed")));
+            }
+            Ok(Rows {
+                rows: RowContents::Filled(res_rows),
+            }) => {
+                // rows are produced lazily, so collect them before stopping the timer
+                let schema = res_rows.schema();
+                let all_rows: Vec<Row> = match res_rows.map(|r| r.map(|r| r.into_owned())).collect()
+                {
+                    Ok(rows) => rows,
+                    Err(err) => {
+                        println!("{err}");
+                        return;
+                    }
+                };
+                let elapsed = start.elapsed();
+                match self.mode {
+                    OutputMode::Table => print!("{}", Repl::format_table(&schema, &all_rows)),
+                    OutputMode::Csv => print!("{}", Repl::format_csv(&schema, &all_rows)),
+                }
+                self.report_timing(elapsed, Some(format!("{} rows returned", all_rows.len())));
+            }
+        };
+    }
+
+    /// Prints the error, preceded by the line of the statement it happened on with a
+    /// caret under the spot, when its position is known
+    fn print_error(statement: &str, err: &DatabaseError) {
+        if let Some(position) = err.position() {
+            if let Some(line) = statement.lines().nth(position.line - 1) {
+                // keep any tabs so the caret lines up with the text above it
+                let padding: String = line
+                    .chars()
+                    .take(position.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                println!("{line}");
+                println!("{padding}^");
+            }
         }
+        println!("{err:#}");
     }
 
-    fn value_format(val: &DbValue, width: usize) -> String {
+    fn report_timing(&self, elapsed: Duration, row_info: Option<String>) {
+        if !self.timer {
+            return;
+        }
+        let millis = elapsed.as_secs_f64() * 1000.0;
+        match row_info {
+            Some(info) => println!("Run Time: {millis:.1}ms ({info})"),
+            None => println!("Run Time: {millis:.1}ms"),
+        }
+    }
+
+    fn run_meta_command(&mut self, line: &str, db: &mut Database) -> Result<()> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let arg = parts.next();
+        match command {
+            ".dump" => {
+                let statements = db.transaction()?.dump();
+                match arg {
+                    Some(path) => fs::write(path, statements.join("\n") + "\n")?,
+                    None => statements.iter().for_each(|stmt| println!("{stmt}")),
+                }
+            }
+            ".read" => match arg {
+                Some(path) => {
+                    let contents = fs::read_to_string(path)?;
+                    let affected = db.execute(&contents)?;
+                    println!("ok ({affected} rows affected by last statement)");
+                }
+                None => println!("usage: .read <file>"),
+            },
+            ".schema" => match arg {
+                Some(table) => match db.table_info(table) {
+                    Some(info) => println!("{info}"),
+                    None => println!("No table named {table}"),
+                },
+                None => {
+                    for info in db.table_names().iter().filter_map(|t| db.table_info(t)) {
+                        println!("{info}");
+                    }
+                }
+            },
+            ".tables" => {
+                for info in db.table_names().iter().filter_map(|t| db.table_info(t)) {
+                    println!("{} ({} rows)", info.name, info.row_count);
+                }
+            }
+            ".mode" => match arg {
+                Some("table") => self.mode = OutputMode::Table,
+                Some("csv") => self.mode = OutputMode::Csv,
+                _ => println!("usage: .mode table|csv"),
+            },
+            ".timer" => match arg {
+                Some("on") => self.timer = true,
+                Some("off") => self.timer = false,
+                _ => println!("usage: .timer on|off"),
+            },
+            ".history" => {
+                let count = match arg.map(|a| a.parse::<usize>()) {
+                    Some(Ok(count)) => count,
+                    _ => 20,
+                };
+                let skip = self.history.len().saturating_sub(count);
+                for (i, entry) in self.history.entries.iter().enumerate().skip(skip) {
+                    println!("{:>5}  {}", i + 1, entry);
+                }
+            }
+            _ => println!("Unknown command: {command}"),
+        }
+        Ok(())
+    }
+
+    /// The text shown for a value in a result table. Strings are shown
+    /// without quotes, and floats in scientific notation.
+    fn cell_text(val: &DbValue) -> String {
         match val {
-            DbValue::Float(f) => format!("| {:<width$e} ", f),
-            _ => format!("| {:<width$} ", val),
+            DbValue::Float(f) => format!("{:e}", f),
+            DbValue::String(s) | DbValue::Char(s) => s.clone(),
+            _ => val.to_string(),
         }
     }
 
-    fn print_row(col_widths: &[usize], row: &Row) {
-        for (val, width) in zip(row.data.iter(), col_widths) {
-            print!("{}", Repl::value_format(val, *width));
+    fn format_row<'a>(col_widths: &[usize], cells: impl Iterator<Item = &'a str>) -> String {
+        let mut line = String::new();
+        for (cell, width) in zip(cells, col_widths) {
+            // pad by char count rather than byte length so non-ascii text lines up
+            let padding = width - cell.chars().count();
+            line += &format!("| {}{} ", cell, " ".repeat(padding));
         }
-        println!("|");
+        line + "|\n"
     }
 
     fn row_width(col_widths: &[usize]) -> usize {
@@ -290,33 +608,117 @@ impl Repl {
         row_width + 1 // last dividider;
     }
 
-    fn display_rows(rows: ResultRows) {
-        let schema = rows.schema();
-        let name_widths: Vec<usize> = schema
-            .columns()
-            .map(|c: &crate::storage::Column| c.name.len())
+    fn format_csv(schema: &Schema, rows: &[Row]) -> String {
+        let mut out = Vec::new();
+        export::write_csv_header(&mut out, schema).expect("Writing to a Vec can't fail");
+        for row in rows {
+            export::write_csv_row(&mut out, row).expect("Writing to a Vec can't fail");
+        }
+        String::from_utf8(out).expect("Rows only contain valid strings")
+    }
+
+    fn format_table(schema: &Schema, rows: &[Row]) -> String {
+        let names: Vec<&str> = schema.columns().map(|c| c.name.as_str()).collect();
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|r| r.data.iter().map(Repl::cell_text).collect())
             .collect();
-        let all_rows: Vec<Row> = rows.map(|r| r.into_owned()).collect();
-        let col_widths = all_rows.iter().fold(name_widths, |widths, row| {
-            let row_widths = row.data.iter().map(Repl::value_len);
+
+        let name_widths = names.iter().map(|n| n.chars().count()).collect();
+        let col_widths: Vec<usize> = cells.iter().fold(name_widths, |widths, row| {
+            let row_widths = row.iter().map(|c| c.chars().count());
             zip(widths, row_widths).map(|(a, b)| max(a, b)).collect()
         });
 
-        let divider = "-".repeat(Repl::row_width(&col_widths));
+        let divider = "-".repeat(Repl::row_width(&col_widths)) + "\n";
 
-        // header
-        println!("{}", divider);
-        for (col, width) in zip(schema.columns(), col_widths.iter()) {
-            print!("| {:<width$} ", col.name);
+        let mut table = divider.clone();
+        table += &Repl::format_row(&col_widths, names.into_iter());
+        table += &divider;
+        for row in cells.iter() {
+            table += &Repl::format_row(&col_widths, row.iter().map(|c| c.as_str()));
         }
-        println!("|");
-        println!("{}", divider);
+        table += &divider;
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Column, DbType};
 
-        // body
-        for row in all_rows {
-            Repl::print_row(&col_widths, &row);
+    #[test]
+    fn statement_end_finds_first_terminator() {
+        assert_eq!(statement_end("select * from t; select"), Some(15));
+        assert_eq!(statement_end("select *\nfrom t"), None);
+        assert_eq!(statement_end(""), None);
+    }
+
+    #[test]
+    fn statement_end_ignores_terminators_in_strings() {
+        let input = "insert into t (a) values (\"x;y\");";
+        assert_eq!(statement_end(input), Some(input.len() - 1));
+
+        let input = "insert into t (a) values (\"x\\\";y\");";
+        assert_eq!(statement_end(input), Some(input.len() - 1));
+
+        assert_eq!(statement_end("insert into t (a) values (\"x;"), None);
+
+        let input = "insert into t (a) values (\"x\\\\\", 'y;''z');";
+        assert_eq!(statement_end(input), Some(input.len() - 1));
+        assert_eq!(statement_end("insert into t (a) values ('x'';"), None);
+    }
+
+    #[test]
+    fn history_is_persisted_and_truncated() {
+        let file = PathBuf::from("history_is_persisted_and_truncated.test");
+        let mut history = History::load(Some(file.clone()), 3);
+        assert!(history.is_empty());
+        for entry in ["a;", "", "b;", "c;", "d;"] {
+            history.push(entry.to_string()).unwrap();
         }
+        assert_eq!(history.entries, vec!["b;", "c;", "d;"]);
+
+        let reloaded = History::load(Some(file.clone()), 2);
+        assert_eq!(reloaded.entries, vec!["c;", "d;"]);
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn history_is_kept_when_saving_fails() {
+        let file = PathBuf::from("missing_dir/history_is_kept_when_saving_fails.test");
+        let mut history = History::load(Some(file), 3);
+        assert!(history.push(String::from("a;")).is_err());
+        assert!(history.push(String::from("b;")).is_err());
+        assert_eq!(history.entries, vec!["a;", "b;"]);
+    }
 
-        println!("{}", divider);
+    #[test]
+    fn format_table_aligns_columns() {
+        let schema = Schema::new(vec![
+            Column::new(String::from("name"), DbType::String),
+            Column::new(String::from("n"), DbType::Integer),
+        ]);
+        let rows = vec![
+            Row::new(vec![
+                DbValue::String(String::from("a")),
+                DbValue::Integer(100),
+            ]),
+            Row::new(vec![
+                DbValue::String(String::from("longer")),
+                DbValue::Integer(-1),
+            ]),
+        ];
+        let expected = "\
+----------------
+| name   | n   |
+----------------
+| a      | 100 |
+| longer | -1  |
+----------------
+";
+        assert_eq!(Repl::format_table(&schema, &rows), expected);
     }
 }
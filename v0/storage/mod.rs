@@ -1,11 +1,12 @@
 use std::{
     borrow::Cow,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
+    ffi::OsString,
     fmt::{Display, Write as FmtWrite},
-    fs::{File, OpenOptions},
-    io::{self, Read, Seek, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek},
     iter::zip,
-    path::Path,
+    path::{Path, PathBuf},
     str::Utf8Error,
 };
 
@@ -14,7 +15,9 @@ use serde::{de, ser, Deserialize, Serialize};
 
 use crate::{
     generate::{Generate, RNG},
-    has_duplicates, DbFloat, DbType, DbValue,
+    has_duplicates,
+    query::{tokenize::quote_identifier, CheckPredicate},
+    DbFloat, DbType, DbValue,
 };
 
 pub mod read;
@@ -31,12 +34,59 @@ pub enum StorageError {
     DuplicateColumnNames,
     EmptyTableName,
     EmptySchemaProvided,
-    SchemaDoesntMatch,
+    ColumnCountMismatch {
+        expected: usize,
+        got: usize,
+    },
+    SchemaMismatch {
+        expected: DbType,
+        got: DbType,
+        column: String,
+    },
+    /// A string longer than its `CHAR` or `VARCHAR` column allows
+    ValueTooLong {
+        column: String,
+        max: u32,
+    },
     UniquenessConstraintViolated,
+    /// A row didn't satisfy the named CHECK constraint, or it couldn't be evaluated
+    CheckConstraintFailed(String),
+    /// A foreign key must reference its parent table's primary key, from a column of
+    /// the same type
+    InvalidForeignKey {
+        column: String,
+        parent_table: String,
+        parent_column: String,
+    },
+    /// An inserted row's `column` had no matching primary key in `parent_table`
+    MissingParentKey {
+        table: String,
+        column: String,
+        parent_table: String,
+    },
+    /// Rows of `table` are still referenced by `child_table`'s `child_column`
+    ReferencedByChild {
+        table: String,
+        child_table: String,
+        child_column: String,
+    },
     UnkownPrimaryKeyColumn,
-    UnknownColumnNameProvided,
+    UnknownColumnName(String),
     NonIndexedConflictColumn,
     ReservedColumnName,
+    ReadOnly,
+    /// `BEGIN` was run while a transaction was already open
+    TransactionAlreadyOpen,
+    /// `COMMIT` or `ROLLBACK` was run without a transaction open
+    NoTransaction,
+    /// Another session has a transaction open, so nothing else can change the database
+    /// until it's committed or rolled back
+    Busy,
+    /// The file was written in a newer format than this build can read
+    UnsupportedVersion {
+        found: u16,
+        supported: u16,
+    },
 }
 impl Display for StorageError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -47,16 +97,71 @@ impl Display for StorageError {
             Self::DuplicateColumnNames => f.write_str("Duplicate column names found"),
             Self::EmptyTableName => f.write_str("An empty table name was provided"),
             Self::EmptySchemaProvided => f.write_str("Empty schema provided"),
-            Self::SchemaDoesntMatch => f.write_str("Non-matching schema provided"),
+            Self::ColumnCountMismatch { expected, got } => f.write_fmt(format_args!(
+                "Expected {expected} columns but {got} were provided"
+            )),
+            Self::SchemaMismatch {
+                expected,
+                got,
+                column,
+            } => f.write_fmt(format_args!(
+                "Column '{column}' expects a value of type {expected:?}, but got {got:?}"
+            )),
+            Self::ValueTooLong { column, max } => f.write_fmt(format_args!(
+                "Column '{column}' can't hold more than {max} characters"
+            )),
             Self::UniquenessConstraintViolated => {
                 f.write_str("A uniqueness constraint was violated")
             }
+            Self::CheckConstraintFailed(name) => {
+                f.write_fmt(format_args!("CHECK constraint failed: '{name}'"))
+            }
+            Self::InvalidForeignKey {
+                column,
+                parent_table,
+                parent_column,
+            } => f.write_fmt(format_args!(
+                "Foreign key column '{column}' can't reference {parent_table}({parent_column}), which must be a primary key of the same type"
+            )),
+            Self::MissingParentKey {
+                table,
+                column,
+                parent_table,
+            } => f.write_fmt(format_args!(
+                "{table}({column}) has no matching primary key in {parent_table}"
+            )),
+            Self::ReferencedByChild {
+                table,
+                child_table,
+                child_column,
+            } => f.write_fmt(format_args!(
+                "Rows of {table} are still referenced by {child_table}({child_column})"
+            )),
             Self::UnkownPrimaryKeyColumn => f.write_str("Unknown primary key column provided"),
-            Self::UnknownColumnNameProvided => f.write_str("Unknown column name provided"),
+            Self::UnknownColumnName(name) => {
+                f.write_fmt(format_args!("Unknown column name provided: '{name}'"))
+            }
             Self::NonIndexedConflictColumn => {
                 f.write_str("A non-indexed column name was provided as part of a conlict rule")
             }
             Self::ReservedColumnName => f.write_str("A column using a reserved name was provided"),
+            Self::ReadOnly => f.write_str("The database was opened read-only"),
+            Self::TransactionAlreadyOpen => {
+                f.write_str("A transaction is already open, and they can't be nested")
+            }
+            Self::NoTransaction => f.write_str("No transaction is open"),
+            Self::Busy => f.write_str("The database is in use by another session's transaction"),
+            Self::UnsupportedVersion { found, supported } => f.write_fmt(format_args!(
+                "The file uses format version {found}, but only versions up to {supported} can be read"
+            )),
+        }
+    }
+}
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SerdeError(err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -73,27 +178,116 @@ impl From<io::Error> for StorageError {
 
 type Result<T> = std::result::Result<T, StorageError>;
 
-#[derive(Deserialize, Debug)]
+/// The start of a database file, up to the database header's version, which
+/// every version of the format keeps in the same place
+#[derive(Deserialize)]
+struct VersionPrefix {
+    db_header: HeaderVersion,
+}
+#[derive(Deserialize)]
+struct HeaderVersion {
+    header_version: u16,
+}
+
+/// The start of a table, up to the versions of its header and rows
+#[derive(Deserialize)]
+struct TableVersionPrefix {
+    header: TableVersions,
+}
+#[derive(Deserialize)]
+struct TableVersions {
+    header_version: u16,
+    row_header_version: u16,
+}
+
+#[derive(Debug)]
 struct DeserializableStorageLayer {
     db_header: DbHeader,
     tables: Vec<Table>,
 }
 impl DeserializableStorageLayer {
-    fn into_storage_layer(self, file: File) -> StorageLayer {
+    /// Parses a database file's contents, checking it was written in a format this
+    /// build understands
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        // the database header's version comes first, so it's checked before trying
+        // to parse the rest in a format that may not match
+        let prefix: VersionPrefix = read::prefix_from_bytes(bytes)?;
+        check_version(prefix.db_header.header_version, DB_HEADER_VERSION)?;
+
+        // the layer is written as a sequence of the database header and the tables,
+        // which are read one at a time so each one's versions are checked first too
+        let (_field_count, rest): (u64, _) = read::split_prefix(bytes)?;
+        let (db_header, rest): (DbHeader, _) = read::split_prefix(rest)?;
+        let (table_count, mut rest): (u64, _) = read::split_prefix(rest)?;
+        let mut tables = Vec::new();
+        for _ in 0..table_count {
+            let prefix: TableVersionPrefix = read::prefix_from_bytes(rest)?;
+            check_version(prefix.header.header_version, TABLE_HEADER_VERSION)?;
+            check_version(prefix.header.row_header_version, ROW_HEADER_VERSION)?;
+            let (mut table, remaining): (Table, _) = read::split_prefix(rest)?;
+            table.migrate();
+            tables.push(table);
+            rest = remaining;
+        }
+        if !rest.is_empty() {
+            return Err(SerdeError::TrailingBytes.into());
+        }
+        let mut db = DeserializableStorageLayer { db_header, tables };
+        db.migrate();
+        Ok(db)
+    }
+
+    /// Runs the migrations needed to bring a database read from an older version up
+    /// to the current one, so the next flush writes it in the current format
+    fn migrate(&mut self) {
+        let from = self.db_header.header_version as usize;
+        for migration in &DB_MIGRATIONS[from..] {
+            migration(self);
+        }
+        self.db_header.header_version = DB_HEADER_VERSION;
+    }
+
+    fn into_storage_layer(self, backing: Backing) -> StorageLayer {
         StorageLayer {
-            file,
+            backing,
             db_header: self.db_header,
             tables: self.tables,
+            last_insert_rowid: None,
+            read_only: false,
+            session: 0,
+            transaction_owner: None,
         }
     }
 }
 
+/// Where the database is persisted when it's flushed
+#[derive(Debug)]
+enum Backing {
+    /// Flushing writes a new copy of the file beside it, then renames that over the
+    /// original, so the file at `path` is never left half-written
+    File { file: File, path: PathBuf },
+    /// Nothing is persisted. Flushing keeps a copy of the tables in memory
+    /// instead, which reloading goes back to.
+    Memory { flushed: Vec<Table> },
+}
+
 #[derive(Debug, Serialize)]
 pub struct StorageLayer {
     #[serde(skip)]
-    file: File,
+    backing: Backing,
     pub db_header: DbHeader,
     tables: Vec<Table>,
+    #[serde(skip)]
+    last_insert_rowid: Option<usize>,
+    #[serde(skip)]
+    read_only: bool,
+    /// Which session is using the storage. Whoever holds the storage sets this, so a
+    /// transaction can tell its own statements apart from other sessions'.
+    #[serde(skip)]
+    session: usize,
+    /// The session that started the open transaction, if there is one
+    #[serde(skip)]
+    transaction_owner: Option<usize>,
 }
 impl StorageLayer {
     pub fn init(db_file: &Path) -> Result<Self> {
@@ -108,8 +302,12 @@ impl StorageLayer {
         let mut file = OpenOptions::new().read(true).write(true).open(db_file)?;
         let mut buff = Vec::new();
         file.read_to_end(&mut buff)?;
-        let ser_db: DeserializableStorageLayer = read::from_bytes(&buff)?;
-        let db = ser_db.into_storage_layer(file);
+        let ser_db = DeserializableStorageLayer::parse(&buff)?;
+        let backing = Backing::File {
+            file,
+            path: db_file.to_path_buf(),
+        };
+        let db = ser_db.into_storage_layer(backing);
         Ok(db)
     }
 
@@ -120,34 +318,191 @@ impl StorageLayer {
             .create_new(true)
             .open(db_file)?;
         let db = StorageLayer {
-            file,
+            backing: Backing::File {
+                file,
+                path: db_file.to_path_buf(),
+            },
             db_header: DbHeader::new(),
             tables: Vec::new(),
+            last_insert_rowid: None,
+            read_only: false,
+            session: 0,
+            transaction_owner: None,
         };
         Ok(db)
     }
 
+    /// Opens an existing database file without write access. Flushing and reloading
+    /// do nothing, and anything that would modify the database fails with `ReadOnly`.
+    pub fn open_read_only(db_file: &Path) -> Result<Self> {
+        let mut db = StorageLayer::read_file(db_file)?;
+        db.read_only = true;
+        Ok(db)
+    }
+
+    fn read_file(db_file: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new().read(true).open(db_file)?;
+        let mut buff = Vec::new();
+        file.read_to_end(&mut buff)?;
+        let ser_db = DeserializableStorageLayer::parse(&buff)?;
+        let backing = Backing::File {
+            file,
+            path: db_file.to_path_buf(),
+        };
+        Ok(ser_db.into_storage_layer(backing))
+    }
+
+    /// Creates an empty database that's never written to disk
+    pub fn in_memory() -> Self {
+        StorageLayer {
+            backing: Backing::Memory {
+                flushed: Vec::new(),
+            },
+            db_header: DbHeader::new(),
+            tables: Vec::new(),
+            last_insert_rowid: None,
+            read_only: false,
+            session: 0,
+            transaction_owner: None,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(StorageError::ReadOnly);
+        }
+        self.ensure_not_busy()
+    }
+
+    /// Changes made while another session's transaction is open would be committed or
+    /// rolled back along with it, so they aren't allowed
+    fn ensure_not_busy(&self) -> Result<()> {
+        match self.transaction_owner {
+            Some(owner) if owner != self.session => Err(StorageError::Busy),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the session the following operations are done by
+    pub fn set_session(&mut self, session: usize) {
+        self.session = session;
+    }
+
     pub fn flush(&mut self) -> Result<()> {
-        // temporary file reference to allow borrow of self in to_writer
-        let mut file = self.file.try_clone()?;
-        file.rewind()?;
-        file.set_len(0)?;
+        if self.read_only {
+            return Ok(());
+        }
+        self.ensure_not_busy()?;
+        let path = match &mut self.backing {
+            Backing::File { file: _, path } => path.clone(),
+            Backing::Memory { flushed } => {
+                flushed.clone_from(&self.tables);
+                return Ok(());
+            }
+        };
         self.db_header.last_modified = Utc::now();
-        write::to_writer(&mut file, self)?;
-        file.flush()?;
+        let temp_path = StorageLayer::temp_path(&path);
+        let file = match self.write_new_file(&temp_path) {
+            Ok(file) => file,
+            Err(err) => {
+                // the original is untouched, so all that's lost is this flush
+                _ = fs::remove_file(&temp_path);
+                return Err(err);
+            }
+        };
+        fs::rename(&temp_path, &path)?;
+        self.backing = Backing::File { file, path };
         Ok(())
     }
 
+    /// Where a flush writes the new copy of the file at `path` before replacing it
+    fn temp_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+        name.push(".tmp");
+        path.with_file_name(name)
+    }
+
+    /// Writes the whole database to a new file at `path`, and waits for it to reach
+    /// the disk
+    fn write_new_file(&self, path: &Path) -> Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        // serialized straight into the file, without holding the whole database in memory,
+        // but buffered so each value isn't written separately
+        let mut writer = BufWriter::new(file);
+        write::to_writer(&mut writer, self)?;
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        Ok(file)
+    }
+
     pub fn reload(&mut self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        self.ensure_not_busy()?;
+        let file = match &mut self.backing {
+            Backing::File { file, path: _ } => file,
+            Backing::Memory { flushed } => {
+                self.tables.clone_from(flushed);
+                return Ok(());
+            }
+        };
         let mut buff = Vec::new();
-        self.file.rewind()?;
-        self.file.read_to_end(&mut buff)?;
-        let ser_db: DeserializableStorageLayer = read::from_bytes(&buff)?;
+        file.rewind()?;
+        file.read_to_end(&mut buff)?;
+        let ser_db = DeserializableStorageLayer::parse(&buff)?;
         self.db_header = ser_db.db_header;
         self.tables = ser_db.tables;
         Ok(())
     }
 
+    /// Starts a transaction for the current session. Rolling back returns to the last
+    /// flush, so changes made before this should already be flushed.
+    pub fn begin(&mut self) -> Result<()> {
+        self.ensure_not_busy()?;
+        if self.transaction_owner.is_some() {
+            return Err(StorageError::TransactionAlreadyOpen);
+        }
+        self.transaction_owner = Some(self.session);
+        Ok(())
+    }
+
+    /// Ends the current session's transaction, flushing the changes made during it
+    pub fn commit(&mut self) -> Result<()> {
+        self.ensure_not_busy()?;
+        if self.transaction_owner.is_none() {
+            return Err(StorageError::NoTransaction);
+        }
+        self.flush()?;
+        self.transaction_owner = None;
+        Ok(())
+    }
+
+    /// Ends the current session's transaction, discarding the changes made during it
+    pub fn rollback(&mut self) -> Result<()> {
+        self.ensure_not_busy()?;
+        if self.transaction_owner.is_none() {
+            return Err(StorageError::NoTransaction);
+        }
+        self.reload()?;
+        self.transaction_owner = None;
+        Ok(())
+    }
+
+    /// The session whose transaction is open, if any
+    pub fn transaction_owner(&self) -> Option<usize> {
+        self.transaction_owner
+    }
+
     pub fn table_exists(&self, name: &str) -> bool {
         self.tables.iter().any(|t| t.header.table_name == name)
     }
@@ -157,7 +512,10 @@ impl StorageLayer {
         name: String,
         schema: Schema,
         primary_key_col: PrimaryKey,
+        checks: Vec<CheckConstraint>,
+        foreign_keys: Vec<ForeignKey>,
     ) -> Result<()> {
+        self.ensure_writable()?;
         if self.table_exists(&name) {
             return Err(StorageError::TableAlreadyExists);
         }
@@ -178,22 +536,106 @@ impl StorageLayer {
         {
             return Err(StorageError::ReservedColumnName);
         }
-        let table = Table::build(name, schema, primary_key_col)?;
+        let mut table = Table::build(name, schema, primary_key_col)?;
+        table.header.checks = checks;
+        for fk in &foreign_keys {
+            // a table can reference itself, so the parent may be the new table
+            let parent = match self.table(&fk.parent_table) {
+                Some(parent) => parent,
+                None if fk.parent_table == table.header.table_name => &table,
+                None => return Err(StorageError::TableDoesNotExist),
+            };
+            let column = table
+                .header
+                .schema
+                .column(&fk.column)
+                .ok_or_else(|| StorageError::UnknownColumnName(fk.column.clone()))?;
+            match &parent.primary_key {
+                PrimaryKey::Column { col, keyset: _ }
+                    if col.name == fk.parent_column && col._type == column._type => {}
+                _ => {
+                    return Err(StorageError::InvalidForeignKey {
+                        column: fk.column.clone(),
+                        parent_table: fk.parent_table.clone(),
+                        parent_column: fk.parent_column.clone(),
+                    })
+                }
+            }
+        }
+        table.header.foreign_keys = foreign_keys;
         self.tables.push(table);
         Ok(())
     }
 
+    /// The tables with foreign keys referencing `table_name`, which may include itself,
+    /// along with those keys
+    fn children<'a>(
+        &'a self,
+        table_name: &'a str,
+    ) -> impl Iterator<Item = (&'a Table, &'a ForeignKey)> {
+        self.tables
+            .iter()
+            .flat_map(|t| t.header.foreign_keys.iter().map(move |fk| (t, fk)))
+            .filter(move |(_, fk)| fk.parent_table == table_name)
+    }
+
     pub fn destroy_table(&mut self, name: &str) -> Result<()> {
+        self.ensure_writable()?;
         let idx = self.tables.iter().position(|t| t.header.table_name == name);
         let idx = match idx {
             Some(idx) => idx,
             None => return Err(StorageError::TableDoesNotExist),
         };
+        if let Some((child, fk)) = self
+            .children(name)
+            .find(|(t, _)| t.header.table_name != name)
+        {
+            return Err(child.referenced_by(name, fk));
+        }
 
         self.tables.swap_remove(idx);
         Ok(())
     }
 
+    /// Returns a `CREATE TABLE` statement for every table, each followed by one
+    /// `INSERT` statement per row. Executing the statements in order against an
+    /// empty database reconstructs it, although rowids are re-assigned densely
+    /// in insertion order, so gaps left by deletes are not preserved.
+    pub fn dump(&self) -> Vec<String> {
+        let mut statements = Vec::new();
+        for table in self.tables.iter() {
+            statements.push(table.create_statement());
+            statements.extend(table.insert_statements());
+        }
+        statements
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        self.tables
+            .iter()
+            .map(|t| t.header.table_name.clone())
+            .collect()
+    }
+
+    pub fn table_schema_info(&self, table_name: &str) -> Result<String> {
+        match self.table(table_name) {
+            None => Err(StorageError::TableDoesNotExist),
+            Some(table) => Ok(table.table_info().to_string()),
+        }
+    }
+
+    pub fn table_info(&self, table_name: &str) -> Option<TableInfo> {
+        self.table(table_name).map(|t| t.table_info())
+    }
+
+    /// Cardinality information about a table, for choosing how to run a query against it
+    pub fn table_stats(&self, table_name: &str) -> Result<TableStats> {
+        match self.table(table_name) {
+            None => Err(StorageError::TableDoesNotExist),
+            Some(table) => Ok(table.stats()),
+        }
+    }
+
     pub fn show_table_info(&self) {
         for t in self.tables.iter() {
             println!("{}", t.info());
@@ -201,6 +643,15 @@ impl StorageLayer {
         println!("------------");
     }
 
+    /// Checks every table's rows against its schema, primary key and next rowid,
+    /// returning what's wrong rather than stopping at the first problem
+    pub fn check_integrity(&self) -> Vec<IntegrityProblem> {
+        self.tables
+            .iter()
+            .flat_map(|t| t.check_integrity())
+            .collect()
+    }
+
     pub fn table_row_count(&self, table_name: &str) -> Result<usize> {
         match self.table(table_name) {
             None => Err(StorageError::TableDoesNotExist),
@@ -226,19 +677,127 @@ impl StorageLayer {
         rows: &[Row],
         conflict_rule: Option<ConflictRule>,
     ) -> Result<usize> {
-        let table = match self.table_mut(table_name) {
+        self.ensure_writable()?;
+        let table = match self.table(table_name) {
             Some(table) => table,
             None => return Err(StorageError::TableDoesNotExist),
         };
-        table.insert_rows(rows, conflict_rule)
+        for fk in &table.header.foreign_keys {
+            let parent = self
+                .table(&fk.parent_table)
+                .ok_or(StorageError::TableDoesNotExist)?;
+            for row in rows {
+                table.header.schema.check_row(row)?;
+                let val = table.header.schema.column_value(&fk.column, row)?;
+                if !parent.has_key(val) {
+                    return Err(StorageError::MissingParentKey {
+                        table: table_name.to_string(),
+                        column: fk.column.clone(),
+                        parent_table: fk.parent_table.clone(),
+                    });
+                }
+            }
+        }
+        let table = self
+            .table_mut(table_name)
+            .ok_or(StorageError::TableDoesNotExist)?;
+        let (affected, last_id) = table.insert_rows(rows, conflict_rule)?;
+        if last_id.is_some() {
+            self.last_insert_rowid = last_id;
+        }
+        Ok(affected)
+    }
+
+    /// The rowid of the most recently inserted row, if any row has been inserted
+    /// since this was opened. Inserts that are skipped by a conflict rule leave it unchanged.
+    pub fn last_insert_rowid(&self) -> Option<usize> {
+        self.last_insert_rowid
     }
 
-    pub fn delete_rows(&mut self, table_name: &str, ids: &[usize]) -> Result<usize> {
+    /// Deletes the rows matching `predicate` in a single pass over the table, returning how
+    /// many were deleted. When `with_row_id` is set, the predicate sees each row with its
+    /// row id appended, as in `table_scan`.
+    pub fn delete_rows_where(
+        &mut self,
+        table_name: &str,
+        with_row_id: bool,
+        predicate: impl FnMut(&Row) -> bool,
+    ) -> Result<usize> {
+        self.ensure_writable()?;
+        let table = match self.table(table_name) {
+            Some(table) => table,
+            None => return Err(StorageError::TableDoesNotExist),
+        };
+        if self.children(table_name).next().is_none() {
+            let table = self
+                .table_mut(table_name)
+                .ok_or(StorageError::TableDoesNotExist)?;
+            return Ok(table.delete_rows_where(with_row_id, predicate));
+        }
+        // nothing is deleted until it's known no child references any of the rows
+        let (ids, keys) = table.matching_keys(with_row_id, predicate);
+        let none_deleted = HashSet::new();
+        for (child, fk) in self.children(table_name) {
+            // rows referencing their own table don't count when they're deleted too
+            let deleted = if child.header.table_name == table_name {
+                &ids
+            } else {
+                &none_deleted
+            };
+            if child.references_any(fk, &keys, table.primary_key_collation(), deleted) {
+                return Err(child.referenced_by(table_name, fk));
+            }
+        }
+        let table = self
+            .table_mut(table_name)
+            .ok_or(StorageError::TableDoesNotExist)?;
+        Ok(table.delete_rows_where(true, |row| match row.data.last() {
+            Some(DbValue::UnsignedInt(id)) => ids.contains(&(*id as usize)),
+            _ => false,
+        }))
+    }
+
+    /// Compacts a table, or every table when `table_name` is `None`, renumbering its row ids
+    /// densely, then flushes so space left behind by deleted rows is released. This changes
+    /// the row ids of existing rows. Returns how much smaller the file got, which is always
+    /// zero for an in-memory database.
+    pub fn vacuum(&mut self, table_name: Option<&str>) -> Result<u64> {
+        self.ensure_writable()?;
+        match table_name {
+            Some(name) => match self.table_mut(name) {
+                Some(table) => table.vacuum(),
+                None => return Err(StorageError::TableDoesNotExist),
+            },
+            None => self.tables.iter_mut().for_each(|t| t.vacuum()),
+        };
+        // the last inserted row may have been renumbered
+        self.last_insert_rowid = None;
+        let size_before = self.file_size()?;
+        self.flush()?;
+        Ok(size_before.saturating_sub(self.file_size()?))
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        match &self.backing {
+            Backing::File { file, path: _ } => Ok(file.metadata()?.len()),
+            Backing::Memory { flushed: _ } => Ok(0),
+        }
+    }
+
+    /// Deletes every row in the table, returning how many there were
+    pub fn truncate_table(&mut self, table_name: &str) -> Result<usize> {
+        self.ensure_writable()?;
+        if let Some((child, fk)) = self
+            .children(table_name)
+            .find(|(t, _)| t.header.table_name != table_name && !t.rows.is_empty())
+        {
+            return Err(child.referenced_by(table_name, fk));
+        }
         let table = match self.table_mut(table_name) {
             Some(table) => table,
             None => return Err(StorageError::TableDoesNotExist),
         };
-        table.delete_rows(ids)
+        Ok(table.truncate())
     }
 
     pub fn table_scan(&self, table_name: &str, with_row_id: bool) -> Result<Rows> {
@@ -249,6 +808,21 @@ impl StorageLayer {
         Ok(table.rows(with_row_id))
     }
 
+    /// Like `table_scan`, starting from the first row with a rowid greater than `after`.
+    /// Rows are kept in rowid order, so this doesn't go through the rows before it.
+    pub fn table_scan_after(
+        &self,
+        table_name: &str,
+        after: usize,
+        with_row_id: bool,
+    ) -> Result<Rows<'_>> {
+        let table = match self.table(table_name) {
+            Some(table) => table,
+            None => return Err(StorageError::TableDoesNotExist),
+        };
+        Ok(table.rows_after(after, with_row_id))
+    }
+
     pub fn table_schema(&self, table_name: &str) -> Result<&Schema> {
         let table = match self.table(table_name) {
             Some(table) => table,
@@ -259,6 +833,19 @@ impl StorageLayer {
 }
 
 const DB_HEADER_VERSION: u16 = 0;
+
+/// Upgrades a database from one version of the format to the next, after it's been
+/// read. `DB_MIGRATIONS[v]` upgrades a version `v` database.
+type DbMigration = fn(&mut DeserializableStorageLayer);
+const DB_MIGRATIONS: [DbMigration; DB_HEADER_VERSION as usize] = [];
+
+fn check_version(found: u16, supported: u16) -> Result<()> {
+    if found > supported {
+        return Err(StorageError::UnsupportedVersion { found, supported });
+    }
+    Ok(())
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DbHeader {
     header_version: u16,
@@ -278,14 +865,28 @@ impl Default for DbHeader {
     }
 }
 
-const TABLE_HEADER_VERSION: u16 = 0;
+/// Version 1 added `Column::collation`, version 2 added `TableHeader::checks`, and
+/// version 3 added `TableHeader::foreign_keys`
+const TABLE_HEADER_VERSION: u16 = 3;
 const ROW_HEADER_VERSION: u16 = 0;
-#[derive(Serialize, Deserialize, Debug)]
+
+/// Upgrades a table from one version of the format to the next, after it's been
+/// read. `TABLE_MIGRATIONS[v]` upgrades a version `v` table.
+type TableMigration = fn(&mut Table);
+const TABLE_MIGRATIONS: [TableMigration; TABLE_HEADER_VERSION as usize] =
+    [Table::migrate_v0, Table::migrate_v1, Table::migrate_v2];
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TableHeader {
     header_version: u16,
     row_header_version: u16,
     table_name: String,
     schema: Schema,
+    /// Tables written before version 2 don't store this, so they read as having none
+    #[serde(default)]
+    checks: Vec<CheckConstraint>,
+    /// Tables written before version 3 don't store this, so they read as having none
+    #[serde(default)]
+    foreign_keys: Vec<ForeignKey>,
 }
 impl TableHeader {
     pub fn new(table_name: String, schema: Schema) -> Self {
@@ -294,47 +895,191 @@ impl TableHeader {
             row_header_version: ROW_HEADER_VERSION,
             table_name,
             schema,
+            checks: Vec::new(),
+            foreign_keys: Vec::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A predicate every row in a table must satisfy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckConstraint {
+    /// The name given with `CONSTRAINT`, or the predicate itself if there wasn't one
+    pub name: String,
+    /// The predicate as it was written. It's stored as text and parsed again for
+    /// each insert, so it doesn't tie the file format to the parser's.
+    pub predicate: String,
+}
+
+/// A column whose values must each match the primary key of a row in another table.
+/// Rows of that table can't be deleted while they're referenced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub column: String,
+    pub parent_table: String,
+    /// The parent table's primary key column
+    pub parent_column: String,
+}
+
+/// A description of a table, for finding out what a database contains
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub schema: Schema,
+    /// The name of the primary key column, which is `rowid` if the table didn't declare one
+    pub primary_key: String,
+    pub row_count: usize,
+}
+impl Display for TableInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}\n  primary key: {}",
+            self.name, self.schema, self.primary_key
+        )
+    }
+}
+
+/// Cardinality information about a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStats {
+    pub row_count: usize,
+    /// The number of distinct values in each indexed column, as compared by its collation.
+    /// Only a declared primary key is indexed, so its count is always the row count.
+    pub distinct_values: HashMap<String, usize>,
+}
+
+/// Something `StorageLayer::check_integrity` found wrong with a table
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityProblem {
+    /// A stored row doesn't fit the table's schema
+    RowDoesNotMatchSchema {
+        table: String,
+        rowid: usize,
+        reason: String,
+    },
+    /// A row's primary key is missing from the table's key set
+    KeyMissingFromKeySet { table: String, key: DbValue },
+    /// The table's key set holds a primary key no row has
+    KeyWithoutRow { table: String, key: DbValue },
+    /// More than one row has the same primary key
+    DuplicateKey { table: String, key: DbValue },
+    /// The primary key's column isn't in the table's schema, so no keys were checked
+    PrimaryKeyColumnMissing { table: String, column: String },
+    /// A row's id isn't below the table's next rowid, so a new row could be given it too
+    RowidNotBelowNextId {
+        table: String,
+        rowid: usize,
+        next_id: usize,
+    },
+}
+impl Display for IntegrityProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RowDoesNotMatchSchema {
+                table,
+                rowid,
+                reason,
+            } => write!(f, "{table}: Row {rowid} doesn't match the schema: {reason}"),
+            Self::KeyMissingFromKeySet { table, key } => {
+                write!(f, "{table}: Primary key {key} is missing from the key set")
+            }
+            Self::KeyWithoutRow { table, key } => {
+                write!(f, "{table}: The key set holds {key}, which no row has")
+            }
+            Self::DuplicateKey { table, key } => {
+                write!(f, "{table}: More than one row has the primary key {key}")
+            }
+            Self::PrimaryKeyColumnMissing { table, column } => {
+                write!(
+                    f,
+                    "{table}: The primary key column {column} isn't in the schema"
+                )
+            }
+            Self::RowidNotBelowNextId {
+                table,
+                rowid,
+                next_id,
+            } => write!(
+                f,
+                "{table}: Row {rowid} isn't below the next rowid to be handed out, {next_id}"
+            ),
+        }
+    }
+}
+
+/// How a column's values are compared when filtering, sorting, and checking uniqueness
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Values are compared as they are, so case matters
+    #[default]
+    Binary,
+    /// Strings are compared ignoring case
+    NoCase,
+}
+impl Collation {
+    /// What `val` is compared as. Only the comparison is affected, the value stored
+    /// is never changed.
+    pub fn key<'a>(&self, val: &'a DbValue) -> Cow<'a, DbValue> {
+        match (self, val) {
+            (Self::NoCase, DbValue::String(s)) => Cow::Owned(DbValue::String(s.to_lowercase())),
+            (Self::NoCase, DbValue::Char(s)) => Cow::Owned(DbValue::Char(s.to_lowercase())),
+            _ => Cow::Borrowed(val),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Column {
     pub name: String,
     pub _type: DbType,
+    /// Tables written before version 1 don't store this, so their columns read as `Binary`
+    #[serde(default)]
+    pub collation: Collation,
 }
 impl Column {
     pub fn new(name: String, _type: DbType) -> Self {
-        Column { name, _type }
+        Column {
+            name,
+            _type,
+            collation: Collation::Binary,
+        }
+    }
+
+    pub fn collated(self, collation: Collation) -> Self {
+        Column { collation, ..self }
     }
 
     pub fn with_name(&self, name: String) -> Self {
         Column {
             name,
             _type: self._type,
+            collation: self.collation,
         }
     }
 }
 impl Display for Column {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{} ({:?})", self.name, self._type))
+        match self.collation {
+            Collation::Binary => write!(f, "{} ({:?})", self.name, self._type),
+            Collation::NoCase => write!(f, "{} ({:?} COLLATE NOCASE)", self.name, self._type),
+        }
     }
 }
 impl Generate for Column {
+    /// Quotes are left out of the name, so it can be written without quoting it
     fn generate(rng: &mut crate::generate::RNG) -> Self {
-        let mut name = String::generate(rng);
+        let mut name = String::new();
         while name.is_empty() {
             name = String::generate(rng);
+            name.retain(|c| !matches!(c, '\'' | '"' | '`'));
         }
         name.truncate(6);
-        Column {
-            name,
-            _type: DbType::generate(rng),
-        }
+        Column::new(name, DbType::generate(rng))
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ColumnWithIndex {
     pub column: Column,
     pub index: usize,
@@ -345,20 +1090,32 @@ impl ColumnWithIndex {
     }
 }
 
-// TODO: Need to consider storing column order explicitly somewhere
-//      so that we're not re-sorting it every time, or consider how to do
-//      differently the things `columns()` is currently being used for.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "SchemaMap")]
 pub struct Schema {
     schema: HashMap<String, ColumnWithIndex>,
+    /// Column names by index, so columns can be listed in order without sorting them.
+    /// Not serialized, since the indexes in `schema` already record it
+    #[serde(skip)]
+    order: Vec<String>,
 }
 impl Schema {
     pub fn new(schema: Vec<Column>) -> Self {
         let mut map = HashMap::new();
+        let mut order = Vec::with_capacity(schema.len());
         for (index, col) in schema.into_iter().enumerate() {
+            order.push(col.name.clone());
             map.insert(col.name.clone(), ColumnWithIndex::new(col, index));
         }
-        Schema { schema: map }
+        Schema { schema: map, order }
+    }
+
+    /// Adds a column after the existing ones
+    pub fn push(&mut self, column: Column) {
+        let index = self.order.len();
+        self.order.push(column.name.clone());
+        self.schema
+            .insert(column.name.clone(), ColumnWithIndex::new(column, index));
     }
 
     pub fn column_position(&self, name: &str) -> Option<usize> {
@@ -374,17 +1131,46 @@ impl Schema {
     }
 
     pub fn matches(&self, row: &Row) -> bool {
+        self.check_row(row).is_ok()
+    }
+
+    /// Like `matches`, but reports which column didn't match
+    pub fn check_row(&self, row: &Row) -> Result<()> {
         let our_count = self.schema.len();
         if row.data.len() != our_count {
-            return false;
+            return Err(StorageError::ColumnCountMismatch {
+                expected: our_count,
+                got: row.data.len(),
+            });
         }
-        let our_types = self.columns().map(|c| c._type);
-        let their_types = row.data.iter().map(|v| v.db_type());
-        zip(our_types, their_types).all(|(a, b)| a == b)
+        for (col, val) in zip(self.columns(), row.data.iter()) {
+            let too_long = |s: &str, max: u32| s.chars().count() > max as usize;
+            match (col._type, val) {
+                (DbType::Char(max), DbValue::Char(s))
+                | (DbType::Varchar(max), DbValue::String(s))
+                    if too_long(s, max) =>
+                {
+                    return Err(StorageError::ValueTooLong {
+                        column: col.name.clone(),
+                        max,
+                    });
+                }
+                (DbType::Varchar(_), DbValue::String(_)) => (),
+                (_type, val) if _type != val.db_type() => {
+                    return Err(StorageError::SchemaMismatch {
+                        expected: col._type,
+                        got: val.db_type(),
+                        column: col.name.clone(),
+                    });
+                }
+                _ => (),
+            }
+        }
+        Ok(())
     }
 
     pub fn columns(&self) -> impl Iterator<Item = &Column> {
-        SchemaColumns::new(self)
+        self.order.iter().map(|name| &self.schema[name].column)
     }
 
     pub fn gen_row(&self, rng: &mut RNG) -> Row {
@@ -398,11 +1184,16 @@ impl Schema {
     pub fn column_value<'a>(&self, name: &str, row: &'a Row) -> Result<&'a DbValue> {
         let pos = match self.column_position(name) {
             Some(p) => p,
-            None => return Err(StorageError::UnknownColumnNameProvided),
+            None => return Err(StorageError::UnknownColumnName(name.to_string())),
         };
         let val = match row.data.get(pos) {
             Some(v) => v,
-            None => return Err(StorageError::SchemaDoesntMatch),
+            None => {
+                return Err(StorageError::ColumnCountMismatch {
+                    expected: self.schema.len(),
+                    got: row.data.len(),
+                })
+            }
         };
         Ok(val)
     }
@@ -411,15 +1202,33 @@ impl Schema {
         let removed = self.schema.remove(name);
         match removed {
             None => (),
-            Some(ci) => self
-                .schema
-                .iter_mut()
-                .map(|(_, col_index)| {
-                    if col_index.index > ci.index {
-                        col_index.index -= 1;
-                    }
-                })
-                .collect(),
+            Some(ci) => {
+                self.order.remove(ci.index);
+                self.schema
+                    .iter_mut()
+                    .map(|(_, col_index)| {
+                        if col_index.index > ci.index {
+                            col_index.index -= 1;
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+/// How a `Schema` is stored, which is all it needs to rebuild the column order
+#[derive(Deserialize)]
+struct SchemaMap {
+    schema: HashMap<String, ColumnWithIndex>,
+}
+impl From<SchemaMap> for Schema {
+    fn from(value: SchemaMap) -> Self {
+        let mut columns: Vec<&ColumnWithIndex> = value.schema.values().collect();
+        columns.sort_by_key(|ci| ci.index);
+        let order = columns.iter().map(|ci| ci.column.name.clone()).collect();
+        Schema {
+            schema: value.schema,
+            order,
         }
     }
 }
@@ -452,67 +1261,108 @@ impl Generate for Schema {
     }
 }
 
-struct SchemaColumns<'a> {
-    columns: Vec<&'a Column>,
-    cursor: usize,
-}
-impl<'a> SchemaColumns<'a> {
-    fn new(schema: &'a Schema) -> Self {
-        let mut columns_with_index: Vec<&ColumnWithIndex> = schema.schema.values().collect();
-        columns_with_index.sort_by_key(|ci| ci.index);
-        let columns = columns_with_index.iter().map(|ci| &ci.column).collect();
-        SchemaColumns { columns, cursor: 0 }
-    }
-}
-impl<'a> Iterator for SchemaColumns<'a> {
-    type Item = &'a Column;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.cursor >= self.columns.len() {
-            return None;
-        }
-        let res = self.columns.get(self.cursor).copied();
-        self.cursor += 1;
-        res
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum PrimaryKey {
     Rowid,
     Column { col: Column, keyset: KeySet },
 }
+impl PrimaryKey {
+    /// A primary key on `col`, with no keys in it yet
+    pub fn column(col: Column) -> Self {
+        let keyset = match col._type {
+            DbType::Float => KeySet::Floats(BTreeSet::new()),
+            DbType::Integer => KeySet::Integers(BTreeSet::new()),
+            DbType::String | DbType::Char(_) | DbType::Varchar(_) => {
+                KeySet::Strings(BTreeSet::new())
+            }
+            DbType::UnsignedInt => KeySet::UnsignedInts(BTreeSet::new()),
+            DbType::SmallInt => KeySet::SmallInts(BTreeSet::new()),
+        };
+        PrimaryKey::Column { col, keyset }
+    }
+}
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum KeySet {
     Strings(BTreeSet<String>),
     Integers(BTreeSet<i64>),
     Floats(BTreeSet<DbFloat>),
     UnsignedInts(BTreeSet<u64>),
+    SmallInts(BTreeSet<i32>),
 }
 impl KeySet {
     pub fn contains(&self, v: &DbValue) -> bool {
         match (self, v) {
-            (Self::Strings(set), DbValue::String(v)) => set.contains(v.as_str()),
+            (Self::Strings(set), DbValue::String(v) | DbValue::Char(v)) => set.contains(v.as_str()),
             (Self::Integers(set), DbValue::Integer(v)) => set.contains(v),
             (Self::Floats(set), DbValue::Float(v)) => set.contains(v),
             (Self::UnsignedInts(set), DbValue::UnsignedInt(v)) => set.contains(v),
+            (Self::SmallInts(set), DbValue::SmallInt(v)) => set.contains(v),
             _ => panic!("This assumes matching types"),
         }
     }
 
     pub fn insert(&mut self, v: DbValue) {
         match (self, v) {
-            (Self::Strings(set), DbValue::String(v)) => set.insert(v),
+            (Self::Strings(set), DbValue::String(v) | DbValue::Char(v)) => set.insert(v),
             (Self::Integers(set), DbValue::Integer(v)) => set.insert(v),
             (Self::Floats(set), DbValue::Float(v)) => set.insert(v),
             (Self::UnsignedInts(set), DbValue::UnsignedInt(v)) => set.insert(v),
+            (Self::SmallInts(set), DbValue::SmallInt(v)) => set.insert(v),
+            _ => panic!("This assumes matching types"),
+        };
+    }
+
+    pub fn remove(&mut self, v: &DbValue) {
+        match (self, v) {
+            (Self::Strings(set), DbValue::String(v) | DbValue::Char(v)) => set.remove(v.as_str()),
+            (Self::Integers(set), DbValue::Integer(v)) => set.remove(v),
+            (Self::Floats(set), DbValue::Float(v)) => set.remove(v),
+            (Self::UnsignedInts(set), DbValue::UnsignedInt(v)) => set.remove(v),
+            (Self::SmallInts(set), DbValue::SmallInt(v)) => set.remove(v),
             _ => panic!("This assumes matching types"),
         };
     }
+
+    /// The number of distinct keys
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Strings(set) => set.len(),
+            Self::Integers(set) => set.len(),
+            Self::Floats(set) => set.len(),
+            Self::UnsignedInts(set) => set.len(),
+            Self::SmallInts(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            Self::Strings(set) => set.clear(),
+            Self::Integers(set) => set.clear(),
+            Self::Floats(set) => set.clear(),
+            Self::UnsignedInts(set) => set.clear(),
+            Self::SmallInts(set) => set.clear(),
+        }
+    }
+
+    /// Every key, in order. Strings come back as `DbValue::String`, even if the
+    /// column holds `CHAR`s.
+    pub fn values(&self) -> Vec<DbValue> {
+        match self {
+            Self::Strings(set) => set.iter().cloned().map(DbValue::String).collect(),
+            Self::Integers(set) => set.iter().copied().map(DbValue::Integer).collect(),
+            Self::Floats(set) => set.iter().cloned().map(DbValue::Float).collect(),
+            Self::UnsignedInts(set) => set.iter().copied().map(DbValue::UnsignedInt).collect(),
+            Self::SmallInts(set) => set.iter().copied().map(DbValue::SmallInt).collect(),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Table {
     header: TableHeader,
     rows: Vec<StorageRow>,
@@ -520,6 +1370,33 @@ pub struct Table {
     primary_key: PrimaryKey,
 }
 impl Table {
+    /// Runs the migrations needed to bring a table read from an older version up to
+    /// the current one, so the next flush writes it in the current format
+    fn migrate(&mut self) {
+        let from = self.header.header_version as usize;
+        for migration in &TABLE_MIGRATIONS[from..] {
+            migration(self);
+        }
+        self.header.header_version = TABLE_HEADER_VERSION;
+    }
+
+    /// Version 1 had no check constraints. Its tables are read with none, so
+    /// there's nothing to change.
+    fn migrate_v1(&mut self) {}
+
+    /// Version 2 had no foreign keys, and is read the same way as version 1
+    fn migrate_v2(&mut self) {}
+
+    /// Version 0 had no collations, so its columns compare values as they are
+    fn migrate_v0(&mut self) {
+        for ci in self.header.schema.schema.values_mut() {
+            ci.column.collation = Collation::Binary;
+        }
+        if let PrimaryKey::Column { col, keyset: _ } = &mut self.primary_key {
+            col.collation = Collation::Binary;
+        }
+    }
+
     pub fn build(table_name: String, schema: Schema, primary_key: PrimaryKey) -> Result<Self> {
         match &primary_key {
             PrimaryKey::Rowid => (),
@@ -546,17 +1423,271 @@ impl Table {
         )
     }
 
+    pub fn table_info(&self) -> TableInfo {
+        let primary_key = match &self.primary_key {
+            PrimaryKey::Rowid => "rowid",
+            PrimaryKey::Column { col, keyset: _ } => col.name.as_str(),
+        };
+        TableInfo {
+            name: self.header.table_name.clone(),
+            schema: self.header.schema.clone(),
+            primary_key: primary_key.to_string(),
+            row_count: self.rows.len(),
+        }
+    }
+
+    pub fn stats(&self) -> TableStats {
+        let distinct_values = match &self.primary_key {
+            PrimaryKey::Rowid => HashMap::new(),
+            PrimaryKey::Column { col, keyset } => HashMap::from([(col.name.clone(), keyset.len())]),
+        };
+        TableStats {
+            row_count: self.rows.len(),
+            distinct_values,
+        }
+    }
+
+    fn check_integrity(&self) -> Vec<IntegrityProblem> {
+        let table = &self.header.table_name;
+        let mut problems = Vec::new();
+        let mut matching_rows = Vec::new();
+        for row in self.rows.iter() {
+            match self.header.schema.check_row(&row.row) {
+                Ok(()) => matching_rows.push(&row.row),
+                Err(err) => problems.push(IntegrityProblem::RowDoesNotMatchSchema {
+                    table: table.clone(),
+                    rowid: row.id,
+                    reason: err.to_string(),
+                }),
+            }
+            if row.id >= self.next_id {
+                problems.push(IntegrityProblem::RowidNotBelowNextId {
+                    table: table.clone(),
+                    rowid: row.id,
+                    next_id: self.next_id,
+                });
+            }
+        }
+
+        if let PrimaryKey::Column { col, keyset } = &self.primary_key {
+            let Some(pos) = self.header.schema.column_position(&col.name) else {
+                problems.push(IntegrityProblem::PrimaryKeyColumnMissing {
+                    table: table.clone(),
+                    column: col.name.clone(),
+                });
+                return problems;
+            };
+            // rows that don't match the schema may not have a key of the key set's type
+            let mut live_keys = keyset.clone();
+            live_keys.clear();
+            for row in matching_rows {
+                let key = col.collation.key(&row.data[pos]).into_owned();
+                if live_keys.contains(&key) {
+                    problems.push(IntegrityProblem::DuplicateKey {
+                        table: table.clone(),
+                        key,
+                    });
+                    continue;
+                }
+                if !keyset.contains(&key) {
+                    problems.push(IntegrityProblem::KeyMissingFromKeySet {
+                        table: table.clone(),
+                        key: key.clone(),
+                    });
+                }
+                live_keys.insert(key);
+            }
+            for key in keyset.values() {
+                if !live_keys.contains(&key) {
+                    problems.push(IntegrityProblem::KeyWithoutRow {
+                        table: table.clone(),
+                        key,
+                    });
+                }
+            }
+        }
+        problems
+    }
+
+    fn is_primary_key_column(&self, name: &str) -> bool {
+        match &self.primary_key {
+            PrimaryKey::Rowid => false,
+            PrimaryKey::Column { col, keyset: _ } => col.name == name,
+        }
+    }
+
+    fn create_statement(&self) -> String {
+        let columns: Vec<String> = self
+            .header
+            .schema
+            .columns()
+            .map(|c| {
+                let mut col = format!(
+                    "{} {}",
+                    quote_identifier(&c.name),
+                    c._type.as_sql_type_str()
+                );
+                if c.collation == Collation::NoCase {
+                    col += " COLLATE NOCASE";
+                }
+                if self.is_primary_key_column(&c.name) {
+                    col += " PRIMARY KEY";
+                }
+                col
+            })
+            .chain(self.header.checks.iter().map(|check| {
+                if check.name == check.predicate {
+                    format!("CHECK ({})", check.predicate)
+                } else {
+                    format!(
+                        "CONSTRAINT {} CHECK ({})",
+                        quote_identifier(&check.name),
+                        check.predicate
+                    )
+                }
+            }))
+            .chain(self.header.foreign_keys.iter().map(|fk| {
+                format!(
+                    "FOREIGN KEY ({}) REFERENCES {}({})",
+                    quote_identifier(&fk.column),
+                    quote_identifier(&fk.parent_table),
+                    quote_identifier(&fk.parent_column)
+                )
+            }))
+            .collect();
+        format!(
+            "CREATE TABLE {} ({});",
+            quote_identifier(&self.header.table_name),
+            columns.join(", ")
+        )
+    }
+
+    fn insert_statements(&self) -> impl Iterator<Item = String> + '_ {
+        let column_names: Vec<_> = self
+            .header
+            .schema
+            .columns()
+            .map(|c| quote_identifier(&c.name))
+            .collect();
+        let column_names = column_names.join(", ");
+        let table_name = quote_identifier(&self.header.table_name);
+        self.rows.iter().map(move |r| {
+            let values: Vec<String> = r
+                .row
+                .data
+                .iter()
+                .map(|v| v.as_insertable_sql_str())
+                .collect();
+            format!(
+                "INSERT INTO {} ({}) VALUES ({});",
+                table_name,
+                column_names,
+                values.join(", ")
+            )
+        })
+    }
+
     fn primary_key_constraint_passes(&self, row: &Row) -> Result<bool> {
         match &self.primary_key {
             PrimaryKey::Rowid => Ok(true),
             PrimaryKey::Column { col, keyset } => {
                 let val = self.header.schema.column_value(&col.name, row)?;
-                Ok(!keyset.contains(val))
+                Ok(!keyset.contains(&col.collation.key(val)))
+            }
+        }
+    }
+
+    fn primary_key_collation(&self) -> Collation {
+        match &self.primary_key {
+            PrimaryKey::Column { col, keyset: _ } => col.collation,
+            PrimaryKey::Rowid => Collation::Binary,
+        }
+    }
+
+    /// Whether a row has `val` as its primary key
+    fn has_key(&self, val: &DbValue) -> bool {
+        match &self.primary_key {
+            PrimaryKey::Column { col, keyset } => keyset.contains(&col.collation.key(val)),
+            PrimaryKey::Rowid => false,
+        }
+    }
+
+    /// The ids of the rows matching `predicate`, which sees them as in `delete_rows_where`,
+    /// along with their primary keys as they're compared
+    fn matching_keys(
+        &self,
+        with_row_id: bool,
+        mut predicate: impl FnMut(&Row) -> bool,
+    ) -> (HashSet<usize>, HashSet<DbValue>) {
+        let pk_position = match &self.primary_key {
+            PrimaryKey::Column { col, keyset: _ } => self.header.schema.column_position(&col.name),
+            PrimaryKey::Rowid => None,
+        };
+        let collation = self.primary_key_collation();
+        let mut ids = HashSet::new();
+        let mut keys = HashSet::new();
+        for (storage_row, row) in zip(&self.rows, self.rows(with_row_id)) {
+            if predicate(&row) {
+                ids.insert(storage_row.id);
+                if let Some(pos) = pk_position {
+                    keys.insert(collation.key(&storage_row.row.data[pos]).into_owned());
+                }
             }
         }
+        (ids, keys)
     }
 
-    fn insert_rows(&mut self, rows: &[Row], conflict_rule: Option<ConflictRule>) -> Result<usize> {
+    /// Whether any row, other than those in `skipped_ids`, has one of `keys` in its `fk`
+    /// column, compared with `collation`
+    fn references_any(
+        &self,
+        fk: &ForeignKey,
+        keys: &HashSet<DbValue>,
+        collation: Collation,
+        skipped_ids: &HashSet<usize>,
+    ) -> bool {
+        let pos = match self.header.schema.column_position(&fk.column) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        self.rows.iter().any(|r| {
+            !skipped_ids.contains(&r.id) && keys.contains(collation.key(&r.row.data[pos]).as_ref())
+        })
+    }
+
+    /// The error for deleting rows of `parent` that this table references through `fk`
+    fn referenced_by(&self, parent: &str, fk: &ForeignKey) -> StorageError {
+        StorageError::ReferencedByChild {
+            table: parent.to_string(),
+            child_table: self.header.table_name.clone(),
+            child_column: fk.column.clone(),
+        }
+    }
+
+    /// Deletes the row with the same primary key as `row`, along with its key
+    fn delete_conflicting_row(&mut self, row: &Row) -> Result<()> {
+        let (pos, collation) = match &self.primary_key {
+            PrimaryKey::Column { col, keyset: _ } => {
+                let pos = self
+                    .header
+                    .schema
+                    .column_position(&col.name)
+                    .ok_or_else(|| StorageError::UnknownColumnName(col.name.clone()))?;
+                (pos, col.collation)
+            }
+            PrimaryKey::Rowid => return Ok(()),
+        };
+        let key = collation.key(&row.data[pos]).into_owned();
+        self.delete_rows_where(false, |r| *collation.key(&r.data[pos]) == key);
+        Ok(())
+    }
+
+    /// Returns the number of rows inserted, and the rowid of the last one
+    fn insert_rows(
+        &mut self,
+        rows: &[Row],
+        conflict_rule: Option<ConflictRule>,
+    ) -> Result<(usize, Option<usize>)> {
         match (&conflict_rule, &self.primary_key) {
             (Some(rule), PrimaryKey::Column { col, keyset: _ }) if rule.column != col.name => {
                 return Err(StorageError::NonIndexedConflictColumn);
@@ -567,10 +1698,25 @@ impl Table {
             .map(|r| r.action)
             .unwrap_or(ConflictAction::Abort);
 
+        let checks = self
+            .header
+            .checks
+            .iter()
+            .map(|check| {
+                CheckPredicate::build(&check.predicate, &self.header.schema)
+                    .map(|predicate| (check.name.clone(), predicate))
+                    .map_err(|_| StorageError::CheckConstraintFailed(check.name.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         let mut affected_rows = 0;
+        let mut last_id = None;
         for row in rows {
-            if !self.header.schema.matches(row) {
-                return Err(StorageError::SchemaDoesntMatch);
+            self.header.schema.check_row(row)?;
+            for (name, predicate) in &checks {
+                if !predicate.passes(row).unwrap_or(false) {
+                    return Err(StorageError::CheckConstraintFailed(name.clone()));
+                }
             }
             // verify constraint based on conflict rule
             if !self.primary_key_constraint_passes(row)? {
@@ -579,6 +1725,7 @@ impl Table {
                     ConflictAction::Abort => {
                         return Err(StorageError::UniquenessConstraintViolated)
                     }
+                    ConflictAction::Replace => self.delete_conflicting_row(row)?,
                 }
             }
             let storage_row = StorageRow {
@@ -590,26 +1737,75 @@ impl Table {
                 PrimaryKey::Rowid => (),
                 PrimaryKey::Column { col, keyset } => {
                     let v = self.header.schema.column_value(&col.name, row)?;
-                    keyset.insert(v.clone());
+                    keyset.insert(col.collation.key(v).into_owned());
                 }
             }
 
+            last_id = Some(storage_row.id);
             self.rows.push(storage_row);
             affected_rows += 1;
         }
-        Ok(affected_rows)
+        Ok((affected_rows, last_id))
     }
 
-    fn delete_rows(&mut self, ids: &[usize]) -> Result<usize> {
+    fn delete_rows_where(
+        &mut self,
+        with_row_id: bool,
+        mut predicate: impl FnMut(&Row) -> bool,
+    ) -> usize {
+        let (pk_position, pk_collation) = match &self.primary_key {
+            PrimaryKey::Column { col, keyset: _ } => {
+                (self.header.schema.column_position(&col.name), col.collation)
+            }
+            PrimaryKey::Rowid => (None, Collation::Binary),
+        };
         let initial_len = self.rows.len();
-        self.rows.retain(|row| !ids.contains(&row.id));
-        let after_len = self.rows.len();
-        Ok(initial_len - after_len)
+        self.rows.retain(|storage_row| {
+            let matches = if with_row_id {
+                let mut row = storage_row.row.clone();
+                row.data.push(DbValue::UnsignedInt(storage_row.id as u64));
+                predicate(&row)
+            } else {
+                predicate(&storage_row.row)
+            };
+            if matches {
+                if let (Some(pos), PrimaryKey::Column { col: _, keyset }) =
+                    (pk_position, &mut self.primary_key)
+                {
+                    keyset.remove(&pk_collation.key(&storage_row.row.data[pos]));
+                }
+            }
+            !matches
+        });
+        initial_len - self.rows.len()
+    }
+
+    fn vacuum(&mut self) {
+        for (id, row) in self.rows.iter_mut().enumerate() {
+            row.id = id;
+        }
+        self.next_id = self.rows.len();
+        self.rows.shrink_to_fit();
+    }
+
+    fn truncate(&mut self) -> usize {
+        if let PrimaryKey::Column { col: _, keyset } = &mut self.primary_key {
+            keyset.clear();
+        }
+        let deleted = self.rows.len();
+        self.rows.clear();
+        deleted
     }
 
     pub fn rows(&self, with_rowid: bool) -> Rows {
         Rows::new(&self.rows, with_rowid, &self.header.schema)
     }
+
+    fn rows_after(&self, after: usize, with_rowid: bool) -> Rows<'_> {
+        let mut rows = self.rows(with_rowid);
+        rows.cursor = self.rows.partition_point(|r| r.id <= after);
+        rows
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -619,7 +1815,7 @@ struct StorageRow {
 }
 
 // TODO: Add reference to column list, and a way to get a specific columns value
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Row {
     // id: usize,
     pub data: Vec<DbValue>,
@@ -655,13 +1851,7 @@ impl<'a> Rows<'a> {
     fn new(rows: &'a [StorageRow], with_id: bool, schema: &'a Schema) -> Self {
         let schema = if with_id {
             let mut schema = schema.clone();
-            schema.schema.insert(
-                String::from("rowid"),
-                ColumnWithIndex {
-                    column: Column::new(String::from("rowid"), DbType::UnsignedInt),
-                    index: schema.schema.len(),
-                },
-            );
+            schema.push(Column::new(String::from("rowid"), DbType::UnsignedInt));
             Cow::Owned(schema)
         } else {
             Cow::Borrowed(schema)
@@ -747,9 +1937,453 @@ impl From<Utf8Error> for SerdeError {
 pub enum ConflictAction {
     Nothing,
     Abort,
+    /// Deletes the conflicting row before inserting the new one. Unlike updating
+    /// it in place, the new row gets a fresh rowid and is placed after every other row.
+    Replace,
 }
 
 pub struct ConflictRule {
     pub column: String,
     pub action: ConflictAction,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        fs, io,
+        path::Path,
+    };
+
+    use serde::Serialize;
+
+    use super::{
+        write, Collation, Column, ConflictAction, ConflictRule, DbHeader, IntegrityProblem, KeySet,
+        PrimaryKey, Schema, StorageError, StorageLayer, StorageRow, ROW_HEADER_VERSION,
+        TABLE_HEADER_VERSION,
+    };
+    use crate::{DbType, DbValue, Row};
+
+    // The table format before version 1, whose columns have no collation
+    #[derive(Serialize)]
+    struct V0Column {
+        name: String,
+        _type: DbType,
+    }
+    #[derive(Serialize)]
+    struct V0ColumnWithIndex {
+        column: V0Column,
+        index: usize,
+    }
+    #[derive(Serialize)]
+    struct V0Schema {
+        schema: HashMap<String, V0ColumnWithIndex>,
+    }
+    #[derive(Serialize)]
+    struct V0TableHeader {
+        header_version: u16,
+        row_header_version: u16,
+        table_name: String,
+        schema: V0Schema,
+    }
+    #[derive(Serialize)]
+    struct V0Table {
+        header: V0TableHeader,
+        rows: Vec<StorageRow>,
+        next_id: usize,
+        primary_key: PrimaryKey,
+    }
+    #[derive(Serialize)]
+    struct V0StorageLayer {
+        db_header: DbHeader,
+        tables: Vec<V0Table>,
+    }
+
+    #[test]
+    fn version_0_tables_are_read() {
+        let file = Path::new("version_0_tables_are_read.test");
+        let column = V0Column {
+            name: String::from("name"),
+            _type: DbType::String,
+        };
+        let row = Row::new(vec![DbValue::String(String::from("Abc"))]);
+        let old = V0StorageLayer {
+            db_header: DbHeader::new(),
+            tables: vec![V0Table {
+                header: V0TableHeader {
+                    header_version: 0,
+                    row_header_version: ROW_HEADER_VERSION,
+                    table_name: String::from("t"),
+                    schema: V0Schema {
+                        schema: HashMap::from([(
+                            String::from("name"),
+                            V0ColumnWithIndex { column, index: 0 },
+                        )]),
+                    },
+                },
+                rows: vec![StorageRow {
+                    row: row.clone(),
+                    id: 0,
+                }],
+                next_id: 1,
+                primary_key: PrimaryKey::Rowid,
+            }],
+        };
+        let mut bytes = Vec::new();
+        write::to_writer(&mut bytes, &old).unwrap();
+        fs::write(file, bytes).unwrap();
+
+        let mut storage = StorageLayer::init(file).unwrap();
+        let schema = storage.table_schema("t").unwrap();
+        assert_eq!(schema.column("name").unwrap().collation, Collation::Binary);
+        let rows: Vec<Row> = storage
+            .table_scan("t", false)
+            .unwrap()
+            .map(|r| r.into_owned())
+            .collect();
+        assert_eq!(rows, vec![row.clone()]);
+
+        // the next flush writes the table in the current format
+        storage.flush().unwrap();
+        drop(storage);
+        let storage = StorageLayer::init(file).unwrap();
+        let table = storage.table("t").unwrap();
+        assert_eq!(table.header.header_version, TABLE_HEADER_VERSION);
+        let rows: Vec<Row> = table.rows(false).map(|r| r.into_owned()).collect();
+        assert_eq!(rows, vec![row]);
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn failed_flushes_leave_the_file_intact() {
+        let file = Path::new("failed_flushes_leave_the_file_intact.test");
+        let temp = StorageLayer::temp_path(file);
+        let rows = vec![Row::new(vec![DbValue::Integer(1)])];
+        let mut storage = StorageLayer::init(file).unwrap();
+        let schema = Schema::new(vec![Column::new(String::from("a"), DbType::Integer)]);
+        storage
+            .create_table(
+                String::from("t"),
+                schema,
+                PrimaryKey::Rowid,
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        storage.insert_rows("t", &rows, None).unwrap();
+        storage.flush().unwrap();
+        assert!(!temp.exists());
+
+        // a directory where the new copy would be written makes writing it fail
+        fs::create_dir(&temp).unwrap();
+        storage.insert_rows("t", &rows, None).unwrap();
+        let result = storage.flush();
+        fs::remove_dir(&temp).unwrap();
+        assert!(result.is_err());
+        drop(storage);
+
+        let storage = StorageLayer::init(file).unwrap();
+        let stored: Vec<Row> = storage
+            .table_scan("t", false)
+            .unwrap()
+            .map(|r| r.into_owned())
+            .collect();
+        assert_eq!(stored, rows);
+
+        drop(storage);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn newer_table_versions_are_rejected_before_parsing() {
+        // a table from a newer version whose header this build can't parse
+        #[derive(Serialize)]
+        struct NewerTable {
+            header: NewerTableHeader,
+        }
+        #[derive(Serialize)]
+        struct NewerTableHeader {
+            header_version: u16,
+            row_header_version: u16,
+            something_new: bool,
+        }
+        #[derive(Serialize)]
+        struct NewerStorageLayer {
+            db_header: DbHeader,
+            tables: Vec<NewerTable>,
+        }
+
+        let file = Path::new("newer_table_versions_are_rejected_before_parsing.test");
+        let newer = NewerStorageLayer {
+            db_header: DbHeader::new(),
+            tables: vec![NewerTable {
+                header: NewerTableHeader {
+                    header_version: u16::MAX,
+                    row_header_version: ROW_HEADER_VERSION,
+                    something_new: true,
+                },
+            }],
+        };
+        let mut bytes = Vec::new();
+        write::to_writer(&mut bytes, &newer).unwrap();
+        fs::write(file, bytes).unwrap();
+
+        let result = StorageLayer::init(file);
+        fs::remove_file(file).unwrap();
+        assert!(
+            matches!(
+                result,
+                Err(StorageError::UnsupportedVersion {
+                    found: u16::MAX,
+                    supported: TABLE_HEADER_VERSION
+                })
+            ),
+            "Unexpected result: {result:?}"
+        );
+    }
+
+    /// Records the size of each write, to check nothing is buffered in full before it's written
+    struct WriteSizes {
+        total: usize,
+        largest: usize,
+    }
+    impl io::Write for WriteSizes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.total += buf.len();
+            self.largest = self.largest.max(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn databases_are_written_as_they_are_serialized() {
+        let mut storage = StorageLayer::in_memory();
+        let schema = Schema::new(vec![Column::new(String::from("s"), DbType::String)]);
+        storage
+            .create_table(
+                String::from("t"),
+                schema,
+                PrimaryKey::Rowid,
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        let rows: Vec<Row> = (0..10_000)
+            .map(|i| Row::new(vec![DbValue::String(format!("row number {i}"))]))
+            .collect();
+        storage.insert_rows("t", &rows, None).unwrap();
+
+        let mut sizes = WriteSizes {
+            total: 0,
+            largest: 0,
+        };
+        write::to_writer(&mut sizes, &storage).unwrap();
+        assert!(sizes.total > 100_000);
+        assert!(sizes.largest < 100);
+    }
+
+    #[test]
+    fn table_stats() {
+        let mut storage = StorageLayer::in_memory();
+        let name = Column::new(String::from("name"), DbType::String).collated(Collation::NoCase);
+        let schema = Schema::new(vec![name.clone()]);
+        let primary_key = PrimaryKey::Column {
+            col: name,
+            keyset: KeySet::Strings(BTreeSet::new()),
+        };
+        storage
+            .create_table(
+                String::from("t"),
+                schema.clone(),
+                primary_key,
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        storage
+            .create_table(
+                String::from("u"),
+                schema,
+                PrimaryKey::Rowid,
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        let rows: Vec<Row> = ["a", "b", "c"]
+            .iter()
+            .map(|s| Row::new(vec![DbValue::String(String::from(*s))]))
+            .collect();
+        storage.insert_rows("t", &rows, None).unwrap();
+        storage.insert_rows("u", &rows, None).unwrap();
+        storage
+            .delete_rows_where("t", false, |r| r == &rows[1])
+            .unwrap();
+
+        let stats = storage.table_stats("t").unwrap();
+        assert_eq!(stats.row_count, 2);
+        assert_eq!(
+            stats.distinct_values,
+            HashMap::from([(String::from("name"), 2)])
+        );
+        let stats = storage.table_stats("u").unwrap();
+        assert_eq!(stats.row_count, 3);
+        assert!(stats.distinct_values.is_empty());
+        assert!(matches!(
+            storage.table_stats("v"),
+            Err(StorageError::TableDoesNotExist)
+        ));
+    }
+
+    #[test]
+    fn integrity_problems_are_all_reported() {
+        let mut storage = StorageLayer::in_memory();
+        let id = Column::new(String::from("id"), DbType::Integer);
+        let schema = Schema::new(vec![
+            id.clone(),
+            Column::new(String::from("name"), DbType::String),
+        ]);
+        storage
+            .create_table(
+                String::from("t"),
+                schema,
+                PrimaryKey::column(id),
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        let row = |id, name: &str| {
+            Row::new(vec![
+                DbValue::Integer(id),
+                DbValue::String(String::from(name)),
+            ])
+        };
+        storage
+            .insert_rows("t", &[row(1, "a"), row(2, "b"), row(3, "c")], None)
+            .unwrap();
+        assert_eq!(storage.check_integrity(), Vec::new());
+
+        let table = storage.table_mut("t").unwrap();
+        table.rows[0].row = Row::new(vec![DbValue::Integer(1)]);
+        table.rows[1].row = row(3, "b");
+        table.rows[2].id = 5;
+        table.next_id = 3;
+        if let PrimaryKey::Column { keyset, .. } = &mut table.primary_key {
+            keyset.insert(DbValue::Integer(9));
+        }
+
+        let t = || String::from("t");
+        assert_eq!(
+            storage.check_integrity(),
+            vec![
+                IntegrityProblem::RowDoesNotMatchSchema {
+                    table: t(),
+                    rowid: 0,
+                    reason: StorageError::ColumnCountMismatch {
+                        expected: 2,
+                        got: 1
+                    }
+                    .to_string(),
+                },
+                IntegrityProblem::RowidNotBelowNextId {
+                    table: t(),
+                    rowid: 5,
+                    next_id: 3,
+                },
+                IntegrityProblem::DuplicateKey {
+                    table: t(),
+                    key: DbValue::Integer(3),
+                },
+                // row 0's key is left out, since the row it came from is unreadable
+                IntegrityProblem::KeyWithoutRow {
+                    table: t(),
+                    key: DbValue::Integer(1),
+                },
+                IntegrityProblem::KeyWithoutRow {
+                    table: t(),
+                    key: DbValue::Integer(2),
+                },
+                IntegrityProblem::KeyWithoutRow {
+                    table: t(),
+                    key: DbValue::Integer(9),
+                },
+            ]
+        );
+
+        if let PrimaryKey::Column { keyset, .. } = &mut storage.table_mut("t").unwrap().primary_key
+        {
+            keyset.clear();
+        }
+        let problems = storage.check_integrity();
+        assert!(problems.contains(&IntegrityProblem::KeyMissingFromKeySet {
+            table: t(),
+            key: DbValue::Integer(3),
+        }));
+    }
+
+    #[test]
+    fn replacing_conflicting_rows() {
+        let mut storage = StorageLayer::in_memory();
+        let id = Column::new(String::from("id"), DbType::Integer);
+        let schema = Schema::new(vec![
+            id.clone(),
+            Column::new(String::from("name"), DbType::String),
+        ]);
+        let primary_key = PrimaryKey::Column {
+            col: id,
+            keyset: KeySet::Integers(BTreeSet::new()),
+        };
+        storage
+            .create_table(
+                String::from("t"),
+                schema,
+                primary_key,
+                Vec::new(),
+                Vec::new(),
+            )
+            .unwrap();
+        let row = |id, name: &str| {
+            Row::new(vec![
+                DbValue::Integer(id),
+                DbValue::String(String::from(name)),
+            ])
+        };
+        let replace = || {
+            Some(ConflictRule {
+                column: String::from("id"),
+                action: ConflictAction::Replace,
+            })
+        };
+        storage
+            .insert_rows("t", &[row(1, "old"), row(2, "other")], None)
+            .unwrap();
+
+        let affected = storage
+            .insert_rows("t", &[row(1, "new")], replace())
+            .unwrap();
+        assert_eq!(affected, 1);
+        // the replacement is a new row, with a new rowid
+        assert_eq!(storage.last_insert_rowid(), Some(2));
+        let rows: Vec<Row> = storage
+            .table_scan("t", false)
+            .unwrap()
+            .map(|r| r.into_owned())
+            .collect();
+        assert_eq!(rows, vec![row(2, "other"), row(1, "new")]);
+
+        // the key is still taken by the replacement, and freed once it's deleted
+        assert!(matches!(
+            storage.insert_rows("t", &[row(1, "again")], None),
+            Err(StorageError::UniquenessConstraintViolated)
+        ));
+        storage
+            .delete_rows_where("t", false, |r| r.data[0] == DbValue::Integer(1))
+            .unwrap();
+        storage.insert_rows("t", &[row(1, "again")], None).unwrap();
+    }
+}
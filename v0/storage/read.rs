@@ -33,6 +33,25 @@ where
     }
 }
 
+/// Like `from_bytes`, but only reads as much of `bytes` as `T` needs, ignoring the rest
+pub fn prefix_from_bytes<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(bytes);
+    T::deserialize(&mut deserializer)
+}
+
+/// Like `prefix_from_bytes`, but also returns the bytes after the value
+pub fn split_prefix<'a, T>(bytes: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(bytes);
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.input))
+}
+
 impl<'de> Deserializer<'de> {
     fn next_bytes(&mut self, num: usize) -> Option<&[u8]> {
         if self.input.len() < num {
@@ -0,0 +1,412 @@
+//! Runs random statements against a database and checks the results against a
+//! simple model of what the tables should hold, to catch regressions no test was
+//! written for. Runs are deterministic for a given seed, so a failure can be replayed.
+
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    generate::{Generate, RNG},
+    storage::{Column, Row, Schema},
+    Database, DatabaseError, DbType, DbValue,
+};
+
+const MAX_COLUMNS: u32 = 5;
+
+/// A comparison a `SELECT` can filter with, and which orderings of a column's value
+/// against the compared value it accepts
+type Comparison = (&'static str, fn(Ordering) -> bool);
+
+const COMPARISONS: [Comparison; 5] = [
+    ("=", Ordering::is_eq),
+    ("<", Ordering::is_lt),
+    ("<=", Ordering::is_le),
+    (">", Ordering::is_gt),
+    (">=", Ordering::is_ge),
+];
+
+#[derive(Debug)]
+pub enum TrawlerError {
+    DatabaseError(DatabaseError),
+    /// The database disagreed with the model after running `statement`
+    InvariantViolated {
+        seed: u64,
+        statement: String,
+        problem: String,
+    },
+}
+impl fmt::Display for TrawlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DatabaseError(err) => err.fmt(f),
+            Self::InvariantViolated {
+                seed,
+                statement,
+                problem,
+            } => write!(f, "{problem}\n  after `{statement}` (seed {seed})"),
+        }
+    }
+}
+impl std::error::Error for TrawlerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::DatabaseError(err) => Some(err),
+            Self::InvariantViolated { .. } => None,
+        }
+    }
+}
+impl From<DatabaseError> for TrawlerError {
+    fn from(value: DatabaseError) -> Self {
+        Self::DatabaseError(value)
+    }
+}
+
+type Result<T> = std::result::Result<T, TrawlerError>;
+
+pub struct TrawlerConfig {
+    /// Runs with the same seed and config run the same statements
+    pub seed: u64,
+    /// How many statements to run after creating the tables
+    pub operations: usize,
+    pub tables: usize,
+}
+impl Default for TrawlerConfig {
+    fn default() -> Self {
+        TrawlerConfig {
+            seed: RNG::new().seed(),
+            operations: 200,
+            tables: 3,
+        }
+    }
+}
+
+/// What a table should contain, in the order a scan returns its rows
+struct ModelTable {
+    name: String,
+    schema: Schema,
+    rows: Vec<Row>,
+}
+
+struct Trawler {
+    rng: RNG,
+    seed: u64,
+    db_file: PathBuf,
+    db: Database,
+    tables: Vec<ModelTable>,
+    /// Every statement run so far
+    log: Vec<String>,
+}
+impl Trawler {
+    fn choose(&mut self, count: usize) -> usize {
+        self.rng.next_value() as usize % count
+    }
+
+    fn violation(&self, problem: String) -> TrawlerError {
+        TrawlerError::InvariantViolated {
+            seed: self.seed,
+            statement: self.log.last().cloned().unwrap_or_default(),
+            problem,
+        }
+    }
+
+    /// Runs a statement that modifies the database, with each value bound to the
+    /// placeholder of the same name
+    fn execute(&mut self, statement: String, values: &[(&str, DbValue)]) -> Result<usize> {
+        self.log.push(statement);
+        let statement = self.log.last().expect("Just pushed it");
+        let affected = self.db.prepare(statement)?.execute(values)?;
+        Ok(affected)
+    }
+
+    fn create_table(&mut self, index: usize) -> Result<()> {
+        let column_count = self.rng.next_value() % MAX_COLUMNS + 1;
+        let columns: Vec<Column> = (0..column_count)
+            .map(|i| Column::new(format!("c{i}"), DbType::generate(&mut self.rng)))
+            .collect();
+        let name = format!("t{index}");
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{} {}", c.name, c._type.as_sql_type_str()))
+            .collect();
+        self.execute(
+            format!("CREATE TABLE {name} ({});", definitions.join(", ")),
+            &[],
+        )?;
+        self.tables.push(ModelTable {
+            name,
+            schema: Schema::new(columns),
+            rows: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn insert(&mut self, table: usize) -> Result<()> {
+        let row = self.tables[table].schema.gen_row(&mut self.rng);
+        let names: Vec<String> = self.tables[table]
+            .schema
+            .columns()
+            .map(|c| c.name.clone())
+            .collect();
+        let placeholders: Vec<String> = names.iter().map(|n| format!(":{n}")).collect();
+        let statement = format!(
+            "INSERT INTO {} ({}) VALUES ({});",
+            self.tables[table].name,
+            names.join(", "),
+            placeholders.join(", ")
+        );
+        let values: Vec<(&str, DbValue)> = placeholders
+            .iter()
+            .map(|p| p.as_str())
+            .zip(row.data.iter().cloned())
+            .collect();
+        let affected = self.execute(statement, &values)?;
+        if affected != 1 {
+            return Err(self.violation(format!("Inserted {affected} rows instead of 1")));
+        }
+        self.tables[table].rows.push(row);
+        Ok(())
+    }
+
+    /// A value to compare a column with. It's usually taken from a row that exists,
+    /// so something matches it.
+    fn column_value(&mut self, table: usize, column: usize) -> DbValue {
+        let row_count = self.tables[table].rows.len();
+        if row_count > 0 && self.choose(4) > 0 {
+            let row = self.choose(row_count);
+            self.tables[table].rows[row].data[column].clone()
+        } else {
+            let schema = &self.tables[table].schema;
+            let _type = schema
+                .columns()
+                .nth(column)
+                .expect("Chose an existing one")
+                ._type;
+            _type.generate_val(&mut self.rng)
+        }
+    }
+
+    /// Deletes the rows matching a value of one of the table's columns
+    fn delete(&mut self, table: usize) -> Result<()> {
+        let column_count = self.tables[table].schema.columns().count();
+        let column = self.choose(column_count);
+        let value = self.column_value(table, column);
+        let statement = format!(
+            "DELETE FROM {} WHERE c{column} = :value;",
+            self.tables[table].name
+        );
+        let deleted = self.execute(statement, &[(":value", value.clone())])?;
+
+        let model = &mut self.tables[table];
+        let before = model.rows.len();
+        model.rows.retain(|r| r.data[column] != value);
+        let expected = before - model.rows.len();
+        if deleted != expected {
+            return Err(self.violation(format!("Deleted {deleted} rows instead of {expected}")));
+        }
+        Ok(())
+    }
+
+    /// Selects some of the table's columns in a random order, filtered by comparing a
+    /// column with a value, and sometimes sorted by one of the selected columns.
+    /// Checks the rows match the model's rows filtered, projected, and sorted the same
+    /// way. Rows that tie when sorting can come back in any order.
+    fn select(&mut self, table: usize) -> Result<()> {
+        let column_count = self.tables[table].schema.columns().count();
+        let mut projection: Vec<usize> = (0..column_count).collect();
+        for i in (1..column_count).rev() {
+            let j = self.choose(i + 1);
+            projection.swap(i, j);
+        }
+        projection.truncate(self.choose(column_count) + 1);
+
+        let filter_column = self.choose(column_count);
+        let value = self.column_value(table, filter_column);
+        let (op, accepts) = COMPARISONS[self.choose(COMPARISONS.len())];
+        // the position in the projection of the column to sort by, and whether it's descending
+        let order = match self.choose(3) {
+            0 => None,
+            n => Some((self.choose(projection.len()), n == 2)),
+        };
+
+        let names: Vec<String> = projection.iter().map(|c| format!("c{c}")).collect();
+        let mut statement = format!(
+            "SELECT {} FROM {} WHERE c{filter_column} {op} :value",
+            names.join(", "),
+            self.tables[table].name
+        );
+        if let Some((pos, descending)) = order {
+            statement.push_str(&format!(" ORDER BY {}", names[pos]));
+            if descending {
+                statement.push_str(" DESC");
+            }
+        }
+        statement.push(';');
+        self.log.push(statement.clone());
+        let mut rows = self
+            .db
+            .prepare_read(&statement)?
+            .query_with(&[(":value", value.clone())][..])?
+            .map(|r| r.map(Cow::into_owned))
+            .collect::<std::result::Result<Vec<Row>, _>>()?;
+
+        let mut expected: Vec<Row> = self.tables[table]
+            .rows
+            .iter()
+            .filter(|r| accepts(r.data[filter_column].cmp(&value)))
+            .map(|r| Row::new(projection.iter().map(|&c| r.data[c].clone()).collect()))
+            .collect();
+        match order {
+            None => (),
+            Some((pos, descending)) => {
+                let in_order = rows.windows(2).all(|pair| {
+                    let ordering = pair[0].data[pos].cmp(&pair[1].data[pos]);
+                    if descending {
+                        ordering.is_ge()
+                    } else {
+                        ordering.is_le()
+                    }
+                });
+                if !in_order {
+                    return Err(self.violation(format!("The rows aren't sorted by {}", names[pos])));
+                }
+                rows.sort_by(|a, b| a.data.cmp(&b.data));
+                expected.sort_by(|a, b| a.data.cmp(&b.data));
+            }
+        }
+        if rows != expected {
+            return Err(self.violation(format!(
+                "Selected {} rows that differ from the {} expected",
+                rows.len(),
+                expected.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks the table's rows and row count match the model
+    fn check_table(&mut self, table: usize) -> Result<()> {
+        let name = self.tables[table].name.clone();
+        let statement = format!("SELECT * FROM {name};");
+        self.log.push(statement.clone());
+        let rows = self
+            .db
+            .prepare_read(&statement)?
+            .query()?
+            .map(|r| r.map(Cow::into_owned))
+            .collect::<std::result::Result<Vec<Row>, _>>()?;
+        if rows != self.tables[table].rows {
+            return Err(self.violation(format!(
+                "{name} holds {} rows that differ from the {} expected",
+                rows.len(),
+                self.tables[table].rows.len()
+            )));
+        }
+
+        let statement = format!("SELECT COUNT(*) FROM {name};");
+        self.log.push(statement.clone());
+        let count = self
+            .db
+            .prepare_read(&statement)?
+            .query()?
+            .next()
+            .transpose()?
+            .map(|r| r.data[0].clone());
+        let expected = DbValue::UnsignedInt(rows.len() as u64);
+        if count.as_ref() != Some(&expected) {
+            return Err(self.violation(format!("{name} counted {count:?} rows, not {expected}")));
+        }
+        Ok(())
+    }
+
+    /// Writes the database out, opens it again, and checks nothing was lost
+    fn reload(&mut self) -> Result<()> {
+        self.log.push(String::from("-- commit and reopen"));
+        self.db.commit()?;
+        self.db = Database::init(&self.db_file)?;
+        for table in 0..self.tables.len() {
+            self.check_table(table)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs random statements against a new database at `db_file`, which must not
+/// exist yet, and checks the database's contents as it goes. Returns every
+/// statement run, so a failing run can be reproduced without the trawler.
+pub fn trawl(db_file: &Path, config: &TrawlerConfig) -> Result<Vec<String>> {
+    if db_file.exists() {
+        let err = io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", db_file.display()),
+        );
+        return Err(DatabaseError::from(err).into());
+    }
+    let mut trawler = Trawler {
+        rng: RNG::with_seed(config.seed),
+        seed: config.seed,
+        db_file: db_file.to_path_buf(),
+        db: Database::init(db_file)?,
+        tables: Vec::new(),
+        log: Vec::new(),
+    };
+    for index in 0..config.tables.max(1) {
+        trawler.create_table(index)?;
+    }
+    for _ in 0..config.operations {
+        let table = trawler.choose(trawler.tables.len());
+        match trawler.choose(20) {
+            0..=9 => trawler.insert(table)?,
+            10..=12 => trawler.delete(table)?,
+            13..=15 => trawler.select(table)?,
+            16..=18 => trawler.check_table(table)?,
+            _ => trawler.reload()?,
+        }
+    }
+    trawler.reload()?;
+    Ok(trawler.log)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn trawls_without_finding_problems() {
+        let file = Path::new("trawls_without_finding_problems.test");
+        for seed in 0..2 {
+            let config = TrawlerConfig {
+                seed,
+                operations: 60,
+                tables: 3,
+            };
+            let result = trawl(file, &config);
+            fs::remove_file(file).unwrap();
+            if let Err(err) = result {
+                panic!("{err}");
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_runs_same_statements() {
+        let file = Path::new("same_seed_runs_same_statements.test");
+        let config = TrawlerConfig {
+            seed: 7,
+            operations: 25,
+            tables: 2,
+        };
+        let first = trawl(file, &config).unwrap();
+        fs::remove_file(file).unwrap();
+        let second = trawl(file, &config).unwrap();
+        fs::remove_file(file).unwrap();
+        assert_eq!(first, second);
+        assert!(first.iter().any(|s| s.starts_with("INSERT INTO")));
+        assert!(first.iter().any(|s| s.contains(" WHERE ")));
+    }
+}